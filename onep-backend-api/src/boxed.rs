@@ -0,0 +1,239 @@
+//! Type-erasure for backends, allowing the backend in use to be picked at
+//! runtime rather than at compile time.
+
+use crate::{
+    AccountMetadata, Backend, BackendError, Capabilities, DocumentMetadata, Filter, Item,
+    ItemCategory, ItemEdit, ItemMetadata, Page, PasswordRecipe, VaultMetadata,
+};
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+use std::path::Path;
+use tracing_futures::Instrument;
+
+/// A backend chosen at runtime, see [`Boxed`].
+pub type DynBackend = dyn Backend<Error = BackendError> + Send + Sync;
+
+/// Wraps a backend, sorting its errors into [`BackendError`]s so it can be
+/// used as a [`DynBackend`]. Every call is made in a `tracing` span, leaving
+/// out passwords and one-time password secrets.
+pub struct Boxed<T>(pub T);
+
+#[async_trait]
+impl<T> Backend for Boxed<T>
+where
+    T: Backend + Send + Sync,
+    T::Error: std::error::Error + Send + Sync + 'static,
+{
+    type Error = BackendError;
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn capabilities(&self) -> Result<Capabilities, Self::Error> {
+        self.0.capabilities().await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn signin(&self, account: Option<&str>) -> Result<(), Self::Error> {
+        self.0.signin(account).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn signout(&self) -> Result<(), Self::Error> {
+        self.0.signout().await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn account(&self) -> Result<AccountMetadata, Self::Error> {
+        self.0.account().await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn vaults(&self) -> Result<Vec<VaultMetadata>, Self::Error> {
+        self.0.vaults().await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn search(&self, terms: Option<&str>) -> Result<Vec<ItemMetadata>, Self::Error> {
+        self.0.search(terms).await.map_err(Into::into)
+    }
+
+    fn search_stream<'a>(
+        &'a self,
+        terms: Option<&'a str>,
+    ) -> BoxStream<'a, Result<ItemMetadata, Self::Error>> {
+        self.0
+            .search_stream(terms)
+            .map(|v| v.map_err(Into::into))
+            .instrument(tracing::debug_span!("search_stream", ?terms))
+            .boxed()
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn search_page(
+        &self,
+        terms: Option<&str>,
+        page: Page,
+    ) -> Result<Vec<ItemMetadata>, Self::Error> {
+        self.0.search_page(terms, page).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn search_filtered(
+        &self,
+        terms: Option<&str>,
+        filter: Filter<'_>,
+    ) -> Result<Vec<ItemMetadata>, Self::Error> {
+        self.0
+            .search_filtered(terms, filter)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn get(&self, uuid: &str) -> Result<Option<Item>, Self::Error> {
+        self.0.get(uuid).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn get_many(&self, uuids: &[String]) -> Result<Vec<Option<Item>>, Self::Error> {
+        self.0.get_many(uuids).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn generate(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &PasswordRecipe,
+    ) -> Result<Item, Self::Error> {
+        self.0
+            .generate(name, username, url, tags, vault, recipe)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, password), err)]
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<Item, Self::Error> {
+        self.0
+            .create(name, username, url, tags, vault, password)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, item), err)]
+    async fn create_item(
+        &self,
+        item: &Item,
+        category: ItemCategory,
+        tags: &[String],
+        vault: Option<&str>,
+    ) -> Result<Item, Self::Error> {
+        self.0
+            .create_item(item, category, tags, vault)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn create_vault(&self, name: &str) -> Result<VaultMetadata, Self::Error> {
+        self.0.create_vault(name).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn delete_vault(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.0.delete_vault(uuid).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn rename_vault(&self, uuid: &str, name: &str) -> Result<(), Self::Error> {
+        self.0.rename_vault(uuid, name).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, edit), err)]
+    async fn edit(&self, uuid: &str, edit: &ItemEdit) -> Result<(), Self::Error> {
+        self.0.edit(uuid, edit).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn set_tags(&self, uuid: &str, tags: &[String]) -> Result<(), Self::Error> {
+        self.0.set_tags(uuid, tags).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn set_favorite(&self, uuid: &str, favorite: bool) -> Result<(), Self::Error> {
+        self.0
+            .set_favorite(uuid, favorite)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, uri), err)]
+    async fn set_otp(&self, uuid: &str, uri: &str) -> Result<(), Self::Error> {
+        self.0.set_otp(uuid, uri).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn archived(&self) -> Result<Vec<ItemMetadata>, Self::Error> {
+        self.0.archived().await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn archive(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.0.archive(uuid).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn restore(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.0.restore(uuid).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn documents(&self) -> Result<Vec<DocumentMetadata>, Self::Error> {
+        self.0.documents().await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn get_document(&self, uuid: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.0.get_document(uuid).await.map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn create_document(
+        &self,
+        path: &Path,
+        title: Option<&str>,
+        vault: Option<&str>,
+    ) -> Result<DocumentMetadata, Self::Error> {
+        self.0
+            .create_document(path, title, vault)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn get_attachment(
+        &self,
+        item_uuid: &str,
+        attachment_uuid: &str,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.0
+            .get_attachment(item_uuid, attachment_uuid)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err)]
+    async fn get_all(&self) -> Result<Vec<(ItemMetadata, Item)>, Self::Error> {
+        self.0.get_all().await.map_err(Into::into)
+    }
+}
@@ -0,0 +1,120 @@
+//! Builds items a piece at a time, checking they make sense before handing
+//! them out.
+
+use crate::{AttachmentMetadata, HistoricalValue, Item, ItemField, ItemSection, ItemUrl};
+
+/// Why [`ItemBuilder::build`] refused to build an item.
+#[derive(Debug)]
+pub enum InvalidItem {
+    NoTitle,
+    /// More than one section has this name.
+    DuplicateSection(String),
+}
+
+impl std::fmt::Display for InvalidItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoTitle => f.write_str("the item has no title"),
+            Self::DuplicateSection(name) => {
+                write!(f, "the item has more than one section named {:?}", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidItem {}
+
+/// See [`Item::builder`].
+#[derive(Debug, Default)]
+pub struct ItemBuilder {
+    title: String,
+    fields: Vec<ItemField>,
+    sections: Vec<ItemSection>,
+    urls: Vec<ItemUrl>,
+    attachments: Vec<AttachmentMetadata>,
+    history: Vec<HistoricalValue>,
+}
+
+impl ItemBuilder {
+    #[must_use]
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    #[must_use]
+    pub fn field(mut self, field: ItemField) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    #[must_use]
+    pub fn fields(mut self, fields: impl IntoIterator<Item = ItemField>) -> Self {
+        self.fields.extend(fields);
+        self
+    }
+
+    /// Adds a section, which needs a name no other section has, see
+    /// [`ItemSection::merge`].
+    #[must_use]
+    pub fn section(mut self, section: ItemSection) -> Self {
+        self.sections.push(section);
+        self
+    }
+
+    #[must_use]
+    pub fn sections(mut self, sections: impl IntoIterator<Item = ItemSection>) -> Self {
+        self.sections.extend(sections);
+        self
+    }
+
+    #[must_use]
+    pub fn url(mut self, url: ItemUrl) -> Self {
+        self.urls.push(url);
+        self
+    }
+
+    #[must_use]
+    pub fn urls(mut self, urls: impl IntoIterator<Item = ItemUrl>) -> Self {
+        self.urls.extend(urls);
+        self
+    }
+
+    #[must_use]
+    pub fn attachments(
+        mut self,
+        attachments: impl IntoIterator<Item = AttachmentMetadata>,
+    ) -> Self {
+        self.attachments.extend(attachments);
+        self
+    }
+
+    #[must_use]
+    pub fn history(mut self, history: impl IntoIterator<Item = HistoricalValue>) -> Self {
+        self.history.extend(history);
+        self
+    }
+
+    /// Builds the item, as long as it has a title and its sections' names are
+    /// unique.
+    pub fn build(self) -> Result<Item, InvalidItem> {
+        if self.title.trim().is_empty() {
+            return Err(InvalidItem::NoTitle);
+        }
+
+        for (i, section) in self.sections.iter().enumerate() {
+            if self.sections[..i].iter().any(|v| v.name == section.name) {
+                return Err(InvalidItem::DuplicateSection(section.name.clone()));
+            }
+        }
+
+        Ok(Item {
+            title: self.title,
+            fields: self.fields,
+            sections: self.sections,
+            urls: self.urls,
+            attachments: self.attachments,
+            history: self.history,
+        })
+    }
+}
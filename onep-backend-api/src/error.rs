@@ -0,0 +1,63 @@
+//! Errors every backend's own errors can be turned into, so callers can tell
+//! kinds of failure apart without knowing which backend they're using.
+
+use crate::Unsupported;
+
+/// The error a backend returned, which is what's shown for a
+/// [`BackendError`].
+pub type Source = Box<dyn std::error::Error + Send + Sync>;
+
+/// A backend's error sorted by what went wrong, see [`Backend::Error`].
+///
+/// [`Backend::Error`]: crate::Backend::Error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BackendError {
+    /// The item, vault or document asked for doesn't exist.
+    NotFound(Source),
+    /// Signing in is needed first, or the session has expired.
+    AuthRequired(Source),
+    /// Signed in, but not allowed to do what was asked.
+    PermissionDenied(Source),
+    /// The backend, or the server behind it, couldn't be reached.
+    Network(Source),
+    /// The backend returned something that couldn't be understood.
+    Parse(Source),
+    /// The backend has no way of doing what was asked.
+    Unsupported(Unsupported),
+    Other(Source),
+}
+
+impl BackendError {
+    /// The backend's own error.
+    #[must_use]
+    pub fn inner(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        match self {
+            Self::NotFound(e)
+            | Self::AuthRequired(e)
+            | Self::PermissionDenied(e)
+            | Self::Network(e)
+            | Self::Parse(e)
+            | Self::Other(e) => e.as_ref(),
+            Self::Unsupported(e) => e,
+        }
+    }
+}
+
+impl std::fmt::Display for BackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self.inner(), f)
+    }
+}
+
+impl std::error::Error for BackendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner().source()
+    }
+}
+
+impl From<Unsupported> for BackendError {
+    fn from(e: Unsupported) -> Self {
+        Self::Unsupported(e)
+    }
+}
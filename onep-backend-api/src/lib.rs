@@ -1,67 +1,895 @@
 #![deny(clippy::pedantic)]
 #![allow(clippy::missing_errors_doc)]
 
+mod boxed;
+mod builder;
+mod error;
+mod secret;
+pub mod session;
+mod unredacted;
+
+pub use boxed::{Boxed, DynBackend};
+pub use builder::{InvalidItem, ItemBuilder};
+pub use error::{BackendError, Source};
+pub use secret::Secret;
+pub use unredacted::Unredacted;
+
 use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
+use std::{path::Path, time::SystemTime};
 
-#[derive(Debug)]
+/// Most `get` calls the default [`Backend::get_many`] makes at once.
+pub const GET_MANY_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct AccountMetadata {
     pub name: String,
     pub domain: String,
+    /// Whether the backend is signed in with a service account, made for
+    /// automation, rather than as a person.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub service_account: bool,
 }
 
-#[derive(Debug)]
+impl AccountMetadata {
+    #[must_use]
+    pub fn new(name: impl Into<String>, domain: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            domain: domain.into(),
+            service_account: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct VaultMetadata {
     pub uuid: String,
     pub name: String,
 }
 
-#[derive(Debug)]
+impl VaultMetadata {
+    #[must_use]
+    pub fn new(uuid: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            uuid: uuid.into(),
+            name: name.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct ItemMetadata {
     pub uuid: String,
     pub vault_uuid: String,
     pub title: String,
     pub account_info: String,
+    /// Websites the item is for, where the backend lists them along with
+    /// the rest of its metadata.
+    pub urls: Vec<String>,
+    pub tags: Vec<String>,
+    pub favorite: bool,
+    /// Whether the item has been archived, or moved to the trash for backends
+    /// without an archive.
+    pub archived: bool,
+    /// When the item was created, if the backend keeps track of it.
+    pub created: Option<SystemTime>,
+    /// When the item was last changed, if the backend keeps track of it.
+    pub updated: Option<SystemTime>,
+    pub category: ItemCategory,
+}
+
+impl ItemMetadata {
+    /// A login with nothing known about it but its title, the rest being
+    /// filled in by setting the fields the backend knows.
+    #[must_use]
+    pub fn new(
+        uuid: impl Into<String>,
+        vault_uuid: impl Into<String>,
+        title: impl Into<String>,
+    ) -> Self {
+        Self {
+            uuid: uuid.into(),
+            vault_uuid: vault_uuid.into(),
+            title: title.into(),
+            account_info: String::new(),
+            urls: Vec::new(),
+            tags: Vec::new(),
+            favorite: false,
+            archived: false,
+            created: None,
+            updated: None,
+            category: ItemCategory::Login,
+        }
+    }
+}
+
+/// What kind of thing an item holds. Backends that only store logins give
+/// every item [`ItemCategory::Login`].
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ItemCategory {
+    Login,
+    Password,
+    SecureNote,
+    CreditCard,
+    Identity,
+    Document,
+    BankAccount,
+    ApiCredential,
+    Database,
+    Server,
+    SshKey,
+    SoftwareLicense,
+    WirelessRouter,
+    Unknown,
+}
+
+impl ItemCategory {
+    /// Every category other than `Unknown`.
+    pub const ALL: &'static [Self] = &[
+        Self::Login,
+        Self::Password,
+        Self::SecureNote,
+        Self::CreditCard,
+        Self::Identity,
+        Self::Document,
+        Self::BankAccount,
+        Self::ApiCredential,
+        Self::Database,
+        Self::Server,
+        Self::SshKey,
+        Self::SoftwareLicense,
+        Self::WirelessRouter,
+    ];
+
+    /// The category's name as 1Password shows it, such as `Secure Note`.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Login => "Login",
+            Self::Password => "Password",
+            Self::SecureNote => "Secure Note",
+            Self::CreditCard => "Credit Card",
+            Self::Identity => "Identity",
+            Self::Document => "Document",
+            Self::BankAccount => "Bank Account",
+            Self::ApiCredential => "API Credential",
+            Self::Database => "Database",
+            Self::Server => "Server",
+            Self::SshKey => "SSH Key",
+            Self::SoftwareLicense => "Software License",
+            Self::WirelessRouter => "Wireless Router",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for ItemCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parses a category's name ignoring case, spaces, dashes and underscores,
+/// so `secure-note`, `SECURE_NOTE` and `Secure Note` are all the same.
+impl std::str::FromStr for ItemCategory {
+    type Err = UnknownCategory;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalise = |v: &str| {
+            v.chars()
+                .filter(|c| !matches!(c, ' ' | '-' | '_'))
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        };
+        let s = normalise(s);
+
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|v| normalise(v.name()) == s)
+            .ok_or(UnknownCategory)
+    }
 }
 
 #[derive(Debug)]
+pub struct UnknownCategory;
+
+impl std::fmt::Display for UnknownCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown category, expected one of: {}",
+            ItemCategory::ALL
+                .iter()
+                .map(|v| v.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownCategory {}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct Item {
     pub title: String,
     pub fields: Vec<ItemField>,
     pub sections: Vec<ItemSection>,
+    /// Websites the item is for.
+    pub urls: Vec<ItemUrl>,
+    pub attachments: Vec<AttachmentMetadata>,
+    pub history: Vec<HistoricalValue>,
 }
 
-#[derive(Debug)]
+impl Item {
+    #[must_use]
+    pub fn builder() -> ItemBuilder {
+        ItemBuilder::default()
+    }
+
+    /// The item's password, falling back to a field named `password` for
+    /// backends that can't tell it apart from other fields.
+    #[must_use]
+    pub fn password(&self) -> Option<&ItemField> {
+        self.find(ItemFieldType::Password, "password")
+    }
+
+    /// The item's username, falling back to a field named `username` for
+    /// backends that can't tell it apart from other fields.
+    #[must_use]
+    pub fn username(&self) -> Option<&ItemField> {
+        self.find(ItemFieldType::Username, "username")
+    }
+
+    /// Debug formats the item with every value shown, for tests. The item's
+    /// own `Debug` hides secrets.
+    #[must_use]
+    pub fn debug_unredacted(&self) -> Unredacted<'_, Self> {
+        Unredacted(self)
+    }
+
+    fn find(&self, field_type: ItemFieldType, name: &str) -> Option<&ItemField> {
+        self.fields
+            .iter()
+            .find(|v| v.field_type == field_type)
+            .or_else(|| {
+                self.fields
+                    .iter()
+                    .find(|v| v.name.eq_ignore_ascii_case(name))
+            })
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct ItemField {
     pub name: String,
     pub field_type: ItemFieldType,
-    pub value: String,
+    /// Kept as a [`Secret`] whatever the field's type, as most of them are
+    /// concealed and any of them might be.
+    pub value: Secret,
 }
 
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
+impl ItemField {
+    /// A field of unknown type, see [`ItemField::with_type`].
+    #[must_use]
+    pub fn new(name: impl Into<String>, value: impl Into<Secret>) -> Self {
+        Self {
+            name: name.into(),
+            field_type: ItemFieldType::Unknown,
+            value: value.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_type(mut self, field_type: ItemFieldType) -> Self {
+        self.field_type = field_type;
+        self
+    }
+
+    /// Whether the value's a secret, rather than something like a username
+    /// or URL. Backends that can't tell fields apart still name their
+    /// passwords.
+    #[must_use]
+    pub fn is_secret(&self) -> bool {
+        match self.field_type {
+            ItemFieldType::Password
+            | ItemFieldType::Concealed
+            | ItemFieldType::Totp
+            | ItemFieldType::SshKey => true,
+            ItemFieldType::Unknown => self.name.eq_ignore_ascii_case("password"),
+            _ => false,
+        }
+    }
+
+    /// Debug formats the field with its value shown even if it's a secret,
+    /// for tests.
+    #[must_use]
+    pub fn debug_unredacted(&self) -> Unredacted<'_, Self> {
+        Unredacted(self)
+    }
+}
+
+/// Shows the value unless it's a secret.
+impl std::fmt::Debug for ItemField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value: &dyn std::fmt::Debug = if self.is_secret() {
+            &self.value
+        } else {
+            &self.value.as_str()
+        };
+
+        f.debug_struct("ItemField")
+            .field("name", &self.name)
+            .field("field_type", &self.field_type)
+            .field("value", value)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ItemFieldType {
     Totp,
+    /// An SSH private key, in `OpenSSH` format where the backend allows it.
+    SshKey,
+    /// The password an item is for, as opposed to any other secrets in it.
+    Password,
+    Username,
+    Email,
+    Url,
+    Date,
+    Phone,
+    Address,
+    /// A secret other than the item's password, such as a PIN or API key.
+    Concealed,
+    Note,
     Unknown,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct DocumentMetadata {
+    pub uuid: String,
+    pub vault_uuid: String,
+    pub title: String,
+}
+
+impl DocumentMetadata {
+    #[must_use]
+    pub fn new(
+        uuid: impl Into<String>,
+        vault_uuid: impl Into<String>,
+        title: impl Into<String>,
+    ) -> Self {
+        Self {
+            uuid: uuid.into(),
+            vault_uuid: vault_uuid.into(),
+            title: title.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct ItemSection {
     pub name: String,
     pub fields: Vec<ItemField>,
 }
 
+impl ItemSection {
+    /// An empty section, where an empty name is the item's unnamed section.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_field(mut self, field: ItemField) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    #[must_use]
+    pub fn with_fields(mut self, fields: impl IntoIterator<Item = ItemField>) -> Self {
+        self.fields.extend(fields);
+        self
+    }
+
+    /// Moves the fields of sections sharing a name into the first of them,
+    /// for backends whose items can have sections named alike, such as more
+    /// than one without a name, which [`ItemBuilder::build`] refuses.
+    #[must_use]
+    pub fn merge(sections: impl IntoIterator<Item = Self>) -> Vec<Self> {
+        let mut merged: Vec<Self> = Vec::new();
+
+        for section in sections {
+            match merged.iter_mut().find(|v| v.name == section.name) {
+                Some(existing) => existing.fields.extend(section.fields),
+                None => merged.push(section),
+            }
+        }
+
+        merged
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct ItemUrl {
+    /// What the website is, which is empty where the backend doesn't label
+    /// them.
+    pub label: String,
+    pub url: String,
+}
+
+impl ItemUrl {
+    #[must_use]
+    pub fn new(label: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            url: url.into(),
+        }
+    }
+}
+
+/// A previous value of an item's password.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct HistoricalValue {
+    pub value: Secret,
+    /// When the value was replaced, if the backend keeps track of it.
+    pub time: Option<SystemTime>,
+}
+
+impl HistoricalValue {
+    #[must_use]
+    pub fn new(value: impl Into<Secret>, time: Option<SystemTime>) -> Self {
+        Self {
+            value: value.into(),
+            time,
+        }
+    }
+}
+
+/// A file attached to an item, the contents of which can be fetched with
+/// [`Backend::get_attachment`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct AttachmentMetadata {
+    pub uuid: String,
+    pub name: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+}
+
+impl AttachmentMetadata {
+    #[must_use]
+    pub fn new(uuid: impl Into<String>, name: impl Into<String>, size: u64) -> Self {
+        Self {
+            uuid: uuid.into(),
+            name: name.into(),
+            size,
+        }
+    }
+}
+
+/// A slice of search results, see [`Backend::search_page`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Page {
+    /// Number of results to skip.
+    pub offset: usize,
+    /// Most results to return.
+    pub limit: usize,
+}
+
+impl Page {
+    #[must_use]
+    pub fn new(offset: usize, limit: usize) -> Self {
+        Self { offset, limit }
+    }
+
+    /// Picks this page out of a full list of results.
+    #[must_use]
+    pub fn slice<T>(self, results: Vec<T>) -> Vec<T> {
+        results
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit)
+            .collect()
+    }
+}
+
+/// Narrows a search down to items meeting every criterion given, see
+/// [`Backend::search_filtered`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Filter<'a> {
+    /// Only items in the vault with this uuid.
+    pub vault: Option<&'a str>,
+    /// Only items with every one of these tags, or a tag nested beneath
+    /// each, ignoring case.
+    pub tags: &'a [String],
+    pub category: Option<ItemCategory>,
+}
+
+impl Filter<'_> {
+    /// Whether there's nothing to filter on.
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self.vault.is_none() && self.tags.is_empty() && self.category.is_none()
+    }
+
+    #[must_use]
+    pub fn matches(self, item: &ItemMetadata) -> bool {
+        if self.vault.map_or(false, |v| v != item.vault_uuid) {
+            return false;
+        }
+
+        for tag in self.tags {
+            let tag = tag.to_lowercase();
+            let nested = format!("{}/", tag);
+
+            if !item.tags.iter().any(|v| {
+                let v = v.to_lowercase();
+                v == tag || v.starts_with(&nested)
+            }) {
+                return false;
+            }
+        }
+
+        self.category.map_or(true, |v| v == item.category)
+    }
+}
+
+/// Changes to make to an item, see [`Backend::edit`]. Anything left unset is
+/// left as it is.
+#[derive(Debug, Default, Clone)]
+#[non_exhaustive]
+pub struct ItemEdit {
+    pub title: Option<String>,
+    /// Fields to set by name, outside of any section. Those the item doesn't
+    /// have yet are added.
+    pub fields: Vec<ItemField>,
+}
+
+impl ItemEdit {
+    /// Whether there's nothing to change.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.fields.is_empty()
+    }
+}
+
+/// Rules for generating a new password. Backends that can't follow part of a
+/// recipe get as close to it as they can.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct PasswordRecipe {
+    pub length: usize,
+    pub letters: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    /// Avoid characters that are easily confused with each other, such as
+    /// `l`, `1` and `I`.
+    pub exclude_ambiguous: bool,
+}
+
+impl PasswordRecipe {
+    pub const LETTERS: &'static str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    pub const DIGITS: &'static str = "0123456789";
+    pub const SYMBOLS: &'static str = "!#$%&*+,.:;=?@^_~";
+    pub const AMBIGUOUS: &'static str = "Il1O0o";
+
+    /// Every character a password following this recipe may contain, for
+    /// backends that only accept a character set.
+    #[must_use]
+    pub fn characters(&self) -> String {
+        let mut characters = String::new();
+
+        if self.letters {
+            characters.push_str(Self::LETTERS);
+        }
+
+        if self.digits {
+            characters.push_str(Self::DIGITS);
+        }
+
+        if self.symbols {
+            characters.push_str(Self::SYMBOLS);
+        }
+
+        if self.exclude_ambiguous {
+            characters.retain(|c| !Self::AMBIGUOUS.contains(c));
+        }
+
+        characters
+    }
+}
+
+impl Default for PasswordRecipe {
+    fn default() -> Self {
+        Self {
+            length: 32,
+            letters: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+        }
+    }
+}
+
+/// What a backend is able to do, see [`Backend::capabilities`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Items can have one-time passwords, returned as
+    /// [`ItemFieldType::Totp`] fields.
+    pub supports_totp: bool,
+    /// Items can be created and changed rather than only read.
+    pub supports_write: bool,
+    /// Documents can be stored on their own, rather than only attached to
+    /// items.
+    pub supports_documents: bool,
+    /// `signin` can pick between several accounts.
+    pub supports_multiple_accounts: bool,
+    /// Everything is stored locally, so no network connection is needed.
+    pub offline: bool,
+}
+
+impl Default for Capabilities {
+    /// What the default implementations of [`Backend`]'s methods support.
+    fn default() -> Self {
+        Self {
+            supports_totp: true,
+            supports_write: true,
+            supports_documents: false,
+            supports_multiple_accounts: false,
+            offline: false,
+        }
+    }
+}
+
+/// Returned by backends for operations they have no way of supporting, such
+/// as documents in a backend that can only store logins.
+#[derive(Debug)]
+pub struct Unsupported(pub &'static str);
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} isn't supported by this backend", self.0)
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
 #[async_trait]
 pub trait Backend {
-    type Error;
+    /// The backend's own error, which can be sorted into a [`BackendError`]
+    /// for callers that don't know which backend they're using.
+    type Error: From<Unsupported> + Into<BackendError>;
+
+    /// What the backend is able to do, so callers can say so up front rather
+    /// than waiting for an operation to fail. The default describes the
+    /// default implementations of the other methods, backends overriding
+    /// those should override this too.
+    async fn capabilities(&self) -> Result<Capabilities, Self::Error> {
+        Ok(Capabilities::default())
+    }
 
+    async fn signin(&self, account: Option<&str>) -> Result<(), Self::Error>;
+    async fn signout(&self) -> Result<(), Self::Error>;
     async fn account(&self) -> Result<AccountMetadata, Self::Error>;
     async fn vaults(&self) -> Result<Vec<VaultMetadata>, Self::Error>;
     async fn search(&self, terms: Option<&str>) -> Result<Vec<ItemMetadata>, Self::Error>;
+
+    /// Same as `search`, but yields items as they're found so they can be
+    /// shown before the whole list has been fetched. The default waits for
+    /// `search` to finish, backends that can parse their results
+    /// incrementally should override it.
+    fn search_stream<'a>(
+        &'a self,
+        terms: Option<&'a str>,
+    ) -> BoxStream<'a, Result<ItemMetadata, Self::Error>>
+    where
+        Self: Sync,
+        Self::Error: Send,
+    {
+        futures::stream::once(self.search(terms))
+            .map_ok(|items| futures::stream::iter(items.into_iter().map(Ok)))
+            .try_flatten()
+            .boxed()
+    }
+
+    /// Same as `search`, but only returns a single page of the results. The
+    /// default fetches every result and slices them, backends that can stop
+    /// once the page is full should override it.
+    async fn search_page(
+        &self,
+        terms: Option<&str>,
+        page: Page,
+    ) -> Result<Vec<ItemMetadata>, Self::Error> {
+        Ok(page.slice(self.search(terms).await?))
+    }
+
+    /// Same as `search`, but only returns items matching `filter`. The
+    /// default filters every result of `search`, backends that can have the
+    /// filtering done for them should override it.
+    async fn search_filtered(
+        &self,
+        terms: Option<&str>,
+        filter: Filter<'_>,
+    ) -> Result<Vec<ItemMetadata>, Self::Error> {
+        let mut results = self.search(terms).await?;
+        results.retain(|v| filter.matches(v));
+
+        Ok(results)
+    }
+
     async fn get(&self, uuid: &str) -> Result<Option<Item>, Self::Error>;
+    /// Fetches several items at once, returned in the same order as `uuids`.
+    /// The default makes up to [`GET_MANY_CONCURRENCY`] calls to `get` at a
+    /// time.
+    async fn get_many(&self, uuids: &[String]) -> Result<Vec<Option<Item>>, Self::Error>
+    where
+        Self::Error: Send,
+    {
+        let gets: Vec<_> = uuids.iter().map(|uuid| self.get(uuid)).collect();
+
+        futures::stream::iter(gets)
+            .buffered(GET_MANY_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// Creates a new login with a generated password, in the vault with the
+    /// uuid `vault` or, without one, wherever the backend puts new items.
     async fn generate(
         &self,
         name: &str,
         username: Option<&str>,
         url: Option<&str>,
         tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &PasswordRecipe,
     ) -> Result<Item, Self::Error>;
+    /// Creates a new login with the given password, rather than having the
+    /// backend generate one.
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<Item, Self::Error>;
+    /// Creates an item of any category with the title, fields, sections and
+    /// urls of `item`, such as a copy of one fetched from the backend. Its
+    /// attachments and history aren't carried over.
+    async fn create_item(
+        &self,
+        _item: &Item,
+        _category: ItemCategory,
+        _tags: &[String],
+        _vault: Option<&str>,
+    ) -> Result<Item, Self::Error> {
+        Err(Unsupported("creating items other than logins").into())
+    }
+
+    async fn create_vault(&self, _name: &str) -> Result<VaultMetadata, Self::Error> {
+        Err(Unsupported("managing vaults").into())
+    }
+
+    /// Deletes a vault along with every item in it.
+    async fn delete_vault(&self, _uuid: &str) -> Result<(), Self::Error> {
+        Err(Unsupported("managing vaults").into())
+    }
+
+    async fn rename_vault(&self, _uuid: &str, _name: &str) -> Result<(), Self::Error> {
+        Err(Unsupported("managing vaults").into())
+    }
+
+    /// Changes the item with the given uuid, such as to rename it.
+    async fn edit(&self, _uuid: &str, _edit: &ItemEdit) -> Result<(), Self::Error> {
+        Err(Unsupported("editing items").into())
+    }
+
+    /// Replaces every tag on the item with the given ones.
+    async fn set_tags(&self, _uuid: &str, _tags: &[String]) -> Result<(), Self::Error> {
+        Err(Unsupported("tags").into())
+    }
+
+    async fn set_favorite(&self, _uuid: &str, _favorite: bool) -> Result<(), Self::Error> {
+        Err(Unsupported("favorites").into())
+    }
+
+    /// Sets the item's one-time password, given as an `otpauth://` URI,
+    /// replacing any it already has.
+    async fn set_otp(&self, _uuid: &str, _uri: &str) -> Result<(), Self::Error> {
+        Err(Unsupported("editing one-time passwords").into())
+    }
+
+    /// Lists archived items, which are never returned by `search`.
+    async fn archived(&self) -> Result<Vec<ItemMetadata>, Self::Error> {
+        Err(Unsupported("archiving").into())
+    }
+
+    async fn archive(&self, _uuid: &str) -> Result<(), Self::Error> {
+        Err(Unsupported("archiving").into())
+    }
+
+    async fn restore(&self, _uuid: &str) -> Result<(), Self::Error> {
+        Err(Unsupported("restoring archived items").into())
+    }
+
+    async fn documents(&self) -> Result<Vec<DocumentMetadata>, Self::Error> {
+        Err(Unsupported("documents").into())
+    }
+
+    async fn get_document(&self, _uuid: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        Err(Unsupported("documents").into())
+    }
+
+    /// Uploads the file at `path` as a document in `vault`, by uuid, or the
+    /// backend's default vault.
+    async fn create_document(
+        &self,
+        _path: &Path,
+        _title: Option<&str>,
+        _vault: Option<&str>,
+    ) -> Result<DocumentMetadata, Self::Error> {
+        Err(Unsupported("documents").into())
+    }
+
+    async fn get_attachment(
+        &self,
+        _item_uuid: &str,
+        _attachment_uuid: &str,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        Err(Unsupported("attachments").into())
+    }
+
+    /// Fetches every item along with its metadata. The default passes every
+    /// item returned by `search` to `get_many`, backends that can fetch full
+    /// items in bulk should override it.
+    async fn get_all(&self) -> Result<Vec<(ItemMetadata, Item)>, Self::Error>
+    where
+        Self::Error: Send,
+    {
+        let results = self.search(None).await?;
+        let uuids: Vec<_> = results.iter().map(|v| v.uuid.clone()).collect();
+        let items = self.get_many(&uuids).await?;
+
+        Ok(results
+            .into_iter()
+            .zip(items)
+            .filter_map(|(metadata, item)| item.map(|item| (metadata, item)))
+            .collect())
+    }
 }
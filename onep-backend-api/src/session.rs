@@ -0,0 +1,61 @@
+//! Helpers for backends that cache a session token on disk between
+//! invocations of the cli.
+
+use std::{io, path::PathBuf};
+
+/// Path the session token for the named backend is cached at. The runtime dir
+/// is preferred as it's private to the user and cleared on logout.
+#[must_use]
+pub fn path(backend: &str) -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .map(|v| v.join("1p").join(format!("{}-session", backend)))
+}
+
+/// Reads the cached session token for the named backend, if there is one.
+#[must_use]
+pub fn read(backend: &str) -> Option<String> {
+    let session = std::fs::read_to_string(path(backend)?).ok()?;
+    let session = session.trim();
+
+    if session.is_empty() {
+        None
+    } else {
+        Some(session.to_string())
+    }
+}
+
+/// Caches a session token for the named backend, readable only by the
+/// current user.
+pub fn write(backend: &str, session: &str) -> io::Result<()> {
+    use std::io::Write;
+
+    let path = path(backend)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+
+    let mut dir = std::fs::DirBuilder::new();
+    dir.recursive(true);
+    let mut file = std::fs::OpenOptions::new();
+    file.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+        dir.mode(0o700);
+        file.mode(0o600);
+    }
+
+    if let Some(parent) = path.parent() {
+        dir.create(parent)?;
+    }
+
+    file.open(path)?.write_all(session.as_bytes())
+}
+
+/// Removes the cached session token for the named backend.
+pub fn remove(backend: &str) -> io::Result<()> {
+    match path(backend).map(std::fs::remove_file) {
+        Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
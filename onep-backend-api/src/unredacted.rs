@@ -0,0 +1,54 @@
+//! Debug formatting of items with their secrets shown, which their own
+//! `Debug` implementations leave out.
+
+use crate::{HistoricalValue, Item, ItemField, ItemSection};
+use std::fmt;
+
+/// Debug formats the item or field it wraps with every value shown, see
+/// [`Item::debug_unredacted`].
+pub struct Unredacted<'a, T>(pub(crate) &'a T);
+
+fn all<T>(values: &[T]) -> Vec<Unredacted<'_, T>> {
+    values.iter().map(Unredacted).collect()
+}
+
+impl fmt::Debug for Unredacted<'_, Item> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Item")
+            .field("title", &self.0.title)
+            .field("fields", &all(&self.0.fields))
+            .field("sections", &all(&self.0.sections))
+            .field("urls", &self.0.urls)
+            .field("attachments", &self.0.attachments)
+            .field("history", &all(&self.0.history))
+            .finish()
+    }
+}
+
+impl fmt::Debug for Unredacted<'_, ItemSection> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ItemSection")
+            .field("name", &self.0.name)
+            .field("fields", &all(&self.0.fields))
+            .finish()
+    }
+}
+
+impl fmt::Debug for Unredacted<'_, ItemField> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ItemField")
+            .field("name", &self.0.name)
+            .field("field_type", &self.0.field_type)
+            .field("value", &self.0.value.as_str())
+            .finish()
+    }
+}
+
+impl fmt::Debug for Unredacted<'_, HistoricalValue> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HistoricalValue")
+            .field("value", &self.0.value.as_str())
+            .field("time", &self.0.time)
+            .finish()
+    }
+}
@@ -0,0 +1,612 @@
+//! A backend for the [bw] tool distributed by Bitwarden. This crate uses
+//! `onep_backend_api::Backend` to provide an implementation of a Bitwarden
+//! backend for use 1p-cli.
+//!
+//! Bitwarden has no concept of vaults for personal accounts, so folders are
+//! exposed as vaults instead.
+//!
+//! [bw]: https://bitwarden.com/help/article/cli/
+
+#![deny(clippy::pedantic)]
+#![allow(clippy::used_underscore_binding)]
+
+use async_trait::async_trait;
+use onep_backend_api as api;
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, process::Stdio, sync::RwLock, time::SystemTime};
+use tokio::process::Command;
+
+/// Vault uuid given to items that aren't in any folder.
+const NO_FOLDER: &str = "";
+
+/// Type bw gives custom fields that are hidden by default.
+const BW_FIELD_HIDDEN: u8 = 1;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("bw backend returned an error:\n{0}")]
+    Backend(String),
+    #[error("failed to exec backend:\n{0}")]
+    Exec(std::io::Error),
+    #[error("bw unlock failed, check your credentials and try again")]
+    Signin,
+    #[error("failed to cache bw session token:\n{0}")]
+    Session(std::io::Error),
+    #[error("failed to parse json from bw:\n{0}")]
+    Json(#[from] serde_json::error::Error),
+    #[error("failed to convert bw response to utf-8:\n{0}")]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error("bw returned an item that isn't valid:\n{0}")]
+    InvalidItem(#[from] api::InvalidItem),
+    #[error(transparent)]
+    Unsupported(#[from] api::Unsupported),
+}
+
+impl From<Error> for api::BackendError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Signin => Self::AuthRequired(e.into()),
+            Error::Json(_) | Error::Utf8(_) | Error::InvalidItem(_) => Self::Parse(e.into()),
+            Error::Unsupported(e) => Self::Unsupported(e),
+            _ => Self::Other(e.into()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetStatus {
+    server_url: Option<String>,
+    user_email: Option<String>,
+    status: String,
+}
+
+impl Into<api::AccountMetadata> for GetStatus {
+    fn into(self) -> api::AccountMetadata {
+        api::AccountMetadata::new(
+            self.user_email.unwrap_or_default(),
+            self.server_url
+                .unwrap_or_else(|| "bitwarden.com".to_string()),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ListFolder {
+    id: Option<String>,
+    name: String,
+}
+
+impl Into<api::VaultMetadata> for ListFolder {
+    fn into(self) -> api::VaultMetadata {
+        api::VaultMetadata::new(self.id.unwrap_or_else(|| NO_FOLDER.to_string()), self.name)
+    }
+}
+
+/// Parses one of the RFC 3339 timestamps bw gives items.
+fn timestamp(v: &str) -> Option<SystemTime> {
+    chrono::DateTime::parse_from_rfc3339(v).ok().map(Into::into)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BwItem {
+    #[serde(skip_serializing)]
+    id: String,
+    folder_id: Option<String>,
+    #[serde(rename = "type")]
+    item_type: u8,
+    name: String,
+    notes: Option<String>,
+    login: Option<BwLogin>,
+    #[serde(default)]
+    fields: Vec<BwField>,
+    #[serde(default, skip_serializing)]
+    attachments: Vec<BwAttachment>,
+    #[serde(default)]
+    favorite: bool,
+    #[serde(default, skip_serializing)]
+    deleted_date: Option<String>,
+    #[serde(default, skip_serializing)]
+    creation_date: Option<String>,
+    #[serde(default, skip_serializing)]
+    revision_date: Option<String>,
+    #[serde(default, skip_serializing)]
+    password_history: Option<Vec<BwPasswordHistory>>,
+}
+
+impl BwItem {
+    fn username(&self) -> &str {
+        self.login
+            .as_ref()
+            .and_then(|v| v.username.as_deref())
+            .unwrap_or_default()
+    }
+
+    fn metadata(&self) -> api::ItemMetadata {
+        let mut metadata = api::ItemMetadata::new(
+            self.id.clone(),
+            self.folder_id
+                .clone()
+                .unwrap_or_else(|| NO_FOLDER.to_string()),
+            self.name.clone(),
+        );
+        metadata.account_info = self.username().to_string();
+        metadata.urls = self
+            .login
+            .iter()
+            .flat_map(|v| v.uris.iter().map(|v| v.uri.clone()))
+            .collect();
+        metadata.favorite = self.favorite;
+        metadata.archived = self.deleted_date.is_some();
+        metadata.created = self.creation_date.as_deref().and_then(timestamp);
+        metadata.updated = self.revision_date.as_deref().and_then(timestamp);
+        metadata.category = match self.item_type {
+            1 => api::ItemCategory::Login,
+            2 => api::ItemCategory::SecureNote,
+            3 => api::ItemCategory::CreditCard,
+            4 => api::ItemCategory::Identity,
+            _ => api::ItemCategory::Unknown,
+        };
+        metadata
+    }
+}
+
+impl Into<api::ItemMetadata> for BwItem {
+    fn into(self) -> api::ItemMetadata {
+        self.metadata()
+    }
+}
+
+impl TryFrom<BwItem> for api::Item {
+    type Error = api::InvalidItem;
+
+    fn try_from(item: BwItem) -> Result<Self, Self::Error> {
+        let mut fields = Vec::new();
+        let mut extra = Vec::new();
+        let mut urls = Vec::new();
+
+        if let Some(login) = item.login {
+            urls.extend(login.uris.into_iter().map(|v| api::ItemUrl::new("", v.uri)));
+            fields.extend(login.username.map(|value| {
+                api::ItemField::new("username", value).with_type(api::ItemFieldType::Username)
+            }));
+            fields.extend(login.password.map(|value| {
+                api::ItemField::new("password", value).with_type(api::ItemFieldType::Password)
+            }));
+            extra.extend(login.totp.map(|value| {
+                api::ItemField::new("one-time password", value).with_type(api::ItemFieldType::Totp)
+            }));
+        }
+
+        fields.extend(
+            item.notes.map(|value| {
+                api::ItemField::new("notes", value).with_type(api::ItemFieldType::Note)
+            }),
+        );
+
+        extra.extend(item.fields.into_iter().map(|f| {
+            api::ItemField::new(f.name, f.value.unwrap_or_default()).with_type(
+                if f.field_type == BW_FIELD_HIDDEN {
+                    api::ItemFieldType::Concealed
+                } else {
+                    api::ItemFieldType::Unknown
+                },
+            )
+        }));
+
+        api::Item::builder()
+            .title(item.name)
+            .fields(fields.into_iter().filter(|f| !f.value.is_empty()))
+            .section(
+                api::ItemSection::new("")
+                    .with_fields(extra.into_iter().filter(|f| !f.value.is_empty())),
+            )
+            .urls(urls)
+            .attachments(item.attachments.into_iter().map(Into::into))
+            .history(
+                item.password_history
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Into::into),
+            )
+            .build()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct BwLogin {
+    #[serde(default)]
+    uris: Vec<BwLoginUri>,
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct BwLoginUri {
+    uri: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct BwField {
+    name: String,
+    value: Option<String>,
+    #[serde(rename = "type", default)]
+    field_type: u8,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BwPasswordHistory {
+    last_used_date: String,
+    password: String,
+}
+
+impl Into<api::HistoricalValue> for BwPasswordHistory {
+    fn into(self) -> api::HistoricalValue {
+        api::HistoricalValue::new(self.password, timestamp(&self.last_used_date))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BwAttachment {
+    id: String,
+    file_name: String,
+    // bw sends the size in bytes as a string
+    #[serde(default)]
+    size: String,
+}
+
+impl Into<api::AttachmentMetadata> for BwAttachment {
+    fn into(self) -> api::AttachmentMetadata {
+        api::AttachmentMetadata::new(
+            self.id,
+            self.file_name,
+            self.size.parse().unwrap_or_default(),
+        )
+    }
+}
+
+pub struct BwBackend {
+    session: RwLock<Option<String>>,
+}
+
+impl Default for BwBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BwBackend {
+    /// Creates a new backend, reusing the session token cached by a previous
+    /// `signin` if there is one. Without a cached token bw falls back to the
+    /// `BW_SESSION` variable in the environment.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            session: RwLock::new(api::session::read("bw")),
+        }
+    }
+
+    fn session(&self) -> Option<String> {
+        self.session.read().unwrap().clone()
+    }
+
+    async fn exec<I, S>(&self, args: I) -> Result<Vec<u8>, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let mut cmd = Command::new("bw");
+        cmd.args(args).arg("--nointeraction").kill_on_drop(true);
+
+        if let Some(session) = self.session() {
+            cmd.arg("--session").arg(session);
+        }
+
+        let cmd = cmd.output().await.map_err(Error::Exec)?;
+
+        if cmd.status.success() {
+            Ok(cmd.stdout)
+        } else {
+            Err(Error::Backend(
+                std::str::from_utf8(&cmd.stderr)?.trim().to_string(),
+            ))
+        }
+    }
+
+    async fn list_items(&self) -> Result<Vec<BwItem>, Error> {
+        Ok(serde_json::from_slice(
+            &self.exec(&["list", "items"]).await?,
+        )?)
+    }
+}
+
+#[async_trait]
+impl api::Backend for BwBackend {
+    type Error = Error;
+
+    async fn signin(&self, account: Option<&str>) -> Result<(), Self::Error> {
+        let status: GetStatus = serde_json::from_slice(&self.exec(&["status"]).await?)?;
+
+        let mut cmd = Command::new("bw");
+
+        // a vault that's never been logged into needs a full login before it
+        // can be unlocked, both return a session token.
+        if status.status == "unauthenticated" {
+            cmd.arg("login");
+
+            if let Some(account) = account {
+                cmd.arg(account);
+            }
+        } else {
+            cmd.arg("unlock");
+        }
+
+        // bw prompts for the master password on the tty, so only stdout (where
+        // the raw token is written) is captured.
+        let cmd = cmd
+            .arg("--raw")
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .output()
+            .await
+            .map_err(Error::Exec)?;
+
+        if !cmd.status.success() {
+            return Err(Error::Signin);
+        }
+
+        let session = std::str::from_utf8(&cmd.stdout)?.trim().to_string();
+        api::session::write("bw", &session).map_err(Error::Session)?;
+        *self.session.write().unwrap() = Some(session);
+
+        Ok(())
+    }
+
+    async fn signout(&self) -> Result<(), Self::Error> {
+        self.exec(&["lock"]).await?;
+
+        api::session::remove("bw").map_err(Error::Session)?;
+        *self.session.write().unwrap() = None;
+
+        Ok(())
+    }
+
+    async fn account(&self) -> Result<api::AccountMetadata, Self::Error> {
+        let ret: GetStatus = serde_json::from_slice(&self.exec(&["status"]).await?)?;
+
+        Ok(ret.into())
+    }
+
+    async fn vaults(&self) -> Result<Vec<api::VaultMetadata>, Self::Error> {
+        let ret: Vec<ListFolder> = serde_json::from_slice(&self.exec(&["list", "folders"]).await?)?;
+
+        Ok(ret.into_iter().map(|v| v.into()).collect())
+    }
+
+    async fn search(&self, terms: Option<&str>) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        let ret = self.list_items().await?;
+
+        let terms = terms.map(str::to_lowercase);
+
+        Ok(ret
+            .into_iter()
+            .filter(|v| {
+                if let Some(terms) = &terms {
+                    let terms = terms.as_ref();
+
+                    v.id == terms
+                        || v.folder_id.as_deref().unwrap_or(NO_FOLDER) == terms
+                        || v.login
+                            .iter()
+                            .flat_map(|v| &v.uris)
+                            .any(|v| v.uri.to_lowercase().contains(terms))
+                        || v.name.to_lowercase().contains(terms)
+                        || v.username().to_lowercase().contains(terms)
+                } else {
+                    true
+                }
+            })
+            .map(|v| v.into())
+            .collect())
+    }
+
+    async fn get(&self, uuid: &str) -> Result<Option<api::Item>, Self::Error> {
+        match self.exec(&["get", "item", uuid]).await {
+            Ok(ret) => Ok(Some(api::Item::try_from(
+                serde_json::from_slice::<BwItem>(&ret)?,
+            )?)),
+            Err(Error::Backend(e)) if e == "Not found." => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn generate(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &api::PasswordRecipe,
+    ) -> Result<api::Item, Self::Error> {
+        let length = recipe.length.to_string();
+        let mut args = vec!["generate", "--length", &length];
+
+        if recipe.letters {
+            args.push("--uppercase");
+            args.push("--lowercase");
+        }
+
+        if recipe.digits {
+            args.push("--number");
+        }
+
+        if recipe.symbols {
+            args.push("--special");
+        }
+
+        if recipe.exclude_ambiguous {
+            args.push("--ambiguous");
+        }
+
+        let password = self.exec(&args).await?;
+
+        self.create(
+            name,
+            username,
+            url,
+            tags,
+            vault,
+            std::str::from_utf8(&password)?.trim(),
+        )
+        .await
+    }
+
+    /// Bitwarden has no concept of tags, so they're ignored when creating the
+    /// item. Vaults are folders, so the item is put in the folder `vault`.
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        _tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<api::Item, Self::Error> {
+        let item = BwItem {
+            id: String::new(),
+            folder_id: vault.filter(|v| *v != NO_FOLDER).map(ToString::to_string),
+            item_type: 1,
+            name: name.to_string(),
+            notes: None,
+            login: Some(BwLogin {
+                uris: url
+                    .map(|uri| BwLoginUri {
+                        uri: uri.to_string(),
+                    })
+                    .into_iter()
+                    .collect(),
+                username: username.map(ToString::to_string),
+                password: Some(password.to_string()),
+                totp: None,
+            }),
+            fields: Vec::new(),
+            attachments: Vec::new(),
+            favorite: false,
+            deleted_date: None,
+            creation_date: None,
+            revision_date: None,
+            password_history: None,
+        };
+
+        // bw takes the item to create as base64 encoded json
+        let encoded = base64::encode(serde_json::to_vec(&item)?);
+        let ret: BwItem = serde_json::from_slice(&self.exec(&["create", "item", &encoded]).await?)?;
+
+        Ok(api::Item::try_from(ret)?)
+    }
+
+    async fn create_vault(&self, name: &str) -> Result<api::VaultMetadata, Self::Error> {
+        let encoded = base64::encode(serde_json::to_vec(&serde_json::json!({ "name": name }))?);
+        let ret: ListFolder =
+            serde_json::from_slice(&self.exec(&["create", "folder", &encoded]).await?)?;
+
+        Ok(ret.into())
+    }
+
+    /// Deleting a folder in Bitwarden leaves its items in place, they're just
+    /// no longer in any folder.
+    async fn delete_vault(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.exec(&["delete", "folder", uuid]).await?;
+        Ok(())
+    }
+
+    async fn rename_vault(&self, uuid: &str, name: &str) -> Result<(), Self::Error> {
+        let encoded = base64::encode(serde_json::to_vec(&serde_json::json!({ "name": name }))?);
+        self.exec(&["edit", "folder", uuid, &encoded]).await?;
+        Ok(())
+    }
+
+    async fn set_favorite(&self, uuid: &str, favorite: bool) -> Result<(), Self::Error> {
+        // bw edit replaces the whole item, so the raw json is edited rather
+        // than going through BwItem which only knows about a subset of fields
+        let mut item: serde_json::Value =
+            serde_json::from_slice(&self.exec(&["get", "item", uuid]).await?)?;
+        item["favorite"] = serde_json::Value::Bool(favorite);
+
+        let encoded = base64::encode(serde_json::to_vec(&item)?);
+        self.exec(&["edit", "item", uuid, &encoded]).await?;
+
+        Ok(())
+    }
+
+    /// Only logins have somewhere to keep a one-time password.
+    async fn set_otp(&self, uuid: &str, uri: &str) -> Result<(), Self::Error> {
+        let mut item: serde_json::Value =
+            serde_json::from_slice(&self.exec(&["get", "item", uuid]).await?)?;
+
+        if !item["login"].is_object() {
+            return Err(api::Unsupported("one-time passwords on items other than logins").into());
+        }
+
+        item["login"]["totp"] = serde_json::Value::String(uri.to_string());
+
+        let encoded = base64::encode(serde_json::to_vec(&item)?);
+        self.exec(&["edit", "item", uuid, &encoded]).await?;
+
+        Ok(())
+    }
+
+    /// Bitwarden has no archive, so the trash is used in its place.
+    async fn archived(&self) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        let ret: Vec<BwItem> =
+            serde_json::from_slice(&self.exec(&["list", "items", "--trash"]).await?)?;
+        Ok(ret.into_iter().map(|v| v.into()).collect())
+    }
+
+    async fn archive(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.exec(&["delete", "item", uuid]).await?;
+        Ok(())
+    }
+
+    async fn restore(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.exec(&["restore", "item", uuid]).await?;
+        Ok(())
+    }
+
+    async fn get_attachment(
+        &self,
+        item_uuid: &str,
+        attachment_uuid: &str,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        match self
+            .exec(&[
+                "get",
+                "attachment",
+                attachment_uuid,
+                "--itemid",
+                item_uuid,
+                "--raw",
+            ])
+            .await
+        {
+            Ok(ret) => Ok(Some(ret)),
+            Err(Error::Backend(e)) if e == "Not found." => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    /// `bw list items` already returns every item in full, so there's no need
+    /// to fetch them one at a time.
+    async fn get_all(&self) -> Result<Vec<(api::ItemMetadata, api::Item)>, Self::Error> {
+        Ok(self
+            .list_items()
+            .await?
+            .into_iter()
+            .map(|v| Ok((v.metadata(), api::Item::try_from(v)?)))
+            .collect::<Result<_, api::InvalidItem>>()?)
+    }
+}
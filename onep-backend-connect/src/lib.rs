@@ -0,0 +1,572 @@
+//! A backend for [1Password Connect] servers. This crate uses
+//! `onep_backend_api::Backend` to provide an implementation of a 1password
+//! backend for use 1p-cli that talks to the Connect REST API directly rather
+//! than shelling out to `op`.
+//!
+//! [1Password Connect]: https://support.1password.com/secrets-automation/
+
+#![deny(clippy::pedantic)]
+#![allow(clippy::used_underscore_binding)]
+
+use async_trait::async_trait;
+use onep_backend_api as api;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{convert::TryFrom, time::SystemTime};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("connect server returned an error:\n{0}")]
+    Backend(String),
+    #[error("connect server didn't accept the token:\n{0}")]
+    Unauthorized(String),
+    #[error("the token doesn't have permission to do that:\n{0}")]
+    Forbidden(String),
+    #[error("connect server has no item with uuid {0}")]
+    ItemNotFound(String),
+    #[error("failed to send request to connect server:\n{0}")]
+    Http(#[from] reqwest::Error),
+    #[error("connect server has no vaults this token can write to")]
+    NoVault,
+    #[error("connect server returned an item that isn't valid:\n{0}")]
+    InvalidItem(#[from] api::InvalidItem),
+    #[error(transparent)]
+    Unsupported(#[from] api::Unsupported),
+}
+
+impl From<Error> for api::BackendError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Unauthorized(_) => Self::AuthRequired(e.into()),
+            Error::Forbidden(_) => Self::PermissionDenied(e.into()),
+            Error::ItemNotFound(_) => Self::NotFound(e.into()),
+            Error::Http(ref http) if http.is_connect() || http.is_timeout() => {
+                Self::Network(e.into())
+            }
+            Error::Http(ref http) if http.is_decode() => Self::Parse(e.into()),
+            Error::InvalidItem(_) => Self::Parse(e.into()),
+            Error::Unsupported(e) => Self::Unsupported(e),
+            _ => Self::Other(e.into()),
+        }
+    }
+}
+
+/// Parses one of the RFC 3339 timestamps the connect server gives items.
+fn timestamp(v: &str) -> Option<SystemTime> {
+    chrono::DateTime::parse_from_rfc3339(v).ok().map(Into::into)
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListVault {
+    id: String,
+    name: String,
+}
+
+impl Into<api::VaultMetadata> for ListVault {
+    fn into(self) -> api::VaultMetadata {
+        api::VaultMetadata::new(self.id, self.name)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct VaultRef {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListItem {
+    id: String,
+    title: String,
+    vault: VaultRef,
+    #[serde(default)]
+    urls: Vec<ItemUrl>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    favorite: bool,
+    #[serde(rename = "createdAt", default)]
+    created_at: String,
+    #[serde(rename = "updatedAt", default)]
+    updated_at: String,
+    #[serde(default)]
+    category: String,
+}
+
+impl Into<api::ItemMetadata> for ListItem {
+    fn into(self) -> api::ItemMetadata {
+        let mut metadata = api::ItemMetadata::new(self.id, self.vault.id, self.title);
+        metadata.urls = self.urls.into_iter().map(|v| v.href).collect();
+        metadata.tags = self.tags;
+        metadata.favorite = self.favorite;
+        metadata.created = timestamp(&self.created_at);
+        metadata.updated = timestamp(&self.updated_at);
+        metadata.category = self.category.parse().unwrap_or(api::ItemCategory::Unknown);
+        metadata
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ItemUrl {
+    href: String,
+    #[serde(default)]
+    primary: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItem {
+    title: String,
+    #[serde(default)]
+    sections: Vec<GetItemSection>,
+    #[serde(default)]
+    fields: Vec<GetItemField>,
+    #[serde(default)]
+    urls: Vec<ItemUrl>,
+}
+
+impl TryFrom<GetItem> for api::Item {
+    type Error = api::InvalidItem;
+
+    fn try_from(item: GetItem) -> Result<Self, Self::Error> {
+        let GetItem {
+            title,
+            sections: raw_sections,
+            fields: raw_fields,
+            urls,
+        } = item;
+
+        let mut fields = Vec::new();
+        let mut sections: Vec<_> = raw_sections
+            .iter()
+            .map(|v| api::ItemSection::new(v.label.clone().unwrap_or_default()))
+            .collect();
+
+        for field in raw_fields {
+            let section = field.section.as_ref().and_then(|section| {
+                raw_sections
+                    .iter()
+                    .position(|v| v.id == section.id)
+                    .map(|idx| &mut sections[idx])
+            });
+
+            let field: api::ItemField = field.into();
+
+            if field.value.is_empty() {
+                continue;
+            }
+
+            match section {
+                Some(section) => section.fields.push(field),
+                None => fields.push(field),
+            }
+        }
+
+        api::Item::builder()
+            .title(title)
+            .fields(fields)
+            .sections(api::ItemSection::merge(sections))
+            .urls(
+                urls.into_iter()
+                    .map(|v| api::ItemUrl::new(v.label.unwrap_or_default(), v.href)),
+            )
+            .build()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemSection {
+    id: String,
+    label: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemField {
+    section: Option<GetItemFieldSection>,
+    #[serde(rename = "type", default)]
+    field_type: String,
+    purpose: Option<String>,
+    label: Option<String>,
+    value: Option<String>,
+}
+
+impl Into<api::ItemField> for GetItemField {
+    fn into(self) -> api::ItemField {
+        let purpose = self.purpose;
+
+        let field_type = match (purpose.as_deref(), self.field_type.as_str()) {
+            (Some("PASSWORD"), _) => api::ItemFieldType::Password,
+            (Some("USERNAME"), _) => api::ItemFieldType::Username,
+            (Some("NOTES"), _) => api::ItemFieldType::Note,
+            (_, "OTP") => api::ItemFieldType::Totp,
+            (_, "SSHKEY") => api::ItemFieldType::SshKey,
+            (_, "CONCEALED") => api::ItemFieldType::Concealed,
+            (_, "EMAIL") => api::ItemFieldType::Email,
+            (_, "URL") => api::ItemFieldType::Url,
+            (_, kind) if kind == "DATE" || kind == "MONTH_YEAR" => api::ItemFieldType::Date,
+            (_, "PHONE") => api::ItemFieldType::Phone,
+            (_, "ADDRESS") => api::ItemFieldType::Address,
+            _ => api::ItemFieldType::Unknown,
+        };
+        let name = self
+            .label
+            .or_else(|| purpose.map(|v| v.to_lowercase()))
+            .unwrap_or_default();
+
+        api::ItemField::new(name, self.value.unwrap_or_default()).with_type(field_type)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemFieldSection {
+    id: String,
+}
+
+/// A single JSON Patch operation, as accepted by `PATCH` on an item.
+#[derive(Debug, Serialize)]
+struct PatchOperation<'a, T> {
+    op: &'a str,
+    path: &'a str,
+    value: T,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateItem<'a> {
+    vault: VaultRef,
+    title: &'a str,
+    category: &'a str,
+    urls: Vec<ItemUrl>,
+    tags: Vec<&'a str>,
+    fields: Vec<CreateItemField<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateItemField<'a> {
+    purpose: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<&'a str>,
+    generate: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recipe: Option<GeneratorRecipe>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeneratorRecipe {
+    length: usize,
+    character_sets: Vec<&'static str>,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    exclude_characters: &'static str,
+}
+
+impl From<&api::PasswordRecipe> for GeneratorRecipe {
+    fn from(recipe: &api::PasswordRecipe) -> Self {
+        let mut character_sets = Vec::with_capacity(3);
+
+        if recipe.letters {
+            character_sets.push("LETTERS");
+        }
+
+        if recipe.digits {
+            character_sets.push("DIGITS");
+        }
+
+        if recipe.symbols {
+            character_sets.push("SYMBOLS");
+        }
+
+        Self {
+            length: recipe.length,
+            character_sets,
+            exclude_characters: if recipe.exclude_ambiguous {
+                api::PasswordRecipe::AMBIGUOUS
+            } else {
+                ""
+            },
+        }
+    }
+}
+
+pub struct ConnectBackend {
+    client: reqwest::Client,
+    host: String,
+    token: String,
+}
+
+impl ConnectBackend {
+    /// Creates a new backend for the Connect server at `host`, authenticating
+    /// with the given bearer token.
+    #[must_use]
+    pub fn new(host: &str, token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            host: host.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Option<T>, Error> {
+        let res = request.bearer_auth(&self.token).send().await?;
+
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            Ok(None)
+        } else if res.status().is_success() {
+            Ok(Some(res.json().await?))
+        } else {
+            let status = res.status();
+            let message = res
+                .json::<ErrorResponse>()
+                .await
+                .map_or_else(|_| status.to_string(), |v| v.message);
+
+            Err(match status {
+                reqwest::StatusCode::UNAUTHORIZED => Error::Unauthorized(message),
+                reqwest::StatusCode::FORBIDDEN => Error::Forbidden(message),
+                _ => Error::Backend(message),
+            })
+        }
+    }
+
+    async fn fetch<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, Error> {
+        self.send(self.client.get(&format!("{}{}", self.host, path)))
+            .await
+    }
+
+    async fn list_vaults(&self) -> Result<Vec<ListVault>, Error> {
+        Ok(self.fetch("/v1/vaults").await?.unwrap_or_default())
+    }
+
+    async fn list_items(&self) -> Result<Vec<ListItem>, Error> {
+        let mut items = Vec::new();
+
+        for vault in self.list_vaults().await? {
+            let ret: Option<Vec<ListItem>> = self
+                .fetch(&format!("/v1/vaults/{}/items", vault.id))
+                .await?;
+            items.extend(ret.unwrap_or_default());
+        }
+
+        Ok(items)
+    }
+
+    /// Replaces a single attribute of an item.
+    async fn patch_item<T: Serialize>(
+        &self,
+        uuid: &str,
+        path: &str,
+        value: T,
+    ) -> Result<(), Error> {
+        let item = self
+            .list_items()
+            .await?
+            .into_iter()
+            .find(|v| v.id == uuid)
+            .ok_or_else(|| Error::ItemNotFound(uuid.to_string()))?;
+
+        let operations = [PatchOperation {
+            op: "replace",
+            path,
+            value,
+        }];
+
+        let _: Option<GetItem> = self
+            .send(
+                self.client
+                    .patch(&format!(
+                        "{}/v1/vaults/{}/items/{}",
+                        self.host, item.vault.id, uuid
+                    ))
+                    .json(&operations),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Creates a login in `vault`, or the first vault the token can see.
+    async fn create_login(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: CreateItemField<'_>,
+    ) -> Result<api::Item, Error> {
+        let vault = match vault {
+            Some(vault) => vault.to_string(),
+            None => {
+                self.list_vaults()
+                    .await?
+                    .into_iter()
+                    .next()
+                    .ok_or(Error::NoVault)?
+                    .id
+            }
+        };
+
+        let mut fields = vec![password];
+
+        if let Some(username) = username {
+            fields.push(CreateItemField {
+                purpose: "USERNAME",
+                value: Some(username),
+                generate: false,
+                recipe: None,
+            });
+        }
+
+        let body = CreateItem {
+            vault: VaultRef { id: vault.clone() },
+            title: name,
+            category: "LOGIN",
+            urls: url
+                .map(|href| ItemUrl {
+                    href: href.to_string(),
+                    primary: true,
+                    label: None,
+                })
+                .into_iter()
+                .collect(),
+            tags: tags.map_or_else(Vec::new, |v| v.split(',').map(str::trim).collect()),
+            fields,
+        };
+
+        let ret: Option<GetItem> = self
+            .send(
+                self.client
+                    .post(&format!("{}/v1/vaults/{}/items", self.host, vault))
+                    .json(&body),
+            )
+            .await?;
+
+        Ok(api::Item::try_from(ret.ok_or(Error::NoVault)?)?)
+    }
+}
+
+#[async_trait]
+impl api::Backend for ConnectBackend {
+    type Error = Error;
+
+    /// Connect authenticates every request with the token, so signing in only
+    /// checks the token is accepted.
+    async fn signin(&self, _account: Option<&str>) -> Result<(), Self::Error> {
+        self.list_vaults().await?;
+        Ok(())
+    }
+
+    async fn signout(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn account(&self) -> Result<api::AccountMetadata, Self::Error> {
+        Ok(api::AccountMetadata::new(
+            "1Password Connect",
+            self.host.clone(),
+        ))
+    }
+
+    async fn vaults(&self) -> Result<Vec<api::VaultMetadata>, Self::Error> {
+        Ok(self
+            .list_vaults()
+            .await?
+            .into_iter()
+            .map(|v| v.into())
+            .collect())
+    }
+
+    async fn search(&self, terms: Option<&str>) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        let ret = self.list_items().await?;
+
+        let terms = terms.map(str::to_lowercase);
+
+        Ok(ret
+            .into_iter()
+            .filter(|v| {
+                if let Some(terms) = &terms {
+                    let terms = terms.as_ref();
+
+                    v.id == terms
+                        || v.vault.id == terms
+                        || v.urls.iter().any(|v| v.href.to_lowercase().contains(terms))
+                        || v.title.to_lowercase().contains(terms)
+                        || v.tags.iter().any(|v| v.to_lowercase().contains(terms))
+                } else {
+                    true
+                }
+            })
+            .map(|v| v.into())
+            .collect())
+    }
+
+    /// Connect addresses items by vault, so every vault the token can see is
+    /// tried in turn until the item is found.
+    async fn get(&self, uuid: &str) -> Result<Option<api::Item>, Self::Error> {
+        for vault in self.list_vaults().await? {
+            let ret: Option<GetItem> = self
+                .fetch(&format!("/v1/vaults/{}/items/{}", vault.id, uuid))
+                .await?;
+
+            if let Some(ret) = ret {
+                return Ok(Some(api::Item::try_from(ret)?));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn generate(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &api::PasswordRecipe,
+    ) -> Result<api::Item, Self::Error> {
+        let password = CreateItemField {
+            purpose: "PASSWORD",
+            value: None,
+            generate: true,
+            recipe: Some(recipe.into()),
+        };
+
+        self.create_login(name, username, url, tags, vault, password)
+            .await
+    }
+
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<api::Item, Self::Error> {
+        let password = CreateItemField {
+            purpose: "PASSWORD",
+            value: Some(password),
+            generate: false,
+            recipe: None,
+        };
+
+        self.create_login(name, username, url, tags, vault, password)
+            .await
+    }
+
+    async fn set_tags(&self, uuid: &str, tags: &[String]) -> Result<(), Self::Error> {
+        self.patch_item(uuid, "/tags", tags).await
+    }
+
+    async fn set_favorite(&self, uuid: &str, favorite: bool) -> Result<(), Self::Error> {
+        self.patch_item(uuid, "/favorite", favorite).await
+    }
+}
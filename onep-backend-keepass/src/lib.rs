@@ -0,0 +1,428 @@
+//! A backend for local [KeePass] databases, driven through the
+//! `keepassxc-cli` tool distributed with `KeePassXC`. This crate uses
+//! `onep_backend_api::Backend` to provide an implementation of a `KeePass`
+//! backend for use 1p-cli that works entirely offline.
+//!
+//! Top-level groups are exposed as vaults and entries as items, keyed by
+//! their full path within the database.
+//!
+//! [KeePass]: https://keepass.info/
+
+#![deny(clippy::pedantic)]
+#![allow(clippy::used_underscore_binding)]
+
+use async_trait::async_trait;
+use onep_backend_api as api;
+use std::{path::PathBuf, process::Stdio, sync::RwLock};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+/// Vault uuid given to entries at the root of the database.
+const ROOT_VAULT: &str = "";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("keepassxc-cli returned an error:\n{0}")]
+    Backend(String),
+    #[error("failed to exec backend:\n{0}")]
+    Exec(std::io::Error),
+    #[error("failed to read database password:\n{0}")]
+    Password(std::io::Error),
+    #[error("failed to convert keepassxc-cli response to utf-8:\n{0}")]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error("the root group of the database can't be deleted")]
+    RootVault,
+    #[error("keepassxc-cli returned an entry that isn't valid:\n{0}")]
+    InvalidItem(#[from] api::InvalidItem),
+    #[error(transparent)]
+    Unsupported(#[from] api::Unsupported),
+}
+
+impl From<Error> for api::BackendError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Utf8(_) | Error::InvalidItem(_) => Self::Parse(e.into()),
+            Error::Unsupported(e) => Self::Unsupported(e),
+            _ => Self::Other(e.into()),
+        }
+    }
+}
+
+pub struct KeepassBackend {
+    database: PathBuf,
+    key_file: Option<PathBuf>,
+    password: RwLock<Option<String>>,
+}
+
+impl KeepassBackend {
+    /// Creates a new backend for the given database, optionally unlocked with
+    /// a key file. The database password is prompted for on first use and
+    /// kept in memory for the lifetime of the backend.
+    #[must_use]
+    pub fn new(database: PathBuf, key_file: Option<PathBuf>) -> Self {
+        Self {
+            database,
+            key_file,
+            password: RwLock::new(None),
+        }
+    }
+
+    fn password(&self) -> Result<String, Error> {
+        if let Some(password) = self.password.read().unwrap().as_ref() {
+            return Ok(password.clone());
+        }
+
+        let password = rpassword::read_password_from_tty(Some(&format!(
+            "Password for {}: ",
+            self.database.display()
+        )))
+        .map_err(Error::Password)?;
+
+        *self.password.write().unwrap() = Some(password.clone());
+
+        Ok(password)
+    }
+
+    async fn exec(&self, command: &str, args: &[&str]) -> Result<Vec<u8>, Error> {
+        self.exec_with_input(command, args, None).await
+    }
+
+    /// Runs a command, writing `input` to its stdin on the line after the
+    /// database password for commands that prompt more than once.
+    async fn exec_with_input(
+        &self,
+        command: &str,
+        args: &[&str],
+        input: Option<&str>,
+    ) -> Result<Vec<u8>, Error> {
+        let password = self.password()?;
+
+        let mut cmd = Command::new("keepassxc-cli");
+        cmd.arg(command).arg("--quiet");
+
+        if let Some(key_file) = &self.key_file {
+            cmd.arg("--key-file").arg(key_file);
+        }
+
+        let mut cmd = cmd
+            .arg(&self.database)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(Error::Exec)?;
+
+        if let Some(mut stdin) = cmd.stdin.take() {
+            stdin
+                .write_all(format!("{}\n", password).as_bytes())
+                .await
+                .map_err(Error::Exec)?;
+
+            if let Some(input) = input {
+                stdin
+                    .write_all(format!("{}\n", input).as_bytes())
+                    .await
+                    .map_err(Error::Exec)?;
+            }
+        }
+
+        let cmd = cmd.wait_with_output().await.map_err(Error::Exec)?;
+
+        if cmd.status.success() {
+            Ok(cmd.stdout)
+        } else {
+            Err(Error::Backend(
+                std::str::from_utf8(&cmd.stderr)?.trim().to_string(),
+            ))
+        }
+    }
+
+    /// Lists the full path of every entry in the database, excluding the
+    /// recycle bin.
+    async fn entries(&self) -> Result<Vec<String>, Error> {
+        let ret = self.exec("ls", &["--recursive", "--flatten"]).await?;
+
+        Ok(std::str::from_utf8(&ret)?
+            .lines()
+            .filter(|v| !v.is_empty() && !v.ends_with('/') && !v.starts_with("Recycle Bin/"))
+            .map(ToString::to_string)
+            .collect())
+    }
+}
+
+/// Splits an entry path into the vault (top-level group) it belongs to and
+/// its title.
+fn split_entry(entry: &str) -> (&str, &str) {
+    let vault = match entry.find('/') {
+        Some(idx) => &entry[..idx],
+        None => ROOT_VAULT,
+    };
+
+    let title = entry.rsplit('/').next().unwrap_or(entry);
+
+    (vault, title)
+}
+
+/// The path of the entry for an item called `name` in `vault`.
+fn entry_name(vault: Option<&str>, name: &str) -> String {
+    match vault {
+        Some(vault) if vault != ROOT_VAULT => format!("{}/{}", vault, name),
+        _ => name.to_string(),
+    }
+}
+
+/// Parses the `Key: value` output of `keepassxc-cli show`. Values can span
+/// multiple lines (notes generally do), so any line that doesn't look like a
+/// new attribute is appended to the previous one.
+fn parse_show(contents: &str) -> Vec<(String, String)> {
+    let mut attributes: Vec<(String, String)> = Vec::new();
+
+    for line in contents.lines() {
+        let mut parts = line.splitn(2, ": ");
+
+        match (parts.next(), parts.next(), attributes.last_mut()) {
+            (Some(key), Some(value), _) if !key.is_empty() && !key.contains(' ') => {
+                attributes.push((key.to_string(), value.to_string()));
+            }
+            (_, _, Some((_, value))) => {
+                value.push('\n');
+                value.push_str(line);
+            }
+            _ => {}
+        }
+    }
+
+    attributes
+}
+
+#[async_trait]
+impl api::Backend for KeepassBackend {
+    type Error = Error;
+
+    async fn capabilities(&self) -> Result<api::Capabilities, Self::Error> {
+        let mut capabilities = api::Capabilities::default();
+        capabilities.offline = true;
+        Ok(capabilities)
+    }
+
+    /// Verifies the database can be unlocked. keepassxc-cli has no session to
+    /// cache, so the password is only kept for the lifetime of the process.
+    async fn signin(&self, _account: Option<&str>) -> Result<(), Self::Error> {
+        self.exec("ls", &[]).await?;
+        Ok(())
+    }
+
+    async fn signout(&self) -> Result<(), Self::Error> {
+        *self.password.write().unwrap() = None;
+        Ok(())
+    }
+
+    async fn account(&self) -> Result<api::AccountMetadata, Self::Error> {
+        Ok(api::AccountMetadata::new(
+            self.database.file_stem().map_or_else(
+                || "KeePass".to_string(),
+                |v| v.to_string_lossy().into_owned(),
+            ),
+            self.database.display().to_string(),
+        ))
+    }
+
+    async fn vaults(&self) -> Result<Vec<api::VaultMetadata>, Self::Error> {
+        let ret = self.exec("ls", &[]).await?;
+
+        let mut vaults = vec![api::VaultMetadata::new(
+            ROOT_VAULT,
+            self.account().await?.name,
+        )];
+
+        vaults.extend(
+            std::str::from_utf8(&ret)?
+                .lines()
+                .filter(|v| v.ends_with('/') && *v != "Recycle Bin/")
+                .map(|v| {
+                    let name = v.trim_end_matches('/');
+
+                    api::VaultMetadata::new(name, name)
+                }),
+        );
+
+        Ok(vaults)
+    }
+
+    async fn search(&self, terms: Option<&str>) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        let terms = terms.map(str::to_lowercase);
+
+        Ok(self
+            .entries()
+            .await?
+            .into_iter()
+            .filter(|v| {
+                if let Some(terms) = &terms {
+                    let terms: &str = terms.as_ref();
+
+                    v == terms || split_entry(v).0 == terms || v.to_lowercase().contains(terms)
+                } else {
+                    true
+                }
+            })
+            .map(|v| {
+                let (vault, title) = split_entry(&v);
+
+                api::ItemMetadata::new(v.clone(), vault, title)
+            })
+            .collect())
+    }
+
+    async fn get(&self, uuid: &str) -> Result<Option<api::Item>, Self::Error> {
+        let ret = match self.exec("show", &["--show-protected", uuid]).await {
+            Ok(ret) => ret,
+            Err(Error::Backend(e)) if e.contains("Could not find entry") => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut title = split_entry(uuid).1.to_string();
+        let mut fields = Vec::new();
+
+        for (key, value) in parse_show(std::str::from_utf8(&ret)?) {
+            match key.as_str() {
+                // untitled entries keep the name they're listed under
+                "Title" if !value.is_empty() => title = value,
+                "Title" | "Uuid" => {}
+                _ => {
+                    let (name, field_type) = match key.as_str() {
+                        "UserName" => ("username".to_string(), api::ItemFieldType::Username),
+                        "Password" => ("password".to_string(), api::ItemFieldType::Password),
+                        "URL" => ("url".to_string(), api::ItemFieldType::Url),
+                        "Notes" => ("notes".to_string(), api::ItemFieldType::Note),
+                        _ => (key, api::ItemFieldType::Unknown),
+                    };
+
+                    fields.push(api::ItemField::new(name, value).with_type(field_type));
+                }
+            }
+        }
+
+        // KeePassXC keeps the otpauth uri in its own attribute, which isn't
+        // included in the default output.
+        let otp = match self
+            .exec("show", &["--show-protected", "--attributes", "otp", uuid])
+            .await
+        {
+            Ok(otp) => {
+                vec![
+                    api::ItemField::new("one-time password", std::str::from_utf8(&otp)?.trim())
+                        .with_type(api::ItemFieldType::Totp),
+                ]
+            }
+            Err(_) => Vec::new(),
+        };
+
+        // the entry's url is one of its fields
+        Ok(Some(
+            api::Item::builder()
+                .title(title)
+                .fields(fields.into_iter().filter(|f| !f.value.is_empty()))
+                .section(
+                    api::ItemSection::new("")
+                        .with_fields(otp.into_iter().filter(|f| !f.value.is_empty())),
+                )
+                .build()?,
+        ))
+    }
+
+    /// `KeePass` has no concept of tags, so they're ignored when creating the
+    /// entry.
+    async fn generate(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        _tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &api::PasswordRecipe,
+    ) -> Result<api::Item, Self::Error> {
+        let name = &entry_name(vault, name);
+        let length = recipe.length.to_string();
+        let mut args = vec!["--generate", "--length", &length];
+
+        if recipe.letters {
+            args.push("--lower");
+            args.push("--upper");
+        }
+
+        if recipe.digits {
+            args.push("--numeric");
+        }
+
+        if recipe.symbols {
+            args.push("--special");
+        }
+
+        if recipe.exclude_ambiguous {
+            args.push("--exclude-similar");
+        }
+
+        if let Some(username) = username {
+            args.push("--username");
+            args.push(username);
+        }
+
+        if let Some(url) = url {
+            args.push("--url");
+            args.push(url);
+        }
+
+        args.push(name);
+
+        self.exec("add", &args).await?;
+
+        Ok(self.get(name).await?.unwrap_or_else(|| unreachable!()))
+    }
+
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        _tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<api::Item, Self::Error> {
+        let name = &entry_name(vault, name);
+        let mut args = vec!["--password-prompt"];
+
+        if let Some(username) = username {
+            args.push("--username");
+            args.push(username);
+        }
+
+        if let Some(url) = url {
+            args.push("--url");
+            args.push(url);
+        }
+
+        args.push(name);
+
+        self.exec_with_input("add", &args, Some(password)).await?;
+
+        Ok(self.get(name).await?.unwrap_or_else(|| unreachable!()))
+    }
+
+    async fn create_vault(&self, name: &str) -> Result<api::VaultMetadata, Self::Error> {
+        self.exec("mkdir", &[name]).await?;
+
+        Ok(api::VaultMetadata::new(name, name))
+    }
+
+    /// `KeePassXC` moves deleted groups to the recycle bin rather than
+    /// removing them outright.
+    async fn delete_vault(&self, uuid: &str) -> Result<(), Self::Error> {
+        if uuid == ROOT_VAULT {
+            return Err(Error::RootVault);
+        }
+
+        self.exec("rmdir", &[uuid]).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+//! A backend holding a small set of made up items in memory, so 1p can be
+//! tried out without a password manager to point it at. This crate uses
+//! `onep_backend_mock::MockBackend` for the backend itself, filled with the
+//! items from [`demo`].
+//!
+//! Everything is made up, so it's also safe to show in screenshots and
+//! recordings. Changes are kept for as long as the backend's around, which
+//! for the CLI is a single command.
+
+#![deny(clippy::pedantic)]
+
+use onep_backend_api as api;
+use std::time::{Duration, SystemTime};
+
+pub use onep_backend_mock::{Error, MockBackend as MemoryBackend};
+
+/// A made up login.
+struct Login {
+    uuid: &'static str,
+    vault: &'static str,
+    title: &'static str,
+    username: &'static str,
+    password: &'static str,
+    url: &'static str,
+    tags: &'static [&'static str],
+}
+
+/// Logins in the demo. A couple of passwords are weak or reused so `audit`
+/// has something to say.
+const LOGINS: &[Login] = &[
+    Login {
+        uuid: "github",
+        vault: "personal",
+        title: "GitHub",
+        username: "octocat",
+        password: "vR8#tq2Lw!mZ4pXe",
+        url: "https://github.com",
+        tags: &["dev"],
+    },
+    Login {
+        uuid: "netflix",
+        vault: "personal",
+        title: "Netflix",
+        username: "sam@example.com",
+        password: "password123",
+        url: "https://www.netflix.com",
+        tags: &["streaming"],
+    },
+    Login {
+        uuid: "spotify",
+        vault: "personal",
+        title: "Spotify",
+        username: "sam@example.com",
+        password: "password123",
+        url: "https://open.spotify.com",
+        tags: &["streaming"],
+    },
+    Login {
+        uuid: "bank",
+        vault: "personal",
+        title: "Example Bank",
+        username: "12345678",
+        password: "f7&Kd9@pQ2z!Lm0s",
+        url: "https://online.examplebank.com",
+        tags: &["finance"],
+    },
+    Login {
+        uuid: "aws",
+        vault: "work",
+        title: "AWS",
+        username: "sam.doe",
+        password: "Hx4$uN8qT!c2Wb7z",
+        url: "https://console.aws.amazon.com",
+        tags: &["infra/prod"],
+    },
+    Login {
+        uuid: "jira",
+        vault: "work",
+        title: "Jira",
+        username: "sam.doe@example.com",
+        password: "Cw9!pR3m#Yt6Qz1v",
+        url: "https://example.atlassian.net",
+        tags: &[],
+    },
+];
+
+/// One-time password secrets, by the uuid of the login they belong to.
+const TOTP: &[(&str, &str)] = &[
+    ("github", "JBSWY3DPEHPK3PXP"),
+    ("bank", "KRSXG5CTMVRXEZLU"),
+    ("aws", "GEZDGNBVGY3TQOJQ"),
+];
+
+const FAVORITES: &[&str] = &["github", "aws"];
+
+/// A backend holding a couple of vaults of made up logins, along with a
+/// few other kinds of item, signed in to a demo account.
+#[must_use]
+pub fn demo() -> MemoryBackend {
+    let mut backend = MemoryBackend::new()
+        .with_account(api::AccountMetadata::new("Demo", "demo.example.com"))
+        .with_vault(api::VaultMetadata::new("personal", "Personal"))
+        .with_vault(api::VaultMetadata::new("work", "Work"));
+
+    for (i, login) in LOGINS.iter().enumerate() {
+        let mut metadata = item_metadata(
+            i,
+            login.uuid,
+            login.vault,
+            login.title,
+            api::ItemCategory::Login,
+        );
+        metadata.account_info = login.username.to_string();
+        metadata.urls = vec![login.url.to_string()];
+        metadata.tags = login.tags.iter().map(|v| (*v).to_string()).collect();
+        metadata.favorite = FAVORITES.contains(&login.uuid);
+
+        let totp = TOTP
+            .iter()
+            .filter(|(uuid, _)| *uuid == login.uuid)
+            .map(|(_, secret)| {
+                api::ItemField::new("one-time password", *secret)
+                    .with_type(api::ItemFieldType::Totp)
+            });
+
+        let item = api::Item::builder()
+            .title(login.title)
+            .field(
+                api::ItemField::new("username", login.username)
+                    .with_type(api::ItemFieldType::Username),
+            )
+            .field(
+                api::ItemField::new("password", login.password)
+                    .with_type(api::ItemFieldType::Password),
+            )
+            .fields(totp)
+            .url(api::ItemUrl::new("website", login.url));
+
+        backend = backend.with_item(metadata, build(item));
+    }
+
+    let i = LOGINS.len();
+
+    let item = api::Item::builder()
+        .title("Home Wi-Fi")
+        .field(api::ItemField::new("network name", "Sam's Network"))
+        .field(
+            api::ItemField::new("wireless network password", "lemon-kettle-orbit-42")
+                .with_type(api::ItemFieldType::Password),
+        );
+    let metadata = item_metadata(
+        i,
+        "wifi",
+        "personal",
+        "Home Wi-Fi",
+        api::ItemCategory::WirelessRouter,
+    );
+    backend = backend.with_item(metadata, build(item));
+
+    let item = api::Item::builder()
+        .title("Production database")
+        .field(api::ItemField::new("server", "db.internal.example.com"))
+        .field(api::ItemField::new("port", "5432"))
+        .field(api::ItemField::new("username", "app").with_type(api::ItemFieldType::Username))
+        .field(
+            api::ItemField::new("password", "s3Q!x8Rn#Vd2Lp6w")
+                .with_type(api::ItemFieldType::Password),
+        );
+    let mut metadata = item_metadata(
+        i + 1,
+        "database",
+        "work",
+        "Production database",
+        api::ItemCategory::Database,
+    );
+    metadata.account_info = "app".to_string();
+    metadata.tags = vec!["infra/prod".to_string()];
+    backend = backend.with_item(metadata, build(item));
+
+    let item = api::Item::builder().title("Stripe API key").field(
+        api::ItemField::new("credential", "sk_test_4eC39HqLyjWDarjtT1zdp7dc")
+            .with_type(api::ItemFieldType::Concealed),
+    );
+    let mut metadata = item_metadata(
+        i + 2,
+        "stripe",
+        "work",
+        "Stripe API key",
+        api::ItemCategory::ApiCredential,
+    );
+    metadata.tags = vec!["dev".to_string()];
+    backend = backend.with_item(metadata, build(item));
+
+    let item = api::Item::builder().title("Passport").field(
+        api::ItemField::new("notes", "Number 123456789, renew by March 2030.")
+            .with_type(api::ItemFieldType::Note),
+    );
+    let metadata = item_metadata(
+        i + 3,
+        "passport",
+        "personal",
+        "Passport",
+        api::ItemCategory::SecureNote,
+    );
+    backend.with_item(metadata, build(item))
+}
+
+/// Metadata for the `i`th item, which was created a couple of weeks after
+/// the one before and last changed a few days later, so the demo always looks
+/// the same.
+fn item_metadata(
+    i: usize,
+    uuid: &str,
+    vault: &str,
+    title: &str,
+    category: api::ItemCategory,
+) -> api::ItemMetadata {
+    const DAY: u64 = 24 * 60 * 60;
+
+    let created = SystemTime::UNIX_EPOCH
+        + Duration::from_secs(1_600_000_000)
+        + Duration::from_secs(14 * DAY * i as u64);
+
+    let mut metadata = api::ItemMetadata::new(uuid, vault, title);
+    metadata.category = category;
+    metadata.created = Some(created);
+    metadata.updated = Some(created + Duration::from_secs(3 * DAY));
+    metadata
+}
+
+fn build(item: api::ItemBuilder) -> api::Item {
+    item.build().expect("demo items all have titles")
+}
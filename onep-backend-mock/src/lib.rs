@@ -0,0 +1,711 @@
+//! A scriptable backend that keeps everything in memory. This crate uses
+//! `onep_backend_api::Backend` to provide a backend for tools built on 1p to
+//! test against, without needing op or any other password manager installed.
+//!
+//! Accounts, vaults, items and documents are added up front, along with
+//! errors for particular calls to fail with and how long calls should take:
+//!
+//! ```ignore
+//! let backend = MockBackend::new()
+//!     .with_vault(api::VaultMetadata::new("personal", "Personal"))
+//!     .with_item(api::ItemMetadata::new("github", "personal", "GitHub"), item)
+//!     .latency(Duration::from_millis(50))
+//!     .fail("get", Error::Network);
+//! ```
+//!
+//! Changes made through the backend are kept for as long as it's around, and
+//! [`MockBackend::calls`] lists every call made to it so far.
+
+#![deny(clippy::pedantic)]
+#![allow(clippy::used_underscore_binding)]
+
+use async_trait::async_trait;
+use onep_backend_api as api;
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("you're not signed in to the mock backend")]
+    NotSignedIn,
+    #[error("the mock backend has no account named {0}")]
+    UnknownAccount(String),
+    #[error("the mock backend has no item with uuid {0}")]
+    ItemNotFound(String),
+    #[error("the mock backend has no vault with uuid {0}")]
+    VaultNotFound(String),
+    #[error("the mock backend denied permission")]
+    PermissionDenied,
+    #[error("the mock backend couldn't be reached")]
+    Network,
+    #[error("the mock backend returned something that couldn't be parsed")]
+    Parse,
+    #[error("mock backend returned an error:\n{0}")]
+    Backend(String),
+    #[error("failed to read document:\n{0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Unsupported(#[from] api::Unsupported),
+}
+
+impl From<Error> for api::BackendError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::NotSignedIn | Error::UnknownAccount(_) => Self::AuthRequired(e.into()),
+            Error::ItemNotFound(_) | Error::VaultNotFound(_) => Self::NotFound(e.into()),
+            Error::PermissionDenied => Self::PermissionDenied(e.into()),
+            Error::Network => Self::Network(e.into()),
+            Error::Parse => Self::Parse(e.into()),
+            Error::Unsupported(e) => Self::Unsupported(e),
+            _ => Self::Other(e.into()),
+        }
+    }
+}
+
+/// Everything stored in the backend, which changes as it's written to.
+#[derive(Default)]
+struct State {
+    accounts: Vec<api::AccountMetadata>,
+    /// Index into `accounts` of the account signed in to.
+    account: usize,
+    signed_in: bool,
+    vaults: Vec<api::VaultMetadata>,
+    items: Vec<(api::ItemMetadata, api::Item)>,
+    documents: Vec<(api::DocumentMetadata, Vec<u8>)>,
+    /// Contents of attachments, keyed by the uuids of their item and
+    /// themselves.
+    attachments: HashMap<(String, String), Vec<u8>>,
+    /// Used to give everything created through the backend a unique uuid.
+    created: usize,
+}
+
+impl State {
+    fn uuid(&mut self, kind: &str) -> String {
+        self.created += 1;
+        format!("{}-{}", kind, self.created)
+    }
+
+    fn item(&mut self, uuid: &str) -> Result<&mut (api::ItemMetadata, api::Item), Error> {
+        self.items
+            .iter_mut()
+            .find(|(metadata, _)| metadata.uuid == uuid)
+            .ok_or_else(|| Error::ItemNotFound(uuid.to_string()))
+    }
+
+    fn vault(&mut self, uuid: &str) -> Result<&mut api::VaultMetadata, Error> {
+        self.vaults
+            .iter_mut()
+            .find(|v| v.uuid == uuid)
+            .ok_or_else(|| Error::VaultNotFound(uuid.to_string()))
+    }
+}
+
+pub struct MockBackend {
+    state: Mutex<State>,
+    capabilities: api::Capabilities,
+    /// How long every call takes, unless it's given one of its own in
+    /// `latencies`.
+    latency: Duration,
+    latencies: HashMap<&'static str, Duration>,
+    /// Errors for the next calls to each method to fail with, in order.
+    failures: Mutex<HashMap<&'static str, VecDeque<Error>>>,
+    calls: Mutex<Vec<&'static str>>,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockBackend {
+    /// Creates a backend that's already signed in, with nothing stored in it.
+    /// It can do everything a backend can.
+    #[must_use]
+    pub fn new() -> Self {
+        let mut capabilities = api::Capabilities::default();
+        capabilities.supports_documents = true;
+        capabilities.supports_multiple_accounts = true;
+        capabilities.offline = true;
+
+        let state = State {
+            signed_in: true,
+            ..State::default()
+        };
+
+        Self {
+            state: Mutex::new(state),
+            capabilities,
+            latency: Duration::default(),
+            latencies: HashMap::new(),
+            failures: Mutex::new(HashMap::new()),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Adds an account that can be picked by name or domain when signing in,
+    /// the first one added being signed in to from the start.
+    #[must_use]
+    pub fn with_account(self, account: api::AccountMetadata) -> Self {
+        self.state.lock().unwrap().accounts.push(account);
+        self
+    }
+
+    /// Whether the backend starts off signed in, which it does by default.
+    /// Every call but `signin` fails while signed out.
+    #[must_use]
+    pub fn signed_in(self, signed_in: bool) -> Self {
+        self.state.lock().unwrap().signed_in = signed_in;
+        self
+    }
+
+    #[must_use]
+    pub fn with_vault(self, vault: api::VaultMetadata) -> Self {
+        self.state.lock().unwrap().vaults.push(vault);
+        self
+    }
+
+    /// Adds an item, returned by `search` with the given metadata and by
+    /// `get` in full.
+    #[must_use]
+    pub fn with_item(self, metadata: api::ItemMetadata, item: api::Item) -> Self {
+        self.state.lock().unwrap().items.push((metadata, item));
+        self
+    }
+
+    #[must_use]
+    pub fn with_document(
+        self,
+        metadata: api::DocumentMetadata,
+        contents: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .documents
+            .push((metadata, contents.into()));
+        self
+    }
+
+    /// Sets the contents of one of an item's attachments, which should also
+    /// be listed in the item's `attachments`.
+    #[must_use]
+    pub fn with_attachment(
+        self,
+        item_uuid: impl Into<String>,
+        attachment_uuid: impl Into<String>,
+        contents: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .attachments
+            .insert((item_uuid.into(), attachment_uuid.into()), contents.into());
+        self
+    }
+
+    /// What the backend says it's able to do. It'll still do everything
+    /// whatever it says.
+    #[must_use]
+    pub fn with_capabilities(mut self, capabilities: api::Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// How long every call waits before doing anything.
+    #[must_use]
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// How long calls to `method`, named as in [`api::Backend`], wait before
+    /// doing anything, in place of the latency given to every call.
+    #[must_use]
+    pub fn method_latency(mut self, method: &'static str, latency: Duration) -> Self {
+        self.latencies.insert(method, latency);
+        self
+    }
+
+    /// Makes the next call to `method`, named as in [`api::Backend`], fail
+    /// with `error` rather than doing anything. Failing more than once queues
+    /// the errors up for the calls after.
+    #[must_use]
+    pub fn fail(self, method: &'static str, error: Error) -> Self {
+        self.failures
+            .lock()
+            .unwrap()
+            .entry(method)
+            .or_default()
+            .push_back(error);
+        self
+    }
+
+    /// Every method called so far, in the order they were called. Calls
+    /// default methods make to others, such as `get_many` calling `get`, are
+    /// listed too.
+    pub fn calls(&self) -> Vec<&'static str> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Records a call to `method`, waits for as long as it should take then
+    /// fails if it's been told to or the backend isn't signed in.
+    async fn call(&self, method: &'static str) -> Result<(), Error> {
+        self.calls.lock().unwrap().push(method);
+
+        let latency = self.latencies.get(method).copied().unwrap_or(self.latency);
+
+        if latency > Duration::default() {
+            tokio::time::delay_for(latency).await;
+        }
+
+        let failure = self
+            .failures
+            .lock()
+            .unwrap()
+            .get_mut(method)
+            .and_then(VecDeque::pop_front);
+
+        if let Some(e) = failure {
+            return Err(e);
+        }
+
+        if method != "signin" && !self.state.lock().unwrap().signed_in {
+            return Err(Error::NotSignedIn);
+        }
+
+        Ok(())
+    }
+
+    /// Stores a new login, in the first vault when one isn't given.
+    fn insert_login(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: String,
+    ) -> Result<api::Item, Error> {
+        let mut fields = Vec::with_capacity(2);
+
+        if let Some(username) = username {
+            fields.push(
+                api::ItemField::new("username", username).with_type(api::ItemFieldType::Username),
+            );
+        }
+
+        fields.push(
+            api::ItemField::new("password", password).with_type(api::ItemFieldType::Password),
+        );
+
+        let item = api::Item::builder()
+            .title(name)
+            .fields(fields)
+            .urls(url.map(|url| api::ItemUrl::new("", url)))
+            .build()
+            .map_err(|e| Error::Backend(e.to_string()))?;
+
+        let tags = tags
+            .map(|v| v.split(',').map(|v| v.trim().to_string()).collect())
+            .unwrap_or_default();
+
+        self.insert(item, api::ItemCategory::Login, tags, vault)
+    }
+
+    /// Stores a new item in the vault with the uuid `vault`, or the first
+    /// vault without one.
+    fn insert(
+        &self,
+        item: api::Item,
+        category: api::ItemCategory,
+        tags: Vec<String>,
+        vault: Option<&str>,
+    ) -> Result<api::Item, Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let vault = match vault {
+            Some(vault) => state.vault(vault)?.uuid.clone(),
+            None => state
+                .vaults
+                .first()
+                .map(|v| v.uuid.clone())
+                .unwrap_or_default(),
+        };
+
+        let mut metadata = api::ItemMetadata::new(state.uuid("item"), vault, item.title.clone());
+        metadata.account_info = item
+            .username()
+            .map(|v| v.value.as_str().to_string())
+            .unwrap_or_default();
+        metadata.urls = item.urls.iter().map(|v| v.url.clone()).collect();
+        metadata.tags = tags;
+        metadata.category = category;
+        metadata.created = Some(SystemTime::now());
+        metadata.updated = metadata.created;
+
+        state.items.push((metadata, item.clone()));
+
+        Ok(item)
+    }
+
+    /// Changes the metadata of the item with the given uuid.
+    fn update(&self, uuid: &str, f: impl FnOnce(&mut api::ItemMetadata)) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        let (metadata, _) = state.item(uuid)?;
+
+        f(metadata);
+        metadata.updated = Some(SystemTime::now());
+
+        Ok(())
+    }
+}
+
+/// Whether `metadata` matches `terms`, which should already be lowercase,
+/// checked the same way as op.
+fn matches(metadata: &api::ItemMetadata, terms: Option<&str>) -> bool {
+    if let Some(terms) = terms {
+        metadata.uuid == terms
+            || metadata.vault_uuid == terms
+            || metadata.title.to_lowercase().contains(terms)
+            || metadata.account_info.to_lowercase().contains(terms)
+            || metadata
+                .urls
+                .iter()
+                .any(|v| v.to_lowercase().contains(terms))
+            || metadata
+                .tags
+                .iter()
+                .any(|v| v.to_lowercase().contains(terms))
+    } else {
+        true
+    }
+}
+
+/// A password following `recipe`, made by cycling through the characters it
+/// allows so the same recipe always gives the same password.
+fn generate_password(recipe: &api::PasswordRecipe) -> String {
+    let mut characters = recipe.characters();
+
+    if characters.is_empty() {
+        characters = api::PasswordRecipe::LETTERS.to_string();
+    }
+
+    characters.chars().cycle().take(recipe.length).collect()
+}
+
+#[async_trait]
+impl api::Backend for MockBackend {
+    type Error = Error;
+
+    async fn capabilities(&self) -> Result<api::Capabilities, Self::Error> {
+        self.call("capabilities").await?;
+        Ok(self.capabilities)
+    }
+
+    /// Signs in to the account with the given name or domain, or the one
+    /// last signed in to.
+    async fn signin(&self, account: Option<&str>) -> Result<(), Self::Error> {
+        self.call("signin").await?;
+
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(account) = account {
+            state.account = state
+                .accounts
+                .iter()
+                .position(|v| v.name == account || v.domain == account)
+                .ok_or_else(|| Error::UnknownAccount(account.to_string()))?;
+        }
+
+        state.signed_in = true;
+
+        Ok(())
+    }
+
+    async fn signout(&self) -> Result<(), Self::Error> {
+        self.call("signout").await?;
+        self.state.lock().unwrap().signed_in = false;
+        Ok(())
+    }
+
+    async fn account(&self) -> Result<api::AccountMetadata, Self::Error> {
+        self.call("account").await?;
+
+        let state = self.state.lock().unwrap();
+
+        // an account needn't have been added to sign in to one
+        Ok(state
+            .accounts
+            .get(state.account)
+            .cloned()
+            .unwrap_or_else(|| api::AccountMetadata::new("Mock", "mock.local")))
+    }
+
+    async fn vaults(&self) -> Result<Vec<api::VaultMetadata>, Self::Error> {
+        self.call("vaults").await?;
+        Ok(self.state.lock().unwrap().vaults.clone())
+    }
+
+    async fn search(&self, terms: Option<&str>) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        self.call("search").await?;
+
+        let terms = terms.map(str::to_lowercase);
+
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .items
+            .iter()
+            .map(|(metadata, _)| metadata)
+            .filter(|v| !v.archived && matches(v, terms.as_deref()))
+            .cloned()
+            .collect())
+    }
+
+    async fn get(&self, uuid: &str) -> Result<Option<api::Item>, Self::Error> {
+        self.call("get").await?;
+
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .items
+            .iter()
+            .find(|(metadata, _)| metadata.uuid == uuid)
+            .map(|(_, item)| item.clone()))
+    }
+
+    async fn generate(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &api::PasswordRecipe,
+    ) -> Result<api::Item, Self::Error> {
+        self.call("generate").await?;
+        self.insert_login(name, username, url, tags, vault, generate_password(recipe))
+    }
+
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<api::Item, Self::Error> {
+        self.call("create").await?;
+        self.insert_login(name, username, url, tags, vault, password.to_string())
+    }
+
+    async fn create_item(
+        &self,
+        item: &api::Item,
+        category: api::ItemCategory,
+        tags: &[String],
+        vault: Option<&str>,
+    ) -> Result<api::Item, Self::Error> {
+        self.call("create_item").await?;
+
+        let mut item = item.clone();
+        item.attachments.clear();
+        item.history.clear();
+
+        self.insert(item, category, tags.to_vec(), vault)
+    }
+
+    async fn create_vault(&self, name: &str) -> Result<api::VaultMetadata, Self::Error> {
+        self.call("create_vault").await?;
+
+        let mut state = self.state.lock().unwrap();
+        let vault = api::VaultMetadata::new(state.uuid("vault"), name);
+        state.vaults.push(vault.clone());
+
+        Ok(vault)
+    }
+
+    async fn delete_vault(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.call("delete_vault").await?;
+
+        let mut state = self.state.lock().unwrap();
+        state.vault(uuid)?;
+        state.vaults.retain(|v| v.uuid != uuid);
+        state
+            .items
+            .retain(|(metadata, _)| metadata.vault_uuid != uuid);
+        state
+            .documents
+            .retain(|(metadata, _)| metadata.vault_uuid != uuid);
+
+        Ok(())
+    }
+
+    async fn rename_vault(&self, uuid: &str, name: &str) -> Result<(), Self::Error> {
+        self.call("rename_vault").await?;
+        self.state.lock().unwrap().vault(uuid)?.name = name.to_string();
+        Ok(())
+    }
+
+    async fn edit(&self, uuid: &str, edit: &api::ItemEdit) -> Result<(), Self::Error> {
+        self.call("edit").await?;
+
+        let mut state = self.state.lock().unwrap();
+        let (metadata, item) = state.item(uuid)?;
+
+        if let Some(title) = &edit.title {
+            item.title.clone_from(title);
+            metadata.title.clone_from(title);
+        }
+
+        for field in &edit.fields {
+            match item.fields.iter_mut().find(|v| v.name == field.name) {
+                Some(existing) => *existing = field.clone(),
+                None => item.fields.push(field.clone()),
+            }
+        }
+
+        metadata.account_info = item
+            .username()
+            .map(|v| v.value.as_str().to_string())
+            .unwrap_or_default();
+        metadata.updated = Some(SystemTime::now());
+
+        Ok(())
+    }
+
+    async fn set_tags(&self, uuid: &str, tags: &[String]) -> Result<(), Self::Error> {
+        self.call("set_tags").await?;
+        self.update(uuid, |metadata| metadata.tags = tags.to_vec())
+    }
+
+    async fn set_favorite(&self, uuid: &str, favorite: bool) -> Result<(), Self::Error> {
+        self.call("set_favorite").await?;
+        self.update(uuid, |metadata| metadata.favorite = favorite)
+    }
+
+    async fn set_otp(&self, uuid: &str, uri: &str) -> Result<(), Self::Error> {
+        self.call("set_otp").await?;
+
+        let mut state = self.state.lock().unwrap();
+        let (metadata, item) = state.item(uuid)?;
+
+        item.fields
+            .retain(|v| v.field_type != api::ItemFieldType::Totp);
+        item.fields.push(
+            api::ItemField::new("one-time password", uri).with_type(api::ItemFieldType::Totp),
+        );
+        metadata.updated = Some(SystemTime::now());
+
+        Ok(())
+    }
+
+    async fn archived(&self) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        self.call("archived").await?;
+
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .items
+            .iter()
+            .map(|(metadata, _)| metadata)
+            .filter(|v| v.archived)
+            .cloned()
+            .collect())
+    }
+
+    async fn archive(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.call("archive").await?;
+        self.update(uuid, |metadata| metadata.archived = true)
+    }
+
+    async fn restore(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.call("restore").await?;
+        self.update(uuid, |metadata| metadata.archived = false)
+    }
+
+    async fn documents(&self) -> Result<Vec<api::DocumentMetadata>, Self::Error> {
+        self.call("documents").await?;
+
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .documents
+            .iter()
+            .map(|(metadata, _)| metadata.clone())
+            .collect())
+    }
+
+    async fn get_document(&self, uuid: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.call("get_document").await?;
+
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .documents
+            .iter()
+            .find(|(metadata, _)| metadata.uuid == uuid)
+            .map(|(_, contents)| contents.clone()))
+    }
+
+    /// Reads the file straight away, so later changes to it aren't seen.
+    async fn create_document(
+        &self,
+        path: &Path,
+        title: Option<&str>,
+        vault: Option<&str>,
+    ) -> Result<api::DocumentMetadata, Self::Error> {
+        self.call("create_document").await?;
+
+        let contents = std::fs::read(path)?;
+        let title = title.map_or_else(
+            || {
+                path.file_name()
+                    .map(|v| v.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            },
+            str::to_string,
+        );
+
+        let mut state = self.state.lock().unwrap();
+        let vault = match vault {
+            Some(vault) => state.vault(vault)?.uuid.clone(),
+            None => state
+                .vaults
+                .first()
+                .map(|v| v.uuid.clone())
+                .unwrap_or_default(),
+        };
+        let metadata = api::DocumentMetadata::new(state.uuid("document"), vault, title);
+        state.documents.push((metadata.clone(), contents));
+
+        Ok(metadata)
+    }
+
+    async fn get_attachment(
+        &self,
+        item_uuid: &str,
+        attachment_uuid: &str,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.call("get_attachment").await?;
+
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .attachments
+            .get(&(item_uuid.to_string(), attachment_uuid.to_string()))
+            .cloned())
+    }
+}
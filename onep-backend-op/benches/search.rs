@@ -0,0 +1,107 @@
+//! Measures parsing op's item listings, and searching the items parsed, over
+//! made up listings of 10k and 100k items in both op v1 and v2's schemas.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use onep_backend_op::bench;
+use serde_json::{json, Value};
+
+const SIZES: &[usize] = &[10_000, 100_000];
+
+/// Words titles are made from, so searches match some items but not all.
+const WORDS: &[&str] = &[
+    "GitHub", "Gmail", "Bank", "AWS", "Netflix", "Router", "Jira", "Slack", "Amazon", "Stripe",
+];
+
+fn title(i: usize) -> String {
+    format!("{} {}", WORDS[i % WORDS.len()], i)
+}
+
+fn listing_v1(len: usize) -> Vec<u8> {
+    let items: Vec<Value> = (0..len)
+        .map(|i| {
+            json!({
+                "uuid": format!("{:026}", i),
+                "vaultUuid": format!("vault{}", i % 5),
+                "createdAt": "2021-04-01T10:00:00Z",
+                "updatedAt": "2022-01-01T10:00:00Z",
+                "templateUuid": "001",
+                "trashed": "N",
+                "favIndex": i % 20,
+                "overview": {
+                    "URLs": [{ "l": "website", "u": format!("https://example{}.com", i) }],
+                    "title": title(i),
+                    "url": format!("https://example{}.com", i),
+                    "ainfo": format!("user{}@example.com", i),
+                    "tags": [format!("tag{}", i % 50)],
+                },
+            })
+        })
+        .collect();
+
+    serde_json::to_vec(&items).unwrap()
+}
+
+fn listing_v2(len: usize) -> Vec<u8> {
+    let items: Vec<Value> = (0..len)
+        .map(|i| {
+            json!({
+                "id": format!("{:026}", i),
+                "title": title(i),
+                "vault": { "id": format!("vault{}", i % 5), "name": "Private" },
+                "additional_information": format!("user{}@example.com", i),
+                "urls": [{ "label": "website", "href": format!("https://example{}.com", i) }],
+                "tags": [format!("tag{}", i % 50)],
+                "favorite": i % 20 == 0,
+                "category": "LOGIN",
+                "created_at": "2021-04-01T10:00:00Z",
+                "updated_at": "2022-01-01T10:00:00Z",
+            })
+        })
+        .collect();
+
+    serde_json::to_vec(&items).unwrap()
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+
+    for &len in SIZES {
+        group.throughput(Throughput::Elements(len as u64));
+
+        let v1 = listing_v1(len);
+        group.bench_with_input(BenchmarkId::new("v1", len), &v1, |b, out| {
+            b.iter(|| bench::parse(out, false).unwrap())
+        });
+
+        let v2 = listing_v2(len);
+        group.bench_with_input(BenchmarkId::new("v2", len), &v2, |b, out| {
+            b.iter(|| bench::parse(out, true).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("search");
+
+    for &len in SIZES {
+        group.throughput(Throughput::Elements(len as u64));
+
+        let listing = bench::parse(&listing_v2(len), true).unwrap();
+
+        // every item, a tenth of them, then none at all
+        for &terms in &[None, Some("github"), Some("nothing matches this")] {
+            let name = format!("{}/{}", terms.unwrap_or("everything"), len);
+
+            group.bench_with_input(BenchmarkId::from_parameter(name), &terms, |b, terms| {
+                b.iter(|| listing.search(*terms))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, parse, search);
+criterion_main!(benches);
@@ -7,321 +7,1523 @@
 #![deny(clippy::pedantic)]
 #![allow(clippy::used_underscore_binding)]
 
+mod listing;
+mod parse;
+mod v1;
+mod v2;
+
 use async_trait::async_trait;
+use futures::{stream::BoxStream, Future, Stream, StreamExt, TryStreamExt};
+use listing::Listing;
 use onep_backend_api as api;
-use serde::Deserialize;
-use serde_json::Value;
-use std::borrow::Cow;
-use tokio::process::Command;
+use parse::Parser;
+use serde::de::DeserializeOwned;
+use std::{
+    borrow::Cow,
+    convert::{Infallible, TryFrom, TryInto},
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::RwLock,
+    time::{Duration, SystemTime},
+};
+use tokio::{process::Command, sync::Mutex};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    #[error("you're not signed in to op, run `1p signin` first")]
+    NotSignedIn,
+    #[error("your op session has expired, run `1p signin` to start a new one")]
+    SessionExpired,
+    #[error("op couldn't find that item")]
+    ItemNotFound,
+    #[error("more than one item matches in op, use its uuid")]
+    AmbiguousItem,
+    #[error("1password is rate limiting requests from op, wait a while and try again")]
+    RateLimited,
+    #[error("op couldn't reach 1password, check your connection:\n{0}")]
+    NetworkError(String),
     #[error("op backend returned an error:\n{0}")]
-    Backend(String),
+    Unknown(String),
     #[error("failed to exec backend:\n{0}")]
     Exec(std::io::Error),
+    #[error("op didn't finish within {0:?}, check your connection and try again")]
+    Timeout(Duration),
+    #[error("op signin failed, check your credentials and try again")]
+    Signin,
+    #[error("op is using the service account token in OP_SERVICE_ACCOUNT_TOKEN, unset it to sign in as yourself")]
+    ServiceAccount,
+    #[error("your account doesn't have permission to do that:\n{0}")]
+    Permission(String),
+    #[error("failed to cache op session token:\n{0}")]
+    Session(std::io::Error),
     #[error("failed to parse json from op:\n{0}")]
     Json(#[from] serde_json::error::Error),
+    #[error("failed to parse `{0}` in json from op:\n{1}")]
+    Field(String, serde_json::error::Error),
+    #[error(
+        "op returned `{0}`, which 1p doesn't know about, run without --strict-parse to ignore it"
+    )]
+    UnknownField(String),
     #[error("failed to convert op response to utf-8:\n{0}")]
     Utf8(#[from] std::str::Utf8Error),
+    #[error("unsupported op version {0}, only v1 and v2 are supported")]
+    UnsupportedVersion(String),
+    #[error("op returned an item that isn't valid:\n{0}")]
+    InvalidItem(#[from] api::InvalidItem),
+    #[error(transparent)]
+    Unsupported(#[from] api::Unsupported),
 }
 
-#[derive(Debug, Deserialize)]
-struct GetAccount {
-    name: String,
-    domain: String,
-}
-
-impl Into<api::AccountMetadata> for GetAccount {
-    fn into(self) -> api::AccountMetadata {
-        api::AccountMetadata {
-            name: self.name,
-            domain: self.domain,
+impl From<Error> for api::BackendError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::NotSignedIn | Error::SessionExpired | Error::Signin => {
+                Self::AuthRequired(e.into())
+            }
+            Error::ItemNotFound => Self::NotFound(e.into()),
+            Error::Permission(_) => Self::PermissionDenied(e.into()),
+            Error::RateLimited | Error::NetworkError(_) | Error::Timeout(_) => {
+                Self::Network(e.into())
+            }
+            Error::Json(_)
+            | Error::Field(..)
+            | Error::UnknownField(_)
+            | Error::Utf8(_)
+            | Error::InvalidItem(_) => Self::Parse(e.into()),
+            Error::Unsupported(e) => Self::Unsupported(e),
+            _ => Self::Other(e.into()),
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct ListVault {
-    uuid: String,
-    name: String,
+/// For conversions in [`OpBackend::exec_versioned`] that can't fail.
+impl From<Infallible> for Error {
+    fn from(v: Infallible) -> Self {
+        match v {}
+    }
 }
 
-impl Into<api::VaultMetadata> for ListVault {
-    fn into(self) -> api::VaultMetadata {
-        api::VaultMetadata {
-            uuid: self.uuid,
-            name: self.name,
+impl Error {
+    /// Whether the failure might not happen again, so the command is worth
+    /// another try.
+    fn is_transient(&self) -> bool {
+        matches!(self, Self::RateLimited | Self::NetworkError(_))
+    }
+
+    /// op reports every failure the same way, so the kind of failure is
+    /// picked out of the message it writes to stderr. The message isn't
+    /// always UTF-8, such as when it's been localised on Windows, so invalid
+    /// bytes are replaced rather than losing the whole message.
+    fn from_stderr(stderr: &[u8]) -> Self {
+        let stderr = String::from_utf8_lossy(stderr);
+        let message = stderr.to_lowercase();
+        let mentions = |needles: &[&str]| needles.iter().any(|v| message.contains(v));
+
+        if mentions(&["session expired", "session has expired", "(401)"]) {
+            Self::SessionExpired
+        } else if mentions(&["not currently signed in", "not signed in"]) {
+            Self::NotSignedIn
+        } else if mentions(&["permission"]) {
+            Self::Permission(stderr.to_string())
+        } else if mentions(&["isn't an item", "item not found", "no item found"]) {
+            Self::ItemNotFound
+        } else if mentions(&["more than one item matches"]) {
+            Self::AmbiguousItem
+        } else if mentions(&["(429)", "too many requests", "rate limit"]) {
+            Self::RateLimited
+        } else if mentions(&[
+            "dial tcp",
+            "no such host",
+            "connection refused",
+            "network is unreachable",
+            "i/o timeout",
+            "tls handshake",
+        ]) {
+            Self::NetworkError(stderr.to_string())
+        } else {
+            Self::Unknown(stderr.to_string())
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct ListItem {
+/// Whether op only reads with these arguments, such as `item get` or `list
+/// items`, so running it again after losing the connection can't make the
+/// same change twice.
+fn is_read(args: &[OsString]) -> bool {
+    args.iter()
+        .take(2)
+        .any(|arg| ["get", "list", "read", "whoami"].iter().any(|v| arg == v))
+}
+
+/// Major version of the op binary, which determines both the commands to run
+/// and the schema of the JSON they return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Version {
+    V1,
+    V2,
+}
+
+/// Version-independent view of an item in a listing. Both the v1 and v2
+/// schemas are parsed into this so searching only has to be written once.
+#[derive(Debug)]
+struct ItemSummary {
     uuid: String,
     vault_uuid: String,
-    created_at: String,
-    updated_at: String,
-    overview: ItemOverview,
+    title: String,
+    account_info: String,
+    urls: Vec<String>,
+    tags: Vec<String>,
+    favorite: bool,
+    archived: bool,
+    created: Option<SystemTime>,
+    updated: Option<SystemTime>,
+    category: api::ItemCategory,
 }
 
-impl Into<api::ItemMetadata> for ListItem {
+impl ItemSummary {
+    /// Whether the item matches `terms`, which should already be lowercase.
+    fn matches(&self, terms: Option<&str>) -> bool {
+        if let Some(terms) = terms {
+            self.uuid == terms
+                || self.vault_uuid == terms
+                || self.urls.iter().any(|v| v.to_lowercase().contains(terms))
+                || self.title.to_lowercase().contains(terms)
+                || self.account_info.to_lowercase().contains(terms)
+                || self.tags.iter().any(|v| v.to_lowercase().contains(terms))
+        } else {
+            true
+        }
+    }
+}
+
+impl Into<api::ItemMetadata> for ItemSummary {
     fn into(self) -> api::ItemMetadata {
-        api::ItemMetadata {
-            title: self.overview.title,
-            account_info: self.overview.account_info,
-            uuid: self.uuid,
-            vault_uuid: self.vault_uuid,
+        let mut metadata = api::ItemMetadata::new(self.uuid, self.vault_uuid, self.title);
+        metadata.account_info = self.account_info;
+        metadata.urls = self.urls;
+        metadata.tags = self.tags;
+        metadata.favorite = self.favorite;
+        metadata.archived = self.archived;
+        metadata.created = self.created;
+        metadata.updated = self.updated;
+        metadata.category = self.category;
+        metadata
+    }
+}
+
+/// Parses a listing of items, as printed all at once by the given version of
+/// op.
+fn parse_listing(parser: &Parser, version: Version, out: &[u8]) -> Result<Vec<ItemSummary>, Error> {
+    Ok(match version {
+        Version::V1 => parser
+            .json::<Vec<v1::ListItem>>(out)?
+            .into_iter()
+            .map(|v| v.into())
+            .collect(),
+        Version::V2 => parser
+            .json::<Vec<v2::ListItem>>(out)?
+            .into_iter()
+            .map(|v| v.into())
+            .collect(),
+    })
+}
+
+/// Entry points for the benchmarks in `benches`, which can only reach what's
+/// public. Not meant to be used otherwise.
+#[doc(hidden)]
+pub mod bench {
+    use super::{parse_listing, Error, ItemSummary, Parser, Version};
+
+    /// A listing parsed the same way as one returned by op.
+    pub struct Listing(Vec<ItemSummary>);
+
+    /// Parses the output of `op list items`, or `op item list` when `v2`.
+    pub fn parse(out: &[u8], v2: bool) -> Result<Listing, Error> {
+        let version = if v2 { Version::V2 } else { Version::V1 };
+        parse_listing(&Parser::default(), version, out).map(Listing)
+    }
+
+    impl Listing {
+        /// How many items match `terms`, which should already be lowercase.
+        #[must_use]
+        pub fn search(&self, terms: Option<&str>) -> usize {
+            self.0.iter().filter(|v| v.matches(terms)).count()
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct ItemOverview {
-    #[serde(rename = "URLs", default)]
-    urls: Vec<ItemOverviewUrl>,
-    title: String,
-    url: Option<String>,
-    #[serde(rename = "ainfo")]
-    account_info: String,
-    #[serde(default)]
-    tags: Vec<String>,
+/// The session token `op signin` gave the account with the given shorthand,
+/// which it has the shell export as `OP_SESSION_<shorthand>`. Without a
+/// shorthand, the token is only used when it's the only one there.
+fn env_session(account: Option<&str>) -> Option<String> {
+    const PREFIX: &str = "OP_SESSION_";
+
+    if let Some(account) = account {
+        return std::env::var(format!("{}{}", PREFIX, account)).ok();
+    }
+
+    // `vars` would panic on anything else in the environment that isn't
+    // UTF-8
+    let mut sessions =
+        std::env::vars_os().filter(|(k, _)| k.to_str().map_or(false, |k| k.starts_with(PREFIX)));
+
+    match (sessions.next(), sessions.next()) {
+        (Some((_, session)), None) => session.into_string().ok(),
+        _ => None,
+    }
+}
+
+/// Parses one of the RFC 3339 timestamps op gives items.
+fn timestamp(v: &str) -> Option<SystemTime> {
+    chrono::DateTime::parse_from_rfc3339(v).ok().map(Into::into)
 }
 
-#[derive(Debug, Deserialize)]
-struct ItemOverviewUrl {
-    #[serde(rename = "l")]
-    label: String,
-    #[serde(rename = "u")]
-    url: String,
+/// How long op is given to finish a command by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many times a command is tried again by default when it fails in a way
+/// that might not last.
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// How long to wait before trying a command again by default, doubled after
+/// each try.
+pub const DEFAULT_BACKOFF: Duration = Duration::from_millis(500);
+
+/// The longest to wait between tries, however many there have been.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub struct OpBackend {
+    bin: PathBuf,
+    args: Vec<OsString>,
+    account: Option<String>,
+    vault: Option<String>,
+    cache: Option<bool>,
+    /// Whether op authenticates with `OP_SERVICE_ACCOUNT_TOKEN`, which only
+    /// v2 supports, rather than a session.
+    service_account: bool,
+    /// Whether op should unlock through the desktop app, or `None` to go by
+    /// `OP_BIOMETRIC_UNLOCK_ENABLED`.
+    app_integration: Option<bool>,
+    session: RwLock<Option<String>>,
+    version: RwLock<Option<Version>>,
+    reauth: bool,
+    timeout: Option<Duration>,
+    retries: u32,
+    backoff: Duration,
+    parser: Parser,
+    /// Held while signing in again, so commands that find the session has
+    /// expired at the same time only prompt once.
+    reauth_lock: Mutex<()>,
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct GetItem {
-    details: GetItemDetails,
-    overview: ItemOverview,
+/// How to run op, see [`OpBackend::new`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct OpConfig {
+    /// The op executable to run, found on the path if it isn't a path
+    /// itself. Defaults to `op`.
+    pub bin: PathBuf,
+    /// Arguments to give op before those of every command, such as
+    /// `--config <dir>`.
+    pub args: Vec<OsString>,
+    /// Account to run every command against, by its shorthand, sign-in
+    /// address or id, rather than whichever op last signed in to. Also the
+    /// account `signin` signs in to when it isn't given one.
+    pub account: Option<String>,
+    /// Vault, by name or uuid, that items are listed from and created in
+    /// when they aren't given one.
+    pub vault: Option<String>,
+    /// Whether op should use the cache kept by its daemon, or `None` to
+    /// leave it to op. v1 only caches when asked to, v2 does by default.
+    pub cache: Option<bool>,
+    /// Whether op should unlock through the 1Password desktop app, with
+    /// Touch ID or whatever else the app's set up for, rather than with a
+    /// session token. op then talks to the app itself, so no session is
+    /// given to it or cached. `None` leaves it to
+    /// `OP_BIOMETRIC_UNLOCK_ENABLED`, as op does.
+    pub app_integration: Option<bool>,
+    /// Session token to pass to op, in place of one from the environment or
+    /// the cache. It's still replaced if the backend has to sign in again.
+    pub session: Option<String>,
+    /// Whether to run `op signin` when there's no session or it has expired,
+    /// prompting for the master password on the tty, then retry the command
+    /// once.
+    pub reauth: bool,
+    /// How long to wait for op to finish a command before killing it, or
+    /// `None` to wait for as long as it takes. Listings are only given up on
+    /// if op goes this long without printing anything.
+    pub timeout: Option<Duration>,
+    /// How many times to try a command again when op couldn't reach
+    /// 1password or was rate limited, waiting `backoff` before the first
+    /// retry and twice as long before each one after, up to 30 seconds.
+    /// Commands that change something are only tried again when rate
+    /// limited, as op may have saved the change before losing the
+    /// connection.
+    pub retries: u32,
+    pub backoff: Duration,
+    /// Whether to fail on fields in op's output that 1p doesn't know about,
+    /// rather than skipping them. Fields are only ever skipped with a message
+    /// in the debug log otherwise, so this is for finding out what a new
+    /// release of op has changed.
+    pub strict: bool,
 }
 
-impl Into<api::Item> for GetItem {
-    fn into(self) -> api::Item {
-        api::Item {
-            title: self.overview.title,
-            fields: self
-                .details
-                .fields
-                .into_iter()
-                .map(|f| f.into())
-                .filter(|f: &api::ItemField| !f.value.is_empty())
-                .collect(),
-            sections: self
-                .details
-                .sections
-                .into_iter()
-                .map(|v| api::ItemSection {
-                    name: v.title,
-                    fields: v
-                        .fields
-                        .into_iter()
-                        .map(|f| f.into())
-                        .filter(|f: &api::ItemField| !f.value.is_empty())
-                        .collect(),
-                })
-                .collect(),
+impl Default for OpConfig {
+    fn default() -> Self {
+        Self {
+            bin: PathBuf::from("op"),
+            args: Vec::new(),
+            account: None,
+            vault: None,
+            cache: None,
+            app_integration: None,
+            session: None,
+            reauth: false,
+            timeout: Some(DEFAULT_TIMEOUT),
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+            strict: false,
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct GetItemDetails {
-    #[serde(default)]
-    fields: Vec<GetItemDetailsField>,
-    #[serde(default)]
-    sections: Vec<GetItemSection>,
+impl Default for OpBackend {
+    fn default() -> Self {
+        Self::new(OpConfig::default())
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct GetItemDetailsField {
-    name: String,
-    #[serde(rename = "designation")]
-    field_type: String,
-    value: Value,
+impl OpBackend {
+    /// Creates a new backend. Unless it's given a session, it uses the token
+    /// in `OP_SESSION_*` for the configured account, or if there isn't one
+    /// the only account with a session in the environment, or otherwise the
+    /// one cached by a previous `signin` if there is one. When
+    /// `OP_SERVICE_ACCOUNT_TOKEN` is set op uses that instead, and there's
+    /// no signing in at all.
+    #[must_use]
+    pub fn new(config: OpConfig) -> Self {
+        let account = config.account.as_deref();
+        let session = config
+            .session
+            .clone()
+            .or_else(|| account.and_then(|v| env_session(Some(v))))
+            .or_else(|| env_session(None))
+            .or_else(|| api::session::read("op"));
+
+        Self {
+            bin: config.bin,
+            args: config.args,
+            account: config.account,
+            vault: config.vault,
+            cache: config.cache,
+            service_account: std::env::var_os("OP_SERVICE_ACCOUNT_TOKEN")
+                .map_or(false, |v| !v.is_empty()),
+            app_integration: config.app_integration,
+            session: RwLock::new(session),
+            version: RwLock::new(None),
+            reauth: config.reauth,
+            timeout: config.timeout,
+            retries: config.retries,
+            backoff: config.backoff,
+            parser: Parser::new(config.strict),
+            reauth_lock: Mutex::new(()),
+        }
+    }
+
+    /// Waits on `fut`, which runs op, for no longer than the timeout. op is
+    /// killed when `fut` is dropped, as every command is built with
+    /// `kill_on_drop`.
+    async fn within<F: Future>(&self, fut: F) -> Result<F::Output, Error> {
+        match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, fut)
+                .await
+                .map_err(|_| Error::Timeout(timeout)),
+            None => Ok(fut.await),
+        }
+    }
+
+    /// Whether a command that failed with `e` should be retried after signing
+    /// in. op can't prompt for the master password itself as its input is
+    /// never the terminal, so it's asked for through `op signin` instead when
+    /// there's someone there to answer.
+    fn should_reauth(&self, e: &Error) -> bool {
+        self.reauth
+            && !self.service_account
+            && matches!(e, Error::SessionExpired | Error::NotSignedIn)
+            && atty::is(atty::Stream::Stdin)
+    }
+
+    /// Signs in again after a command run with the `session` it was given
+    /// failed as it was missing or had expired.
+    async fn reauthenticate(&self, session: Option<String>) -> Result<(), Error> {
+        let _guard = self.reauth_lock.lock().await;
+
+        // another command may have already signed in again while this one
+        // was waiting
+        if self.session() == session {
+            api::Backend::signin(self, None).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Detects the version of op on the path from `op --version`, the result
+    /// is cached for the lifetime of the backend.
+    async fn version(&self) -> Result<Version, Error> {
+        if let Some(version) = *self.version.read().unwrap() {
+            return Ok(version);
+        }
+
+        let cmd = self
+            .within(self.op().arg("--version").kill_on_drop(true).output())
+            .await?
+            .map_err(Error::Exec)?;
+        let raw = std::str::from_utf8(&cmd.stdout)?.trim();
+
+        let version = match raw.split('.').next() {
+            Some("1") => Version::V1,
+            Some("2") => Version::V2,
+            _ => return Err(Error::UnsupportedVersion(raw.to_string())),
+        };
+
+        *self.version.write().unwrap() = Some(version);
+
+        Ok(version)
+    }
+
+    /// Runs the command appropriate for the detected version of op and parses
+    /// its output with the matching schema.
+    async fn exec_versioned<V1, V2, T>(&self, v1: &[&str], v2: &[&str]) -> Result<T, Error>
+    where
+        V1: DeserializeOwned + TryInto<T>,
+        V2: DeserializeOwned + TryInto<T>,
+        Error: From<V1::Error> + From<V2::Error>,
+    {
+        Ok(match self.version().await? {
+            Version::V1 => self.parser.json::<V1>(&self.exec(v1).await?)?.try_into()?,
+            Version::V2 => self.parser.json::<V2>(&self.exec(v2).await?)?.try_into()?,
+        })
+    }
+
+    async fn create_login(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: NewPassword<'_>,
+    ) -> Result<api::Item, Error> {
+        let version = self.version().await?;
+
+        let mut args = Vec::with_capacity(16);
+
+        if version == Version::V1 {
+            args.push(Cow::Borrowed("create"));
+            args.push(Cow::Borrowed("item"));
+            args.push(Cow::Borrowed("Login"));
+        } else {
+            args.push(Cow::Borrowed("item"));
+            args.push(Cow::Borrowed("create"));
+            args.push(Cow::Borrowed("--category"));
+            args.push(Cow::Borrowed("login"));
+            args.push(Cow::Borrowed("--format"));
+            args.push(Cow::Borrowed("json"));
+        }
+
+        args.push(Cow::Owned(password.arg()));
+        args.push(Cow::Borrowed("--title"));
+        args.push(Cow::Borrowed(name));
+
+        if let Some(url) = url {
+            args.push(Cow::Borrowed("--url"));
+            args.push(Cow::Borrowed(url));
+        }
+
+        if let Some(tags) = tags {
+            args.push(Cow::Borrowed("--tags"));
+            args.push(Cow::Borrowed(tags));
+        }
+
+        if let Some(vault) = vault.or(self.vault.as_deref()) {
+            args.push(Cow::Borrowed("--vault"));
+            args.push(Cow::Borrowed(vault));
+        }
+
+        if let Some(username) = username {
+            args.push(Cow::Owned(format!("username={}", username)));
+        }
+
+        let ret = self.exec(args.iter().map(Cow::as_ref)).await?;
+
+        // op v2 returns the created item in full
+        if version == Version::V2 {
+            let ret: v2::GetItem = self.parser.json(&ret)?;
+            return Ok(api::Item::try_from(ret)?);
+        }
+
+        // whereas v1 only gives back its uuid, but everything in it other
+        // than a generated password is already known
+        let ret: v1::CreateItem = self.parser.json(&ret)?;
+
+        let password = match password {
+            NewPassword::Given(password) => password.to_string(),
+            NewPassword::Generate(_) => {
+                let ret = self
+                    .exec(&["get", "item", &ret.uuid, "--fields", "password"])
+                    .await?;
+                std::str::from_utf8(&ret)?.trim_end().to_string()
+            }
+        };
+
+        Ok(new_login(name, username, url, password)?)
+    }
+
+    /// Lists every item in the account, optionally including those that have
+    /// been archived.
+    async fn list_items(
+        &self,
+        include_archive: bool,
+        filter: api::Filter<'_>,
+    ) -> Result<Vec<ItemSummary>, Error> {
+        let version = self.version().await?;
+        let filter = self.default_vault(filter);
+
+        // service accounts can only list items a vault at a time
+        if self.service_account && filter.vault.is_none() {
+            let mut items = Vec::new();
+
+            for vault in api::Backend::vaults(self).await? {
+                let mut filter = filter;
+                filter.vault = Some(&vault.uuid);
+
+                let out = self
+                    .exec(list_items_args(version, include_archive, filter))
+                    .await?;
+                items.extend(parse_listing(&self.parser, version, &out)?);
+            }
+
+            return Ok(items);
+        }
+
+        let out = self
+            .exec(list_items_args(version, include_archive, filter))
+            .await?;
+
+        parse_listing(&self.parser, version, &out)
+    }
+
+    /// Same as `list_items`, but yields items as op prints them rather than
+    /// once it's finished.
+    fn list_items_stream(
+        &self,
+        include_archive: bool,
+    ) -> impl Stream<Item = Result<ItemSummary, Error>> + '_ {
+        futures::stream::once(async move {
+            // there's no one listing to stream for a service account
+            if self.service_account {
+                let items = self.list_items(include_archive, api::Filter::default());
+                let items = items.await?.into_iter().map(Ok);
+                return Ok(futures::stream::iter(items).boxed());
+            }
+
+            let version = self.version().await?;
+            let args = list_items_args(
+                version,
+                include_archive,
+                self.default_vault(api::Filter::default()),
+            );
+            let session = self.session();
+            tracing::debug!(args = %redacted(&args), "streaming from op");
+
+            let mut items = Listing::spawn(
+                self.command(&args),
+                version,
+                self.timeout,
+                self.parser.clone(),
+            )?
+            .into_stream()
+            .boxed();
+
+            // op fails before listing anything, so there's nothing to take
+            // back when retrying
+            match items.next().await {
+                Some(Err(e)) if self.should_reauth(&e) => {
+                    self.reauthenticate(session).await?;
+
+                    Ok::<_, Error>(
+                        Listing::spawn(
+                            self.command(&args),
+                            version,
+                            self.timeout,
+                            self.parser.clone(),
+                        )?
+                        .into_stream()
+                        .boxed(),
+                    )
+                }
+                first => Ok(futures::stream::iter(first).chain(items).boxed()),
+            }
+        })
+        .try_flatten()
+    }
+
+    fn session(&self) -> Option<String> {
+        self.session.read().unwrap().clone()
+    }
+
+    /// `filter`, limited to the configured vault if it isn't already limited
+    /// to one.
+    fn default_vault<'a>(&'a self, mut filter: api::Filter<'a>) -> api::Filter<'a> {
+        if filter.vault.is_none() {
+            filter.vault = self.vault.as_deref();
+        }
+
+        filter
+    }
+
+    /// Whether op unlocks through the desktop app.
+    fn uses_app(&self) -> bool {
+        self.app_integration.unwrap_or_else(|| {
+            std::env::var("OP_BIOMETRIC_UNLOCK_ENABLED").map_or(false, |v| v == "true" || v == "1")
+        })
+    }
+
+    /// The configured op, given the arguments every command is.
+    fn op(&self) -> Command {
+        let mut cmd = Command::new(&self.bin);
+        cmd.args(&self.args);
+
+        if let Some(enabled) = self.app_integration {
+            cmd.env("OP_BIOMETRIC_UNLOCK_ENABLED", enabled.to_string());
+        }
+
+        cmd
+    }
+
+    /// Builds an op command, passing along the session if there is one. op
+    /// is killed if whatever's waiting on it is dropped, so cancelling a
+    /// call doesn't leave it running.
+    fn command<I, S>(&self, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let mut cmd = self.op();
+        cmd.args(args).kill_on_drop(true);
+
+        if let Some(session) = self
+            .session()
+            .filter(|_| !self.service_account && !self.uses_app())
+        {
+            cmd.arg("--session").arg(session);
+        }
+
+        if let Some(account) = &self.account {
+            cmd.arg("--account").arg(account);
+        }
+
+        match self.cache {
+            Some(true) => {
+                cmd.arg("--cache");
+            }
+            Some(false) => {
+                cmd.arg("--cache=false");
+            }
+            None => {}
+        }
+
+        cmd
+    }
+
+    async fn exec<I, S>(&self, args: I) -> Result<Vec<u8>, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let args: Vec<OsString> = args.into_iter().map(|v| v.as_ref().into()).collect();
+        let session = self.session();
+
+        match self.exec_retrying(&args).await {
+            Err(e) if self.should_reauth(&e) => {
+                self.reauthenticate(session).await?;
+                self.exec_retrying(&args).await
+            }
+            res => res,
+        }
+    }
+
+    /// Runs op, trying again after a growing delay while it fails in ways
+    /// that might not last.
+    async fn exec_retrying(&self, args: &[OsString]) -> Result<Vec<u8>, Error> {
+        let mut delay = self.backoff.min(MAX_BACKOFF);
+        let read = is_read(args);
+
+        for _ in 0..self.retries {
+            match self.exec_once(args).await {
+                Err(e) if e.is_transient() && (read || matches!(e, Error::RateLimited)) => {
+                    tracing::debug!(error = %e, ?delay, "retrying op");
+                    tokio::time::delay_for(delay).await;
+                    delay = delay.checked_mul(2).unwrap_or(MAX_BACKOFF).min(MAX_BACKOFF);
+                }
+                res => return res,
+            }
+        }
+
+        self.exec_once(args).await
+    }
+
+    #[tracing::instrument(level = "debug", name = "op", skip(self, args), fields(args = %redacted(args)))]
+    async fn exec_once(&self, args: &[OsString]) -> Result<Vec<u8>, Error> {
+        let cmd = self
+            .within(self.command(args).output())
+            .await?
+            .map_err(Error::Exec)?;
+        tracing::debug!(status = %cmd.status, "op exited");
+
+        if cmd.status.success() {
+            Ok(cmd.stdout)
+        } else {
+            Err(Error::from_stderr(&cmd.stderr))
+        }
+    }
 }
 
-impl Into<api::ItemField> for GetItemDetailsField {
-    fn into(self) -> api::ItemField {
-        api::ItemField {
-            name: if self.field_type.is_empty() {
-                self.name.clone()
-            } else {
-                self.field_type
-            },
-            value: match self.value {
-                Value::Null => String::new(),
-                Value::String(v) => v,
-                Value::Number(v) => format!("{}", v),
-                Value::Bool(v) => if v { "true" } else { "false" }.to_string(),
-                _ => panic!("unknown item field type for {}", self.name),
-            },
-            field_type: api::ItemFieldType::Unknown,
+/// Joins op's args for logging, hiding the values fields are assigned, such
+/// as `password=hunter2`. Values of flags, which never hold secrets, are
+/// left alone even when they contain an `=`, such as URLs.
+fn redacted<S: AsRef<std::ffi::OsStr>>(args: &[S]) -> String {
+    let mut flag = false;
+
+    args.iter()
+        .map(|v| {
+            let arg = v.as_ref().to_string_lossy();
+            let value = flag;
+            flag = arg.starts_with("--") && !arg.contains('=');
+
+            match arg.find('=') {
+                Some(i) if !value && !arg.starts_with('-') => format!("{}=***", &arg[..i]),
+                _ => arg.into_owned(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Renders a field's value as text. Some fields, such as addresses, have
+/// structured values, which are flattened on to a single line.
+fn field_value(value: serde_json::Value) -> String {
+    use serde_json::Value;
+
+    match value {
+        Value::Null => String::new(),
+        Value::String(v) => v,
+        Value::Number(v) => v.to_string(),
+        Value::Bool(v) => v.to_string(),
+        Value::Array(v) => v
+            .into_iter()
+            .map(field_value)
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>()
+            .join(", "),
+        Value::Object(v) => v
+            .into_iter()
+            .map(|(k, v)| (k, field_value(v)))
+            .filter(|(_, v)| !v.is_empty())
+            .map(|(k, v)| format!("{}: {}", k, v))
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
+
+/// Arguments to list every item, in JSON, for the given version of op,
+/// leaving as much of `filter` as it can to op.
+fn list_items_args(version: Version, include_archive: bool, filter: api::Filter<'_>) -> Vec<&str> {
+    let mut args = match version {
+        Version::V1 => vec!["list", "items"],
+        Version::V2 => vec!["item", "list", "--format", "json"],
+    };
+
+    if include_archive {
+        args.push("--include-archive");
+    }
+
+    if let Some(vault) = filter.vault {
+        args.extend(&["--vault", vault]);
+    }
+
+    // op lists items with any of the tags it's given, so it only gets one
+    // and items missing the others are dropped afterwards
+    if let Some(tag) = filter.tags.first() {
+        args.extend(&["--tags", tag]);
+    }
+
+    match filter.category {
+        Some(api::ItemCategory::Unknown) | None => {}
+        Some(category) => args.extend(&["--categories", category.name()]),
+    }
+
+    args
+}
+
+/// Password to give a new login.
+enum NewPassword<'a> {
+    Generate(&'a api::PasswordRecipe),
+    Given(&'a str),
+}
+
+impl NewPassword<'_> {
+    /// Either the `--generate-password` flag or a `password=` assignment.
+    fn arg(&self) -> String {
+        match self {
+            Self::Generate(recipe) => generate_password_arg(recipe),
+            Self::Given(password) => format!("password={}", password),
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct GetItemSection {
-    title: String,
-    #[serde(default)]
-    fields: Vec<GetItemSectionField>,
+/// Assignment statements, in the `[section.]field[type]=value` form op v2
+/// takes, giving a new item the fields of `item`.
+fn assignments(item: &api::Item) -> Vec<String> {
+    let top = item.fields.iter().map(|v| assignment(None, v));
+    let sections = item.sections.iter().flat_map(|section| {
+        section
+            .fields
+            .iter()
+            .map(move |v| assignment(Some(&section.name), v))
+    });
+
+    top.chain(sections).collect()
 }
 
-#[derive(Debug, Deserialize)]
-struct GetItemSectionField {
-    #[serde(rename = "k")]
-    kind: String,
-    #[serde(rename = "n")]
-    name: String,
-    #[serde(rename = "t")]
-    field_type: String,
-    #[serde(rename = "v", default)]
-    value: Value,
+/// The assignment statement setting `field`. The username, password and notes
+/// every item has are assigned by name alone, so they fill in those fields
+/// rather than adding ones of their own.
+fn assignment(section: Option<&str>, field: &api::ItemField) -> String {
+    let kind = match field.field_type {
+        api::ItemFieldType::Username | api::ItemFieldType::Password | api::ItemFieldType::Note
+            if section.is_none() =>
+        {
+            ""
+        }
+        api::ItemFieldType::Totp => "[otp]",
+        // op can't be given SSH keys this way, so they're at least kept
+        // concealed
+        api::ItemFieldType::Password
+        | api::ItemFieldType::Concealed
+        | api::ItemFieldType::SshKey => "[password]",
+        api::ItemFieldType::Email => "[email]",
+        api::ItemFieldType::Url => "[url]",
+        api::ItemFieldType::Phone => "[phone]",
+        _ => "[text]",
+    };
+
+    let name = match section.filter(|v| !v.is_empty()) {
+        Some(section) => format!("{}.{}", escape(section), escape(&field.name)),
+        None => escape(&field.name),
+    };
+
+    format!("{}{}={}", name, kind, field.value.as_str())
 }
 
-impl Into<api::ItemField> for GetItemSectionField {
-    fn into(self) -> api::ItemField {
-        api::ItemField {
-            name: self.field_type,
-            value: match self.value {
-                Value::Null => String::new(),
-                Value::String(v) => v,
-                Value::Number(v) => format!("{}", v),
-                Value::Bool(v) => if v { "true" } else { "false" }.to_string(),
-                _ => panic!("unknown item field type for {}", self.name),
-            },
-            field_type: if self.name.starts_with("TOTP_") {
-                api::ItemFieldType::Totp
-            } else {
-                api::ItemFieldType::Unknown
-            },
+/// Escapes the characters op gives meaning to in the names of sections and
+/// fields in assignment statements.
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        if matches!(c, '.' | '=' | '[' | ']' | '\\') {
+            escaped.push('\\');
         }
+
+        escaped.push(c);
     }
+
+    escaped
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct CreateItem {
-    uuid: String,
-    vault_uuid: String,
+/// Builds the item op v1 creates for a login, named the same way as the
+/// fields of one fetched with `op get item`.
+fn new_login(
+    name: &str,
+    username: Option<&str>,
+    url: Option<&str>,
+    password: String,
+) -> Result<api::Item, api::InvalidItem> {
+    let mut fields = Vec::with_capacity(2);
+
+    if let Some(username) = username {
+        fields.push(
+            api::ItemField::new("username", username).with_type(api::ItemFieldType::Username),
+        );
+    }
+
+    fields.push(api::ItemField::new("password", password).with_type(api::ItemFieldType::Password));
+
+    api::Item::builder()
+        .title(name)
+        .fields(fields)
+        .urls(url.map(|url| api::ItemUrl::new("", url)))
+        .build()
 }
 
-pub struct OpBackend {}
+/// Translates a recipe to op's `--generate-password=letters,digits,symbols,N`.
+fn generate_password_arg(recipe: &api::PasswordRecipe) -> String {
+    let mut parts = Vec::with_capacity(4);
 
-async fn exec<I, S>(args: I) -> Result<Vec<u8>, Error>
-where
-    I: IntoIterator<Item = S>,
-    S: AsRef<std::ffi::OsStr>,
-{
-    let cmd = Command::new("op")
-        .args(args)
-        .output()
-        .await
-        .map_err(Error::Exec)?;
+    if recipe.letters {
+        parts.push("letters".to_string());
+    }
+
+    if recipe.digits {
+        parts.push("digits".to_string());
+    }
 
-    if cmd.status.success() {
-        Ok(cmd.stdout)
-    } else {
-        Err(Error::Backend(
-            std::str::from_utf8(&cmd.stderr)?.to_string(),
-        ))
+    if recipe.symbols {
+        parts.push("symbols".to_string());
     }
+
+    parts.push(recipe.length.to_string());
+
+    format!("--generate-password={}", parts.join(","))
 }
 
 #[async_trait]
 impl api::Backend for OpBackend {
     type Error = Error;
 
+    async fn capabilities(&self) -> Result<api::Capabilities, Self::Error> {
+        let mut capabilities = api::Capabilities::default();
+        capabilities.supports_documents = true;
+        capabilities.supports_multiple_accounts = true;
+        Ok(capabilities)
+    }
+
+    async fn signin(&self, account: Option<&str>) -> Result<(), Self::Error> {
+        if self.service_account {
+            return Err(Error::ServiceAccount);
+        }
+
+        let mut cmd = self.op();
+        cmd.arg("signin");
+
+        if let Some(account) = account.or(self.account.as_deref()) {
+            if self.version().await? == Version::V2 {
+                cmd.arg("--account");
+            }
+
+            cmd.arg(account);
+        }
+
+        // op asks the app to unlock, and there's no token to keep
+        if self.uses_app() {
+            let status = cmd.status().await.map_err(Error::Exec)?;
+            return if status.success() {
+                Ok(())
+            } else {
+                Err(Error::Signin)
+            };
+        }
+
+        // op prompts for the master password on the tty, so only stdout (where
+        // the raw token is written) is captured. There's no timeout as it
+        // waits on the user, and it isn't killed on drop as it gets Ctrl-C
+        // from the tty itself, and needs to put the terminal back as it was.
+        let cmd = cmd
+            .arg("--raw")
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .output()
+            .await
+            .map_err(Error::Exec)?;
+
+        if !cmd.status.success() {
+            return Err(Error::Signin);
+        }
+
+        let session = std::str::from_utf8(&cmd.stdout)?.trim().to_string();
+        api::session::write("op", &session).map_err(Error::Session)?;
+        *self.session.write().unwrap() = Some(session);
+
+        Ok(())
+    }
+
+    async fn signout(&self) -> Result<(), Self::Error> {
+        if self.service_account {
+            return Err(Error::ServiceAccount);
+        }
+
+        self.exec(&["signout"]).await?;
+
+        api::session::remove("op").map_err(Error::Session)?;
+        *self.session.write().unwrap() = None;
+
+        Ok(())
+    }
+
     async fn account(&self) -> Result<api::AccountMetadata, Self::Error> {
-        let ret: GetAccount = serde_json::from_slice(&exec(&["get", "account"]).await?)?;
+        // service accounts can't see the account itself
+        if self.service_account {
+            let ret: v2::WhoAmI = self
+                .parser
+                .json(&self.exec(&["whoami", "--format", "json"]).await?)?;
+            return Ok(ret.into());
+        }
 
-        Ok(ret.into())
+        self.exec_versioned::<v1::GetAccount, v2::GetAccount, _>(
+            &["get", "account"],
+            &["account", "get", "--format", "json"],
+        )
+        .await
     }
 
     async fn vaults(&self) -> Result<Vec<api::VaultMetadata>, Self::Error> {
-        let ret: Vec<ListVault> = serde_json::from_slice(&exec(&["list", "vaults"]).await?)?;
+        let ret: Vec<api::VaultMetadata> = match self.version().await? {
+            Version::V1 => {
+                let ret: Vec<v1::ListVault> =
+                    self.parser.json(&self.exec(&["list", "vaults"]).await?)?;
+                ret.into_iter().map(|v| v.into()).collect()
+            }
+            Version::V2 => {
+                let ret: Vec<v2::ListVault> = self
+                    .parser
+                    .json(&self.exec(&["vault", "list", "--format", "json"]).await?)?;
+                ret.into_iter().map(|v| v.into()).collect()
+            }
+        };
 
-        Ok(ret.into_iter().map(|v| v.into()).collect())
+        Ok(ret)
     }
 
     #[allow(clippy::filter_map)]
     async fn search(&self, terms: Option<&str>) -> Result<Vec<api::ItemMetadata>, Self::Error> {
-        let ret: Vec<ListItem> = serde_json::from_slice(&exec(&["list", "items"]).await?)?;
+        let ret = self.list_items(false, api::Filter::default()).await?;
 
         let terms = terms.map(str::to_lowercase);
 
         Ok(ret
             .into_iter()
-            .filter(|v| {
-                if let Some(terms) = &terms {
-                    let terms = terms.as_ref();
-
-                    v.uuid == terms
-                        || v.vault_uuid == terms
-                        || v.overview
-                            .urls
-                            .iter()
-                            .any(|v| v.url.to_lowercase().contains(terms))
-                        || v.overview.title.to_lowercase().contains(terms)
-                        || v.overview.account_info.to_lowercase().contains(terms)
-                        || v.overview
-                            .tags
-                            .iter()
-                            .any(|v| v.to_lowercase().contains(terms))
-                } else {
-                    true
-                }
-            })
+            .filter(|v| !v.archived && v.matches(terms.as_deref()))
             .map(|v| v.into())
             .collect())
     }
 
+    fn search_stream<'a>(
+        &'a self,
+        terms: Option<&'a str>,
+    ) -> BoxStream<'a, Result<api::ItemMetadata, Self::Error>> {
+        let terms = terms.map(str::to_lowercase);
+
+        self.list_items_stream(false)
+            .try_filter(move |v| futures::future::ready(!v.archived && v.matches(terms.as_deref())))
+            .map_ok(Into::into)
+            .boxed()
+    }
+
+    /// Stops op as soon as the page is full, so items past it are never
+    /// parsed.
+    async fn search_page(
+        &self,
+        terms: Option<&str>,
+        page: api::Page,
+    ) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        self.search_stream(terms)
+            .skip(page.offset)
+            .take(page.limit)
+            .try_collect()
+            .await
+    }
+
+    /// Leaves picking out vaults, tagged items and categories to op.
+    async fn search_filtered(
+        &self,
+        terms: Option<&str>,
+        filter: api::Filter<'_>,
+    ) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        let ret = self.list_items(false, filter).await?;
+
+        let terms = terms.map(str::to_lowercase);
+
+        Ok(ret
+            .into_iter()
+            .filter(|v| !v.archived && v.matches(terms.as_deref()))
+            .map(Into::<api::ItemMetadata>::into)
+            .filter(|v| filter.matches(v))
+            .collect())
+    }
+
     async fn get(&self, uuid: &str) -> Result<Option<api::Item>, Self::Error> {
-        let ret: GetItem = serde_json::from_slice(&exec(&["get", "item", uuid]).await?)?;
-        Ok(Some(ret.into()))
+        let ret = self
+            .exec_versioned::<v1::GetItem, v2::GetItem, _>(
+                &["get", "item", uuid],
+                &["item", "get", uuid, "--format", "json"],
+            )
+            .await;
+
+        // op only finds items by title when exactly one has it, so finding
+        // several is left for the caller to pick between by searching
+        match ret {
+            Ok(item) => Ok(Some(item)),
+            Err(e) if matches!(e, Error::ItemNotFound) || matches!(e, Error::AmbiguousItem) => {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
     }
 
+    /// op has no way of excluding ambiguous characters, so that part of the
+    /// recipe is ignored.
     async fn generate(
         &self,
         name: &str,
         username: Option<&str>,
         url: Option<&str>,
         tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &api::PasswordRecipe,
     ) -> Result<api::Item, Self::Error> {
-        let mut args = Vec::with_capacity(12);
+        self.create_login(
+            name,
+            username,
+            url,
+            tags,
+            vault,
+            NewPassword::Generate(recipe),
+        )
+        .await
+    }
 
-        args.push(Cow::Borrowed("create"));
-        args.push(Cow::Borrowed("item"));
-        args.push(Cow::Borrowed("Login"));
-        args.push(Cow::Borrowed("--generate-password"));
-        args.push(Cow::Borrowed("--title"));
-        args.push(Cow::Borrowed(name));
+    /// op only accepts field values as arguments, so the password is briefly
+    /// visible to other users on the machine through the process list.
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<api::Item, Self::Error> {
+        self.create_login(
+            name,
+            username,
+            url,
+            tags,
+            vault,
+            NewPassword::Given(password),
+        )
+        .await
+    }
 
-        if let Some(url) = url {
+    async fn create_item(
+        &self,
+        item: &api::Item,
+        category: api::ItemCategory,
+        tags: &[String],
+        vault: Option<&str>,
+    ) -> Result<api::Item, Self::Error> {
+        if self.version().await? == Version::V1 {
+            return Err(api::Unsupported("creating items other than logins on op v1").into());
+        }
+
+        let category = match category {
+            api::ItemCategory::Document => {
+                return Err(api::Unsupported("creating documents from items").into())
+            }
+            // a note can hold whatever fields an item of a category 1p doesn't
+            // know about had
+            api::ItemCategory::Unknown => api::ItemCategory::SecureNote,
+            category => category,
+        };
+        let tags = tags.join(",");
+
+        let mut args = vec![
+            Cow::Borrowed("item"),
+            Cow::Borrowed("create"),
+            Cow::Borrowed("--category"),
+            Cow::Borrowed(category.name()),
+            Cow::Borrowed("--format"),
+            Cow::Borrowed("json"),
+            Cow::Borrowed("--title"),
+            Cow::Borrowed(item.title.as_str()),
+        ];
+
+        // op only takes one url when creating an item
+        if let Some(url) = item.urls.first() {
             args.push(Cow::Borrowed("--url"));
-            args.push(Cow::Borrowed(url));
+            args.push(Cow::Borrowed(&url.url));
         }
 
-        if let Some(tags) = tags {
+        if !tags.is_empty() {
             args.push(Cow::Borrowed("--tags"));
-            args.push(Cow::Borrowed(tags));
+            args.push(Cow::Borrowed(&tags));
         }
 
-        if let Some(username) = username {
-            args.push(Cow::Owned(format!("username={}", username)));
+        if let Some(vault) = vault.or(self.vault.as_deref()) {
+            args.push(Cow::Borrowed("--vault"));
+            args.push(Cow::Borrowed(vault));
+        }
+
+        args.extend(assignments(item).into_iter().map(Cow::Owned));
+
+        let ret = self.exec(args.iter().map(Cow::as_ref)).await?;
+        let ret: v2::GetItem = self.parser.json(&ret)?;
+
+        Ok(api::Item::try_from(ret)?)
+    }
+
+    async fn create_vault(&self, name: &str) -> Result<api::VaultMetadata, Self::Error> {
+        self.exec_versioned::<v1::ListVault, v2::ListVault, _>(
+            &["create", "vault", name],
+            &["vault", "create", name, "--format", "json"],
+        )
+        .await
+    }
+
+    async fn delete_vault(&self, uuid: &str) -> Result<(), Self::Error> {
+        match self.version().await? {
+            Version::V1 => self.exec(&["delete", "vault", uuid]).await,
+            Version::V2 => self.exec(&["vault", "delete", uuid]).await,
+        }?;
+
+        Ok(())
+    }
+
+    async fn rename_vault(&self, uuid: &str, name: &str) -> Result<(), Self::Error> {
+        if self.version().await? == Version::V1 {
+            return Err(api::Unsupported("renaming vaults on op v1").into());
+        }
+
+        self.exec(&["vault", "edit", uuid, "--name", name]).await?;
+
+        Ok(())
+    }
+
+    async fn edit(&self, uuid: &str, edit: &api::ItemEdit) -> Result<(), Self::Error> {
+        if self.version().await? == Version::V1 {
+            return Err(api::Unsupported("editing items on op v1").into());
+        }
+
+        let mut args = vec![
+            Cow::Borrowed("item"),
+            Cow::Borrowed("edit"),
+            Cow::Borrowed(uuid),
+        ];
+
+        if let Some(title) = &edit.title {
+            args.push(Cow::Borrowed("--title"));
+            args.push(Cow::Borrowed(title.as_str()));
+        }
+
+        args.extend(edit.fields.iter().map(|v| Cow::Owned(assignment(None, v))));
+
+        self.exec(args.iter().map(Cow::as_ref)).await?;
+
+        Ok(())
+    }
+
+    async fn set_tags(&self, uuid: &str, tags: &[String]) -> Result<(), Self::Error> {
+        let tags = tags.join(",");
+
+        match self.version().await? {
+            Version::V1 => self.exec(&["edit", "item", uuid, "--tags", &tags]).await?,
+            Version::V2 => self.exec(&["item", "edit", uuid, "--tags", &tags]).await?,
+        };
+
+        Ok(())
+    }
+
+    async fn set_favorite(&self, uuid: &str, favorite: bool) -> Result<(), Self::Error> {
+        if self.version().await? == Version::V1 {
+            return Err(api::Unsupported("favorites on op v1").into());
+        }
+
+        let favorite = format!("--favorite={}", favorite);
+        self.exec(&["item", "edit", uuid, &favorite]).await?;
+
+        Ok(())
+    }
+
+    /// op assigns fields by label, so an existing one-time password is
+    /// assigned by its own label to replace it rather than add another.
+    async fn set_otp(&self, uuid: &str, uri: &str) -> Result<(), Self::Error> {
+        if self.version().await? == Version::V1 {
+            return Err(api::Unsupported("editing one-time passwords on op v1").into());
         }
 
-        let ret: CreateItem = serde_json::from_slice(&exec(args.iter().map(Cow::as_ref)).await?)?;
+        let item = self.get(uuid).await?;
+        let existing = item.as_ref().and_then(|item| {
+            let top = item.fields.iter().map(|v| (None, v));
+            let sections = item.sections.iter().flat_map(|section| {
+                section
+                    .fields
+                    .iter()
+                    .map(move |v| (Some(section.name.as_str()), v))
+            });
+
+            top.chain(sections)
+                .find(|(_, v)| v.field_type == api::ItemFieldType::Totp)
+        });
+
+        let label = match existing {
+            Some((Some(section), field)) if !section.is_empty() => {
+                format!("{}.{}", section, field.name)
+            }
+            Some((_, field)) => field.name.clone(),
+            None => "one-time password".to_string(),
+        };
+
+        let assignment = format!("{}[otp]={}", label, uri);
+        self.exec(&["item", "edit", uuid, &assignment]).await?;
+
+        Ok(())
+    }
+
+    async fn archived(&self) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        Ok(self
+            .list_items(true, api::Filter::default())
+            .await?
+            .into_iter()
+            .filter(|v| v.archived)
+            .map(|v| v.into())
+            .collect())
+    }
+
+    async fn archive(&self, uuid: &str) -> Result<(), Self::Error> {
+        match self.version().await? {
+            Version::V1 => self.exec(&["delete", "item", uuid, "--archive"]).await?,
+            Version::V2 => self.exec(&["item", "delete", uuid, "--archive"]).await?,
+        };
+
+        Ok(())
+    }
+
+    async fn documents(&self) -> Result<Vec<api::DocumentMetadata>, Self::Error> {
+        let ret: Vec<api::DocumentMetadata> = match self.version().await? {
+            Version::V1 => {
+                let ret: Vec<v1::ListDocument> = self
+                    .parser
+                    .json(&self.exec(&["list", "documents"]).await?)?;
+                ret.into_iter().map(|v| v.into()).collect()
+            }
+            Version::V2 => {
+                let ret: Vec<v2::ListDocument> = self
+                    .parser
+                    .json(&self.exec(&["document", "list", "--format", "json"]).await?)?;
+                ret.into_iter().map(|v| v.into()).collect()
+            }
+        };
+
+        Ok(ret)
+    }
+
+    async fn get_document(&self, uuid: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        let ret = match self.version().await? {
+            Version::V1 => self.exec(&["get", "document", uuid]).await?,
+            Version::V2 => self.exec(&["document", "get", uuid]).await?,
+        };
+
+        Ok(Some(ret))
+    }
+
+    async fn create_document(
+        &self,
+        path: &Path,
+        title: Option<&str>,
+        vault: Option<&str>,
+    ) -> Result<api::DocumentMetadata, Self::Error> {
+        let title = title
+            .map(ToString::to_string)
+            .or_else(|| path.file_name().map(|v| v.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+
+        let mut args: Vec<&OsStr> = match self.version().await? {
+            Version::V1 => vec!["create".as_ref(), "document".as_ref()],
+            Version::V2 => vec![
+                "document".as_ref(),
+                "create".as_ref(),
+                "--format".as_ref(),
+                "json".as_ref(),
+            ],
+        };
+        args.push(path.as_os_str());
+        args.push("--title".as_ref());
+        args.push(title.as_ref());
+
+        if let Some(vault) = vault.or(self.vault.as_deref()) {
+            args.push("--vault".as_ref());
+            args.push(vault.as_ref());
+        }
+
+        // both versions return the same minimal response for documents
+        let ret: v1::CreateItem = self.parser.json(&self.exec(args).await?)?;
+
+        Ok(api::DocumentMetadata::new(ret.uuid, ret.vault_uuid, title))
+    }
+
+    async fn get_attachment(
+        &self,
+        item_uuid: &str,
+        attachment_uuid: &str,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        let ret = match self.version().await? {
+            Version::V1 => self.exec(&["get", "document", attachment_uuid]).await?,
+            Version::V2 => {
+                // only part of the item is wanted, so the rest isn't unknown
+                let item: v2::GetItemReference = serde_json::from_slice(
+                    &self
+                        .exec(&["item", "get", item_uuid, "--format", "json"])
+                        .await?,
+                )?;
+
+                self.exec(&["read", "--no-newline", &item.file(attachment_uuid)])
+                    .await?
+            }
+        };
+
+        Ok(Some(ret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_read, Error};
+    use std::ffi::OsString;
+
+    #[test]
+    fn non_utf8_stderr_is_classified() {
+        let e = Error::from_stderr(b"[ERROR] \xff item not found");
+        assert!(matches!(e, Error::ItemNotFound), "{:?}", e);
+    }
+
+    #[test]
+    fn several_matching_items_are_ambiguous() {
+        let e = Error::from_stderr(
+            b"[ERROR] 2024/01/01 00:00:00 More than one item matches \"GitHub\". Try again and specify the item by its ID:",
+        );
+        assert!(matches!(e, Error::AmbiguousItem), "{:?}", e);
+    }
+
+    #[test]
+    fn non_utf8_stderr_is_kept_lossily() {
+        let e = Error::from_stderr(b"[ERROR] \xff couldn't reach the server");
+
+        match e {
+            Error::Unknown(message) => {
+                assert_eq!(message, "[ERROR] \u{fffd} couldn't reach the server");
+            }
+            e => panic!("expected Error::Unknown, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn only_reads_are_retried_after_network_errors() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
 
-        Ok(self.get(&ret.uuid).await?.unwrap_or_else(|| unreachable!()))
+        assert!(is_read(&args(&["item", "get", "abc", "--format", "json"])));
+        assert!(is_read(&args(&["list", "items"])));
+        assert!(!is_read(&args(&["item", "create", "--title", "get"])));
+        assert!(!is_read(&args(&["item", "edit", "abc", "title=list"])));
     }
 }
@@ -0,0 +1,203 @@
+//! Incremental parsing of op's item listings, so items can be handed out as
+//! op prints them rather than once it's finished.
+
+use crate::{parse::Parser, v1, v2, Error, ItemSummary, Version};
+use futures::Stream;
+use std::{process::Stdio, time::Duration};
+use tokio::{
+    io::AsyncReadExt,
+    process::{Child, ChildStdout, Command},
+    task::JoinHandle,
+};
+
+/// Splits a JSON array into its elements as its bytes arrive.
+#[derive(Default)]
+struct Elements {
+    buf: Vec<u8>,
+    /// How much of `buf` has been scanned.
+    pos: usize,
+    /// Where the element being scanned starts, once the opening bracket of the
+    /// array has been seen.
+    start: Option<usize>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Elements {
+    fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns the next element to have arrived in full, if there is one.
+    fn next(&mut self) -> Option<Vec<u8>> {
+        while self.pos < self.buf.len() {
+            let byte = self.buf[self.pos];
+            self.pos += 1;
+
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+
+                continue;
+            }
+
+            let end = match byte {
+                b'"' => {
+                    self.in_string = true;
+                    false
+                }
+                b'[' | b'{' => {
+                    self.depth += 1;
+
+                    if self.depth == 1 {
+                        self.start = Some(self.pos);
+                    }
+
+                    false
+                }
+                b',' => self.depth == 1,
+                b']' | b'}' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    self.depth == 0
+                }
+                _ => false,
+            };
+
+            if let (true, Some(start)) = (end, self.start) {
+                let element = self.buf[start..self.pos - 1].to_vec();
+
+                // everything up to here has been handed out, so there's no
+                // need to hold on to it
+                self.buf.drain(..self.pos);
+                self.pos = 0;
+                self.start = if self.depth == 1 { Some(0) } else { None };
+
+                // an empty array has a single, blank, element
+                if !element.iter().all(u8::is_ascii_whitespace) {
+                    return Some(element);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// A running `op` command listing items.
+pub struct Listing {
+    child: Child,
+    stdout: ChildStdout,
+    /// Everything op writes to stderr, read as it's written so op never
+    /// blocks on a full pipe while stdout's still being read.
+    stderr: Option<JoinHandle<std::io::Result<Vec<u8>>>>,
+    elements: Elements,
+    version: Version,
+    /// How long op can go without printing anything before it's killed.
+    timeout: Option<Duration>,
+    parser: Parser,
+    finished: bool,
+}
+
+impl Listing {
+    /// Starts `cmd`, which should print a JSON array of items in the schema
+    /// used by `version`, parsing each with `parser`.
+    pub fn spawn(
+        mut cmd: Command,
+        version: Version,
+        timeout: Option<Duration>,
+        parser: Parser,
+    ) -> Result<Self, Error> {
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(Error::Exec)?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().map(|mut pipe| {
+            tokio::spawn(async move {
+                let mut stderr = Vec::new();
+                pipe.read_to_end(&mut stderr).await.map(|_| stderr)
+            })
+        });
+
+        Ok(Self {
+            child,
+            stdout,
+            stderr,
+            elements: Elements::default(),
+            version,
+            timeout,
+            parser,
+            finished: false,
+        })
+    }
+
+    /// Yields each item as soon as it's been parsed. Stopping early kills op.
+    pub fn into_stream(self) -> impl Stream<Item = Result<ItemSummary, Error>> {
+        futures::stream::try_unfold(self, |mut listing| async move {
+            Ok(listing.next().await?.map(|v| (v, listing)))
+        })
+    }
+
+    async fn next(&mut self) -> Result<Option<ItemSummary>, Error> {
+        let mut chunk = [0; 8192];
+
+        loop {
+            if let Some(element) = self.elements.next() {
+                return self.parse(&element).map(Some);
+            }
+
+            if self.finished {
+                return Ok(None);
+            }
+
+            let read = self.stdout.read(&mut chunk);
+            let len = match self.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, read)
+                    .await
+                    .map_err(|_| Error::Timeout(timeout))?,
+                None => read.await,
+            }
+            .map_err(Error::Exec)?;
+
+            if len == 0 {
+                self.finished = true;
+                self.wait().await?;
+            } else {
+                self.elements.push(&chunk[..len]);
+            }
+        }
+    }
+
+    fn parse(&self, element: &[u8]) -> Result<ItemSummary, Error> {
+        Ok(match self.version {
+            Version::V1 => self.parser.json::<v1::ListItem>(element)?.into(),
+            Version::V2 => self.parser.json::<v2::ListItem>(element)?.into(),
+        })
+    }
+
+    async fn wait(&mut self) -> Result<(), Error> {
+        let stderr = match self.stderr.take() {
+            // the task only fails to finish if the runtime's shutting down
+            Some(task) => task.await.unwrap_or_else(|_| Ok(Vec::new())),
+            None => Ok(Vec::new()),
+        }
+        .map_err(Error::Exec)?;
+
+        let status = (&mut self.child).await.map_err(Error::Exec)?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&stderr))
+        }
+    }
+}
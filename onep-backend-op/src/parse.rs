@@ -0,0 +1,100 @@
+//! Parsing of the JSON op prints, which gains fields from one release to the
+//! next. By default fields 1p doesn't know about are skipped and logged, so a
+//! new op keeps working, but they can be made an error instead to find where
+//! the schemas in `v1` and `v2` have fallen behind.
+
+use crate::Error;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_ignored::Path;
+use serde_json::Value;
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+};
+
+/// Parses JSON from op, either failing on fields that aren't in the schema
+/// being parsed into or logging them.
+#[derive(Debug, Clone, Default)]
+pub struct Parser {
+    strict: bool,
+    /// Unknown fields that have already been logged, so a listing of
+    /// thousands of items only mentions each one once.
+    logged: Arc<Mutex<BTreeSet<String>>>,
+}
+
+impl Parser {
+    pub fn new(strict: bool) -> Self {
+        Self {
+            strict,
+            ..Self::default()
+        }
+    }
+
+    /// Parses `out` into `T`, pointing at the field that couldn't be parsed
+    /// when it fails.
+    pub fn json<T: DeserializeOwned>(&self, out: &[u8]) -> Result<T, Error> {
+        let mut unknown = Vec::new();
+        let mut de = serde_json::Deserializer::from_slice(out);
+
+        let mut callback = |path: Path| unknown.push((path.to_string(), field(&path)));
+        let ret = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(
+            &mut de,
+            &mut callback,
+        ))
+        .map_err(|e| {
+            let path = e.path().to_string();
+
+            if path == "." {
+                Error::Json(e.into_inner())
+            } else {
+                Error::Field(path, e.into_inner())
+            }
+        })?;
+        de.end()?;
+
+        if self.strict {
+            if let Some((path, _)) = unknown.into_iter().next() {
+                return Err(Error::UnknownField(path));
+            }
+        } else if !unknown.is_empty() {
+            let mut logged = self.logged.lock().unwrap();
+
+            for (_, field) in unknown {
+                if !logged.contains(&field) {
+                    tracing::debug!(%field, "ignoring field op returned that 1p doesn't know about");
+                    logged.insert(field);
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Where `path` is in the schema, leaving out the index of each array it's
+/// in so the same field in every element of a listing is only logged once.
+fn field(path: &Path) -> String {
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, .. } => format!("{}[]", field(parent)),
+        Path::Map { parent, key } => match field(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => field(parent),
+    }
+}
+
+/// A field op is known to return that 1p has no use for, declared so it isn't
+/// taken for one that's new. It's skipped in a way that doesn't count as
+/// unknown, which `serde::de::IgnoredAny` would.
+#[derive(Debug, Default)]
+pub struct Unused;
+
+impl<'de> Deserialize<'de> for Unused {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Value::deserialize(deserializer).map(|_| Self)
+    }
+}
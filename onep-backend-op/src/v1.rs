@@ -0,0 +1,373 @@
+//! Deserializers for the JSON emitted by op v1 (`op list items`, `op get
+//! item`, etc).
+
+use crate::{parse::Unused, timestamp, ItemSummary};
+use onep_backend_api as api;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{
+    convert::TryFrom,
+    time::{Duration, UNIX_EPOCH},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAccount {
+    name: String,
+    domain: String,
+    #[serde(default)]
+    uuid: Unused,
+    #[serde(rename = "type", default)]
+    kind: Unused,
+    #[serde(default)]
+    state: Unused,
+    #[serde(default)]
+    avatar: Unused,
+    #[serde(rename = "baseAvatarURL", default)]
+    base_avatar_url: Unused,
+    #[serde(default)]
+    created_at: Unused,
+}
+
+impl Into<api::AccountMetadata> for GetAccount {
+    fn into(self) -> api::AccountMetadata {
+        api::AccountMetadata::new(self.name, self.domain)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListVault {
+    uuid: String,
+    name: String,
+}
+
+impl Into<api::VaultMetadata> for ListVault {
+    fn into(self) -> api::VaultMetadata {
+        api::VaultMetadata::new(self.uuid, self.name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListItem {
+    uuid: String,
+    vault_uuid: String,
+    created_at: String,
+    updated_at: String,
+    #[serde(default)]
+    template_uuid: String,
+    #[serde(default)]
+    trashed: String,
+    #[serde(default)]
+    fav_index: u32,
+    overview: ItemOverview,
+    #[serde(default)]
+    changer_uuid: Unused,
+    #[serde(default)]
+    item_version: Unused,
+}
+
+impl Into<ItemSummary> for ListItem {
+    fn into(self) -> ItemSummary {
+        ItemSummary {
+            title: self.overview.title,
+            account_info: self.overview.account_info,
+            uuid: self.uuid,
+            vault_uuid: self.vault_uuid,
+            urls: self.overview.urls.into_iter().map(|v| v.url).collect(),
+            tags: self.overview.tags,
+            // favorites are ordered by their index, everything else is 0
+            favorite: self.fav_index > 0,
+            archived: self.trashed == "Y",
+            created: timestamp(&self.created_at),
+            updated: timestamp(&self.updated_at),
+            category: category(&self.template_uuid),
+        }
+    }
+}
+
+/// v1 identifies categories by the uuid of their template.
+fn category(template_uuid: &str) -> api::ItemCategory {
+    match template_uuid {
+        "001" => api::ItemCategory::Login,
+        "002" => api::ItemCategory::CreditCard,
+        "003" => api::ItemCategory::SecureNote,
+        "004" => api::ItemCategory::Identity,
+        "005" => api::ItemCategory::Password,
+        "006" => api::ItemCategory::Document,
+        "100" => api::ItemCategory::SoftwareLicense,
+        "101" => api::ItemCategory::BankAccount,
+        "102" => api::ItemCategory::Database,
+        "109" => api::ItemCategory::WirelessRouter,
+        "110" => api::ItemCategory::Server,
+        "112" => api::ItemCategory::ApiCredential,
+        "114" => api::ItemCategory::SshKey,
+        _ => api::ItemCategory::Unknown,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemOverview {
+    #[serde(rename = "URLs", default)]
+    urls: Vec<ItemOverviewUrl>,
+    title: String,
+    url: Option<String>,
+    #[serde(rename = "ainfo")]
+    account_info: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Password strength, and whether it's in a breach or was generated,
+    /// which `audit` works out for itself.
+    #[serde(default)]
+    ps: Unused,
+    #[serde(default)]
+    pbe: Unused,
+    #[serde(default)]
+    pgrng: Unused,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemOverviewUrl {
+    #[serde(rename = "l", default)]
+    label: String,
+    #[serde(rename = "u")]
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetItem {
+    uuid: String,
+    details: GetItemDetails,
+    overview: ItemOverview,
+    #[serde(default)]
+    vault_uuid: Unused,
+    #[serde(default)]
+    template_uuid: Unused,
+    #[serde(default)]
+    trashed: Unused,
+    #[serde(default)]
+    fav_index: Unused,
+    #[serde(default)]
+    created_at: Unused,
+    #[serde(default)]
+    updated_at: Unused,
+    #[serde(default)]
+    changer_uuid: Unused,
+    #[serde(default)]
+    item_version: Unused,
+}
+
+impl TryFrom<GetItem> for api::Item {
+    type Error = api::InvalidItem;
+
+    fn try_from(item: GetItem) -> Result<Self, Self::Error> {
+        let uuid = item.uuid;
+
+        // older items only have the single url
+        let mut urls: Vec<_> = item
+            .overview
+            .urls
+            .into_iter()
+            .map(|v| api::ItemUrl::new(v.label, v.url))
+            .collect();
+
+        if urls.is_empty() {
+            urls.extend(item.overview.url.map(|url| api::ItemUrl::new("", url)));
+        }
+
+        api::Item::builder()
+            .title(item.overview.title)
+            .fields(
+                item.details
+                    .fields
+                    .into_iter()
+                    .map(Into::into)
+                    .filter(|f: &api::ItemField| !f.value.is_empty()),
+            )
+            .sections(api::ItemSection::merge(
+                item.details.sections.into_iter().map(|v| {
+                    api::ItemSection::new(v.title).with_fields(
+                        v.fields
+                            .into_iter()
+                            .map(Into::into)
+                            .filter(|f: &api::ItemField| !f.value.is_empty()),
+                    )
+                }),
+            ))
+            .urls(urls)
+            // v1 can only attach a file to document items, and the file is
+            // fetched using the uuid of the item itself
+            .attachments(
+                item.details
+                    .document_attributes
+                    .map(|v| api::AttachmentMetadata::new(uuid, v.file_name, v.decrypted_size)),
+            )
+            .history(item.details.password_history.into_iter().map(|v| {
+                api::HistoricalValue::new(v.value, Some(UNIX_EPOCH + Duration::from_secs(v.time)))
+            }))
+            .build()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetItemDetails {
+    #[serde(default)]
+    fields: Vec<GetItemDetailsField>,
+    #[serde(default)]
+    sections: Vec<GetItemSection>,
+    document_attributes: Option<GetItemDocumentAttributes>,
+    #[serde(default)]
+    password_history: Vec<GetItemPasswordHistory>,
+    #[serde(default)]
+    notes_plain: Unused,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemPasswordHistory {
+    value: String,
+    time: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetItemDocumentAttributes {
+    file_name: String,
+    #[serde(default)]
+    decrypted_size: u64,
+    #[serde(default)]
+    document_id: Unused,
+    #[serde(default)]
+    encrypted_size: Unused,
+    #[serde(default)]
+    unencrypted_size: Unused,
+    #[serde(default)]
+    encryption_key: Unused,
+    #[serde(default)]
+    signing_key: Unused,
+    #[serde(default)]
+    integrity_hash: Unused,
+    #[serde(default)]
+    nonce: Unused,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemDetailsField {
+    name: String,
+    #[serde(rename = "designation")]
+    field_type: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    value: Value,
+}
+
+impl Into<api::ItemField> for GetItemDetailsField {
+    fn into(self) -> api::ItemField {
+        let field_type = match (self.field_type.as_str(), self.kind.as_str()) {
+            ("password", _) => api::ItemFieldType::Password,
+            ("username", _) => api::ItemFieldType::Username,
+            (_, "P") => api::ItemFieldType::Concealed,
+            (_, "E") => api::ItemFieldType::Email,
+            (_, "U") => api::ItemFieldType::Url,
+            _ => api::ItemFieldType::Unknown,
+        };
+
+        let name = if self.field_type.is_empty() {
+            self.name
+        } else {
+            self.field_type
+        };
+
+        api::ItemField::new(name, crate::field_value(self.value)).with_type(field_type)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemSection {
+    title: String,
+    #[serde(default)]
+    name: Unused,
+    #[serde(default)]
+    fields: Vec<GetItemSectionField>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemSectionField {
+    #[serde(rename = "k")]
+    kind: String,
+    #[serde(rename = "n")]
+    name: String,
+    #[serde(rename = "t")]
+    field_type: String,
+    #[serde(rename = "v", default)]
+    value: Value,
+    /// Hints for how the field's edited in the apps.
+    #[serde(rename = "a", default)]
+    attributes: Unused,
+    #[serde(rename = "inputTraits", default)]
+    input_traits: Unused,
+}
+
+impl Into<api::ItemField> for GetItemSectionField {
+    fn into(self) -> api::ItemField {
+        let field_type = match self.kind.as_str() {
+            _ if self.name.starts_with("TOTP_") => api::ItemFieldType::Totp,
+            "concealed" => api::ItemFieldType::Concealed,
+            "email" => api::ItemFieldType::Email,
+            "URL" => api::ItemFieldType::Url,
+            "date" | "monthYear" => api::ItemFieldType::Date,
+            "phone" => api::ItemFieldType::Phone,
+            "address" => api::ItemFieldType::Address,
+            _ => api::ItemFieldType::Unknown,
+        };
+
+        api::ItemField::new(self.field_type, crate::field_value(self.value)).with_type(field_type)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateItem {
+    pub uuid: String,
+    pub vault_uuid: String,
+    #[serde(default)]
+    created_at: Unused,
+    #[serde(default)]
+    updated_at: Unused,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListDocument {
+    uuid: String,
+    vault_uuid: String,
+    overview: DocumentOverview,
+    #[serde(default)]
+    template_uuid: Unused,
+    #[serde(default)]
+    trashed: Unused,
+    #[serde(default)]
+    created_at: Unused,
+    #[serde(default)]
+    updated_at: Unused,
+    #[serde(default)]
+    changer_uuid: Unused,
+    #[serde(default)]
+    item_version: Unused,
+}
+
+impl Into<api::DocumentMetadata> for ListDocument {
+    fn into(self) -> api::DocumentMetadata {
+        api::DocumentMetadata::new(self.uuid, self.vault_uuid, self.overview.title)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DocumentOverview {
+    title: String,
+    #[serde(default)]
+    ainfo: Unused,
+    #[serde(default)]
+    tags: Unused,
+}
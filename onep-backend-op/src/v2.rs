@@ -0,0 +1,379 @@
+//! Deserializers for the JSON emitted by op v2 (`op item list --format json`,
+//! `op item get`, etc).
+
+use crate::{parse::Unused, timestamp, ItemSummary};
+use onep_backend_api as api;
+use serde::Deserialize;
+use serde_json::Value;
+use std::convert::TryFrom;
+
+#[derive(Debug, Deserialize)]
+pub struct GetAccount {
+    name: String,
+    domain: String,
+    #[serde(default)]
+    id: Unused,
+    #[serde(rename = "type", default)]
+    kind: Unused,
+    #[serde(default)]
+    state: Unused,
+    #[serde(default)]
+    created_at: Unused,
+}
+
+impl Into<api::AccountMetadata> for GetAccount {
+    fn into(self) -> api::AccountMetadata {
+        api::AccountMetadata::new(self.name, self.domain)
+    }
+}
+
+/// Who op is signed in as, which is all a service account can find out
+/// about the account it's in.
+#[derive(Debug, Deserialize)]
+pub struct WhoAmI {
+    url: String,
+    user_type: String,
+    #[serde(default)]
+    email: Unused,
+    #[serde(default)]
+    user_uuid: Unused,
+    #[serde(default)]
+    account_uuid: Unused,
+}
+
+impl Into<api::AccountMetadata> for WhoAmI {
+    fn into(self) -> api::AccountMetadata {
+        let domain = self
+            .url
+            .trim_start_matches("https://")
+            .trim_end_matches('/')
+            .to_string();
+        // the account's name isn't given, so it's called by its subdomain
+        let name = domain.split('.').next().unwrap_or_default().to_string();
+
+        let mut account = api::AccountMetadata::new(name, domain);
+        account.service_account = self.user_type == "SERVICE_ACCOUNT";
+        account
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListVault {
+    id: String,
+    name: String,
+    #[serde(default)]
+    content_version: Unused,
+    #[serde(default)]
+    created_at: Unused,
+    #[serde(default)]
+    updated_at: Unused,
+    #[serde(default)]
+    items: Unused,
+}
+
+impl Into<api::VaultMetadata> for ListVault {
+    fn into(self) -> api::VaultMetadata {
+        api::VaultMetadata::new(self.id, self.name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VaultRef {
+    id: String,
+    #[serde(default)]
+    name: Unused,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListItem {
+    id: String,
+    title: String,
+    vault: VaultRef,
+    #[serde(default)]
+    additional_information: String,
+    #[serde(default)]
+    urls: Vec<ItemUrl>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    favorite: bool,
+    #[serde(default)]
+    state: String,
+    #[serde(default)]
+    created_at: String,
+    #[serde(default)]
+    updated_at: String,
+    #[serde(default)]
+    category: String,
+    #[serde(default)]
+    version: Unused,
+    #[serde(default)]
+    last_edited_by: Unused,
+}
+
+impl Into<ItemSummary> for ListItem {
+    fn into(self) -> ItemSummary {
+        ItemSummary {
+            uuid: self.id,
+            vault_uuid: self.vault.id,
+            title: self.title,
+            account_info: self.additional_information,
+            urls: self.urls.into_iter().map(|v| v.href).collect(),
+            tags: self.tags,
+            favorite: self.favorite,
+            archived: self.state == "ARCHIVED",
+            created: timestamp(&self.created_at),
+            updated: timestamp(&self.updated_at),
+            category: self.category.parse().unwrap_or(api::ItemCategory::Unknown),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemUrl {
+    href: String,
+    #[serde(default)]
+    label: String,
+    #[serde(default)]
+    primary: Unused,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetItem {
+    title: String,
+    #[serde(default)]
+    sections: Vec<GetItemSection>,
+    #[serde(default)]
+    fields: Vec<GetItemField>,
+    #[serde(default)]
+    urls: Vec<ItemUrl>,
+    #[serde(default)]
+    files: Vec<GetItemFile>,
+    /// Everything else is already known from listing the item.
+    #[serde(default)]
+    id: Unused,
+    #[serde(default)]
+    vault: Unused,
+    #[serde(default)]
+    category: Unused,
+    #[serde(default)]
+    additional_information: Unused,
+    #[serde(default)]
+    tags: Unused,
+    #[serde(default)]
+    favorite: Unused,
+    #[serde(default)]
+    state: Unused,
+    #[serde(default)]
+    version: Unused,
+    #[serde(default)]
+    last_edited_by: Unused,
+    #[serde(default)]
+    created_at: Unused,
+    #[serde(default)]
+    updated_at: Unused,
+}
+
+impl TryFrom<GetItem> for api::Item {
+    type Error = api::InvalidItem;
+
+    fn try_from(item: GetItem) -> Result<Self, Self::Error> {
+        let mut fields = Vec::new();
+        let mut history = Vec::new();
+        let mut sections: Vec<_> = item
+            .sections
+            .into_iter()
+            .map(|v| (v.id, api::ItemSection::new(v.label.unwrap_or_default())))
+            .collect();
+
+        for mut field in item.fields {
+            if let Some(details) = field.password_details.take() {
+                history.extend(
+                    details
+                        .history
+                        .into_iter()
+                        .map(|value| api::HistoricalValue::new(value, None)),
+                );
+            }
+
+            let section = field.section.as_ref().map(|v| v.id.clone());
+            let field: api::ItemField = field.into();
+
+            if field.value.is_empty() {
+                continue;
+            }
+
+            // builtin fields (username, password, notes) aren't in a section
+            // of their own
+            match section.and_then(|id| sections.iter_mut().find(|(v, _)| *v == id)) {
+                Some((_, section)) => section.fields.push(field),
+                None => fields.push(field),
+            }
+        }
+
+        api::Item::builder()
+            .title(item.title)
+            .fields(fields)
+            .sections(api::ItemSection::merge(
+                sections.into_iter().map(|(_, v)| v),
+            ))
+            .urls(
+                item.urls
+                    .into_iter()
+                    .map(|v| api::ItemUrl::new(v.label, v.href)),
+            )
+            .attachments(
+                item.files
+                    .into_iter()
+                    .map(|v| api::AttachmentMetadata::new(v.id, v.name, v.size)),
+            )
+            .history(history)
+            .build()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemFile {
+    id: String,
+    name: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    content_path: Unused,
+    #[serde(default)]
+    section: Unused,
+}
+
+/// Just enough of `op item get` to build a secret reference to one of the
+/// item's files, which is the only way v2 can read them.
+#[derive(Debug, Deserialize)]
+pub struct GetItemReference {
+    id: String,
+    vault: VaultRef,
+}
+
+impl GetItemReference {
+    pub fn file(&self, file_uuid: &str) -> String {
+        format!("op://{}/{}/{}", self.vault.id, self.id, file_uuid)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemSection {
+    id: String,
+    label: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemField {
+    id: String,
+    section: Option<GetItemFieldSection>,
+    #[serde(rename = "type", default)]
+    field_type: String,
+    purpose: Option<String>,
+    label: Option<String>,
+    #[serde(default)]
+    value: Value,
+    password_details: Option<GetItemPasswordDetails>,
+    ssh_formats: Option<GetItemSshFormats>,
+    #[serde(default)]
+    reference: Unused,
+    #[serde(default)]
+    entropy: Unused,
+    /// The current code, which `totp` works out from the secret in `value`.
+    #[serde(default)]
+    totp: Unused,
+}
+
+/// op v2 only keeps the previous values of a password, not when they were
+/// changed.
+#[derive(Debug, Deserialize)]
+struct GetItemPasswordDetails {
+    #[serde(default)]
+    history: Vec<String>,
+    #[serde(default)]
+    strength: Unused,
+    #[serde(default)]
+    entropy: Unused,
+    #[serde(default)]
+    generated: Unused,
+}
+
+/// SSH keys are stored in PKCS#8, but op also gives them in the `OpenSSH`
+/// format that `ssh-keygen` writes.
+#[derive(Debug, Deserialize)]
+struct GetItemSshFormats {
+    openssh: Option<GetItemSshFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemSshFormat {
+    value: String,
+}
+
+/// Maps a field's type, and its purpose on the fields every login has, to
+/// the api's field types.
+fn field_type(kind: &str, purpose: Option<&str>) -> api::ItemFieldType {
+    match (purpose, kind) {
+        (Some("PASSWORD"), _) => api::ItemFieldType::Password,
+        (Some("USERNAME"), _) => api::ItemFieldType::Username,
+        (Some("NOTES"), _) => api::ItemFieldType::Note,
+        (_, "OTP") => api::ItemFieldType::Totp,
+        (_, "SSHKEY") => api::ItemFieldType::SshKey,
+        (_, "CONCEALED") => api::ItemFieldType::Concealed,
+        (_, "EMAIL") => api::ItemFieldType::Email,
+        (_, "URL") => api::ItemFieldType::Url,
+        (_, kind) if kind == "DATE" || kind == "MONTH_YEAR" => api::ItemFieldType::Date,
+        (_, "PHONE") => api::ItemFieldType::Phone,
+        (_, "ADDRESS") => api::ItemFieldType::Address,
+        _ => api::ItemFieldType::Unknown,
+    }
+}
+
+impl Into<api::ItemField> for GetItemField {
+    fn into(self) -> api::ItemField {
+        let id = self.id;
+        let purpose = self.purpose;
+        let field_type = field_type(&self.field_type, purpose.as_deref());
+
+        let value = match self.ssh_formats.and_then(|v| v.openssh) {
+            Some(openssh) => Value::String(openssh.value),
+            None => self.value,
+        };
+
+        let name = self
+            .label
+            .or_else(|| purpose.map(|v| v.to_lowercase()))
+            .unwrap_or(id);
+
+        api::ItemField::new(name, crate::field_value(value)).with_type(field_type)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GetItemFieldSection {
+    id: String,
+    #[serde(default)]
+    label: Unused,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListDocument {
+    id: String,
+    title: String,
+    vault: VaultRef,
+    #[serde(default)]
+    version: Unused,
+    #[serde(default)]
+    last_edited_by: Unused,
+    #[serde(default)]
+    created_at: Unused,
+    #[serde(default)]
+    updated_at: Unused,
+}
+
+impl Into<api::DocumentMetadata> for ListDocument {
+    fn into(self) -> api::DocumentMetadata {
+        api::DocumentMetadata::new(self.id, self.vault.id, self.title)
+    }
+}
@@ -0,0 +1,451 @@
+//! A backend for [pass], the standard unix password manager. This crate uses
+//! `onep_backend_api::Backend` to provide an implementation of a password
+//! store backend for use 1p-cli.
+//!
+//! Top-level directories in the store are exposed as vaults and every
+//! `.gpg` file as an item, keyed by its path relative to the store. Entries
+//! follow the usual pass convention of the password on the first line
+//! followed by `key: value` pairs, anything else is kept as notes.
+//!
+//! [pass]: https://www.passwordstore.org/
+
+#![deny(clippy::pedantic)]
+#![allow(clippy::used_underscore_binding)]
+
+use async_trait::async_trait;
+use onep_backend_api as api;
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+/// Vault uuid given to entries at the root of the store.
+const ROOT_VAULT: &str = "";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("pass backend returned an error:\n{0}")]
+    Backend(String),
+    #[error("failed to exec backend:\n{0}")]
+    Exec(std::io::Error),
+    #[error("failed to read password store:\n{0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to convert pass response to utf-8:\n{0}")]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error("an entry named {0} already exists")]
+    Exists(String),
+    #[error("the root of the password store can't be deleted or renamed")]
+    RootVault,
+    #[error("pass entry isn't a valid item:\n{0}")]
+    InvalidItem(#[from] api::InvalidItem),
+    #[error(transparent)]
+    Unsupported(#[from] api::Unsupported),
+}
+
+impl From<Error> for api::BackendError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Utf8(_) | Error::InvalidItem(_) => Self::Parse(e.into()),
+            Error::Unsupported(e) => Self::Unsupported(e),
+            _ => Self::Other(e.into()),
+        }
+    }
+}
+
+pub struct PassBackend {
+    store: PathBuf,
+}
+
+impl Default for PassBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PassBackend {
+    /// Creates a new backend for the store at `PASSWORD_STORE_DIR`, falling
+    /// back to `~/.password-store` like pass itself.
+    #[must_use]
+    pub fn new() -> Self {
+        let store = std::env::var_os("PASSWORD_STORE_DIR")
+            .map(PathBuf::from)
+            .or_else(|| dirs::home_dir().map(|v| v.join(".password-store")))
+            .unwrap_or_else(|| PathBuf::from(".password-store"));
+
+        Self { store }
+    }
+
+    async fn exec<I, S>(&self, args: I, stdin: Option<&[u8]>) -> Result<Vec<u8>, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        self.exec_with_env(args, stdin, &[]).await
+    }
+
+    async fn exec_with_env<I, S>(
+        &self,
+        args: I,
+        stdin: Option<&[u8]>,
+        env: &[(&str, &str)],
+    ) -> Result<Vec<u8>, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let mut cmd = Command::new("pass")
+            .args(args)
+            .env("PASSWORD_STORE_DIR", &self.store)
+            .envs(env.iter().copied())
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(Error::Exec)?;
+
+        if let (Some(input), Some(mut pipe)) = (stdin, cmd.stdin.take()) {
+            pipe.write_all(input).await.map_err(Error::Exec)?;
+        }
+
+        let cmd = cmd.wait_with_output().await.map_err(Error::Exec)?;
+
+        if cmd.status.success() {
+            Ok(cmd.stdout)
+        } else {
+            Err(Error::Backend(
+                std::str::from_utf8(&cmd.stderr)?.trim().to_string(),
+            ))
+        }
+    }
+
+    /// Recursively lists every entry in the store, relative to the store root
+    /// and without the `.gpg` extension.
+    fn entries(&self) -> Result<Vec<String>, Error> {
+        fn walk(root: &Path, dir: &Path, out: &mut Vec<String>) -> std::io::Result<()> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+
+                // skip .git, .gpg-id, .extensions, etc
+                if path
+                    .file_name()
+                    .and_then(std::ffi::OsStr::to_str)
+                    .map_or(true, |v| v.starts_with('.'))
+                {
+                    continue;
+                }
+
+                if path.is_dir() {
+                    walk(root, &path, out)?;
+                } else if path.extension().map_or(false, |v| v == "gpg") {
+                    if let Ok(relative) = path.with_extension("").strip_prefix(root) {
+                        out.push(relative.to_string_lossy().into_owned());
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        let mut out = Vec::new();
+        walk(&self.store, &self.store, &mut out)?;
+        out.sort();
+        Ok(out)
+    }
+}
+
+/// Splits an entry name into the vault (top-level directory) it belongs to and
+/// its title within that vault.
+fn split_entry(entry: &str) -> (&str, &str) {
+    let mut parts = entry.splitn(2, '/');
+
+    match (parts.next(), parts.next()) {
+        (Some(vault), Some(title)) => (vault, title),
+        _ => (ROOT_VAULT, entry),
+    }
+}
+
+/// The name of the entry for an item called `name` in `vault`.
+fn entry_name(vault: Option<&str>, name: &str) -> String {
+    match vault {
+        Some(vault) if vault != ROOT_VAULT => format!("{}/{}", vault, name),
+        _ => name.to_string(),
+    }
+}
+
+/// Parses a decrypted entry into an item, the first line is always the
+/// password and `key: value` lines become fields of their own.
+fn parse_entry(name: &str, contents: &str) -> Result<api::Item, api::InvalidItem> {
+    let mut lines = contents.lines();
+    let mut fields = Vec::new();
+    let mut otp = Vec::new();
+    let mut notes = Vec::new();
+
+    if let Some(password) = lines.next() {
+        fields.push(
+            api::ItemField::new("password", password).with_type(api::ItemFieldType::Password),
+        );
+    }
+
+    for line in lines {
+        if line.starts_with("otpauth://") {
+            otp.push(
+                api::ItemField::new("one-time password", line).with_type(api::ItemFieldType::Totp),
+            );
+            continue;
+        }
+
+        let mut parts = line.splitn(2, ':');
+
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) if !key.contains(' ') && !value.starts_with("//") => {
+                fields.push(api::ItemField::new(key.trim(), value.trim()));
+            }
+            _ => notes.push(line),
+        }
+    }
+
+    let notes = notes.join("\n");
+    if !notes.trim().is_empty() {
+        fields.push(api::ItemField::new("notes", notes.trim()).with_type(api::ItemFieldType::Note));
+    }
+
+    // any url is just another line of the entry, so stays a field
+    api::Item::builder()
+        .title(split_entry(name).1)
+        .fields(fields.into_iter().filter(|f| !f.value.is_empty()))
+        .section(api::ItemSection::new("").with_fields(otp))
+        .build()
+}
+
+/// Writes an entry in the format understood by [`parse_entry`].
+fn format_entry(
+    password: &str,
+    username: Option<&str>,
+    url: Option<&str>,
+    tags: Option<&str>,
+) -> String {
+    let extra: String = vec![("username", username), ("url", url), ("tags", tags)]
+        .into_iter()
+        .filter_map(|(k, v)| v.map(|v| format!("{}: {}\n", k, v)))
+        .collect();
+
+    format!("{}\n{}", password, extra)
+}
+
+#[async_trait]
+impl api::Backend for PassBackend {
+    type Error = Error;
+
+    async fn capabilities(&self) -> Result<api::Capabilities, Self::Error> {
+        let mut capabilities = api::Capabilities::default();
+        capabilities.offline = true;
+        Ok(capabilities)
+    }
+
+    /// Decryption is handled by gpg-agent, which prompts on demand, so there's
+    /// nothing to sign in to.
+    async fn signin(&self, _account: Option<&str>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Forgets any passphrases cached by gpg-agent.
+    async fn signout(&self) -> Result<(), Self::Error> {
+        let cmd = Command::new("gpgconf")
+            .args(&["--reload", "gpg-agent"])
+            .output()
+            .await
+            .map_err(Error::Exec)?;
+
+        if cmd.status.success() {
+            Ok(())
+        } else {
+            Err(Error::Backend(
+                std::str::from_utf8(&cmd.stderr)?.trim().to_string(),
+            ))
+        }
+    }
+
+    async fn account(&self) -> Result<api::AccountMetadata, Self::Error> {
+        let gpg_id = std::fs::read_to_string(self.store.join(".gpg-id")).unwrap_or_default();
+
+        Ok(api::AccountMetadata::new(
+            gpg_id
+                .lines()
+                .next()
+                .unwrap_or("Password Store")
+                .to_string(),
+            self.store.display().to_string(),
+        ))
+    }
+
+    async fn vaults(&self) -> Result<Vec<api::VaultMetadata>, Self::Error> {
+        let mut vaults = vec![api::VaultMetadata::new(ROOT_VAULT, "Password Store")];
+
+        for entry in std::fs::read_dir(&self.store)? {
+            let path = entry?.path();
+
+            if !path.is_dir() {
+                continue;
+            }
+
+            if let Some(name) = path.file_name().and_then(std::ffi::OsStr::to_str) {
+                if !name.starts_with('.') {
+                    vaults.push(api::VaultMetadata::new(name, name));
+                }
+            }
+        }
+
+        Ok(vaults)
+    }
+
+    async fn search(&self, terms: Option<&str>) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        let terms = terms.map(str::to_lowercase);
+
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter(|v| {
+                if let Some(terms) = &terms {
+                    let terms: &str = terms.as_ref();
+
+                    v == terms || split_entry(v).0 == terms || v.to_lowercase().contains(terms)
+                } else {
+                    true
+                }
+            })
+            .map(|v| {
+                let (vault, title) = split_entry(&v);
+
+                let mut metadata = api::ItemMetadata::new(&v, vault, title);
+                // pass keeps no metadata, but the entry's file was last
+                // written when it was last changed
+                metadata.updated = std::fs::metadata(self.store.join(format!("{}.gpg", v)))
+                    .and_then(|v| v.modified())
+                    .ok();
+                metadata
+            })
+            .collect())
+    }
+
+    async fn get(&self, uuid: &str) -> Result<Option<api::Item>, Self::Error> {
+        if !self.store.join(format!("{}.gpg", uuid)).is_file() {
+            return Ok(None);
+        }
+
+        let ret = self.exec(&["show", uuid], None).await?;
+        Ok(Some(parse_entry(uuid, std::str::from_utf8(&ret)?)?))
+    }
+
+    async fn generate(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &api::PasswordRecipe,
+    ) -> Result<api::Item, Self::Error> {
+        let name = &entry_name(vault, name);
+
+        // pass hands the character set straight to `tr -dc`
+        let characters = recipe.characters();
+        self.exec_with_env(
+            &["generate", name, &recipe.length.to_string()],
+            None,
+            &[("PASSWORD_STORE_CHARACTER_SET", &characters)],
+        )
+        .await?;
+
+        // pass generate only writes the password, so any metadata has to be
+        // appended by rewriting the entry afterwards.
+        if username.is_some() || url.is_some() || tags.is_some() {
+            let password = self.exec(&["show", name], None).await?;
+            let password = std::str::from_utf8(&password)?.lines().next().unwrap_or("");
+
+            let contents = format_entry(password, username, url, tags);
+            self.exec(
+                &["insert", "--multiline", "--force", name],
+                Some(contents.as_bytes()),
+            )
+            .await?;
+        }
+
+        Ok(self.get(name).await?.unwrap_or_else(|| unreachable!()))
+    }
+
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<api::Item, Self::Error> {
+        let name = &entry_name(vault, name);
+
+        // pass insert would otherwise prompt to overwrite on the stdin we're
+        // writing the entry to
+        if self.store.join(format!("{}.gpg", name)).is_file() {
+            return Err(Error::Exists(name.clone()));
+        }
+
+        let contents = format_entry(password, username, url, tags);
+        self.exec(&["insert", "--multiline", name], Some(contents.as_bytes()))
+            .await?;
+
+        Ok(self.get(name).await?.unwrap_or_else(|| unreachable!()))
+    }
+
+    /// Vaults are only directories, so creating one doesn't involve pass.
+    async fn create_vault(&self, name: &str) -> Result<api::VaultMetadata, Self::Error> {
+        std::fs::create_dir(self.store.join(name))?;
+
+        Ok(api::VaultMetadata::new(name, name))
+    }
+
+    async fn delete_vault(&self, uuid: &str) -> Result<(), Self::Error> {
+        if uuid == ROOT_VAULT {
+            return Err(Error::RootVault);
+        }
+
+        self.exec(&["rm", "--recursive", "--force", uuid], None)
+            .await?;
+        Ok(())
+    }
+
+    async fn rename_vault(&self, uuid: &str, name: &str) -> Result<(), Self::Error> {
+        if uuid == ROOT_VAULT {
+            return Err(Error::RootVault);
+        }
+
+        self.exec(&["mv", uuid, name], None).await?;
+        Ok(())
+    }
+
+    /// Keeps the uri on a line of its own, in place of any there already, as
+    /// pass-otp does.
+    async fn set_otp(&self, uuid: &str, uri: &str) -> Result<(), Self::Error> {
+        let contents = self.exec(&["show", uuid], None).await?;
+        let mut lines: Vec<_> = std::str::from_utf8(&contents)?
+            .lines()
+            .filter(|v| !v.starts_with("otpauth://"))
+            .collect();
+        lines.push(uri);
+
+        let contents = format!("{}\n", lines.join("\n"));
+        self.exec(
+            &["insert", "--multiline", "--force", uuid],
+            Some(contents.as_bytes()),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,531 @@
+//! An adapter for backends shipped as standalone executables, allowing third
+//! parties to add support for new password managers without recompiling
+//! 1p-cli. This crate uses `onep_backend_api::Backend` to drive a plugin
+//! named `onep-backend-<name>` found on the `PATH`.
+//!
+//! # Protocol
+//!
+//! The plugin is spawned once and kept alive for the lifetime of the backend.
+//! Requests are written to its stdin as JSON, one per line:
+//!
+//! ```json
+//! {"id": 1, "method": "search", "params": {"terms": "github"}}
+//! ```
+//!
+//! and the plugin must write exactly one response per request to its stdout,
+//! again one per line, echoing back the `id` along with either a `result` or
+//! an `error`:
+//!
+//! ```json
+//! {"id": 1, "result": [{"uuid": "abc", "vault_uuid": "def", "title": "GitHub", "account_info": "jordan"}]}
+//! {"id": 2, "error": {"message": "vault is locked", "kind": "auth_required"}}
+//! ```
+//!
+//! stderr is passed straight through to the user, and plugins that need to
+//! prompt for a password should do so by opening the tty directly.
+//!
+//! | method            | params                                        | result                                                                |
+//! |-------------------|-----------------------------------------------|-----------------------------------------------------------------------|
+//! | `capabilities`    |                                               | capabilities, see below                                               |
+//! | `signin`          | `account`                                     | `null`                                                                |
+//! | `signout`         |                                               | `null`                                                                |
+//! | `account`         |                                               | `{name, domain, service_account}`                                     |
+//! | `vaults`          |                                               | `[{uuid, name}]`                                                      |
+//! | `search`          | `terms`                                       | `[{uuid, vault_uuid, title, account_info, tags, favorite, archived}]` |
+//! | `get`             | `uuid`                                        | `{title, fields, sections, attachments, history}` or `null`           |
+//! | `generate`        | `name`, `username`, `url`, `tags`, `recipe`   | `{title, fields, sections, attachments, history}`                     |
+//! | `create`          | `name`, `username`, `url`, `tags`, `password` | `{title, fields, sections, attachments, history}`                     |
+//! | `create_item`     | `item`, `category`, `tags`, `vault`           | `{title, fields, sections, attachments, history}`                     |
+//! | `create_vault`    | `name`                                        | `{uuid, name}`                                                        |
+//! | `delete_vault`    | `uuid`                                        | `null`                                                                |
+//! | `rename_vault`    | `uuid`, `name`                                | `null`                                                                |
+//! | `edit`            | `uuid`, `title`, `fields`                     | `null`                                                                |
+//! | `set_tags`        | `uuid`, `tags`                                | `null`                                                                |
+//! | `set_favorite`    | `uuid`, `favorite`                            | `null`                                                                |
+//! | `set_otp`         | `uuid`, `uri`                                 | `null`                                                                |
+//! | `archived`        |                                               | `[{uuid, vault_uuid, title, account_info, tags, favorite, archived}]` |
+//! | `archive`         | `uuid`                                        | `null`                                                                |
+//! | `restore`         | `uuid`                                        | `null`                                                                |
+//! | `documents`       |                                               | `[{uuid, vault_uuid, title}]`                                         |
+//! | `get_document`    | `uuid`                                        | base64 encoded contents or `null`                                     |
+//! | `create_document` | `path`, `title`, `vault`                      | `{uuid, vault_uuid, title}`                                           |
+//! | `get_attachment`  | `item_uuid`, `attachment_uuid`                | base64 encoded contents or `null`                                     |
+//!
+//! Plugins that don't support a method should respond with an error. An
+//! error's `kind` is one of `not_found`, `auth_required`,
+//! `permission_denied`, `network`, `parse` or `other`, the default when it's
+//! left out.
+//! Optional params are sent as `null` when not given. Fields are
+//! `{name, value, type}` where `type` is one of `password`, `username`,
+//! `email`, `url`, `date`, `phone`, `address`, `concealed`, `note`, `totp`,
+//! `sshkey` (an `OpenSSH` private key) or `unknown`, sections are
+//! `{name, fields}`, attachments are
+//! `{uuid, name, size}` and history entries are `{value, time}` where `time`
+//! is a unix timestamp or `null`. `service_account` can be left out when the
+//! plugin isn't signed in with one. Items must have a title, and fields of
+//! sections with the same name are put together in one section.
+//! Password recipes are `{length, letters, digits, symbols, exclude_ambiguous}`.
+//! Categories are named in lowercase without spaces, such as `login` or
+//! `securenote`.
+//! Capabilities are `{supports_totp, supports_write, supports_documents,
+//! supports_multiple_accounts, offline}`, with any left out defaulting to
+//! supporting one-time passwords and writing but nothing else. Plugins that
+//! respond to `capabilities` with an error are assumed to support the same.
+//!
+//! The same protocol can be spoken over a unix socket rather than a child's
+//! stdio with [`PluginBackend::socket`], and [`serve`] implements the plugin
+//! side of it for any backend.
+
+#![deny(clippy::pedantic)]
+#![allow(clippy::used_underscore_binding)]
+
+mod protocol;
+mod serve;
+
+pub use serve::serve;
+
+use async_trait::async_trait;
+use onep_backend_api as api;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    process::{Child, Command},
+    sync::Mutex,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("plugin returned an error:\n{0}")]
+    Backend(String, protocol::ErrorKind),
+    #[error("failed to exec plugin:\n{0}")]
+    Exec(std::io::Error),
+    #[error("failed to connect to plugin:\n{0}")]
+    Connect(std::io::Error),
+    #[error("failed to communicate with plugin:\n{0}")]
+    Io(#[from] std::io::Error),
+    #[error("plugin exited unexpectedly")]
+    Closed,
+    #[error("plugin responded to request {1} when {0} was expected")]
+    Protocol(u64, u64),
+    #[error("failed to parse json from plugin:\n{0}")]
+    Json(#[from] serde_json::error::Error),
+    #[error("failed to decode document from plugin:\n{0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("plugin returned an item that isn't valid:\n{0}")]
+    InvalidItem(#[from] api::InvalidItem),
+    #[error(transparent)]
+    Unsupported(#[from] api::Unsupported),
+}
+
+impl From<Error> for api::BackendError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Backend(_, kind) => match kind {
+                protocol::ErrorKind::NotFound => Self::NotFound(e.into()),
+                protocol::ErrorKind::AuthRequired => Self::AuthRequired(e.into()),
+                protocol::ErrorKind::PermissionDenied => Self::PermissionDenied(e.into()),
+                protocol::ErrorKind::Network => Self::Network(e.into()),
+                protocol::ErrorKind::Parse => Self::Parse(e.into()),
+                protocol::ErrorKind::Other => Self::Other(e.into()),
+            },
+            Error::Connect(_) | Error::Closed => Self::Network(e.into()),
+            Error::Protocol(..) | Error::Json(_) | Error::Base64(_) | Error::InvalidItem(_) => {
+                Self::Parse(e.into())
+            }
+            Error::Unsupported(e) => Self::Unsupported(e),
+            _ => Self::Other(e.into()),
+        }
+    }
+}
+
+enum Transport {
+    /// Spawns the plugin, talking to it over its stdin and stdout.
+    Spawn(PathBuf),
+    /// Connects to a plugin listening on a unix socket.
+    #[cfg(unix)]
+    Socket(PathBuf),
+}
+
+struct Connection {
+    // kept so the child is killed when the backend is dropped
+    _child: Option<Child>,
+    writer: Box<dyn AsyncWrite + Send + Unpin>,
+    reader: BufReader<Box<dyn AsyncRead + Send + Unpin>>,
+}
+
+pub struct PluginBackend {
+    transport: Transport,
+    connection: Mutex<Option<Connection>>,
+    next_id: AtomicU64,
+}
+
+impl PluginBackend {
+    fn with_transport(transport: Transport) -> Self {
+        Self {
+            transport,
+            connection: Mutex::new(None),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Creates a new backend driving the given plugin executable, which isn't
+    /// spawned until the first request.
+    #[must_use]
+    pub fn new(program: PathBuf) -> Self {
+        Self::with_transport(Transport::Spawn(program))
+    }
+
+    /// Creates a new backend talking to a plugin already listening on the
+    /// given unix socket, which isn't connected to until the first request.
+    #[cfg(unix)]
+    #[must_use]
+    pub fn socket(path: PathBuf) -> Self {
+        Self::with_transport(Transport::Socket(path))
+    }
+
+    /// Looks for a plugin named `onep-backend-<name>` on the `PATH`.
+    #[must_use]
+    pub fn find(name: &str) -> Option<Self> {
+        let program = format!("onep-backend-{}{}", name, std::env::consts::EXE_SUFFIX);
+
+        std::env::split_paths(&std::env::var_os("PATH")?)
+            .map(|v| v.join(&program))
+            .find(|v| is_executable(v))
+            .map(Self::new)
+    }
+
+    async fn connect(&self) -> Result<Connection, Error> {
+        match &self.transport {
+            Transport::Spawn(program) => {
+                let mut child = Command::new(program)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::inherit())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(Error::Exec)?;
+
+                let stdin = child.stdin.take().ok_or(Error::Closed)?;
+                let stdout = child.stdout.take().ok_or(Error::Closed)?;
+
+                Ok(Connection {
+                    _child: Some(child),
+                    writer: Box::new(stdin),
+                    reader: BufReader::new(Box::new(stdout)),
+                })
+            }
+            #[cfg(unix)]
+            Transport::Socket(path) => {
+                let stream = tokio::net::UnixStream::connect(path)
+                    .await
+                    .map_err(Error::Connect)?;
+                let (reader, writer) = tokio::io::split(stream);
+
+                Ok(Connection {
+                    _child: None,
+                    writer: Box::new(writer),
+                    reader: BufReader::new(Box::new(reader)),
+                })
+            }
+        }
+    }
+
+    /// Sends a single request to the plugin and waits for its response.
+    /// Requests are serialised, so the plugin only has to handle one at a
+    /// time.
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, Error> {
+        let mut slot = self.connection.lock().await;
+
+        // the connection's only put back once a whole response to this
+        // request has been read, so that if writing or reading fails, or the
+        // call's cancelled part way through, the next one reconnects rather
+        // than reading what was left over from this one
+        let mut connection = match slot.take() {
+            Some(connection) => connection,
+            None => self.connect().await?,
+        };
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let response = connection
+            .exchange(&protocol::Request { id, method, params })
+            .await?;
+
+        *slot = Some(connection);
+
+        match response.error {
+            Some(e) => Err(Error::Backend(e.message, e.kind)),
+            None => Ok(serde_json::from_value(response.result)?),
+        }
+    }
+}
+
+impl Connection {
+    /// Writes `request` and reads the response to it.
+    async fn exchange(
+        &mut self,
+        request: &protocol::Request<'_>,
+    ) -> Result<protocol::Response, Error> {
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+
+        self.writer.write_all(&line).await?;
+        self.writer.flush().await?;
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            return Err(Error::Closed);
+        }
+
+        let response: protocol::Response = serde_json::from_str(&line)?;
+
+        if response.id != request.id {
+            return Err(Error::Protocol(request.id, response.id));
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata().map_or(false, |v| {
+        v.is_file() && v.permissions().mode() & 0o111 != 0
+    })
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[async_trait]
+impl api::Backend for PluginBackend {
+    type Error = Error;
+
+    /// Plugins written before `capabilities` was added respond with an
+    /// error, so are assumed to support what the defaults do.
+    async fn capabilities(&self) -> Result<api::Capabilities, Self::Error> {
+        match self
+            .call::<protocol::Capabilities>("capabilities", json!({}))
+            .await
+        {
+            Ok(ret) => Ok(ret.into()),
+            Err(Error::Backend(..)) => Ok(api::Capabilities::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn signin(&self, account: Option<&str>) -> Result<(), Self::Error> {
+        self.call("signin", json!({ "account": account })).await
+    }
+
+    async fn signout(&self) -> Result<(), Self::Error> {
+        self.call("signout", json!({})).await
+    }
+
+    async fn account(&self) -> Result<api::AccountMetadata, Self::Error> {
+        let ret: protocol::Account = self.call("account", json!({})).await?;
+        Ok(ret.into())
+    }
+
+    async fn vaults(&self) -> Result<Vec<api::VaultMetadata>, Self::Error> {
+        let ret: Vec<protocol::Vault> = self.call("vaults", json!({})).await?;
+        Ok(ret.into_iter().map(Into::into).collect())
+    }
+
+    async fn search(&self, terms: Option<&str>) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        let ret: Vec<protocol::ItemMetadata> =
+            self.call("search", json!({ "terms": terms })).await?;
+        Ok(ret.into_iter().map(Into::into).collect())
+    }
+
+    async fn get(&self, uuid: &str) -> Result<Option<api::Item>, Self::Error> {
+        let ret: Option<protocol::Item> = self.call("get", json!({ "uuid": uuid })).await?;
+        Ok(ret.map(api::Item::try_from).transpose()?)
+    }
+
+    async fn generate(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &api::PasswordRecipe,
+    ) -> Result<api::Item, Self::Error> {
+        let ret: protocol::Item = self
+            .call(
+                "generate",
+                json!({
+                    "name": name,
+                    "username": username,
+                    "url": url,
+                    "tags": tags,
+                    "vault": vault,
+                    "recipe": {
+                        "length": recipe.length,
+                        "letters": recipe.letters,
+                        "digits": recipe.digits,
+                        "symbols": recipe.symbols,
+                        "exclude_ambiguous": recipe.exclude_ambiguous,
+                    },
+                }),
+            )
+            .await?;
+        Ok(api::Item::try_from(ret)?)
+    }
+
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<api::Item, Self::Error> {
+        let ret: protocol::Item = self
+            .call(
+                "create",
+                json!({
+                    "name": name,
+                    "username": username,
+                    "url": url,
+                    "tags": tags,
+                    "vault": vault,
+                    "password": password,
+                }),
+            )
+            .await?;
+        Ok(api::Item::try_from(ret)?)
+    }
+
+    async fn create_item(
+        &self,
+        item: &api::Item,
+        category: api::ItemCategory,
+        tags: &[String],
+        vault: Option<&str>,
+    ) -> Result<api::Item, Self::Error> {
+        let ret: protocol::Item = self
+            .call(
+                "create_item",
+                json!({
+                    "item": protocol::Item::from(item.clone()),
+                    "category": protocol::ItemCategory::from(category),
+                    "tags": tags,
+                    "vault": vault,
+                }),
+            )
+            .await?;
+        Ok(api::Item::try_from(ret)?)
+    }
+
+    async fn create_vault(&self, name: &str) -> Result<api::VaultMetadata, Self::Error> {
+        let ret: protocol::Vault = self.call("create_vault", json!({ "name": name })).await?;
+        Ok(ret.into())
+    }
+
+    async fn delete_vault(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.call("delete_vault", json!({ "uuid": uuid })).await
+    }
+
+    async fn rename_vault(&self, uuid: &str, name: &str) -> Result<(), Self::Error> {
+        self.call("rename_vault", json!({ "uuid": uuid, "name": name }))
+            .await
+    }
+
+    async fn edit(&self, uuid: &str, edit: &api::ItemEdit) -> Result<(), Self::Error> {
+        let fields: Vec<_> = edit
+            .fields
+            .iter()
+            .cloned()
+            .map(protocol::ItemField::from)
+            .collect();
+
+        self.call(
+            "edit",
+            json!({ "uuid": uuid, "title": edit.title, "fields": fields }),
+        )
+        .await
+    }
+
+    async fn set_tags(&self, uuid: &str, tags: &[String]) -> Result<(), Self::Error> {
+        self.call("set_tags", json!({ "uuid": uuid, "tags": tags }))
+            .await
+    }
+
+    async fn set_favorite(&self, uuid: &str, favorite: bool) -> Result<(), Self::Error> {
+        self.call(
+            "set_favorite",
+            json!({ "uuid": uuid, "favorite": favorite }),
+        )
+        .await
+    }
+
+    async fn set_otp(&self, uuid: &str, uri: &str) -> Result<(), Self::Error> {
+        self.call("set_otp", json!({ "uuid": uuid, "uri": uri }))
+            .await
+    }
+
+    async fn archived(&self) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        let ret: Vec<protocol::ItemMetadata> = self.call("archived", json!({})).await?;
+        Ok(ret.into_iter().map(Into::into).collect())
+    }
+
+    async fn archive(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.call("archive", json!({ "uuid": uuid })).await
+    }
+
+    async fn restore(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.call("restore", json!({ "uuid": uuid })).await
+    }
+
+    async fn documents(&self) -> Result<Vec<api::DocumentMetadata>, Self::Error> {
+        let ret: Vec<protocol::Document> = self.call("documents", json!({})).await?;
+        Ok(ret.into_iter().map(Into::into).collect())
+    }
+
+    async fn get_document(&self, uuid: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        let ret: Option<String> = self.call("get_document", json!({ "uuid": uuid })).await?;
+        Ok(ret.map(base64::decode).transpose()?)
+    }
+
+    async fn create_document(
+        &self,
+        path: &Path,
+        title: Option<&str>,
+        vault: Option<&str>,
+    ) -> Result<api::DocumentMetadata, Self::Error> {
+        let ret: protocol::Document = self
+            .call(
+                "create_document",
+                json!({ "path": path, "title": title, "vault": vault }),
+            )
+            .await?;
+        Ok(ret.into())
+    }
+
+    async fn get_attachment(
+        &self,
+        item_uuid: &str,
+        attachment_uuid: &str,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        let ret: Option<String> = self
+            .call(
+                "get_attachment",
+                json!({ "item_uuid": item_uuid, "attachment_uuid": attachment_uuid }),
+            )
+            .await?;
+        Ok(ret.map(base64::decode).transpose()?)
+    }
+}
@@ -0,0 +1,553 @@
+//! Types sent over the wire between 1p and a plugin.
+
+use onep_backend_api as api;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    convert::TryFrom,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Times are sent as seconds since the unix epoch.
+fn from_secs(v: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(v)
+}
+
+fn to_secs(v: SystemTime) -> Option<u64> {
+    v.duration_since(UNIX_EPOCH).ok().map(|v| v.as_secs())
+}
+
+#[derive(Debug, Serialize)]
+pub struct Request<'a> {
+    pub id: u64,
+    pub method: &'a str,
+    pub params: Value,
+}
+
+/// A request as read by [`crate::serve`].
+#[derive(Debug, Deserialize)]
+pub struct IncomingRequest {
+    pub id: u64,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub id: u64,
+    #[serde(default)]
+    pub result: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ResponseError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResponseError {
+    pub message: String,
+    #[serde(default)]
+    pub kind: ErrorKind,
+}
+
+/// The kinds of [`api::BackendError`] that can be sent over the protocol.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    NotFound,
+    AuthRequired,
+    PermissionDenied,
+    Network,
+    Parse,
+    #[serde(other)]
+    Other,
+}
+
+impl Default for ErrorKind {
+    fn default() -> Self {
+        Self::Other
+    }
+}
+
+impl From<&api::BackendError> for ErrorKind {
+    fn from(v: &api::BackendError) -> Self {
+        match v {
+            api::BackendError::NotFound(_) => Self::NotFound,
+            api::BackendError::AuthRequired(_) => Self::AuthRequired,
+            api::BackendError::PermissionDenied(_) => Self::PermissionDenied,
+            api::BackendError::Network(_) => Self::Network,
+            api::BackendError::Parse(_) => Self::Parse,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Capabilities {
+    supports_totp: bool,
+    supports_write: bool,
+    supports_documents: bool,
+    supports_multiple_accounts: bool,
+    offline: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        api::Capabilities::default().into()
+    }
+}
+
+impl Into<api::Capabilities> for Capabilities {
+    fn into(self) -> api::Capabilities {
+        let mut capabilities = api::Capabilities::default();
+        capabilities.supports_totp = self.supports_totp;
+        capabilities.supports_write = self.supports_write;
+        capabilities.supports_documents = self.supports_documents;
+        capabilities.supports_multiple_accounts = self.supports_multiple_accounts;
+        capabilities.offline = self.offline;
+        capabilities
+    }
+}
+
+impl From<api::Capabilities> for Capabilities {
+    fn from(v: api::Capabilities) -> Self {
+        Self {
+            supports_totp: v.supports_totp,
+            supports_write: v.supports_write,
+            supports_documents: v.supports_documents,
+            supports_multiple_accounts: v.supports_multiple_accounts,
+            offline: v.offline,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Account {
+    name: String,
+    domain: String,
+    #[serde(default)]
+    service_account: bool,
+}
+
+impl Into<api::AccountMetadata> for Account {
+    fn into(self) -> api::AccountMetadata {
+        let mut account = api::AccountMetadata::new(self.name, self.domain);
+        account.service_account = self.service_account;
+        account
+    }
+}
+
+impl From<api::AccountMetadata> for Account {
+    fn from(v: api::AccountMetadata) -> Self {
+        Self {
+            name: v.name,
+            domain: v.domain,
+            service_account: v.service_account,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Vault {
+    uuid: String,
+    name: String,
+}
+
+impl Into<api::VaultMetadata> for Vault {
+    fn into(self) -> api::VaultMetadata {
+        api::VaultMetadata::new(self.uuid, self.name)
+    }
+}
+
+impl From<api::VaultMetadata> for Vault {
+    fn from(v: api::VaultMetadata) -> Self {
+        Self {
+            uuid: v.uuid,
+            name: v.name,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ItemMetadata {
+    uuid: String,
+    vault_uuid: String,
+    title: String,
+    #[serde(default)]
+    account_info: String,
+    #[serde(default)]
+    urls: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    favorite: bool,
+    #[serde(default)]
+    archived: bool,
+    /// Seconds since the unix epoch.
+    #[serde(default)]
+    created: Option<u64>,
+    /// Seconds since the unix epoch.
+    #[serde(default)]
+    updated: Option<u64>,
+    #[serde(default)]
+    category: ItemCategory,
+}
+
+impl Into<api::ItemMetadata> for ItemMetadata {
+    fn into(self) -> api::ItemMetadata {
+        let mut metadata = api::ItemMetadata::new(self.uuid, self.vault_uuid, self.title);
+        metadata.account_info = self.account_info;
+        metadata.urls = self.urls;
+        metadata.tags = self.tags;
+        metadata.favorite = self.favorite;
+        metadata.archived = self.archived;
+        metadata.created = self.created.map(from_secs);
+        metadata.updated = self.updated.map(from_secs);
+        metadata.category = self.category.into();
+        metadata
+    }
+}
+
+impl From<api::ItemMetadata> for ItemMetadata {
+    fn from(v: api::ItemMetadata) -> Self {
+        Self {
+            uuid: v.uuid,
+            vault_uuid: v.vault_uuid,
+            title: v.title,
+            account_info: v.account_info,
+            urls: v.urls,
+            tags: v.tags,
+            favorite: v.favorite,
+            archived: v.archived,
+            created: v.created.and_then(to_secs),
+            updated: v.updated.and_then(to_secs),
+            category: v.category.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemCategory {
+    Login,
+    Password,
+    SecureNote,
+    CreditCard,
+    Identity,
+    Document,
+    BankAccount,
+    ApiCredential,
+    Database,
+    Server,
+    SshKey,
+    SoftwareLicense,
+    WirelessRouter,
+    #[serde(other)]
+    Unknown,
+}
+
+impl Default for ItemCategory {
+    fn default() -> Self {
+        Self::Unknown
+    }
+}
+
+impl Into<api::ItemCategory> for ItemCategory {
+    fn into(self) -> api::ItemCategory {
+        match self {
+            Self::Login => api::ItemCategory::Login,
+            Self::Password => api::ItemCategory::Password,
+            Self::SecureNote => api::ItemCategory::SecureNote,
+            Self::CreditCard => api::ItemCategory::CreditCard,
+            Self::Identity => api::ItemCategory::Identity,
+            Self::Document => api::ItemCategory::Document,
+            Self::BankAccount => api::ItemCategory::BankAccount,
+            Self::ApiCredential => api::ItemCategory::ApiCredential,
+            Self::Database => api::ItemCategory::Database,
+            Self::Server => api::ItemCategory::Server,
+            Self::SshKey => api::ItemCategory::SshKey,
+            Self::SoftwareLicense => api::ItemCategory::SoftwareLicense,
+            Self::WirelessRouter => api::ItemCategory::WirelessRouter,
+            Self::Unknown => api::ItemCategory::Unknown,
+        }
+    }
+}
+
+impl From<api::ItemCategory> for ItemCategory {
+    fn from(v: api::ItemCategory) -> Self {
+        match v {
+            api::ItemCategory::Login => Self::Login,
+            api::ItemCategory::Password => Self::Password,
+            api::ItemCategory::SecureNote => Self::SecureNote,
+            api::ItemCategory::CreditCard => Self::CreditCard,
+            api::ItemCategory::Identity => Self::Identity,
+            api::ItemCategory::Document => Self::Document,
+            api::ItemCategory::BankAccount => Self::BankAccount,
+            api::ItemCategory::ApiCredential => Self::ApiCredential,
+            api::ItemCategory::Database => Self::Database,
+            api::ItemCategory::Server => Self::Server,
+            api::ItemCategory::SshKey => Self::SshKey,
+            api::ItemCategory::SoftwareLicense => Self::SoftwareLicense,
+            api::ItemCategory::WirelessRouter => Self::WirelessRouter,
+            api::ItemCategory::Unknown => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Item {
+    title: String,
+    #[serde(default)]
+    fields: Vec<ItemField>,
+    #[serde(default)]
+    sections: Vec<ItemSection>,
+    #[serde(default)]
+    urls: Vec<ItemUrl>,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+    #[serde(default)]
+    history: Vec<HistoricalValue>,
+}
+
+impl TryFrom<Item> for api::Item {
+    type Error = api::InvalidItem;
+
+    fn try_from(item: Item) -> Result<Self, Self::Error> {
+        api::Item::builder()
+            .title(item.title)
+            .fields(item.fields.into_iter().map(Into::into))
+            .sections(item.sections.into_iter().map(Into::into))
+            .urls(item.urls.into_iter().map(Into::into))
+            .attachments(item.attachments.into_iter().map(Into::into))
+            .history(item.history.into_iter().map(Into::into))
+            .build()
+    }
+}
+
+impl From<api::Item> for Item {
+    fn from(v: api::Item) -> Self {
+        Self {
+            title: v.title,
+            fields: v.fields.into_iter().map(Into::into).collect(),
+            sections: v.sections.into_iter().map(Into::into).collect(),
+            urls: v.urls.into_iter().map(Into::into).collect(),
+            attachments: v.attachments.into_iter().map(Into::into).collect(),
+            history: v.history.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ItemField {
+    name: String,
+    #[serde(rename = "type", default)]
+    field_type: ItemFieldType,
+    value: String,
+}
+
+impl Into<api::ItemField> for ItemField {
+    fn into(self) -> api::ItemField {
+        let field_type = match self.field_type {
+            ItemFieldType::Totp => api::ItemFieldType::Totp,
+            ItemFieldType::SshKey => api::ItemFieldType::SshKey,
+            ItemFieldType::Password => api::ItemFieldType::Password,
+            ItemFieldType::Username => api::ItemFieldType::Username,
+            ItemFieldType::Email => api::ItemFieldType::Email,
+            ItemFieldType::Url => api::ItemFieldType::Url,
+            ItemFieldType::Date => api::ItemFieldType::Date,
+            ItemFieldType::Phone => api::ItemFieldType::Phone,
+            ItemFieldType::Address => api::ItemFieldType::Address,
+            ItemFieldType::Concealed => api::ItemFieldType::Concealed,
+            ItemFieldType::Note => api::ItemFieldType::Note,
+            ItemFieldType::Unknown => api::ItemFieldType::Unknown,
+        };
+
+        api::ItemField::new(self.name, self.value).with_type(field_type)
+    }
+}
+
+impl From<api::ItemField> for ItemField {
+    fn from(v: api::ItemField) -> Self {
+        Self {
+            name: v.name,
+            field_type: match v.field_type {
+                api::ItemFieldType::Totp => ItemFieldType::Totp,
+                api::ItemFieldType::SshKey => ItemFieldType::SshKey,
+                api::ItemFieldType::Password => ItemFieldType::Password,
+                api::ItemFieldType::Username => ItemFieldType::Username,
+                api::ItemFieldType::Email => ItemFieldType::Email,
+                api::ItemFieldType::Url => ItemFieldType::Url,
+                api::ItemFieldType::Date => ItemFieldType::Date,
+                api::ItemFieldType::Phone => ItemFieldType::Phone,
+                api::ItemFieldType::Address => ItemFieldType::Address,
+                api::ItemFieldType::Concealed => ItemFieldType::Concealed,
+                api::ItemFieldType::Note => ItemFieldType::Note,
+                api::ItemFieldType::Unknown => ItemFieldType::Unknown,
+            },
+            value: v.value.as_str().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ItemFieldType {
+    Totp,
+    SshKey,
+    Password,
+    Username,
+    Email,
+    Url,
+    Date,
+    Phone,
+    Address,
+    Concealed,
+    Note,
+    #[serde(other)]
+    Unknown,
+}
+
+impl Default for ItemFieldType {
+    fn default() -> Self {
+        Self::Unknown
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ItemSection {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    fields: Vec<ItemField>,
+}
+
+impl Into<api::ItemSection> for ItemSection {
+    fn into(self) -> api::ItemSection {
+        api::ItemSection::new(self.name).with_fields(self.fields.into_iter().map(Into::into))
+    }
+}
+
+impl From<api::ItemSection> for ItemSection {
+    fn from(v: api::ItemSection) -> Self {
+        Self {
+            name: v.name,
+            fields: v.fields.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ItemUrl {
+    #[serde(default)]
+    label: String,
+    url: String,
+}
+
+impl Into<api::ItemUrl> for ItemUrl {
+    fn into(self) -> api::ItemUrl {
+        api::ItemUrl::new(self.label, self.url)
+    }
+}
+
+impl From<api::ItemUrl> for ItemUrl {
+    fn from(v: api::ItemUrl) -> Self {
+        Self {
+            label: v.label,
+            url: v.url,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Attachment {
+    uuid: String,
+    name: String,
+    #[serde(default)]
+    size: u64,
+}
+
+impl Into<api::AttachmentMetadata> for Attachment {
+    fn into(self) -> api::AttachmentMetadata {
+        api::AttachmentMetadata::new(self.uuid, self.name, self.size)
+    }
+}
+
+impl From<api::AttachmentMetadata> for Attachment {
+    fn from(v: api::AttachmentMetadata) -> Self {
+        Self {
+            uuid: v.uuid,
+            name: v.name,
+            size: v.size,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HistoricalValue {
+    value: String,
+    /// Seconds since the unix epoch.
+    #[serde(default)]
+    time: Option<u64>,
+}
+
+impl Into<api::HistoricalValue> for HistoricalValue {
+    fn into(self) -> api::HistoricalValue {
+        api::HistoricalValue::new(self.value, self.time.map(from_secs))
+    }
+}
+
+impl From<api::HistoricalValue> for HistoricalValue {
+    fn from(v: api::HistoricalValue) -> Self {
+        Self {
+            value: v.value.as_str().to_string(),
+            time: v.time.and_then(to_secs),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Document {
+    uuid: String,
+    vault_uuid: String,
+    title: String,
+}
+
+impl Into<api::DocumentMetadata> for Document {
+    fn into(self) -> api::DocumentMetadata {
+        api::DocumentMetadata::new(self.uuid, self.vault_uuid, self.title)
+    }
+}
+
+impl From<api::DocumentMetadata> for Document {
+    fn from(v: api::DocumentMetadata) -> Self {
+        Self {
+            uuid: v.uuid,
+            vault_uuid: v.vault_uuid,
+            title: v.title,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct PasswordRecipe {
+    length: usize,
+    letters: bool,
+    digits: bool,
+    symbols: bool,
+    #[serde(default)]
+    exclude_ambiguous: bool,
+}
+
+impl Into<api::PasswordRecipe> for PasswordRecipe {
+    fn into(self) -> api::PasswordRecipe {
+        let mut recipe = api::PasswordRecipe::default();
+        recipe.length = self.length;
+        recipe.letters = self.letters;
+        recipe.digits = self.digits;
+        recipe.symbols = self.symbols;
+        recipe.exclude_ambiguous = self.exclude_ambiguous;
+        recipe
+    }
+}
@@ -0,0 +1,251 @@
+//! The plugin side of the protocol, allowing any backend to be served to a
+//! [`PluginBackend`](crate::PluginBackend).
+
+use crate::protocol::{self, IncomingRequest, Response, ResponseError};
+use onep_backend_api as api;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{convert::TryFrom, path::PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+type DispatchResult = Result<Value, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Reads a single param, treating one that wasn't given as `null`.
+fn param<T: DeserializeOwned>(params: &Value, name: &str) -> Result<T, serde_json::Error> {
+    serde_json::from_value(params.get(name).cloned().unwrap_or(Value::Null))
+}
+
+#[allow(clippy::too_many_lines)]
+async fn dispatch<B>(backend: &B, method: &str, params: &Value) -> DispatchResult
+where
+    B: api::Backend + Sync + ?Sized,
+    B::Error: std::error::Error + Send + Sync + 'static,
+{
+    let string = |name| param::<String>(params, name);
+    let optional = |name| param::<Option<String>>(params, name);
+
+    let result = match method {
+        "capabilities" => {
+            serde_json::to_value(protocol::Capabilities::from(backend.capabilities().await?))?
+        }
+        "signin" => {
+            backend.signin(optional("account")?.as_deref()).await?;
+            Value::Null
+        }
+        "signout" => {
+            backend.signout().await?;
+            Value::Null
+        }
+        "account" => serde_json::to_value(protocol::Account::from(backend.account().await?))?,
+        "vaults" => serde_json::to_value(
+            backend
+                .vaults()
+                .await?
+                .into_iter()
+                .map(protocol::Vault::from)
+                .collect::<Vec<_>>(),
+        )?,
+        "search" => serde_json::to_value(
+            backend
+                .search(optional("terms")?.as_deref())
+                .await?
+                .into_iter()
+                .map(protocol::ItemMetadata::from)
+                .collect::<Vec<_>>(),
+        )?,
+        "get" => serde_json::to_value(
+            backend
+                .get(&string("uuid")?)
+                .await?
+                .map(protocol::Item::from),
+        )?,
+        "generate" => {
+            let recipe: protocol::PasswordRecipe = param(params, "recipe")?;
+            let item = backend
+                .generate(
+                    &string("name")?,
+                    optional("username")?.as_deref(),
+                    optional("url")?.as_deref(),
+                    optional("tags")?.as_deref(),
+                    optional("vault")?.as_deref(),
+                    &recipe.into(),
+                )
+                .await?;
+
+            serde_json::to_value(protocol::Item::from(item))?
+        }
+        "create" => {
+            let item = backend
+                .create(
+                    &string("name")?,
+                    optional("username")?.as_deref(),
+                    optional("url")?.as_deref(),
+                    optional("tags")?.as_deref(),
+                    optional("vault")?.as_deref(),
+                    &string("password")?,
+                )
+                .await?;
+
+            serde_json::to_value(protocol::Item::from(item))?
+        }
+        "create_item" => {
+            let item: protocol::Item = param(params, "item")?;
+            let category: protocol::ItemCategory = param(params, "category")?;
+            let tags: Vec<String> = param(params, "tags")?;
+            let item = backend
+                .create_item(
+                    &api::Item::try_from(item)?,
+                    category.into(),
+                    &tags,
+                    optional("vault")?.as_deref(),
+                )
+                .await?;
+
+            serde_json::to_value(protocol::Item::from(item))?
+        }
+        "create_vault" => serde_json::to_value(protocol::Vault::from(
+            backend.create_vault(&string("name")?).await?,
+        ))?,
+        "delete_vault" => {
+            backend.delete_vault(&string("uuid")?).await?;
+            Value::Null
+        }
+        "rename_vault" => {
+            backend
+                .rename_vault(&string("uuid")?, &string("name")?)
+                .await?;
+            Value::Null
+        }
+        "edit" => {
+            let fields: Vec<protocol::ItemField> = param(params, "fields")?;
+            let mut edit = api::ItemEdit::default();
+            edit.title = optional("title")?;
+            edit.fields = fields.into_iter().map(Into::into).collect();
+
+            backend.edit(&string("uuid")?, &edit).await?;
+            Value::Null
+        }
+        "set_tags" => {
+            let tags: Vec<String> = param(params, "tags")?;
+            backend.set_tags(&string("uuid")?, &tags).await?;
+            Value::Null
+        }
+        "set_favorite" => {
+            backend
+                .set_favorite(&string("uuid")?, param(params, "favorite")?)
+                .await?;
+            Value::Null
+        }
+        "set_otp" => {
+            backend.set_otp(&string("uuid")?, &string("uri")?).await?;
+            Value::Null
+        }
+        "archived" => serde_json::to_value(
+            backend
+                .archived()
+                .await?
+                .into_iter()
+                .map(protocol::ItemMetadata::from)
+                .collect::<Vec<_>>(),
+        )?,
+        "archive" => {
+            backend.archive(&string("uuid")?).await?;
+            Value::Null
+        }
+        "restore" => {
+            backend.restore(&string("uuid")?).await?;
+            Value::Null
+        }
+        "documents" => serde_json::to_value(
+            backend
+                .documents()
+                .await?
+                .into_iter()
+                .map(protocol::Document::from)
+                .collect::<Vec<_>>(),
+        )?,
+        "get_document" => serde_json::to_value(
+            backend
+                .get_document(&string("uuid")?)
+                .await?
+                .map(base64::encode),
+        )?,
+        "create_document" => {
+            let path: PathBuf = param(params, "path")?;
+            let document = backend
+                .create_document(
+                    &path,
+                    optional("title")?.as_deref(),
+                    optional("vault")?.as_deref(),
+                )
+                .await?;
+
+            serde_json::to_value(protocol::Document::from(document))?
+        }
+        "get_attachment" => serde_json::to_value(
+            backend
+                .get_attachment(&string("item_uuid")?, &string("attachment_uuid")?)
+                .await?
+                .map(base64::encode),
+        )?,
+        _ => return Err(format!("unknown method {}", method).into()),
+    };
+
+    Ok(result)
+}
+
+/// Answers requests read from `reader` using `backend`, writing a response
+/// for each to `writer`, until `reader` is closed. Requests are handled one
+/// at a time, in the order they're received.
+///
+/// # Errors
+///
+/// Returns an error if reading or writing fails, or a request isn't valid
+/// JSON. Errors from the backend are sent back as responses instead.
+pub async fn serve<B, R, W>(backend: &B, reader: R, mut writer: W) -> std::io::Result<()>
+where
+    B: api::Backend + Sync + ?Sized,
+    B::Error: std::error::Error + Send + Sync + 'static,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let request: IncomingRequest = serde_json::from_str(&line)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        let response = match dispatch(backend, &request.method, &request.params).await {
+            Ok(result) => Response {
+                id: request.id,
+                result,
+                error: None,
+            },
+            Err(e) => {
+                // errors from the backend, rather than from reading params,
+                // are sorted so the other side can tell them apart
+                let (message, kind) = match e.downcast::<B::Error>() {
+                    Ok(e) => {
+                        let e: api::BackendError = (*e).into();
+                        (e.to_string(), (&e).into())
+                    }
+                    Err(e) => (e.to_string(), protocol::ErrorKind::Other),
+                };
+
+                Response {
+                    id: request.id,
+                    result: Value::Null,
+                    error: Some(ResponseError { message, kind }),
+                }
+            }
+        };
+
+        let mut response = serde_json::to_vec(&response)?;
+        response.push(b'\n');
+
+        writer.write_all(&response).await?;
+        writer.flush().await?;
+    }
+
+    Ok(())
+}
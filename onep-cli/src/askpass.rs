@@ -0,0 +1,98 @@
+//! Prints a single password for programs that ask for one through an
+//! external helper, such as `SSH_ASKPASS` or `SUDO_ASKPASS`.
+//!
+//! Those programs run the helper with their prompt as the only argument, so
+//! a small wrapper script is needed:
+//!
+//! ```sh
+//! #!/bin/sh
+//! exec 1p askpass "$@"
+//! ```
+
+use crate::{
+    config::Config,
+    resolve::{self, Resolver},
+};
+use clap::Clap;
+use onep_backend_api as api;
+use std::io::Write;
+
+/// Environment variable naming the item to use, taking priority over the
+/// config.
+const ITEM_VAR: &str = "ONEP_ASKPASS_ITEM";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("No item to print the password of, pass --item, set ONEP_ASKPASS_ITEM or set askpass.default in your config.")]
+    NoItem,
+}
+
+#[derive(Clap, Debug)]
+pub struct AskpassOpt {
+    /// Item to print the password of, by uuid or secret reference
+    #[clap(long, short = 'i')]
+    item: Option<String>,
+    /// Prompt given by the program asking, used to pick an item from the
+    /// askpass.items config
+    prompt: Option<String>,
+}
+
+/// Works out what's being asked for from the prompt: the host for ssh
+/// passwords, the key's path for ssh key passphrases or `sudo` for sudo.
+fn target(prompt: &str) -> Option<&str> {
+    let prompt = prompt.trim();
+
+    if prompt.starts_with("[sudo]") {
+        return Some("sudo");
+    }
+
+    if let Some(path) = prompt.strip_prefix("Enter passphrase for key '") {
+        return path.find('\'').map(|end| &path[..end]);
+    }
+
+    if let Some(end) = prompt.find("'s password") {
+        let login = &prompt[..end];
+        return Some(login.rsplit('@').next().unwrap_or(login));
+    }
+
+    None
+}
+
+async fn password(backend: &api::DynBackend, item: &str) -> anyhow::Result<String> {
+    if item.starts_with(resolve::PREFIX) {
+        return Resolver::new(backend).resolve(item).await;
+    }
+
+    let item = backend.get(item).await?.ok_or(crate::Error::NotFound)?;
+    let password = item.password().ok_or(crate::Error::NotFound)?;
+
+    Ok(password.value.as_str().to_string())
+}
+
+pub async fn run(
+    backend: &api::DynBackend,
+    config: &Config,
+    opt: AskpassOpt,
+) -> anyhow::Result<()> {
+    let from_prompt = opt
+        .prompt
+        .as_deref()
+        .and_then(target)
+        .and_then(|v| config.askpass.items.get(v))
+        .cloned();
+
+    let item = opt
+        .item
+        .or_else(|| std::env::var(ITEM_VAR).ok())
+        .or(from_prompt)
+        .or_else(|| config.askpass.default.clone())
+        .ok_or(Error::NoItem)?;
+
+    // the password is printed as is, without a trailing newline, as some
+    // callers take everything written as the password
+    let mut stdout = std::io::stdout();
+    stdout.write_all(password(backend, &item).await?.as_bytes())?;
+    stdout.flush()?;
+
+    Ok(())
+}
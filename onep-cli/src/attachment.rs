@@ -0,0 +1,46 @@
+//! Commands for downloading files attached to items.
+
+use crate::Error;
+use clap::Clap;
+use onep_backend_api as api;
+use std::{io::Write, path::PathBuf};
+
+#[derive(Clap, Debug)]
+pub enum AttachmentOpt {
+    /// Download a file attached to an item
+    Get {
+        /// Uuid of the item the file is attached to
+        item: String,
+        /// Name or uuid of the attached file
+        name: String,
+        /// File to write the attachment to, defaults to stdout
+        #[clap(long, short = 'o', parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+}
+
+pub async fn run(backend: &api::DynBackend, opt: AttachmentOpt) -> anyhow::Result<()> {
+    match opt {
+        AttachmentOpt::Get { item, name, output } => {
+            let result = backend.get(&item).await?.ok_or(Error::NotFound)?;
+
+            let attachment = result
+                .attachments
+                .iter()
+                .find(|v| v.name == name || v.uuid == name)
+                .ok_or(Error::NotFound)?;
+
+            let contents = backend
+                .get_attachment(&item, &attachment.uuid)
+                .await?
+                .ok_or(Error::NotFound)?;
+
+            match output {
+                Some(path) => std::fs::write(path, contents)?,
+                None => std::io::stdout().write_all(&contents)?,
+            }
+        }
+    }
+
+    Ok(())
+}
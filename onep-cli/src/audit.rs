@@ -0,0 +1,211 @@
+//! Reports weak, reused, old and breached passwords across every item.
+
+use crate::{config::Config, hibp, output::Output, table};
+use clap::Clap;
+use onep_backend_api as api;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+use term_table::{
+    row::Row,
+    table_cell::{Alignment, TableCell},
+    Table, TableStyle,
+};
+
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+#[derive(Clap, Debug)]
+pub struct AuditOpt {
+    /// Lowest acceptable zxcvbn score for a password, from 0 to 4
+    #[clap(long, default_value = "3")]
+    min_score: u8,
+    /// Report passwords that haven't been changed in this many days
+    #[clap(long, default_value = "365")]
+    max_age: u64,
+    /// Check each password against Have I Been Pwned, only the first five
+    /// characters of each password's SHA-1 hash are sent
+    #[clap(long)]
+    pwned: bool,
+    /// Print the report as JSON
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Finding {
+    uuid: String,
+    vault_uuid: String,
+    title: String,
+    issues: Vec<Issue>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Issue {
+    /// The password scored below the minimum with zxcvbn.
+    Weak { score: u8 },
+    /// The same password is used by `count` other items.
+    Reused { count: usize },
+    /// The password hasn't been changed in `days` days.
+    Old { days: u64 },
+    /// The password has been seen `count` times in breached data.
+    Pwned { count: u64 },
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Weak { score } => write!(f, "weak (score {}/4)", score),
+            Self::Reused { count } => write!(f, "reused by {} other items", count),
+            Self::Old { days } => write!(f, "unchanged for {} days", days),
+            Self::Pwned { count } => write!(f, "seen {} times in breaches", count),
+        }
+    }
+}
+
+fn password(item: &api::Item) -> Option<&str> {
+    item.password()
+        .map(|v| v.value.as_str())
+        .filter(|v| !v.is_empty())
+}
+
+/// When the current password was set, going by the last time a previous one
+/// was replaced. Items that have never had their password changed have no
+/// history, so their age is unknown.
+fn password_set(item: &api::Item) -> Option<SystemTime> {
+    item.history.iter().filter_map(|v| v.time).max()
+}
+
+/// Looks up how many times each distinct password has been breached.
+async fn breach_counts(
+    items: &[(api::ItemMetadata, api::Item)],
+) -> Result<HashMap<&str, u64>, hibp::Error> {
+    let mut client = hibp::Client::new();
+    let mut counts = HashMap::new();
+
+    for (_, item) in items {
+        if let Some(password) = password(item) {
+            if !counts.contains_key(password) {
+                counts.insert(password, client.breach_count(password).await?);
+            }
+        }
+    }
+
+    Ok(counts)
+}
+
+fn audit(
+    items: &[(api::ItemMetadata, api::Item)],
+    breaches: &HashMap<&str, u64>,
+    opt: &AuditOpt,
+) -> Vec<Finding> {
+    let mut uses: HashMap<&str, usize> = HashMap::new();
+
+    for (_, item) in items {
+        if let Some(password) = password(item) {
+            *uses.entry(password).or_default() += 1;
+        }
+    }
+
+    let now = SystemTime::now();
+    let max_age = Duration::from_secs(opt.max_age * SECONDS_PER_DAY);
+
+    items
+        .iter()
+        .filter_map(|(metadata, item)| {
+            let password = password(item)?;
+            let mut issues = Vec::new();
+
+            // zxcvbn only fails on blank passwords, which are skipped above.
+            if let Ok(entropy) = zxcvbn::zxcvbn(password, &[&metadata.title]) {
+                if entropy.score() < opt.min_score {
+                    issues.push(Issue::Weak {
+                        score: entropy.score(),
+                    });
+                }
+            }
+
+            let count = uses[password] - 1;
+            if count > 0 {
+                issues.push(Issue::Reused { count });
+            }
+
+            if let Some(age) = password_set(item).and_then(|v| now.duration_since(v).ok()) {
+                if age > max_age {
+                    issues.push(Issue::Old {
+                        days: age.as_secs() / SECONDS_PER_DAY,
+                    });
+                }
+            }
+
+            if let Some(&count) = breaches.get(password) {
+                if count > 0 {
+                    issues.push(Issue::Pwned { count });
+                }
+            }
+
+            if issues.is_empty() {
+                None
+            } else {
+                Some(Finding {
+                    uuid: metadata.uuid.clone(),
+                    vault_uuid: metadata.vault_uuid.clone(),
+                    title: metadata.title.clone(),
+                    issues,
+                })
+            }
+        })
+        .collect()
+}
+
+fn print_table(config: &Config, findings: Vec<Finding>) {
+    let mut table = Table::new();
+    table.style = TableStyle::extended();
+
+    table.add_row(Row::new(vec![TableCell::new_with_alignment(
+        "Audit",
+        2,
+        Alignment::Center,
+    )]));
+
+    if findings.is_empty() {
+        table.add_row(Row::new(vec![TableCell::new_with_alignment(
+            "No issues found",
+            2,
+            Alignment::Center,
+        )]));
+    }
+
+    for finding in findings {
+        let issues: Vec<_> = finding.issues.iter().map(ToString::to_string).collect();
+
+        table.add_row(Row::new(vec![
+            TableCell::new(finding.title),
+            TableCell::new_with_alignment(issues.join(", "), 1, Alignment::Right),
+        ]));
+    }
+
+    table::print(&mut Output::stdout(), config, table);
+}
+
+pub async fn run(backend: &api::DynBackend, config: &Config, opt: AuditOpt) -> anyhow::Result<()> {
+    let items = backend.get_all().await?;
+
+    let breaches = if opt.pwned {
+        breach_counts(&items).await?
+    } else {
+        HashMap::new()
+    };
+
+    let findings = audit(&items, &breaches, &opt);
+
+    if opt.json {
+        println!("{}", serde_json::to_string_pretty(&findings)?);
+    } else {
+        print_table(config, findings);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,183 @@
+//! Registry of every backend 1p can use, selected at runtime by name. Names
+//! that aren't built in are looked up as `onep-backend-<name>` plugins.
+
+#[cfg(unix)]
+use crate::daemon;
+use crate::{cache, config::Config, replay};
+use onep_backend_api as api;
+use std::time::Duration;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Unknown backend `{0}`, expected one of: {} or a plugin named onep-backend-{0} on your PATH", names().join(", "))]
+    Unknown(String),
+    #[error("A keepass database must be configured to use the keepass backend, set ONEP_KEEPASS_DB or keepass.database in your config.")]
+    NoKeepassDatabase,
+    #[error("A connect server must be configured to use the connect backend, set OP_CONNECT_HOST and OP_CONNECT_TOKEN or connect.host and connect.token in your config.")]
+    NoConnectServer,
+    #[error(transparent)]
+    Fixtures(#[from] replay::Error),
+}
+
+type Constructor = fn(&Config) -> Result<Box<api::DynBackend>, Error>;
+
+/// Every backend 1p knows about, keyed by the name it's selected with. The
+/// first entry is used when none is configured.
+const REGISTRY: &[(&str, Constructor)] = &[
+    ("op", op),
+    ("bw", bw),
+    ("pass", pass),
+    ("keepass", keepass),
+    ("connect", connect),
+    ("demo", demo),
+];
+
+fn names() -> Vec<&'static str> {
+    REGISTRY.iter().map(|(name, _)| *name).collect()
+}
+
+/// Name of the backend to use, from `ONEP_BACKEND`, falling back to the one
+/// in the config file and then to op.
+pub fn name(config: &Config) -> String {
+    std::env::var("ONEP_BACKEND")
+        .ok()
+        .or_else(|| config.backend.clone())
+        .unwrap_or_else(|| REGISTRY[0].0.to_string())
+}
+
+/// Opens the configured backend, talking to it through `1p daemon` if one's
+/// running. `fresh` skips both the daemon and the cache's TTL, for when
+/// stale results won't do.
+///
+/// `ONEP_REPLAY` plays back fixtures in place of any backend, and
+/// `ONEP_RECORD` records the backend's answers to them.
+pub fn open(config: &Config, fresh: bool) -> Result<Box<api::DynBackend>, Error> {
+    if let Some(path) = std::env::var_os("ONEP_REPLAY") {
+        return Ok(Box::new(replay::Fixtures::replay(path.into())?));
+    }
+
+    let name = name(config);
+
+    let backend = match from_daemon(&name, fresh) {
+        Some(daemon) => daemon,
+        None => open_direct(config, &name, fresh)?,
+    };
+
+    match std::env::var_os("ONEP_RECORD") {
+        Some(path) => Ok(Box::new(replay::Fixtures::record(backend, path.into())?)),
+        None => Ok(backend),
+    }
+}
+
+/// Connects to `1p daemon` if one's running for the named backend, unless
+/// `fresh` results are needed.
+#[cfg(unix)]
+fn from_daemon(name: &str, fresh: bool) -> Option<Box<api::DynBackend>> {
+    if fresh {
+        None
+    } else {
+        daemon::connect(name)
+    }
+}
+
+#[cfg(not(unix))]
+fn from_daemon(_: &str, _: bool) -> Option<Box<api::DynBackend>> {
+    None
+}
+
+/// Opens the named backend in this process, wrapped in the offline cache if
+/// it's been enabled.
+pub fn open_direct(
+    config: &Config,
+    name: &str,
+    fresh: bool,
+) -> Result<Box<api::DynBackend>, Error> {
+    let backend = match REGISTRY.iter().find(|(v, _)| *v == name) {
+        Some((_, constructor)) => constructor(config)?,
+        None => match onep_backend_plugin::PluginBackend::find(name) {
+            Some(plugin) => Box::new(api::Boxed(plugin)),
+            None => return Err(Error::Unknown(name.to_string())),
+        },
+    };
+
+    match cache::path(name) {
+        Some(path) if config.cache.enabled => {
+            let ttl = config.cache.ttl.filter(|_| !fresh).map(Duration::from_secs);
+
+            Ok(Box::new(cache::Cached::new(
+                backend,
+                Some(path),
+                config.cache.items,
+                ttl,
+            )))
+        }
+        _ => Ok(backend),
+    }
+}
+
+fn op(config: &Config) -> Result<Box<api::DynBackend>, Error> {
+    let bin = std::env::var_os("ONEP_OP_BIN")
+        .map(Into::into)
+        .or_else(|| config.op.bin.clone())
+        .unwrap_or_else(|| "op".into());
+
+    let mut op = onep_backend_op::OpConfig::default();
+    op.bin = bin;
+    op.args = config.op.args.iter().map(Into::into).collect();
+    op.account.clone_from(&config.op.account);
+    op.vault.clone_from(&config.op.vault);
+    op.cache = config.op.cache;
+    op.app_integration = config.op.biometric;
+    op.session.clone_from(&config.op.session);
+    op.reauth = config.op.reauth;
+    op.timeout = Some(config.op.timeout)
+        .filter(|v| *v > 0)
+        .map(Duration::from_secs);
+    op.retries = config.op.retries;
+    op.backoff = Duration::from_millis(config.op.backoff);
+    op.strict = config.op.strict_parse;
+
+    Ok(Box::new(api::Boxed(onep_backend_op::OpBackend::new(op))))
+}
+
+fn bw(_: &Config) -> Result<Box<api::DynBackend>, Error> {
+    Ok(Box::new(api::Boxed(onep_backend_bw::BwBackend::new())))
+}
+
+fn pass(_: &Config) -> Result<Box<api::DynBackend>, Error> {
+    Ok(Box::new(api::Boxed(onep_backend_pass::PassBackend::new())))
+}
+
+fn keepass(config: &Config) -> Result<Box<api::DynBackend>, Error> {
+    let database = std::env::var_os("ONEP_KEEPASS_DB")
+        .map(Into::into)
+        .or_else(|| config.keepass.database.clone())
+        .ok_or(Error::NoKeepassDatabase)?;
+    let key_file = std::env::var_os("ONEP_KEEPASS_KEYFILE")
+        .map(Into::into)
+        .or_else(|| config.keepass.key_file.clone());
+
+    Ok(Box::new(api::Boxed(
+        onep_backend_keepass::KeepassBackend::new(database, key_file),
+    )))
+}
+
+fn connect(config: &Config) -> Result<Box<api::DynBackend>, Error> {
+    let host = std::env::var("OP_CONNECT_HOST")
+        .ok()
+        .or_else(|| config.connect.host.clone())
+        .ok_or(Error::NoConnectServer)?;
+    let token = std::env::var("OP_CONNECT_TOKEN")
+        .ok()
+        .or_else(|| config.connect.token.clone())
+        .ok_or(Error::NoConnectServer)?;
+
+    Ok(Box::new(api::Boxed(
+        onep_backend_connect::ConnectBackend::new(&host, token),
+    )))
+}
+
+/// Made up items held in memory, for trying 1p out.
+fn demo(_: &Config) -> Result<Box<api::DynBackend>, Error> {
+    Ok(Box::new(api::Boxed(onep_backend_memory::demo())))
+}
@@ -0,0 +1,990 @@
+//! An opt-in, encrypted copy of the vault kept on disk so `list`, `search` and
+//! `show` keep working when the backend can't be reached.
+//!
+//! The cache is refreshed whenever the backend answers, and only read from
+//! when it doesn't, unless a TTL is set in which case the item list is used
+//! without asking the backend until it expires. It's encrypted with XChaCha20-Poly1305 using a random key
+//! kept in the OS keychain, so the file alone is useless to anyone who copies
+//! it.
+//!
+//! One-time password secrets for items that have been picked out in the
+//! config are kept in a file of their own, encrypted the same way, so codes
+//! can be generated without the backend even when the rest of the cache is
+//! turned off.
+
+use crate::private;
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use onep_backend_api as api;
+use rand::RngCore;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+const KEYRING_SERVICE: &str = "1p";
+const KEYRING_USER: &str = "cache-key";
+const NONCE_LEN: usize = 24;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to access the cache key in your keychain: {0}")]
+    Keyring(#[from] keyring::KeyringError),
+    #[error("The cache key in your keychain is corrupt")]
+    InvalidKey,
+    #[error("Failed to decrypt the cache, it was likely written with another key")]
+    Decrypt,
+    #[error("Failed to read or write the cache: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The cache is corrupt: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedAccount {
+    name: String,
+    domain: String,
+    #[serde(default)]
+    service_account: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedVault {
+    uuid: String,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedMetadata {
+    uuid: String,
+    vault_uuid: String,
+    title: String,
+    account_info: String,
+    #[serde(default)]
+    urls: Vec<String>,
+    tags: Vec<String>,
+    favorite: bool,
+    #[serde(default)]
+    created: Option<SystemTime>,
+    #[serde(default)]
+    updated: Option<SystemTime>,
+    #[serde(default)]
+    category: CachedCategory,
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedCategory {
+    Login,
+    Password,
+    SecureNote,
+    CreditCard,
+    Identity,
+    Document,
+    BankAccount,
+    ApiCredential,
+    Database,
+    Server,
+    SshKey,
+    SoftwareLicense,
+    WirelessRouter,
+    Unknown,
+}
+
+impl Default for CachedCategory {
+    fn default() -> Self {
+        Self::Unknown
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedFieldType {
+    Totp,
+    SshKey,
+    Password,
+    Username,
+    Email,
+    Url,
+    Date,
+    Phone,
+    Address,
+    Concealed,
+    Note,
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedField {
+    name: String,
+    field_type: CachedFieldType,
+    value: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedSection {
+    name: String,
+    fields: Vec<CachedField>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedAttachment {
+    uuid: String,
+    name: String,
+    size: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedHistory {
+    value: String,
+    time: Option<SystemTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedItem {
+    title: String,
+    fields: Vec<CachedField>,
+    sections: Vec<CachedSection>,
+    #[serde(default)]
+    urls: Vec<CachedUrl>,
+    attachments: Vec<CachedAttachment>,
+    history: Vec<CachedHistory>,
+    /// When the item was last changed, as of when it was cached, so
+    /// `get_all` can tell whether it needs fetching again.
+    #[serde(default)]
+    updated: Option<SystemTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedUrl {
+    label: String,
+    url: String,
+}
+
+impl From<&api::AccountMetadata> for CachedAccount {
+    fn from(v: &api::AccountMetadata) -> Self {
+        Self {
+            name: v.name.clone(),
+            domain: v.domain.clone(),
+            service_account: v.service_account,
+        }
+    }
+}
+
+impl From<&CachedAccount> for api::AccountMetadata {
+    fn from(v: &CachedAccount) -> Self {
+        let mut account = Self::new(v.name.clone(), v.domain.clone());
+        account.service_account = v.service_account;
+        account
+    }
+}
+
+impl From<&api::VaultMetadata> for CachedVault {
+    fn from(v: &api::VaultMetadata) -> Self {
+        Self {
+            uuid: v.uuid.clone(),
+            name: v.name.clone(),
+        }
+    }
+}
+
+impl From<&CachedVault> for api::VaultMetadata {
+    fn from(v: &CachedVault) -> Self {
+        Self::new(v.uuid.clone(), v.name.clone())
+    }
+}
+
+impl From<&api::ItemMetadata> for CachedMetadata {
+    fn from(v: &api::ItemMetadata) -> Self {
+        Self {
+            uuid: v.uuid.clone(),
+            vault_uuid: v.vault_uuid.clone(),
+            title: v.title.clone(),
+            account_info: v.account_info.clone(),
+            urls: v.urls.clone(),
+            tags: v.tags.clone(),
+            favorite: v.favorite,
+            created: v.created,
+            updated: v.updated,
+            category: match v.category {
+                api::ItemCategory::Login => CachedCategory::Login,
+                api::ItemCategory::Password => CachedCategory::Password,
+                api::ItemCategory::SecureNote => CachedCategory::SecureNote,
+                api::ItemCategory::CreditCard => CachedCategory::CreditCard,
+                api::ItemCategory::Identity => CachedCategory::Identity,
+                api::ItemCategory::Document => CachedCategory::Document,
+                api::ItemCategory::BankAccount => CachedCategory::BankAccount,
+                api::ItemCategory::ApiCredential => CachedCategory::ApiCredential,
+                api::ItemCategory::Database => CachedCategory::Database,
+                api::ItemCategory::Server => CachedCategory::Server,
+                api::ItemCategory::SshKey => CachedCategory::SshKey,
+                api::ItemCategory::SoftwareLicense => CachedCategory::SoftwareLicense,
+                api::ItemCategory::WirelessRouter => CachedCategory::WirelessRouter,
+                api::ItemCategory::Unknown => CachedCategory::Unknown,
+            },
+        }
+    }
+}
+
+impl From<&CachedMetadata> for api::ItemMetadata {
+    fn from(v: &CachedMetadata) -> Self {
+        let mut metadata = Self::new(v.uuid.clone(), v.vault_uuid.clone(), v.title.clone());
+        metadata.account_info.clone_from(&v.account_info);
+        metadata.urls.clone_from(&v.urls);
+        metadata.tags.clone_from(&v.tags);
+        metadata.favorite = v.favorite;
+        metadata.created = v.created;
+        metadata.updated = v.updated;
+        metadata.category = match v.category {
+            CachedCategory::Login => api::ItemCategory::Login,
+            CachedCategory::Password => api::ItemCategory::Password,
+            CachedCategory::SecureNote => api::ItemCategory::SecureNote,
+            CachedCategory::CreditCard => api::ItemCategory::CreditCard,
+            CachedCategory::Identity => api::ItemCategory::Identity,
+            CachedCategory::Document => api::ItemCategory::Document,
+            CachedCategory::BankAccount => api::ItemCategory::BankAccount,
+            CachedCategory::ApiCredential => api::ItemCategory::ApiCredential,
+            CachedCategory::Database => api::ItemCategory::Database,
+            CachedCategory::Server => api::ItemCategory::Server,
+            CachedCategory::SshKey => api::ItemCategory::SshKey,
+            CachedCategory::SoftwareLicense => api::ItemCategory::SoftwareLicense,
+            CachedCategory::WirelessRouter => api::ItemCategory::WirelessRouter,
+            CachedCategory::Unknown => api::ItemCategory::Unknown,
+        };
+        metadata
+    }
+}
+
+impl From<&api::ItemField> for CachedField {
+    fn from(v: &api::ItemField) -> Self {
+        Self {
+            name: v.name.clone(),
+            field_type: match v.field_type {
+                api::ItemFieldType::Totp => CachedFieldType::Totp,
+                api::ItemFieldType::SshKey => CachedFieldType::SshKey,
+                api::ItemFieldType::Password => CachedFieldType::Password,
+                api::ItemFieldType::Username => CachedFieldType::Username,
+                api::ItemFieldType::Email => CachedFieldType::Email,
+                api::ItemFieldType::Url => CachedFieldType::Url,
+                api::ItemFieldType::Date => CachedFieldType::Date,
+                api::ItemFieldType::Phone => CachedFieldType::Phone,
+                api::ItemFieldType::Address => CachedFieldType::Address,
+                api::ItemFieldType::Concealed => CachedFieldType::Concealed,
+                api::ItemFieldType::Note => CachedFieldType::Note,
+                api::ItemFieldType::Unknown => CachedFieldType::Unknown,
+            },
+            value: v.value.as_str().to_string(),
+        }
+    }
+}
+
+impl From<&CachedField> for api::ItemField {
+    fn from(v: &CachedField) -> Self {
+        let field_type = match v.field_type {
+            CachedFieldType::Totp => api::ItemFieldType::Totp,
+            CachedFieldType::SshKey => api::ItemFieldType::SshKey,
+            CachedFieldType::Password => api::ItemFieldType::Password,
+            CachedFieldType::Username => api::ItemFieldType::Username,
+            CachedFieldType::Email => api::ItemFieldType::Email,
+            CachedFieldType::Url => api::ItemFieldType::Url,
+            CachedFieldType::Date => api::ItemFieldType::Date,
+            CachedFieldType::Phone => api::ItemFieldType::Phone,
+            CachedFieldType::Address => api::ItemFieldType::Address,
+            CachedFieldType::Concealed => api::ItemFieldType::Concealed,
+            CachedFieldType::Note => api::ItemFieldType::Note,
+            CachedFieldType::Unknown => api::ItemFieldType::Unknown,
+        };
+
+        api::ItemField::new(v.name.clone(), v.value.clone()).with_type(field_type)
+    }
+}
+
+impl From<&api::Item> for CachedItem {
+    fn from(v: &api::Item) -> Self {
+        Self {
+            title: v.title.clone(),
+            fields: v.fields.iter().map(Into::into).collect(),
+            sections: v
+                .sections
+                .iter()
+                .map(|v| CachedSection {
+                    name: v.name.clone(),
+                    fields: v.fields.iter().map(Into::into).collect(),
+                })
+                .collect(),
+            urls: v
+                .urls
+                .iter()
+                .map(|v| CachedUrl {
+                    label: v.label.clone(),
+                    url: v.url.clone(),
+                })
+                .collect(),
+            attachments: v
+                .attachments
+                .iter()
+                .map(|v| CachedAttachment {
+                    uuid: v.uuid.clone(),
+                    name: v.name.clone(),
+                    size: v.size,
+                })
+                .collect(),
+            history: v
+                .history
+                .iter()
+                .map(|v| CachedHistory {
+                    value: v.value.as_str().to_string(),
+                    time: v.time,
+                })
+                .collect(),
+            updated: None,
+        }
+    }
+}
+
+impl TryFrom<&CachedItem> for api::Item {
+    type Error = api::InvalidItem;
+
+    fn try_from(v: &CachedItem) -> Result<Self, Self::Error> {
+        api::Item::builder()
+            .title(v.title.clone())
+            .fields(v.fields.iter().map(Into::into))
+            .sections(v.sections.iter().map(|v| {
+                api::ItemSection::new(v.name.clone()).with_fields(v.fields.iter().map(Into::into))
+            }))
+            .urls(
+                v.urls
+                    .iter()
+                    .map(|v| api::ItemUrl::new(v.label.clone(), v.url.clone())),
+            )
+            .attachments(
+                v.attachments
+                    .iter()
+                    .map(|v| api::AttachmentMetadata::new(v.uuid.clone(), v.name.clone(), v.size)),
+            )
+            .history(
+                v.history
+                    .iter()
+                    .map(|v| api::HistoricalValue::new(v.value.clone(), v.time)),
+            )
+            .build()
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Contents {
+    account: Option<CachedAccount>,
+    vaults: Vec<CachedVault>,
+    items: Vec<CachedMetadata>,
+    /// Full items keyed by uuid, only populated when caching items is enabled.
+    full_items: HashMap<String, CachedItem>,
+    /// When the item list was last fetched from the backend, cleared whenever
+    /// an item is changed so the change shows up straight away.
+    #[serde(default)]
+    refreshed: Option<SystemTime>,
+}
+
+impl Contents {
+    /// Matches items the same way op's search does.
+    fn search(&self, terms: Option<&str>) -> Vec<api::ItemMetadata> {
+        let terms = terms.map(str::to_lowercase);
+
+        self.items
+            .iter()
+            .filter(|v| {
+                if let Some(terms) = &terms {
+                    let terms = terms.as_str();
+
+                    v.uuid == terms
+                        || v.vault_uuid == terms
+                        || v.title.to_lowercase().contains(terms)
+                        || v.account_info.to_lowercase().contains(terms)
+                        || v.urls.iter().any(|v| v.to_lowercase().contains(terms))
+                        || v.tags.iter().any(|v| v.to_lowercase().contains(terms))
+                } else {
+                    true
+                }
+            })
+            .map(Into::into)
+            .collect()
+    }
+}
+
+/// Fetches the cache key from the keychain, generating one the first time
+/// it's needed.
+fn key() -> Result<Key, Error> {
+    let keyring = keyring::Keyring::new(KEYRING_SERVICE, KEYRING_USER);
+
+    let encoded = match keyring.get_password() {
+        Ok(encoded) => encoded,
+        Err(keyring::KeyringError::NoPasswordFound) => {
+            let mut key = [0; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+
+            let encoded = base64::encode(&key);
+            keyring.set_password(&encoded)?;
+            encoded
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let key = base64::decode(encoded.trim()).map_err(|_| Error::InvalidKey)?;
+
+    if key.len() == 32 {
+        Ok(*Key::from_slice(&key))
+    } else {
+        Err(Error::InvalidKey)
+    }
+}
+
+fn read<T: Default + DeserializeOwned>(path: &Path, key: &Key) -> Result<T, Error> {
+    let encrypted = match std::fs::read(path) {
+        Ok(encrypted) => encrypted,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    if encrypted.len() < NONCE_LEN {
+        return Err(Error::Decrypt);
+    }
+
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+    let plaintext = XChaCha20Poly1305::new(key)
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| Error::Decrypt)?;
+
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+fn write<T: Serialize>(path: &Path, key: &Key, contents: &T) -> Result<(), Error> {
+    use std::io::Write;
+
+    let mut nonce = [0; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let ciphertext = XChaCha20Poly1305::new(key)
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            serde_json::to_vec(contents)?.as_ref(),
+        )
+        .map_err(|_| Error::Decrypt)?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut file = private::create(path)?;
+    file.write_all(&nonce)?;
+    file.write_all(&ciphertext)?;
+
+    Ok(())
+}
+
+/// Where the cache for the named backend is kept, if there's a cache
+/// directory to keep it in.
+pub fn path(backend: &str) -> Option<PathBuf> {
+    dirs::cache_dir().map(|v| v.join("1p").join(format!("{}.cache", backend)))
+}
+
+/// A one-time password secret kept for generating codes offline, along with
+/// the title of its item so it can be found by either.
+#[derive(Serialize, Deserialize)]
+struct CachedSecret {
+    title: String,
+    secret: String,
+}
+
+/// One-time password secrets keyed by the uuid of their item.
+pub struct Secrets {
+    path: PathBuf,
+    key: Key,
+    by_uuid: HashMap<String, CachedSecret>,
+}
+
+impl Secrets {
+    /// Opens the secrets kept so far, if there's a cache directory to keep
+    /// them in.
+    pub fn open() -> Result<Option<Self>, Error> {
+        let path = match dirs::cache_dir() {
+            Some(dir) => dir.join("1p").join("totp.cache"),
+            None => return Ok(None),
+        };
+        let key = key()?;
+        let by_uuid = read(&path, &key)?;
+
+        Ok(Some(Self { path, key, by_uuid }))
+    }
+
+    /// Finds a secret by its item's uuid or title, returning the item's uuid
+    /// and title along with it.
+    pub fn get(&self, item: &str) -> Option<(&str, &str, &str)> {
+        self.by_uuid
+            .iter()
+            .find(|(uuid, v)| *uuid == item || v.title.to_lowercase() == item.to_lowercase())
+            .map(|(uuid, v)| (uuid.as_str(), v.title.as_str(), v.secret.as_str()))
+    }
+
+    /// Keeps the secret for an item, or forgets it given `None`, writing the
+    /// change straight to disk if anything changed.
+    pub fn set(&mut self, uuid: &str, title: &str, secret: Option<&str>) -> Result<(), Error> {
+        let changed = match secret {
+            Some(secret) => {
+                let unchanged = self
+                    .by_uuid
+                    .get(uuid)
+                    .map_or(false, |v| v.title == title && v.secret == secret);
+
+                if !unchanged {
+                    self.by_uuid.insert(
+                        uuid.to_string(),
+                        CachedSecret {
+                            title: title.to_string(),
+                            secret: secret.to_string(),
+                        },
+                    );
+                }
+
+                !unchanged
+            }
+            None => self.by_uuid.remove(uuid).is_some(),
+        };
+
+        if changed {
+            write(&self.path, &self.key, &self.by_uuid)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    key: Option<Key>,
+    contents: Option<Contents>,
+}
+
+/// Wraps a backend, caching what it returns and falling back to the cache
+/// when it fails.
+pub struct Cached {
+    inner: Box<api::DynBackend>,
+    /// Where the cache is kept on disk, or `None` to only keep it in memory.
+    path: Option<PathBuf>,
+    /// Whether full items, including their secrets, are cached rather than
+    /// just their metadata.
+    items: bool,
+    /// How long the item list is used for before asking the backend again,
+    /// if at all.
+    ttl: Option<Duration>,
+    state: Mutex<State>,
+    warned: AtomicBool,
+}
+
+impl Cached {
+    pub fn new(
+        inner: Box<api::DynBackend>,
+        path: Option<PathBuf>,
+        items: bool,
+        ttl: Option<Duration>,
+    ) -> Self {
+        Self {
+            inner,
+            path,
+            items,
+            ttl,
+            state: Mutex::default(),
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    /// Calls `f` with the cache's contents, reading them from disk if they
+    /// haven't been already.
+    fn with<T>(&self, f: impl FnOnce(&mut Contents) -> T) -> Result<T, Error> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.contents.is_none() {
+            state.contents = Some(match &self.path {
+                Some(path) => {
+                    let key = key()?;
+                    state.key = Some(key);
+                    read(path, &key)?
+                }
+                None => Contents::default(),
+            });
+        }
+
+        Ok(f(state.contents.as_mut().unwrap()))
+    }
+
+    /// Writes a change to the cache, warning rather than failing if it can't
+    /// be saved as the backend itself succeeded.
+    fn update(&self, f: impl FnOnce(&mut Contents)) {
+        let res = self.with(f).and_then(|()| {
+            let state = self.state.lock().unwrap();
+
+            match (&self.path, &state.key, &state.contents) {
+                (Some(path), Some(key), Some(contents)) => write(path, key, contents),
+                _ => Ok(()),
+            }
+        });
+
+        if let Err(e) = res {
+            eprintln!("Failed to update cache: {}", e);
+        }
+    }
+
+    /// Fetches the account, vaults and item list from the backend whether or
+    /// not the cached ones have expired.
+    pub async fn warm(&self) -> Result<(), api::BackendError> {
+        let (account, vaults, items) = tokio::try_join!(
+            self.inner.account(),
+            self.inner.vaults(),
+            self.inner.search(None)
+        )?;
+
+        self.update(|v| {
+            v.account = Some((&account).into());
+            v.vaults = vaults.iter().map(Into::into).collect();
+            v.items = items.iter().map(Into::into).collect();
+            v.refreshed = Some(SystemTime::now());
+        });
+
+        Ok(())
+    }
+
+    /// Reads from the cache without asking the backend, if the item list
+    /// hasn't outlived its TTL.
+    fn fresh<T>(&self, f: impl FnOnce(&Contents) -> Option<T>) -> Option<T> {
+        let ttl = self.ttl?;
+
+        self.with(|v| {
+            let age = v.refreshed?.elapsed().ok()?;
+
+            if age < ttl {
+                f(v)
+            } else {
+                None
+            }
+        })
+        .ok()
+        .flatten()
+    }
+
+    /// Forces the item list to be fetched again after a change, so the
+    /// change isn't hidden until the TTL runs out.
+    fn invalidate(&self) {
+        if self.ttl.is_some() {
+            self.update(|v| v.refreshed = None);
+        }
+    }
+
+    /// Reads from the cache in place of the backend, returning the backend's
+    /// error if there's nothing cached.
+    fn fallback<T>(
+        &self,
+        error: api::BackendError,
+        f: impl FnOnce(&Contents) -> Option<T>,
+    ) -> Result<T, api::BackendError> {
+        match self.with(|v| f(v)) {
+            Ok(Some(v)) => {
+                if !self.warned.swap(true, Ordering::Relaxed) {
+                    eprintln!("Falling back to cached items: {}", error);
+                }
+
+                Ok(v)
+            }
+            _ => Err(error),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl api::Backend for Cached {
+    type Error = api::BackendError;
+
+    async fn capabilities(&self) -> Result<api::Capabilities, Self::Error> {
+        self.inner.capabilities().await
+    }
+
+    async fn signin(&self, account: Option<&str>) -> Result<(), Self::Error> {
+        self.inner.signin(account).await
+    }
+
+    async fn signout(&self) -> Result<(), Self::Error> {
+        self.inner.signout().await
+    }
+
+    async fn account(&self) -> Result<api::AccountMetadata, Self::Error> {
+        if let Some(account) = self.fresh(|v| v.account.as_ref().map(Into::into)) {
+            return Ok(account);
+        }
+
+        match self.inner.account().await {
+            Ok(account) => {
+                self.update(|v| v.account = Some((&account).into()));
+                Ok(account)
+            }
+            Err(e) => self.fallback(e, |v| v.account.as_ref().map(Into::into)),
+        }
+    }
+
+    async fn vaults(&self) -> Result<Vec<api::VaultMetadata>, Self::Error> {
+        if let Some(vaults) = self.fresh(|v| Some(v.vaults.iter().map(Into::into).collect())) {
+            return Ok(vaults);
+        }
+
+        match self.inner.vaults().await {
+            Ok(vaults) => {
+                self.update(|v| v.vaults = vaults.iter().map(Into::into).collect());
+                Ok(vaults)
+            }
+            Err(e) => self.fallback(e, |v| {
+                if v.vaults.is_empty() {
+                    None
+                } else {
+                    Some(v.vaults.iter().map(Into::into).collect())
+                }
+            }),
+        }
+    }
+
+    async fn search(&self, terms: Option<&str>) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        if let Some(items) = self.fresh(|v| Some(v.search(terms))) {
+            return Ok(items);
+        }
+
+        match self.inner.search(terms).await {
+            Ok(items) => {
+                // only a full listing tells us about every item, searches are
+                // left alone
+                if terms.is_none() {
+                    self.update(|v| {
+                        v.items = items.iter().map(Into::into).collect();
+                        v.refreshed = Some(SystemTime::now());
+                    });
+                }
+
+                Ok(items)
+            }
+            Err(e) => self.fallback(e, |v| {
+                if v.items.is_empty() {
+                    None
+                } else {
+                    Some(v.search(terms))
+                }
+            }),
+        }
+    }
+
+    async fn get(&self, uuid: &str) -> Result<Option<api::Item>, Self::Error> {
+        match self.inner.get(uuid).await {
+            Ok(Some(item)) if self.items => {
+                self.update(|v| {
+                    v.full_items.insert(uuid.to_string(), (&item).into());
+                });
+                Ok(Some(item))
+            }
+            Ok(item) => Ok(item),
+            Err(e) => self.fallback(e, |v| {
+                v.full_items
+                    .get(uuid)
+                    .and_then(|v| api::Item::try_from(v).ok())
+                    .map(Some)
+            }),
+        }
+    }
+
+    async fn generate(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &api::PasswordRecipe,
+    ) -> Result<api::Item, Self::Error> {
+        let res = self
+            .inner
+            .generate(name, username, url, tags, vault, recipe)
+            .await;
+        self.invalidate();
+        res
+    }
+
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<api::Item, Self::Error> {
+        let res = self
+            .inner
+            .create(name, username, url, tags, vault, password)
+            .await;
+        self.invalidate();
+        res
+    }
+
+    async fn create_item(
+        &self,
+        item: &api::Item,
+        category: api::ItemCategory,
+        tags: &[String],
+        vault: Option<&str>,
+    ) -> Result<api::Item, Self::Error> {
+        let res = self.inner.create_item(item, category, tags, vault).await;
+        self.invalidate();
+        res
+    }
+
+    async fn create_vault(&self, name: &str) -> Result<api::VaultMetadata, Self::Error> {
+        let res = self.inner.create_vault(name).await;
+        self.invalidate();
+        res
+    }
+
+    async fn delete_vault(&self, uuid: &str) -> Result<(), Self::Error> {
+        let res = self.inner.delete_vault(uuid).await;
+        self.invalidate();
+        res
+    }
+
+    async fn rename_vault(&self, uuid: &str, name: &str) -> Result<(), Self::Error> {
+        let res = self.inner.rename_vault(uuid, name).await;
+        self.invalidate();
+        res
+    }
+
+    async fn edit(&self, uuid: &str, edit: &api::ItemEdit) -> Result<(), Self::Error> {
+        let res = self.inner.edit(uuid, edit).await;
+        self.invalidate();
+        res
+    }
+
+    async fn set_tags(&self, uuid: &str, tags: &[String]) -> Result<(), Self::Error> {
+        let res = self.inner.set_tags(uuid, tags).await;
+        self.invalidate();
+        res
+    }
+
+    async fn set_favorite(&self, uuid: &str, favorite: bool) -> Result<(), Self::Error> {
+        let res = self.inner.set_favorite(uuid, favorite).await;
+        self.invalidate();
+        res
+    }
+
+    async fn set_otp(&self, uuid: &str, uri: &str) -> Result<(), Self::Error> {
+        let res = self.inner.set_otp(uuid, uri).await;
+        self.invalidate();
+        res
+    }
+
+    async fn archived(&self) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        self.inner.archived().await
+    }
+
+    async fn archive(&self, uuid: &str) -> Result<(), Self::Error> {
+        let res = self.inner.archive(uuid).await;
+        self.invalidate();
+        res
+    }
+
+    async fn restore(&self, uuid: &str) -> Result<(), Self::Error> {
+        let res = self.inner.restore(uuid).await;
+        self.invalidate();
+        res
+    }
+
+    async fn documents(&self) -> Result<Vec<api::DocumentMetadata>, Self::Error> {
+        self.inner.documents().await
+    }
+
+    async fn get_document(&self, uuid: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.inner.get_document(uuid).await
+    }
+
+    async fn create_document(
+        &self,
+        path: &Path,
+        title: Option<&str>,
+        vault: Option<&str>,
+    ) -> Result<api::DocumentMetadata, Self::Error> {
+        self.inner.create_document(path, title, vault).await
+    }
+
+    async fn get_attachment(
+        &self,
+        item_uuid: &str,
+        attachment_uuid: &str,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        self.inner.get_attachment(item_uuid, attachment_uuid).await
+    }
+
+    async fn get_all(&self) -> Result<Vec<(api::ItemMetadata, api::Item)>, Self::Error> {
+        if !self.items {
+            return self.inner.get_all().await;
+        }
+
+        let results = self.search(None).await?;
+
+        // items are only fetched again if they've changed since they were
+        // cached, which backends without modification times can't tell us
+        let mut cached: HashMap<String, api::Item> = self
+            .with(|v| {
+                results
+                    .iter()
+                    .filter_map(|metadata| {
+                        let item = v.full_items.get(&metadata.uuid)?;
+
+                        if metadata.updated.is_some() && item.updated == metadata.updated {
+                            Some((metadata.uuid.clone(), api::Item::try_from(item).ok()?))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let stale: Vec<_> = results
+            .iter()
+            .filter(|v| !cached.contains_key(&v.uuid))
+            .collect();
+        let uuids: Vec<_> = stale.iter().map(|v| v.uuid.clone()).collect();
+        let fetched = self.inner.get_many(&uuids).await?;
+
+        self.update(|v| {
+            for (metadata, item) in stale.iter().zip(&fetched) {
+                if let Some(item) = item {
+                    let mut item = CachedItem::from(item);
+                    item.updated = metadata.updated;
+                    v.full_items.insert(metadata.uuid.clone(), item);
+                }
+            }
+        });
+
+        cached.extend(
+            uuids
+                .into_iter()
+                .zip(fetched)
+                .filter_map(|(uuid, item)| item.map(|item| (uuid, item))),
+        );
+
+        Ok(results
+            .into_iter()
+            .filter_map(|metadata| {
+                let item = cached.remove(&metadata.uuid)?;
+                Some((metadata, item))
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,92 @@
+//! Checks subcommands against what the backend is able to do, so users are
+//! told which backend can't do what before anything is attempted, and aren't
+//! offered subcommands in the help that would only fail.
+
+use crate::{backends, config::Config};
+use clap::{App, AppSettings};
+use onep_backend_api as api;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("The {0} backend can't {1}.")]
+    Unsupported(String, &'static str),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Need {
+    Totp,
+    Write,
+    Documents,
+    MultipleAccounts,
+}
+
+impl Need {
+    fn met(self, capabilities: api::Capabilities) -> bool {
+        match self {
+            Self::Totp => capabilities.supports_totp,
+            Self::Write => capabilities.supports_write,
+            Self::Documents => capabilities.supports_documents,
+            Self::MultipleAccounts => capabilities.supports_multiple_accounts,
+        }
+    }
+}
+
+/// Subcommands needing more than reading items, with what they need and what
+/// to say they do when the backend can't.
+const SUBCOMMANDS: &[(&str, Need, &str)] = &[
+    ("generate", Need::Write, "create items"),
+    ("duplicate", Need::Write, "create items"),
+    ("totp", Need::Totp, "use one-time passwords"),
+    ("vault", Need::Write, "manage vaults"),
+    ("rename", Need::Write, "rename items"),
+    ("tag", Need::Write, "change tags"),
+    ("fav", Need::Write, "change favorites"),
+    ("unfav", Need::Write, "change favorites"),
+    ("archive", Need::Write, "archive items"),
+    ("restore", Need::Write, "restore archived items"),
+    ("doc", Need::Documents, "store documents"),
+    ("import", Need::Write, "import items"),
+];
+
+/// Fails unless the backend can do what's needed, `what` being what it would
+/// be doing.
+pub async fn require(
+    backend: &api::DynBackend,
+    config: &Config,
+    need: Need,
+    what: &'static str,
+) -> anyhow::Result<()> {
+    if need.met(backend.capabilities().await?) {
+        Ok(())
+    } else {
+        Err(Error::Unsupported(backends::name(config), what).into())
+    }
+}
+
+/// Fails if the backend can't do what the named subcommand needs.
+pub async fn check(
+    backend: &api::DynBackend,
+    config: &Config,
+    subcommand: &str,
+) -> anyhow::Result<()> {
+    match SUBCOMMANDS.iter().find(|(name, _, _)| *name == subcommand) {
+        Some((_, need, what)) => require(backend, config, *need, what).await,
+        None => Ok(()),
+    }
+}
+
+/// Hides the subcommands the backend can't run from the help, leaving them
+/// usable so running one gives a precise error.
+pub fn hide(mut app: App<'_>, capabilities: api::Capabilities) -> App<'_> {
+    for subcommand in app.get_subcommands_mut() {
+        let unsupported = SUBCOMMANDS
+            .iter()
+            .any(|(name, need, _)| *name == subcommand.get_name() && !need.met(capabilities));
+
+        if unsupported {
+            *subcommand = std::mem::take(subcommand).setting(AppSettings::Hidden);
+        }
+    }
+
+    app
+}
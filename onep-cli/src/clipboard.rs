@@ -0,0 +1,34 @@
+//! Puts secrets on the clipboard, clearing them again after a while.
+
+use std::time::Duration;
+
+/// How long secrets stay on the clipboard when the config doesn't say,
+/// unless they stop being useful sooner.
+pub const CLEAR_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to use the clipboard: {0}")]
+    Clipboard(#[from] arboard::Error),
+}
+
+/// Copies `value` and waits to clear it again, leaving the clipboard alone
+/// if something else has been copied since. On some platforms the clipboard
+/// is only filled for as long as the process that set it is running, so this
+/// doesn't return early.
+pub async fn copy(value: &str, clear_after: Duration) -> Result<(), Error> {
+    let mut clipboard = arboard::Clipboard::new()?;
+    clipboard.set_text(value.to_string())?;
+
+    eprintln!(
+        "Copied to the clipboard, clearing it in {} seconds",
+        clear_after.as_secs()
+    );
+    tokio::time::delay_for(clear_after).await;
+
+    if clipboard.get_text().ok().as_deref() == Some(value) {
+        clipboard.clear()?;
+    }
+
+    Ok(())
+}
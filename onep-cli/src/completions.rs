@@ -0,0 +1,151 @@
+//! Shell completion scripts. On top of what clap generates from the argument
+//! definitions, each script calls back into `1p __complete-items` so item
+//! titles and vault names complete from the password manager itself.
+
+use clap::{App, Clap};
+use clap_generate::generators::{Bash, Fish, Zsh};
+use itertools::Itertools;
+use onep_backend_api as api;
+use std::str::FromStr;
+
+#[derive(Clap, Debug)]
+pub struct CompletionsOpt {
+    /// One of bash, zsh or fish
+    shell: Shell,
+}
+
+#[derive(Clap, Debug)]
+pub struct CompleteItemsOpt {
+    /// Complete vault names rather than item titles
+    #[clap(long)]
+    vaults: bool,
+    /// Start of the title or name being completed
+    prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            _ => Err(format!("unknown shell {}", s)),
+        }
+    }
+}
+
+/// Completes item titles after `show`, and vault names after `--vault`,
+/// falling back to clap's completions everywhere else.
+const BASH: &str = r#"
+_1p_dynamic() {
+    local cur=${COMP_WORDS[COMP_CWORD]} prev=${COMP_WORDS[COMP_CWORD-1]}
+    local IFS=$'\n'
+
+    if [[ $prev == --vault ]]; then
+        compopt -o filenames
+        COMPREPLY=($(1p __complete-items --vaults -- "$cur" 2>/dev/null))
+    elif [[ $cur != -* && $COMP_CWORD -gt 1 && ( ${COMP_WORDS[1]} == show || ${COMP_WORDS[1]} == get ) ]]; then
+        compopt -o filenames
+        COMPREPLY=($(1p __complete-items -- "$cur" 2>/dev/null))
+    else
+        _1p "$@"
+    fi
+}
+
+complete -F _1p_dynamic -o bashdefault -o default 1p
+"#;
+
+/// zsh runs the whole file as `_1p` the first time it completes, so clap's
+/// function is kept under another name and `_1p` replaced with one that
+/// calls it.
+const ZSH: &str = r#"
+functions[_1p_clap]=$functions[_1p]
+
+_1p() {
+    local -a completions
+
+    if [[ $words[CURRENT-1] == --vault ]]; then
+        completions=(${(f)"$(1p __complete-items --vaults -- "$PREFIX" 2>/dev/null)"})
+        compadd -a completions
+    elif [[ $PREFIX != -* && CURRENT -gt 2 && $words[2] == (show|get) ]]; then
+        completions=(${(f)"$(1p __complete-items -- "$PREFIX" 2>/dev/null)"})
+        compadd -a completions
+    else
+        _1p_clap "$@"
+    fi
+}
+
+_1p "$@"
+"#;
+
+const FISH: &str = r#"
+complete -c 1p -n "__fish_seen_subcommand_from show get" -f -a "(1p __complete-items -- (commandline -ct) 2>/dev/null)"
+complete -c 1p -l vault -x -a "(1p __complete-items --vaults -- (commandline -ct) 2>/dev/null)"
+"#;
+
+/// Prints the completion script for the given shell.
+pub fn generate(app: &mut App, opt: &CompletionsOpt) {
+    let mut script = Vec::new();
+
+    let dynamic = match opt.shell {
+        Shell::Bash => {
+            clap_generate::generate::<Bash, _>(app, "1p", &mut script);
+            BASH
+        }
+        Shell::Zsh => {
+            clap_generate::generate::<Zsh, _>(app, "1p", &mut script);
+            ZSH
+        }
+        Shell::Fish => {
+            clap_generate::generate::<Fish, _>(app, "1p", &mut script);
+            FISH
+        }
+    };
+
+    let script = String::from_utf8_lossy(&script);
+    // clap's zsh script ends by calling its function, which the dynamic part
+    // does itself once it's wrapped it
+    let script = script.trim_end();
+    let script = script.strip_suffix(r#"_1p "$@""#).unwrap_or(script);
+
+    println!("{}\n{}", script.trim_end(), dynamic.trim_end());
+}
+
+/// Prints every item title or vault name starting with the prefix, one per
+/// line, for the completion scripts to offer.
+pub async fn items(backend: &api::DynBackend, opt: CompleteItemsOpt) -> anyhow::Result<()> {
+    let prefix = opt.prefix.unwrap_or_default().to_lowercase();
+
+    let names: Vec<_> = if opt.vaults {
+        backend
+            .vaults()
+            .await?
+            .into_iter()
+            .map(|v| v.name)
+            .collect()
+    } else {
+        backend
+            .search(None)
+            .await?
+            .into_iter()
+            .map(|v| v.title)
+            .collect()
+    };
+
+    for name in names.into_iter().unique() {
+        if name.to_lowercase().starts_with(&prefix) {
+            println!("{}", name);
+        }
+    }
+
+    Ok(())
+}
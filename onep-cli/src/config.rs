@@ -0,0 +1,330 @@
+//! Loads user configuration from `~/.config/1p/config.toml`, or wherever
+//! `ONEP_CONFIG` points.
+
+use crate::clipboard;
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Name of the backend to use, overridden by `ONEP_BACKEND`.
+    pub backend: Option<String>,
+    /// Account to sign in to when `1p signin` isn't given one, whatever the
+    /// backend. With op it's picked over `[op] account`.
+    pub account: Option<String>,
+    /// Vault new items are created in, by name or uuid, when `--vault`
+    /// isn't given, whatever the backend. With op it's picked over
+    /// `[op] vault`.
+    pub vault: Option<String>,
+    /// Seconds to leave copied secrets on the clipboard for before clearing
+    /// it, or until a one-time password changes if that's sooner.
+    pub clipboard_timeout: Option<u64>,
+    pub color: ColorChoice,
+    /// Draw trees with plain ASCII rather than box-drawing characters, set by
+    /// `--ascii`.
+    pub ascii: bool,
+    pub colors: ColorsConfig,
+    /// Flags added to subcommands, keyed by the subcommand's name, unless
+    /// they're given on the command line.
+    pub defaults: BTreeMap<String, Vec<String>>,
+    /// Names that can be used in place of a subcommand and its arguments.
+    pub alias: BTreeMap<String, Alias>,
+    pub op: OpConfig,
+    pub keepass: KeepassConfig,
+    pub connect: ConnectConfig,
+    pub env: EnvConfig,
+    pub askpass: AskpassConfig,
+    pub otp: OtpConfig,
+    pub cache: CacheConfig,
+    pub tables: TablesConfig,
+    /// Print long output straight to stdout rather than through a pager, set
+    /// by `--no-pager`.
+    #[serde(skip)]
+    pub no_pager: bool,
+    /// Print only the values asked for, leaving out headers and decorations,
+    /// set by `--quiet`.
+    #[serde(skip)]
+    pub quiet: bool,
+}
+
+/// What an alias expands to, either split on whitespace or as a list of
+/// arguments so they can contain spaces.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Alias {
+    Line(String),
+    Args(Vec<String>),
+}
+
+impl Alias {
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Self::Line(line) => line.split_whitespace().map(str::to_string).collect(),
+            Self::Args(args) => args.clone(),
+        }
+    }
+}
+
+/// Whether output is colored, and trees drawn with box-drawing characters,
+/// overridden by `--no-color` or `NO_COLOR`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Only when writing to a terminal.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Auto => atty::is(atty::Stream::Stdout),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+impl Default for ColorChoice {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+/// A color by name, such as `blue` or `bright red`.
+#[derive(Debug, Clone, Copy)]
+pub struct Color(pub colored::Color);
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+
+        name.parse()
+            .map(Self)
+            .map_err(|()| D::Error::custom(format!("unknown color {}", name)))
+    }
+}
+
+/// Colors used to pick out parts of `list` and `search` output.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorsConfig {
+    pub vault: Color,
+    pub account: Color,
+    pub uuid: Color,
+    pub dates: Color,
+}
+
+impl Default for ColorsConfig {
+    fn default() -> Self {
+        Self {
+            vault: Color(colored::Color::Blue),
+            account: Color(colored::Color::Green),
+            uuid: Color(colored::Color::Yellow),
+            dates: Color(colored::Color::Magenta),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpConfig {
+    /// The op executable, overridden by `ONEP_OP_BIN`. Looked up on the path
+    /// when not set.
+    pub bin: Option<PathBuf>,
+    /// Arguments given to op before those of every command.
+    pub args: Vec<String>,
+    /// Account every command runs against, by shorthand, sign-in address
+    /// or id, rather than the one op last signed in to. The top-level
+    /// `account` is still the one `1p signin` signs in to if both are set.
+    pub account: Option<String>,
+    /// Vault items are listed from, and created in when neither `--vault`
+    /// nor the top-level `vault` pick one.
+    pub vault: Option<String>,
+    /// Whether op should use its daemon's cache, left to op when not set.
+    pub cache: Option<bool>,
+    /// Whether op unlocks through the 1Password app, such as with Touch ID,
+    /// rather than with a session. Left to `OP_BIOMETRIC_UNLOCK_ENABLED`
+    /// when not set.
+    pub biometric: Option<bool>,
+    /// Session token given by `--session`, which isn't read from the config
+    /// so it can't be left lying around in it.
+    #[serde(skip)]
+    pub session: Option<String>,
+    /// Sign in when there's no session or it has expired rather than
+    /// failing, overridden by `--no-reauth`.
+    pub reauth: bool,
+    /// Seconds to give op to finish a command before killing it, or 0 to
+    /// wait for as long as it takes.
+    pub timeout: u64,
+    /// Times to try a command again when op couldn't reach 1password or was
+    /// rate limited, or only the latter for commands that change something.
+    pub retries: u32,
+    /// Milliseconds to wait before the first retry, doubled for each after
+    /// up to 30 seconds.
+    pub backoff: u64,
+    /// Fail on fields op returns that 1p doesn't know about rather than
+    /// ignoring them, overridden by `--strict-parse`.
+    pub strict_parse: bool,
+}
+
+impl Default for OpConfig {
+    fn default() -> Self {
+        Self {
+            bin: None,
+            args: Vec::new(),
+            account: None,
+            vault: None,
+            cache: None,
+            biometric: None,
+            session: None,
+            reauth: true,
+            timeout: onep_backend_op::DEFAULT_TIMEOUT.as_secs(),
+            retries: onep_backend_op::DEFAULT_RETRIES,
+            #[allow(clippy::cast_possible_truncation)]
+            backoff: onep_backend_op::DEFAULT_BACKOFF.as_millis() as u64,
+            strict_parse: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeepassConfig {
+    pub database: Option<PathBuf>,
+    pub key_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectConfig {
+    pub host: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvConfig {
+    /// Fields written out by `1p env`, mapped to the suffix of the variable
+    /// they're written to.
+    pub fields: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AskpassConfig {
+    /// Item printed by `1p askpass` when nothing more specific matches.
+    pub default: Option<String>,
+    /// Items to use for particular hosts, ssh key paths or `sudo`.
+    pub items: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OtpConfig {
+    /// Items, by uuid or title, whose one-time passwords are Steam Guard
+    /// codes even though their URI doesn't say so.
+    pub steam: Vec<String>,
+    /// Items, by uuid or title, whose one-time password secrets are kept in
+    /// an encrypted cache so codes can be generated without the backend.
+    pub offline: Vec<String>,
+}
+
+impl OtpConfig {
+    pub fn is_steam(&self, uuid: &str, title: &str) -> bool {
+        listed(&self.steam, uuid, title)
+    }
+
+    pub fn is_offline(&self, uuid: &str, title: &str) -> bool {
+        listed(&self.offline, uuid, title)
+    }
+}
+
+fn listed(items: &[String], uuid: &str, title: &str) -> bool {
+    items
+        .iter()
+        .any(|v| v == uuid || v.to_lowercase() == title.to_lowercase())
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Keep an encrypted copy of item metadata to fall back on when the
+    /// backend can't be reached.
+    pub enabled: bool,
+    /// Cache full items, including their secrets, so `show` works offline
+    /// too.
+    pub items: bool,
+    /// Seconds to use the cached item list for before fetching it again,
+    /// making `list` and `search` near instant.
+    pub ttl: Option<u64>,
+}
+
+/// How tables, such as the ones `1p show` prints, are fit to the terminal.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TablesConfig {
+    /// Cut long values short so tables fit the terminal's width rather than
+    /// wrap, overridden by `--full`.
+    pub truncate: bool,
+    /// Put at the end of values that were cut short.
+    pub ellipsis: String,
+}
+
+impl Default for TablesConfig {
+    fn default() -> Self {
+        Self {
+            truncate: true,
+            #[allow(clippy::non_ascii_literal)]
+            ellipsis: "…".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// How long to leave a secret on the clipboard for, or a one-time
+    /// password with `remaining` seconds left before it changes.
+    pub fn clear_clipboard_after(&self, remaining: Option<u64>) -> Duration {
+        let timeout = self
+            .clipboard_timeout
+            .map_or(clipboard::CLEAR_AFTER, Duration::from_secs);
+
+        remaining.map_or(timeout, |v| timeout.min(Duration::from_secs(v)))
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to read config file {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("Failed to parse config file {0}: {1}")]
+    Parse(PathBuf, toml::de::Error),
+}
+
+fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|v| v.join("1p").join("config.toml"))
+}
+
+/// Loads the config file, falling back to the defaults if there isn't one.
+/// A file given by `ONEP_CONFIG` has to exist.
+pub fn load() -> Result<Config, Error> {
+    let (path, required) = match std::env::var_os("ONEP_CONFIG") {
+        Some(path) => (PathBuf::from(path), true),
+        None => match path() {
+            Some(path) => (path, false),
+            None => return Ok(Config::default()),
+        },
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => {
+            return Ok(Config::default())
+        }
+        Err(e) => return Err(Error::Read(path, e)),
+    };
+
+    toml::from_str(&contents).map_err(|e| Error::Parse(path, e))
+}
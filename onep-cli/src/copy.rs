@@ -0,0 +1,164 @@
+//! Copies a single field of an item to the clipboard, without showing the
+//! rest of it.
+
+use crate::{clipboard, config::Config, otp, totp};
+use clap::Clap;
+use onep_backend_api as api;
+use ratatui::crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use std::convert::TryFrom;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0:?} doesn't have a {1:?} field.")]
+    NoField(String, String),
+    #[error("{0:?} doesn't have a field numbered {1}.")]
+    NoNumber(String, usize),
+    #[error("The one-time password of {0:?} is counter based, use 1p copy {0:?} otp to copy it.")]
+    CounterBased(String),
+    #[error("Fields can only be copied when showing a single item.")]
+    SeveralItems,
+    #[error("Pass the number of the field to copy, there's no terminal to pick it on.")]
+    NoTerminal,
+}
+
+#[derive(Clap, Debug)]
+pub struct CopyOpt {
+    /// Item to copy from, by uuid or title
+    item: String,
+    /// Field to copy, either password, username, otp for the current
+    /// one-time password or the name of any other field
+    #[clap(default_value = "password")]
+    field: String,
+}
+
+/// Finds a field by name in the item or any of its sections, with password
+/// and username also matching fields of that type whatever they're called.
+pub fn find_field<'a>(item: &'a api::Item, name: &str) -> Option<&'a api::ItemField> {
+    match name.to_lowercase().as_str() {
+        "password" => item.password(),
+        "username" => item.username(),
+        _ => fields(item).find(|v| v.name.eq_ignore_ascii_case(name)),
+    }
+}
+
+/// The fields of an item and then its sections, in the order `1p show`
+/// numbers them.
+pub fn fields(item: &api::Item) -> impl Iterator<Item = &api::ItemField> {
+    item.fields
+        .iter()
+        .chain(item.sections.iter().flat_map(|v| &v.fields))
+}
+
+/// Copies the field `1p show` numbered `number`, or the current code of a
+/// one-time password.
+pub async fn numbered(config: &Config, item: &api::Item, number: usize) -> anyhow::Result<()> {
+    let field = number
+        .checked_sub(1)
+        .and_then(|v| fields(item).nth(v))
+        .ok_or_else(|| Error::NoNumber(item.title.clone(), number))?;
+
+    if field.field_type == api::ItemFieldType::Totp {
+        // a counter based code would need to be saved as used, which needs
+        // the item's uuid
+        let tfa = otp::TwoFactorAuth::try_from(field.value.as_str())?;
+        if tfa.is_counter_based() {
+            return Err(Error::CounterBased(item.title.clone()).into());
+        }
+
+        let code = tfa.generate();
+        let clear_after = config.clear_clipboard_after(code.remaining);
+        clipboard::copy(&code.value, clear_after).await?;
+    } else {
+        let clear_after = config.clear_clipboard_after(None);
+        clipboard::copy(field.value.as_str(), clear_after).await?;
+    }
+
+    Ok(())
+}
+
+/// Asks for the number of a field on the terminal, taking each key as it's
+/// pressed so that picking from fewer than ten fields is a single key press.
+/// Returns `None` if the user cancels.
+pub fn pick(item: &api::Item) -> anyhow::Result<Option<usize>> {
+    let count = fields(item).count();
+
+    if count == 0 {
+        return Err(Error::NoNumber(item.title.clone(), 1).into());
+    }
+
+    if !atty::is(atty::Stream::Stdin) || !atty::is(atty::Stream::Stderr) {
+        return Err(Error::NoTerminal.into());
+    }
+
+    eprint!("Field to copy (1-{}, esc to cancel): ", count);
+
+    terminal::enable_raw_mode()?;
+    let res = read_number(count);
+    terminal::disable_raw_mode()?;
+    eprintln!();
+
+    Ok(res?)
+}
+
+fn read_number(count: usize) -> std::io::Result<Option<usize>> {
+    let mut number = 0;
+
+    loop {
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Char(c) if c.is_ascii_digit() => {
+                let next = number * 10 + c as usize - '0' as usize;
+
+                if next == 0 || next > count {
+                    continue;
+                }
+
+                number = next;
+                eprint!("{}", c);
+
+                // no more digits could make a field's number
+                if number * 10 > count {
+                    return Ok(Some(number));
+                }
+            }
+            KeyCode::Backspace if number > 0 => {
+                number /= 10;
+                eprint!("\u{8} \u{8}");
+            }
+            KeyCode::Enter if number > 0 => return Ok(Some(number)),
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+pub async fn run(backend: &api::DynBackend, config: &Config, opt: CopyOpt) -> anyhow::Result<()> {
+    let (value, clear_after) = if opt.field.eq_ignore_ascii_case("otp") {
+        let (uuid, item) = totp::get_otp(backend, config, &opt.item).await?;
+        let code = totp::generate(backend, &uuid, &item).await?;
+        let clear_after = config.clear_clipboard_after(code.remaining);
+
+        (code.value, clear_after)
+    } else {
+        let (_, item) = totp::get(backend, config, &opt.item).await?;
+        let field = find_field(&item, &opt.field)
+            .ok_or_else(|| Error::NoField(item.title.clone(), opt.field.clone()))?;
+
+        (
+            field.value.as_str().to_string(),
+            config.clear_clipboard_after(None),
+        )
+    };
+
+    clipboard::copy(&value, clear_after).await?;
+
+    Ok(())
+}
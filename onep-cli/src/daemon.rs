@@ -0,0 +1,105 @@
+//! A long running process that keeps the backend signed in and the item list
+//! in memory, so commands don't each have to wait on a cold backend. Other
+//! invocations of 1p find the daemon's socket and talk to it using the plugin
+//! protocol, falling back to the backend directly when it's not running.
+
+use crate::{backends, cache::Cached, config::Config};
+use clap::Clap;
+use onep_backend_api as api;
+use std::{os::unix::fs::PermissionsExt, path::PathBuf, sync::Arc, time::Duration};
+use tokio::net::UnixListener;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("No runtime directory to create the daemon socket in")]
+    NoSocketPath,
+    #[error("A daemon is already running for this backend")]
+    AlreadyRunning,
+}
+
+#[derive(Clap, Debug)]
+pub struct DaemonOpt {
+    /// Seconds between fetches of the item list from the backend
+    #[clap(long, default_value = "60")]
+    interval: u64,
+}
+
+/// Path the daemon for the named backend listens on.
+pub fn socket_path(backend: &str) -> Option<PathBuf> {
+    dirs::runtime_dir().map(|v| v.join("1p").join(format!("daemon-{}.sock", backend)))
+}
+
+/// Connects to the daemon for the named backend, if one's running.
+pub fn connect(backend: &str) -> Option<Box<api::DynBackend>> {
+    let path = socket_path(backend)?;
+
+    // sockets left behind by a daemon that didn't shut down cleanly can't be
+    // connected to, so this also weeds those out
+    std::os::unix::net::UnixStream::connect(&path).ok()?;
+
+    Some(Box::new(api::Boxed(
+        onep_backend_plugin::PluginBackend::socket(path),
+    )))
+}
+
+pub async fn run(config: &Config, opt: DaemonOpt) -> anyhow::Result<()> {
+    let name = backends::name(config);
+    let socket = socket_path(&name).ok_or(Error::NoSocketPath)?;
+
+    if connect(&name).is_some() {
+        return Err(Error::AlreadyRunning.into());
+    }
+
+    let interval = Duration::from_secs(opt.interval);
+
+    // the item list is refreshed in the background, but is still allowed to
+    // expire in case refreshing starts to fail
+    let backend = Arc::new(Cached::new(
+        backends::open_direct(config, &name, false)?,
+        None,
+        false,
+        Some(interval * 2),
+    ));
+    backend.warm().await?;
+
+    if let Some(parent) = socket.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match std::fs::remove_file(&socket) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    let mut listener = UnixListener::bind(&socket)?;
+    std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))?;
+
+    eprintln!("Listening on {}", socket.display());
+
+    let refresher = backend.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = refresher.warm().await {
+                eprintln!("Failed to refresh items: {}", e);
+            }
+        }
+    });
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let backend = backend.clone();
+
+        tokio::spawn(async move {
+            let (reader, writer) = tokio::io::split(stream);
+
+            if let Err(e) = onep_backend_plugin::serve(&*backend, reader, writer).await {
+                eprintln!("Daemon connection failed: {}", e);
+            }
+        });
+    }
+}
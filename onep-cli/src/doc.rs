@@ -0,0 +1,89 @@
+//! Commands for listing, downloading and uploading documents.
+
+use crate::{config::Config, output::Output, tree, vault, Error};
+use clap::Clap;
+use colored::Colorize;
+use onep_backend_api as api;
+use std::{collections::BTreeMap, io::Write, path::PathBuf};
+
+#[derive(Clap, Debug)]
+pub enum DocOpt {
+    /// List all documents
+    #[clap(alias = "list")]
+    Ls {
+        #[clap(long, short = 'u')]
+        show_uuids: bool,
+    },
+    /// Download a document
+    Get {
+        uuid: String,
+        /// File to write the document to, defaults to stdout
+        #[clap(long, short = 'o', parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Upload a file as a new document
+    Put {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+        /// Title of the document, defaults to the name of the file
+        #[clap(long, short = 't')]
+        title: Option<String>,
+        /// Vault to upload the document to, by name or uuid, defaulting to
+        /// the one in your config
+        #[clap(long)]
+        vault: Option<String>,
+    },
+}
+
+pub async fn run(backend: &api::DynBackend, config: &Config, opt: DocOpt) -> anyhow::Result<()> {
+    match opt {
+        DocOpt::Ls { show_uuids } => {
+            let (account, vaults, documents) =
+                tokio::try_join!(backend.account(), backend.vaults(), backend.documents())?;
+
+            let mut grouped: BTreeMap<_, Vec<_>> = BTreeMap::new();
+            for document in documents {
+                grouped
+                    .entry(document.vault_uuid)
+                    .or_default()
+                    .push(tree::Leaf {
+                        title: document.title,
+                        details: if show_uuids {
+                            vec![document.uuid.color(config.colors.uuid.0)]
+                        } else {
+                            Vec::new()
+                        },
+                    });
+            }
+
+            tree::print(
+                &mut Output::new(config),
+                config,
+                &account,
+                &vaults,
+                grouped.into_iter().collect(),
+            );
+        }
+        DocOpt::Get { uuid, output } => {
+            let contents = backend.get_document(&uuid).await?.ok_or(Error::NotFound)?;
+
+            match output {
+                Some(path) => std::fs::write(path, contents)?,
+                None => std::io::stdout().write_all(&contents)?,
+            }
+        }
+        DocOpt::Put { path, title, vault } => {
+            let vault = match vault.as_ref().or(config.vault.as_ref()) {
+                Some(vault) => Some(vault::find(backend, vault).await?.uuid),
+                None => None,
+            };
+
+            let document = backend
+                .create_document(&path, title.as_deref(), vault.as_deref())
+                .await?;
+            println!("{}", document.uuid);
+        }
+    }
+
+    Ok(())
+}
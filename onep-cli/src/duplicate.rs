@@ -0,0 +1,62 @@
+//! Copies an item into a new one, fields, sections and all, for when the same
+//! kind of credentials are needed again, such as one per environment.
+
+use crate::{config::Config, output::Output, vault};
+use clap::Clap;
+use onep_backend_api as api;
+
+#[derive(Clap, Debug)]
+pub struct DuplicateOpt {
+    /// Item to copy, by uuid or title
+    item: String,
+    /// Vault to create the copy in, by name or uuid, defaulting to the one
+    /// the item is in
+    #[clap(long)]
+    vault: Option<String>,
+    /// Title of the copy, defaulting to the item's own with "(copy)" added
+    /// when it's in the same vault
+    #[clap(long)]
+    title: Option<String>,
+}
+
+pub async fn run(
+    backend: &api::DynBackend,
+    config: &Config,
+    opt: DuplicateOpt,
+) -> anyhow::Result<()> {
+    let (uuid, mut item) = if let Some(item) = backend.get(&opt.item).await? {
+        (opt.item.clone(), item)
+    } else {
+        let uuid = crate::find_uuid(backend, &opt.item).await?;
+        let item = backend.get(&uuid).await?.ok_or(crate::Error::NotFound)?;
+
+        (uuid, item)
+    };
+
+    // the item's category and tags are only in its metadata
+    let metadata = backend
+        .search(None)
+        .await?
+        .into_iter()
+        .find(|v| v.uuid == uuid)
+        .ok_or(crate::Error::NotFound)?;
+
+    let vault = match &opt.vault {
+        Some(vault) => vault::find(backend, vault).await?.uuid,
+        None => metadata.vault_uuid.clone(),
+    };
+
+    item.title = match opt.title {
+        Some(title) => title,
+        None if vault == metadata.vault_uuid => format!("{} (copy)", item.title),
+        None => item.title,
+    };
+
+    let copy = backend
+        .create_item(&item, metadata.category, &metadata.tags, Some(&vault))
+        .await?;
+
+    crate::show(&mut Output::stdout(), config, copy, false, false);
+
+    Ok(())
+}
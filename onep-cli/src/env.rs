@@ -0,0 +1,110 @@
+//! Writes the fields of tagged items out as `KEY=value` lines, for generating
+//! `.env` files.
+
+use crate::{config::Config, shell};
+use clap::Clap;
+use onep_backend_api as api;
+use std::collections::BTreeMap;
+
+#[derive(Clap, Debug)]
+pub struct EnvOpt {
+    /// Only include items with this tag
+    #[clap(long, short = 't')]
+    tag: String,
+    /// Map a field to a variable suffix, such as password=PASS, replacing the
+    /// mapping from your config
+    #[clap(long, short = 'm', number_of_values = 1)]
+    map: Vec<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid field mapping {0}, expected field=SUFFIX")]
+    InvalidMapping(String),
+    #[error("Both {1} and {2} would set {0}, rename one of the items or map the fields to different suffixes")]
+    Collision(String, String, String),
+}
+
+/// Fields exported when neither the config nor the command line give a
+/// mapping.
+const DEFAULT_MAPPING: &[(&str, &str)] = &[("username", "USERNAME"), ("password", "PASSWORD")];
+
+fn mapping(config: &Config, opt: &EnvOpt) -> Result<BTreeMap<String, String>, Error> {
+    if !opt.map.is_empty() {
+        return opt
+            .map
+            .iter()
+            .map(|v| {
+                let mut parts = v.splitn(2, '=');
+
+                match (parts.next(), parts.next()) {
+                    (Some(field), Some(suffix)) if !field.is_empty() && !suffix.is_empty() => {
+                        Ok((field.to_lowercase(), suffix.to_string()))
+                    }
+                    _ => Err(Error::InvalidMapping(v.clone())),
+                }
+            })
+            .collect();
+    }
+
+    if !config.env.fields.is_empty() {
+        return Ok(config
+            .env
+            .fields
+            .iter()
+            .map(|(field, suffix)| (field.to_lowercase(), suffix.clone()))
+            .collect());
+    }
+
+    Ok(DEFAULT_MAPPING
+        .iter()
+        .map(|(field, suffix)| ((*field).to_string(), (*suffix).to_string()))
+        .collect())
+}
+
+pub async fn run(backend: &api::DynBackend, config: &Config, opt: EnvOpt) -> anyhow::Result<()> {
+    let mapping = mapping(config, &opt)?;
+
+    let mut filter = api::Filter::default();
+    filter.tags = std::slice::from_ref(&opt.tag);
+
+    let items = backend.search_filtered(None, filter).await?;
+    let uuids: Vec<_> = items.iter().map(|v| v.uuid.clone()).collect();
+
+    // variables by name, along with the item and field each came from
+    let mut variables: BTreeMap<String, (String, String)> = BTreeMap::new();
+
+    for (metadata, item) in items.iter().zip(backend.get_many(&uuids).await?) {
+        let item = match item {
+            Some(item) => item,
+            None => continue,
+        };
+
+        let fields = item
+            .fields
+            .iter()
+            .chain(item.sections.iter().flat_map(|v| &v.fields));
+
+        for field in fields {
+            let suffix = match mapping.get(&field.name.to_lowercase()) {
+                Some(suffix) => suffix,
+                None => continue,
+            };
+
+            let name = format!("{}_{}", shell::variable_name(&metadata.title), suffix);
+            let source = format!("{:?} ({})", metadata.title, field.name);
+
+            if let Some((other, _)) = variables.get(&name) {
+                return Err(Error::Collision(name, other.clone(), source).into());
+            }
+
+            variables.insert(name, (source, shell::quote(&field.value).into_owned()));
+        }
+    }
+
+    for (name, (_, value)) in variables {
+        println!("{}={}", name, value);
+    }
+
+    Ok(())
+}
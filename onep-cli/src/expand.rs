@@ -0,0 +1,118 @@
+//! Rewrites the command line before clap parses it, expanding aliases and
+//! adding default flags from the config.
+
+use crate::config::Config;
+use clap::{App, ArgSettings};
+use std::ffi::OsString;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Alias {0:?} expands back to itself.")]
+    AliasLoop(String),
+}
+
+/// Finds the subcommand, which is the first argument that isn't a flag or the
+/// value of one, such as the token in `--session <token>`.
+fn subcommand(app: &App<'_>, args: &[OsString]) -> Option<usize> {
+    let mut args = args.iter().enumerate().skip(1);
+
+    while let Some((position, arg)) = args.next() {
+        let arg = arg.to_string_lossy();
+
+        if !arg.starts_with('-') {
+            return Some(position);
+        }
+
+        if takes_value(app, &arg) {
+            args.next();
+        }
+    }
+
+    None
+}
+
+/// Whether `flag` is followed by its value as a separate argument, rather than
+/// one given as `--flag=value` or `-fvalue`.
+fn takes_value(app: &App<'_>, flag: &str) -> bool {
+    let arg = if let Some(long) = flag.strip_prefix("--") {
+        app.get_arguments().find(|v| v.get_long() == Some(long))
+    } else {
+        let mut shorts = flag.chars().skip(1);
+
+        match (shorts.next(), shorts.next()) {
+            (Some(short), None) => app.get_arguments().find(|v| v.get_short() == Some(short)),
+            _ => None,
+        }
+    };
+
+    arg.map_or(false, |v| v.is_set(ArgSettings::TakesValue))
+}
+
+/// Replaces an alias with what it stands for, keeping any arguments after it,
+/// until the subcommand is a real one. Aliases can't override subcommands.
+pub fn aliases(
+    config: &Config,
+    app: &App<'_>,
+    mut args: Vec<OsString>,
+) -> Result<Vec<OsString>, Error> {
+    let mut seen = Vec::new();
+
+    while let Some(position) = subcommand(app, &args) {
+        let name = args[position].to_string_lossy().into_owned();
+
+        if app.find_subcommand(name.as_str()).is_some() {
+            break;
+        }
+
+        let alias = match config.alias.get(&name) {
+            Some(alias) => alias.args(),
+            None => break,
+        };
+
+        if seen.contains(&name) {
+            return Err(Error::AliasLoop(name));
+        }
+
+        args.splice(position..=position, alias.into_iter().map(OsString::from));
+        seen.push(name);
+    }
+
+    Ok(args)
+}
+
+/// Adds the flags configured for the subcommand being run straight after it,
+/// leaving out any also given on the command line. Flags taking a value are
+/// configured as `--flag=value` so they can be told apart.
+pub fn defaults(config: &Config, app: &App<'_>, mut args: Vec<OsString>) -> Vec<OsString> {
+    let position = match subcommand(app, &args) {
+        Some(position) => position,
+        None => return args,
+    };
+
+    let defaults = app
+        .find_subcommand(args[position].to_string_lossy().as_ref())
+        .and_then(|v| config.defaults.get(v.get_name()));
+
+    if let Some(defaults) = defaults {
+        let given: Vec<_> = args[position + 1..]
+            .iter()
+            .map(|v| v.to_string_lossy().into_owned())
+            .collect();
+        let defaults: Vec<_> = defaults
+            .iter()
+            .filter(|flag| {
+                let name = flag.split('=').next().unwrap_or(flag);
+                !given
+                    .iter()
+                    .any(|v| v == name || v.starts_with(&format!("{}=", name)))
+            })
+            .map(OsString::from)
+            .collect();
+
+        let rest = args.split_off(position + 1);
+        args.extend(defaults);
+        args.extend(rest);
+    }
+
+    args
+}
@@ -0,0 +1,360 @@
+//! Exports every item, with its secrets in plain text, for backups or
+//! migrating to another password manager.
+
+use crate::{private, vault};
+use clap::Clap;
+use onep_backend_api as api;
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Write},
+    path::PathBuf,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clap, Debug)]
+pub struct ExportOpt {
+    /// Format to export to, one of csv, json or 1pux
+    #[clap(long, short = 'f', default_value = "json")]
+    format: Format,
+    /// Only export items in this vault, by name or uuid
+    #[clap(long)]
+    vault: Option<String>,
+    /// File to write the export to, defaults to stdout
+    #[clap(long, short = 'o', parse(from_os_str))]
+    output: Option<PathBuf>,
+    /// Don't ask for confirmation
+    #[clap(long, short = 'y')]
+    yes: bool,
+}
+
+#[derive(Debug)]
+enum Format {
+    Csv,
+    Json,
+    /// The 1Password Unencrypted Export format, a zip file containing every
+    /// account, vault and item as JSON.
+    OnePux,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "1pux" => Ok(Self::OnePux),
+            _ => Err(format!("unknown export format {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedItem<'a> {
+    uuid: &'a str,
+    vault_uuid: &'a str,
+    title: &'a str,
+    tags: &'a [String],
+    favorite: bool,
+    archived: bool,
+    fields: Vec<ExportedField<'a>>,
+    sections: Vec<ExportedSection<'a>>,
+    history: Vec<ExportedHistory<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedSection<'a> {
+    name: &'a str,
+    fields: Vec<ExportedField<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedField<'a> {
+    name: &'a str,
+    value: &'a str,
+    #[serde(rename = "type")]
+    field_type: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedHistory<'a> {
+    value: &'a str,
+    /// Seconds since the unix epoch.
+    time: Option<u64>,
+}
+
+fn unix_time(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|v| v.as_secs())
+}
+
+fn export_fields(fields: &[api::ItemField]) -> Vec<ExportedField<'_>> {
+    fields
+        .iter()
+        .map(|v| ExportedField {
+            name: &v.name,
+            value: &v.value,
+            field_type: match v.field_type {
+                api::ItemFieldType::Totp => "totp",
+                api::ItemFieldType::SshKey => "sshkey",
+                api::ItemFieldType::Password => "password",
+                api::ItemFieldType::Username => "username",
+                api::ItemFieldType::Email => "email",
+                api::ItemFieldType::Url => "url",
+                api::ItemFieldType::Date => "date",
+                api::ItemFieldType::Phone => "phone",
+                api::ItemFieldType::Address => "address",
+                api::ItemFieldType::Concealed => "concealed",
+                api::ItemFieldType::Note => "note",
+                api::ItemFieldType::Unknown => "unknown",
+            },
+        })
+        .collect()
+}
+
+fn field<'a>(item: &'a api::Item, name: &str) -> &'a str {
+    item.fields
+        .iter()
+        .find(|v| v.name.eq_ignore_ascii_case(name))
+        .map_or("", |v| v.value.as_str())
+}
+
+fn value(field: Option<&api::ItemField>) -> &str {
+    field.map_or("", |v| v.value.as_str())
+}
+
+fn totp(item: &api::Item) -> &str {
+    item.fields
+        .iter()
+        .chain(item.sections.iter().flat_map(|v| &v.fields))
+        .find(|v| v.field_type == api::ItemFieldType::Totp)
+        .map_or("", |v| v.value.as_str())
+}
+
+fn to_json(items: &[(api::ItemMetadata, api::Item)]) -> anyhow::Result<Vec<u8>> {
+    let items: Vec<_> = items
+        .iter()
+        .map(|(metadata, item)| ExportedItem {
+            uuid: &metadata.uuid,
+            vault_uuid: &metadata.vault_uuid,
+            title: &metadata.title,
+            tags: &metadata.tags,
+            favorite: metadata.favorite,
+            archived: metadata.archived,
+            fields: export_fields(&item.fields),
+            sections: item
+                .sections
+                .iter()
+                .map(|v| ExportedSection {
+                    name: &v.name,
+                    fields: export_fields(&v.fields),
+                })
+                .collect(),
+            history: item
+                .history
+                .iter()
+                .map(|v| ExportedHistory {
+                    value: &v.value,
+                    time: v.time.and_then(unix_time),
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(serde_json::to_vec_pretty(&items)?)
+}
+
+fn to_csv(
+    items: &[(api::ItemMetadata, api::Item)],
+    vaults: &[api::VaultMetadata],
+) -> anyhow::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&[
+        "title", "vault", "username", "password", "url", "notes", "tags", "totp",
+    ])?;
+
+    for (metadata, item) in items {
+        let vault = vaults
+            .iter()
+            .find(|v| v.uuid == metadata.vault_uuid)
+            .map_or("", |v| v.name.as_str());
+
+        writer.write_record(&[
+            metadata.title.as_str(),
+            vault,
+            value(item.username()),
+            value(item.password()),
+            field(item, "url"),
+            field(item, "notes"),
+            &metadata.tags.join(","),
+            totp(item),
+        ])?;
+    }
+
+    Ok(writer.into_inner()?)
+}
+
+/// 1Password's uuid for the template of each category, with anything it
+/// wouldn't know exported as a login.
+fn category_uuid(category: api::ItemCategory) -> &'static str {
+    match category {
+        api::ItemCategory::Login | api::ItemCategory::Unknown => "001",
+        api::ItemCategory::CreditCard => "002",
+        api::ItemCategory::SecureNote => "003",
+        api::ItemCategory::Identity => "004",
+        api::ItemCategory::Password => "005",
+        api::ItemCategory::Document => "006",
+        api::ItemCategory::SoftwareLicense => "100",
+        api::ItemCategory::BankAccount => "101",
+        api::ItemCategory::Database => "102",
+        api::ItemCategory::WirelessRouter => "109",
+        api::ItemCategory::Server => "110",
+        api::ItemCategory::ApiCredential => "112",
+        api::ItemCategory::SshKey => "114",
+    }
+}
+
+fn to_1pux(
+    items: &[(api::ItemMetadata, api::Item)],
+    account: &api::AccountMetadata,
+    vaults: &[api::VaultMetadata],
+) -> anyhow::Result<Vec<u8>> {
+    let mut grouped: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+
+    for (metadata, item) in items {
+        let sections: Vec<_> = item
+            .sections
+            .iter()
+            .map(|section| {
+                let fields: Vec<_> = section
+                    .fields
+                    .iter()
+                    .map(|v| {
+                        let value = match v.field_type {
+                            api::ItemFieldType::Totp => json!({ "totp": v.value.as_str() }),
+                            api::ItemFieldType::SshKey => {
+                                json!({ "sshKey": { "privateKey": v.value.as_str() } })
+                            }
+                            api::ItemFieldType::Password | api::ItemFieldType::Concealed => {
+                                json!({ "concealed": v.value.as_str() })
+                            }
+                            api::ItemFieldType::Email => json!({ "email": v.value.as_str() }),
+                            api::ItemFieldType::Url => json!({ "url": v.value.as_str() }),
+                            api::ItemFieldType::Phone => json!({ "phone": v.value.as_str() }),
+                            _ => json!({ "string": v.value.as_str() }),
+                        };
+
+                        json!({ "title": v.name, "id": v.name, "value": value })
+                    })
+                    .collect();
+
+                json!({ "title": section.name, "name": section.name, "fields": fields })
+            })
+            .collect();
+
+        let history: Vec<_> = item
+            .history
+            .iter()
+            .map(|v| json!({ "value": v.value.as_str(), "time": v.time.and_then(unix_time) }))
+            .collect();
+
+        grouped
+            .entry(&metadata.vault_uuid)
+            .or_default()
+            .push(json!({
+                "uuid": metadata.uuid,
+                "favIndex": u8::from(metadata.favorite),
+                "state": if metadata.archived { "archived" } else { "active" },
+                "categoryUuid": category_uuid(metadata.category),
+                "details": {
+                    "loginFields": [
+                        {
+                            "value": value(item.username()),
+                            "name": "username",
+                            "fieldType": "T",
+                            "designation": "username",
+                        },
+                        {
+                            "value": value(item.password()),
+                            "name": "password",
+                            "fieldType": "P",
+                            "designation": "password",
+                        },
+                    ],
+                    "notesPlain": field(item, "notes"),
+                    "sections": sections,
+                    "passwordHistory": history,
+                },
+                "overview": {
+                    "title": metadata.title,
+                    "tags": metadata.tags,
+                    "url": field(item, "url"),
+                },
+            }));
+    }
+
+    let data = json!({
+        "accounts": [{
+            "attrs": { "accountName": account.name, "domain": account.domain },
+            "vaults": grouped
+                .into_iter()
+                .map(|(uuid, items)| {
+                    let name = vaults.iter().find(|v| v.uuid == uuid).map_or(uuid, |v| &v.name);
+                    json!({ "attrs": { "uuid": uuid, "name": name }, "items": items })
+                })
+                .collect::<Vec<_>>(),
+        }],
+    });
+
+    let attributes = json!({
+        "version": 3,
+        "description": "1Password Unencrypted Export",
+        "createdAt": unix_time(SystemTime::now()),
+    });
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("export.attributes", zip::write::FileOptions::default())?;
+    zip.write_all(&serde_json::to_vec(&attributes)?)?;
+    zip.start_file("export.data", zip::write::FileOptions::default())?;
+    zip.write_all(&serde_json::to_vec(&data)?)?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+pub async fn run(backend: &api::DynBackend, opt: ExportOpt) -> anyhow::Result<()> {
+    eprintln!(
+        "The export will contain your passwords in plain text, keep it somewhere safe and \
+         delete it once you're done with it."
+    );
+
+    if !opt.yes && !vault::confirm("Export every item?")? {
+        return Ok(());
+    }
+
+    let vault = match &opt.vault {
+        Some(vault) => Some(vault::find(backend, vault).await?),
+        None => None,
+    };
+
+    let (account, vaults, mut items) =
+        tokio::try_join!(backend.account(), backend.vaults(), backend.get_all())?;
+
+    if let Some(vault) = vault {
+        items.retain(|(metadata, _)| metadata.vault_uuid == vault.uuid);
+    }
+
+    let contents = match opt.format {
+        Format::Csv => to_csv(&items, &vaults)?,
+        Format::Json => to_json(&items)?,
+        Format::OnePux => to_1pux(&items, &account, &vaults)?,
+    };
+
+    match opt.output {
+        Some(path) => private::write(path, contents)?,
+        None => std::io::stdout().write_all(&contents)?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,242 @@
+//! Implements git's [credential helper protocol], allowing 1p to be used with
+//! `credential.helper = !1p git-credential`.
+//!
+//! [credential helper protocol]: https://git-scm.com/docs/git-credential
+
+use onep_backend_api as api;
+use std::{collections::HashMap, io::BufRead, str::FromStr};
+use url::Url;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("The item's {0} has a line break or NUL in it, which git would misread")]
+    UnsafeValue(&'static str),
+}
+
+#[derive(Debug)]
+pub enum Operation {
+    Get,
+    Store,
+    Erase,
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "get" => Ok(Self::Get),
+            "store" => Ok(Self::Store),
+            "erase" => Ok(Self::Erase),
+            _ => Err(format!("unknown credential operation {}", s)),
+        }
+    }
+}
+
+/// Reads the `key=value` attributes git sends, which are terminated by a
+/// blank line or the end of input.
+fn read_attributes() -> std::io::Result<HashMap<String, String>> {
+    let mut attributes = HashMap::new();
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+
+        if line.is_empty() {
+            break;
+        }
+
+        let mut parts = line.splitn(2, '=');
+        if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+            attributes.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    Ok(attributes)
+}
+
+/// Whether `url` is for exactly `host`, which includes the port when it isn't
+/// the default, and `protocol` when git sends one. URLs saved without a scheme
+/// match any protocol.
+fn is_for(url: &str, host: &str, protocol: Option<&str>) -> bool {
+    let (url, has_scheme) = match Url::parse(url) {
+        Ok(url) if url.has_host() => (url, true),
+        _ => match Url::parse(&format!("https://{}", url)) {
+            Ok(url) => (url, false),
+            Err(_) => return false,
+        },
+    };
+
+    let authority = match (url.host_str(), url.port()) {
+        (Some(name), Some(port)) => format!("{}:{}", name, port),
+        (Some(name), None) => name.to_string(),
+        (None, _) => return false,
+    };
+
+    authority.eq_ignore_ascii_case(host)
+        && (!has_scheme || protocol.map_or(true, |v| url.scheme() == v))
+}
+
+/// Finds a login with a URL for the host, and the requested username if git
+/// already knows it, as otherwise there's no login for that user. Searching matches any part of an item, so
+/// the results are narrowed down to those for exactly the same host, to keep
+/// the password for `example.com` from being sent to `example.com.evil.net`.
+async fn find(
+    backend: &api::DynBackend,
+    host: &str,
+    protocol: Option<&str>,
+    username: Option<&str>,
+) -> anyhow::Result<Option<api::Item>> {
+    let mut found = Vec::new();
+
+    for metadata in backend.search(Some(host)).await? {
+        // not every backend lists URLs with the rest of the metadata, leaving
+        // them to be checked once the item's been fetched
+        if !metadata.urls.is_empty() && !metadata.urls.iter().any(|v| is_for(v, host, protocol)) {
+            continue;
+        }
+
+        if let Some(item) = backend.get(&metadata.uuid).await? {
+            if item.urls.iter().any(|v| is_for(&v.url, host, protocol)) {
+                found.push((metadata, item));
+            }
+        }
+    }
+
+    let idx = match username {
+        Some(username) => found.iter().position(|(v, _)| v.account_info == username),
+        None if !found.is_empty() => Some(0),
+        None => None,
+    };
+
+    Ok(idx.map(|idx| found.swap_remove(idx).1))
+}
+
+pub async fn run(backend: &api::DynBackend, operation: Operation) -> anyhow::Result<()> {
+    let attributes = read_attributes()?;
+
+    let host = match attributes.get("host") {
+        Some(host) => host,
+        None => return Ok(()),
+    };
+    let protocol = attributes.get("protocol").map(String::as_str);
+    let username = attributes.get("username").map(String::as_str);
+
+    match operation {
+        Operation::Get => {
+            let item = find(backend, host, protocol, username).await?;
+
+            // printing nothing tells git to carry on to the next helper, or
+            // prompt for the credentials itself
+            if let Some(item) = item {
+                let username = item.username().map(|v| v.value.as_str()).or(username);
+                let password = item.password().map(|v| v.value.as_str());
+
+                // the protocol is one attribute per line, so a value with a
+                // line break in it could add attributes of its own
+                for (name, value) in &[("username", username), ("password", password)] {
+                    if value.map_or(false, |v| v.contains('\n') || v.contains('\0')) {
+                        return Err(Error::UnsafeValue(name).into());
+                    }
+                }
+
+                if let Some(username) = username {
+                    println!("username={}", username);
+                }
+
+                if let Some(password) = password {
+                    println!("password={}", password);
+                }
+            }
+        }
+        Operation::Store => {
+            let password = match attributes.get("password") {
+                Some(password) => password,
+                None => return Ok(()),
+            };
+
+            if find(backend, host, protocol, username).await?.is_none() {
+                let url = match protocol {
+                    Some(protocol) => format!("{}://{}", protocol, host),
+                    None => host.clone(),
+                };
+
+                backend
+                    .create(host, username, Some(&url), None, None, password)
+                    .await?;
+            }
+        }
+        // git asks for credentials to be erased whenever they're rejected,
+        // which is as likely to be a typo or an expired token as anything
+        // else, so logins are never deleted automatically.
+        Operation::Erase => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_for;
+
+    #[test]
+    fn same_host() {
+        assert!(is_for("https://example.com", "example.com", Some("https")));
+        assert!(is_for("https://Example.com/login", "example.com", None));
+        assert!(is_for(
+            "https://example.com:8443",
+            "example.com:8443",
+            Some("https")
+        ));
+    }
+
+    #[test]
+    fn other_hosts() {
+        assert!(!is_for(
+            "https://example.com.evil.net",
+            "example.com",
+            Some("https")
+        ));
+        assert!(!is_for(
+            "https://evil.net/example.com",
+            "example.com",
+            Some("https")
+        ));
+        assert!(!is_for(
+            "https://sub.example.com",
+            "example.com",
+            Some("https")
+        ));
+    }
+
+    #[test]
+    fn other_ports() {
+        assert!(!is_for(
+            "https://example.com:8443",
+            "example.com",
+            Some("https")
+        ));
+        assert!(!is_for(
+            "https://example.com",
+            "example.com:8443",
+            Some("https")
+        ));
+    }
+
+    #[test]
+    fn other_schemes() {
+        assert!(!is_for("http://example.com", "example.com", Some("https")));
+        assert!(is_for("http://example.com", "example.com", None));
+    }
+
+    #[test]
+    fn without_a_scheme() {
+        assert!(is_for("example.com", "example.com", Some("https")));
+        assert!(is_for("example.com:8443", "example.com:8443", Some("http")));
+        assert!(!is_for(
+            "example.com.evil.net",
+            "example.com",
+            Some("https")
+        ));
+        assert!(!is_for("example.com:8443", "example.com", Some("https")));
+    }
+}
@@ -0,0 +1,385 @@
+//! The gRPC side of `1p serve`, for services written in other languages that
+//! want typed access to items and search results streamed as the backend
+//! finds them. The service is defined in `proto/onep.proto`, and like the
+//! HTTP server it only reads.
+
+use crate::{serve::State, totp};
+use futures::StreamExt;
+use onep_backend_api as api;
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc;
+use tonic::{Request, Response, Status};
+
+#[allow(clippy::pedantic)]
+mod proto {
+    tonic::include_proto!("onep");
+}
+
+use proto::backend_server::{Backend, BackendServer};
+
+/// How many search results can be waiting to be sent before the backend is
+/// made to wait for the client to catch up.
+const SEARCH_BUFFER: usize = 64;
+
+pub async fn run(state: Arc<State>, listen: SocketAddr) -> anyhow::Result<()> {
+    let auth = state.clone();
+    let service = BackendServer::with_interceptor(Service(state), move |req: Request<()>| {
+        let given = req
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok());
+
+        if auth.authorised(given) {
+            Ok(req)
+        } else {
+            eprintln!("rejected call without the bearer token");
+            Err(Status::unauthenticated("missing or incorrect bearer token"))
+        }
+    });
+
+    eprintln!("Listening for gRPC on {}", listen);
+
+    tonic::transport::Server::builder()
+        .add_service(service)
+        .serve(listen)
+        .await?;
+
+    Ok(())
+}
+
+struct Service(Arc<State>);
+
+/// Logs a call the same way the HTTP server logs requests.
+fn log<T>(method: &str, res: Result<T, Status>) -> Result<Response<T>, Status> {
+    let code = res.as_ref().err().map_or(tonic::Code::Ok, Status::code);
+    eprintln!("{} {:?}", method, code);
+
+    res.map(Response::new)
+}
+
+fn status(e: anyhow::Error) -> Status {
+    match e.downcast::<api::BackendError>() {
+        Ok(e) => backend_status(&e),
+        Err(e) if e.downcast_ref::<crate::Error>().is_some() => Status::not_found(e.to_string()),
+        Err(e) if matches!(e.downcast_ref(), Some(totp::Error::NoTotp(_))) => {
+            Status::not_found(e.to_string())
+        }
+        Err(e) => Status::internal(e.to_string()),
+    }
+}
+
+/// The status closest to what went wrong in the backend.
+fn backend_status(e: &api::BackendError) -> Status {
+    let message = e.to_string();
+
+    match e {
+        api::BackendError::NotFound(_) => Status::not_found(message),
+        api::BackendError::AuthRequired(_) => Status::unauthenticated(message),
+        api::BackendError::PermissionDenied(_) => Status::permission_denied(message),
+        api::BackendError::Network(_) => Status::unavailable(message),
+        api::BackendError::Unsupported(_) => Status::unimplemented(message),
+        _ => Status::internal(message),
+    }
+}
+
+fn not_found() -> Status {
+    Status::not_found(crate::Error::NotFound.to_string())
+}
+
+#[tonic::async_trait]
+impl Backend for Service {
+    type SearchStream = mpsc::Receiver<Result<proto::ItemMetadata, Status>>;
+
+    async fn get_capabilities(
+        &self,
+        _req: Request<proto::Empty>,
+    ) -> Result<Response<proto::Capabilities>, Status> {
+        let res = self.0.backend.capabilities().await;
+        log(
+            "GetCapabilities",
+            res.map(Into::into).map_err(|e| backend_status(&e)),
+        )
+    }
+
+    async fn get_account(
+        &self,
+        _req: Request<proto::Empty>,
+    ) -> Result<Response<proto::Account>, Status> {
+        let res = self.0.backend.account().await;
+        log(
+            "GetAccount",
+            res.map(Into::into).map_err(|e| backend_status(&e)),
+        )
+    }
+
+    async fn list_vaults(
+        &self,
+        _req: Request<proto::Empty>,
+    ) -> Result<Response<proto::Vaults>, Status> {
+        let res = self.0.backend.vaults().await.map(|vaults| proto::Vaults {
+            vaults: vaults.into_iter().map(Into::into).collect(),
+        });
+        log("ListVaults", res.map_err(|e| backend_status(&e)))
+    }
+
+    async fn search(
+        &self,
+        req: Request<proto::SearchRequest>,
+    ) -> Result<Response<Self::SearchStream>, Status> {
+        let terms = Some(req.into_inner().terms).filter(|v| !v.is_empty());
+        let (mut tx, rx) = mpsc::channel(SEARCH_BUFFER);
+        let state = self.0.clone();
+
+        // the backend's stream borrows it, so it's driven from a task of its
+        // own holding on to the state rather than handed to tonic directly
+        tokio::spawn(async move {
+            let mut items = state.backend.search_stream(terms.as_deref());
+
+            while let Some(item) = items.next().await {
+                let failed = item.is_err();
+
+                if tx
+                    .send(item.map(Into::into).map_err(|e| backend_status(&e)))
+                    .await
+                    .is_err()
+                    || failed
+                {
+                    break;
+                }
+            }
+        });
+
+        log("Search", Ok(rx))
+    }
+
+    async fn get(&self, req: Request<proto::GetRequest>) -> Result<Response<proto::Item>, Status> {
+        let uuid = req.into_inner().uuid;
+        let res = match self.0.backend.get(&uuid).await {
+            Ok(Some(item)) => Ok(item.into()),
+            Ok(None) => Err(not_found()),
+            Err(e) => Err(backend_status(&e)),
+        };
+        log("Get", res)
+    }
+
+    async fn totp(&self, req: Request<proto::GetRequest>) -> Result<Response<proto::Code>, Status> {
+        let res = async {
+            let (uuid, item) =
+                totp::get_otp(&*self.0.backend, &self.0.config, &req.get_ref().uuid).await?;
+            totp::generate(&*self.0.backend, &uuid, &item).await
+        }
+        .await
+        .map(|code| proto::Code {
+            code: code.value,
+            remaining: code.remaining.unwrap_or_default(),
+        });
+        log("Totp", res.map_err(status))
+    }
+
+    async fn list_documents(
+        &self,
+        _req: Request<proto::Empty>,
+    ) -> Result<Response<proto::Documents>, Status> {
+        let res = self
+            .0
+            .backend
+            .documents()
+            .await
+            .map(|documents| proto::Documents {
+                documents: documents.into_iter().map(Into::into).collect(),
+            });
+        log("ListDocuments", res.map_err(|e| backend_status(&e)))
+    }
+
+    async fn get_document(
+        &self,
+        req: Request<proto::GetRequest>,
+    ) -> Result<Response<proto::Document>, Status> {
+        let uuid = req.into_inner().uuid;
+        let res = match self.0.backend.get_document(&uuid).await {
+            Ok(Some(content)) => Ok(proto::Document { content }),
+            Ok(None) => Err(not_found()),
+            Err(e) => Err(backend_status(&e)),
+        };
+        log("GetDocument", res)
+    }
+
+    async fn get_attachment(
+        &self,
+        req: Request<proto::GetAttachmentRequest>,
+    ) -> Result<Response<proto::Document>, Status> {
+        let req = req.into_inner();
+        let res = match self
+            .0
+            .backend
+            .get_attachment(&req.item_uuid, &req.attachment_uuid)
+            .await
+        {
+            Ok(Some(content)) => Ok(proto::Document { content }),
+            Ok(None) => Err(not_found()),
+            Err(e) => Err(backend_status(&e)),
+        };
+        log("GetAttachment", res)
+    }
+}
+
+/// Seconds since the unix epoch, or 0 for times the backend doesn't know.
+fn seconds(time: Option<SystemTime>) -> u64 {
+    time.and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |v| v.as_secs())
+}
+
+impl From<api::Capabilities> for proto::Capabilities {
+    fn from(v: api::Capabilities) -> Self {
+        Self {
+            supports_totp: v.supports_totp,
+            supports_write: v.supports_write,
+            supports_documents: v.supports_documents,
+            supports_multiple_accounts: v.supports_multiple_accounts,
+            offline: v.offline,
+        }
+    }
+}
+
+impl From<api::AccountMetadata> for proto::Account {
+    fn from(v: api::AccountMetadata) -> Self {
+        Self {
+            name: v.name,
+            domain: v.domain,
+            service_account: v.service_account,
+        }
+    }
+}
+
+impl From<api::VaultMetadata> for proto::Vault {
+    fn from(v: api::VaultMetadata) -> Self {
+        Self {
+            uuid: v.uuid,
+            name: v.name,
+        }
+    }
+}
+
+impl From<api::DocumentMetadata> for proto::DocumentMetadata {
+    fn from(v: api::DocumentMetadata) -> Self {
+        Self {
+            uuid: v.uuid,
+            vault_uuid: v.vault_uuid,
+            title: v.title,
+        }
+    }
+}
+
+impl From<api::ItemCategory> for proto::Category {
+    fn from(v: api::ItemCategory) -> Self {
+        match v {
+            api::ItemCategory::Login => Self::Login,
+            api::ItemCategory::Password => Self::Password,
+            api::ItemCategory::SecureNote => Self::SecureNote,
+            api::ItemCategory::CreditCard => Self::CreditCard,
+            api::ItemCategory::Identity => Self::Identity,
+            api::ItemCategory::Document => Self::Document,
+            api::ItemCategory::BankAccount => Self::BankAccount,
+            api::ItemCategory::ApiCredential => Self::ApiCredential,
+            api::ItemCategory::Database => Self::Database,
+            api::ItemCategory::Server => Self::Server,
+            api::ItemCategory::SshKey => Self::SshKey,
+            api::ItemCategory::SoftwareLicense => Self::SoftwareLicense,
+            api::ItemCategory::WirelessRouter => Self::WirelessRouter,
+            api::ItemCategory::Unknown => Self::Unknown,
+        }
+    }
+}
+
+impl From<api::ItemMetadata> for proto::ItemMetadata {
+    fn from(v: api::ItemMetadata) -> Self {
+        Self {
+            created: seconds(v.created),
+            updated: seconds(v.updated),
+            category: proto::Category::from(v.category) as i32,
+            uuid: v.uuid,
+            vault_uuid: v.vault_uuid,
+            title: v.title,
+            account_info: v.account_info,
+            urls: v.urls,
+            tags: v.tags,
+            favorite: v.favorite,
+            archived: v.archived,
+        }
+    }
+}
+
+impl From<api::ItemFieldType> for proto::FieldType {
+    fn from(v: api::ItemFieldType) -> Self {
+        match v {
+            api::ItemFieldType::Totp => Self::Totp,
+            api::ItemFieldType::SshKey => Self::SshKey,
+            api::ItemFieldType::Password => Self::Password,
+            api::ItemFieldType::Username => Self::Username,
+            api::ItemFieldType::Email => Self::Email,
+            api::ItemFieldType::Url => Self::Url,
+            api::ItemFieldType::Date => Self::Date,
+            api::ItemFieldType::Phone => Self::Phone,
+            api::ItemFieldType::Address => Self::Address,
+            api::ItemFieldType::Concealed => Self::Concealed,
+            api::ItemFieldType::Note => Self::Note,
+            api::ItemFieldType::Unknown => Self::Unknown,
+        }
+    }
+}
+
+impl From<api::ItemField> for proto::Field {
+    fn from(v: api::ItemField) -> Self {
+        Self {
+            r#type: proto::FieldType::from(v.field_type) as i32,
+            value: v.value.as_str().to_string(),
+            name: v.name,
+        }
+    }
+}
+
+impl From<api::Item> for proto::Item {
+    fn from(v: api::Item) -> Self {
+        Self {
+            title: v.title,
+            fields: v.fields.into_iter().map(Into::into).collect(),
+            sections: v
+                .sections
+                .into_iter()
+                .map(|section| proto::Section {
+                    name: section.name,
+                    fields: section.fields.into_iter().map(Into::into).collect(),
+                })
+                .collect(),
+            urls: v
+                .urls
+                .into_iter()
+                .map(|url| proto::Url {
+                    label: url.label,
+                    url: url.url,
+                })
+                .collect(),
+            attachments: v
+                .attachments
+                .into_iter()
+                .map(|attachment| proto::Attachment {
+                    uuid: attachment.uuid,
+                    name: attachment.name,
+                    size: attachment.size,
+                })
+                .collect(),
+            history: v
+                .history
+                .into_iter()
+                .map(|value| proto::HistoricalValue {
+                    time: seconds(value.time),
+                    value: value.value.as_str().to_string(),
+                })
+                .collect(),
+        }
+    }
+}
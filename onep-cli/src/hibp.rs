@@ -0,0 +1,72 @@
+//! Checks passwords against the [Have I Been Pwned] Pwned Passwords range API.
+//! Only the first five characters of each password's SHA-1 hash are sent, the
+//! rest of the hash is matched locally against the returned suffixes.
+//!
+//! [Have I Been Pwned]: https://haveibeenpwned.com/Passwords
+
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+
+const RANGE_URL: &str = "https://api.pwnedpasswords.com/range/";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to query Have I Been Pwned: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Unexpected response from Have I Been Pwned: {0}")]
+    Response(String),
+}
+
+/// Client for the range API, caching each range so passwords sharing a hash
+/// prefix only result in a single request.
+pub struct Client {
+    http: reqwest::Client,
+    ranges: HashMap<String, HashMap<String, u64>>,
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            ranges: HashMap::new(),
+        }
+    }
+
+    async fn range(&self, prefix: &str) -> Result<HashMap<String, u64>, Error> {
+        let body = self
+            .http
+            .get(&format!("{}{}", RANGE_URL, prefix))
+            // pads the response with fake suffixes so its size doesn't give
+            // away which prefix was requested
+            .header("Add-Padding", "true")
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        body.lines()
+            .map(|line| {
+                let mut parts = line.trim().splitn(2, ':');
+
+                match (parts.next(), parts.next().map(str::parse)) {
+                    (Some(suffix), Some(Ok(count))) => Ok((suffix.to_string(), count)),
+                    _ => Err(Error::Response(line.to_string())),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the number of times the password has appeared in a breach.
+    pub async fn breach_count(&mut self, password: &str) -> Result<u64, Error> {
+        let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+
+        if !self.ranges.contains_key(prefix) {
+            let range = self.range(prefix).await?;
+            self.ranges.insert(prefix.to_string(), range);
+        }
+
+        Ok(self.ranges[prefix].get(suffix).copied().unwrap_or_default())
+    }
+}
@@ -0,0 +1,266 @@
+//! Imports logins exported from other password managers.
+
+use crate::{config::Config, output::Output, table};
+use clap::Clap;
+use onep_backend_api as api;
+use serde::Deserialize;
+use std::{collections::HashSet, path::PathBuf, str::FromStr};
+use term_table::{
+    row::Row,
+    table_cell::{Alignment, TableCell},
+    Table, TableStyle,
+};
+
+#[derive(Clap, Debug)]
+pub struct ImportOpt {
+    /// Format of the file, one of bitwarden, lastpass, chrome-csv or
+    /// keepass-csv
+    #[clap(long, short = 'f')]
+    format: Format,
+    /// Show what would be imported without creating anything
+    #[clap(long, short = 'n')]
+    dry_run: bool,
+    #[clap(parse(from_os_str))]
+    file: PathBuf,
+}
+
+#[derive(Debug)]
+enum Format {
+    /// Unencrypted JSON export from Bitwarden.
+    Bitwarden,
+    /// CSV export from `LastPass`.
+    LastPass,
+    /// Passwords exported from Chrome, or any browser based on it.
+    ChromeCsv,
+    /// CSV export from `KeePassXC`.
+    KeepassCsv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bitwarden" => Ok(Self::Bitwarden),
+            "lastpass" => Ok(Self::LastPass),
+            "chrome-csv" => Ok(Self::ChromeCsv),
+            "keepass-csv" => Ok(Self::KeepassCsv),
+            _ => Err(format!("unknown import format {}", s)),
+        }
+    }
+}
+
+/// A login read from an export, with folders or groups carried over as tags.
+#[derive(Debug)]
+struct Login {
+    title: String,
+    username: Option<String>,
+    password: String,
+    url: Option<String>,
+    tag: Option<String>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.is_empty())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenExport {
+    #[serde(default)]
+    folders: Vec<BitwardenFolder>,
+    items: Vec<BitwardenItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BitwardenFolder {
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenItem {
+    name: String,
+    folder_id: Option<String>,
+    login: Option<BitwardenLogin>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BitwardenLogin {
+    username: Option<String>,
+    password: Option<String>,
+    #[serde(default)]
+    uris: Vec<BitwardenUri>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BitwardenUri {
+    uri: Option<String>,
+}
+
+fn parse_bitwarden(contents: &[u8]) -> anyhow::Result<Vec<Login>> {
+    let export: BitwardenExport = serde_json::from_slice(contents)?;
+    let folders = export.folders;
+
+    Ok(export
+        .items
+        .into_iter()
+        .filter_map(|item| {
+            // only logins can be created through the backend, cards and
+            // secure notes are skipped
+            let login = item.login?;
+            let tag = item
+                .folder_id
+                .and_then(|id| folders.iter().find(|v| v.id == id).map(|v| v.name.clone()));
+
+            Some(Login {
+                title: item.name,
+                username: non_empty(login.username),
+                password: login.password.unwrap_or_default(),
+                url: non_empty(login.uris.into_iter().find_map(|v| v.uri)),
+                tag,
+            })
+        })
+        .collect())
+}
+
+#[derive(Debug, Deserialize)]
+struct LastPassRow {
+    url: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    name: String,
+    grouping: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChromeRow {
+    name: String,
+    url: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct KeepassRow {
+    group: Option<String>,
+    title: String,
+    username: Option<String>,
+    password: Option<String>,
+    #[serde(rename = "URL")]
+    url: Option<String>,
+}
+
+fn parse_csv<T, F>(contents: &[u8], into_login: F) -> anyhow::Result<Vec<Login>>
+where
+    T: serde::de::DeserializeOwned,
+    F: Fn(T) -> Login,
+{
+    csv::Reader::from_reader(contents)
+        .deserialize()
+        .map(|row| Ok(into_login(row?)))
+        .collect()
+}
+
+fn parse(format: &Format, contents: &[u8]) -> anyhow::Result<Vec<Login>> {
+    match format {
+        Format::Bitwarden => parse_bitwarden(contents),
+        Format::LastPass => parse_csv(contents, |v: LastPassRow| Login {
+            title: v.name,
+            username: non_empty(v.username),
+            password: v.password.unwrap_or_default(),
+            // LastPass exports secure notes with a placeholder url
+            url: non_empty(v.url).filter(|v| v != "http://sn"),
+            tag: non_empty(v.grouping),
+        }),
+        Format::ChromeCsv => parse_csv(contents, |v: ChromeRow| Login {
+            title: v.name,
+            username: non_empty(v.username),
+            password: v.password.unwrap_or_default(),
+            url: non_empty(v.url),
+            tag: None,
+        }),
+        Format::KeepassCsv => parse_csv(contents, |v: KeepassRow| Login {
+            title: v.title,
+            username: non_empty(v.username),
+            password: v.password.unwrap_or_default(),
+            url: non_empty(v.url),
+            // every group is nested under the database's root group
+            tag: non_empty(v.group.map(|v| match v.find('/') {
+                Some(idx) => v[idx + 1..].to_string(),
+                None => String::new(),
+            })),
+        }),
+    }
+}
+
+pub async fn run(backend: &api::DynBackend, config: &Config, opt: ImportOpt) -> anyhow::Result<()> {
+    let logins = parse(&opt.format, &std::fs::read(&opt.file)?)?;
+
+    let existing = backend.search(None).await?;
+    let mut seen: HashSet<(String, String)> = existing
+        .into_iter()
+        .map(|v| (v.title, v.account_info))
+        .collect();
+
+    let mut table = Table::new();
+    table.style = TableStyle::extended();
+
+    let (mut imported, mut skipped) = (0, 0);
+
+    for login in logins {
+        let key = (
+            login.title.clone(),
+            login.username.clone().unwrap_or_default(),
+        );
+
+        let status = if seen.contains(&key) {
+            skipped += 1;
+            "duplicate"
+        } else if opt.dry_run {
+            imported += 1;
+            "new"
+        } else {
+            backend
+                .create(
+                    &login.title,
+                    login.username.as_deref(),
+                    login.url.as_deref(),
+                    login.tag.as_deref(),
+                    None,
+                    &login.password,
+                )
+                .await?;
+            imported += 1;
+            "imported"
+        };
+
+        table.add_row(Row::new(vec![
+            TableCell::new(&login.title),
+            TableCell::new(login.username.as_deref().unwrap_or_default()),
+            TableCell::new_with_alignment(status, 1, Alignment::Right),
+        ]));
+
+        seen.insert(key);
+    }
+
+    table::print(&mut Output::stdout(), config, table);
+
+    if !config.quiet {
+        if opt.dry_run {
+            eprintln!(
+                "Would import {} items, skipping {} duplicates",
+                imported, skipped
+            );
+        } else {
+            eprintln!(
+                "Imported {} items, skipped {} duplicates",
+                imported, skipped
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Renders templates containing `{{ op://vault/item/field }}` secret
+//! references, for config files that need secrets in them.
+
+use crate::{
+    private,
+    resolve::{self, Resolver},
+};
+use clap::Clap;
+use onep_backend_api as api;
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Unclosed {{{{ in template at byte {0}")]
+    Unclosed(usize),
+}
+
+#[derive(Clap, Debug)]
+pub struct InjectOpt {
+    /// Template to render, defaults to stdin
+    #[clap(long, short = 'i', parse(from_os_str))]
+    input: Option<PathBuf>,
+    /// File to write the rendered template to, readable only by the current
+    /// user. Defaults to stdout
+    #[clap(long, short = 'o', parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+/// Replaces every `{{ op://... }}` in the template with the value it refers
+/// to. Anything else between braces is left as is, so templates for other
+/// tools can pass through untouched.
+async fn render(resolver: &mut Resolver<'_>, template: &str) -> anyhow::Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| Error::Unclosed(template.len() - rest.len() + start))?
+            + start;
+
+        rendered.push_str(&rest[..start]);
+
+        let reference = rest[start + 2..end].trim();
+
+        if reference.starts_with(resolve::PREFIX) {
+            rendered.push_str(&resolver.resolve(reference).await?);
+        } else {
+            rendered.push_str(&rest[start..end + 2]);
+        }
+
+        rest = &rest[end + 2..];
+    }
+
+    rendered.push_str(rest);
+
+    Ok(rendered)
+}
+
+pub async fn run(backend: &api::DynBackend, opt: InjectOpt) -> anyhow::Result<()> {
+    let template = if let Some(path) = opt.input {
+        std::fs::read_to_string(path)?
+    } else {
+        let mut template = String::new();
+        std::io::stdin().read_to_string(&mut template)?;
+        template
+    };
+
+    let rendered = render(&mut Resolver::new(backend), &template).await?;
+
+    match opt.output {
+        Some(path) => private::write(path, rendered)?,
+        None => std::io::stdout().write_all(rendered.as_bytes())?,
+    }
+
+    Ok(())
+}
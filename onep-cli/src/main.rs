@@ -1,49 +1,331 @@
 #![deny(clippy::pedantic)]
 #![allow(clippy::used_underscore_binding)]
 
+mod askpass;
+mod attachment;
+mod audit;
+mod backends;
+mod cache;
+mod capabilities;
+mod clipboard;
+mod completions;
+mod config;
+mod copy;
+#[cfg(unix)]
+mod daemon;
+mod doc;
+mod duplicate;
+mod env;
+mod expand;
+mod export;
+mod git_credential;
+mod grpc;
+mod hibp;
+mod import;
+mod inject;
+mod matcher;
+mod mcp;
 mod otp;
+mod output;
+mod passphrase;
+mod private;
+mod qr;
+mod replay;
+mod resolve;
+mod run;
+#[cfg(target_os = "linux")]
+mod secret_service;
+mod serve;
+mod shell;
+mod ssh;
+#[cfg(unix)]
+mod ssh_agent;
+mod table;
+mod tag;
+mod totp;
+mod tree;
+mod tui;
+mod vault;
+mod watch;
 
-use clap::Clap;
-use colored::Colorize;
+use clap::{AppSettings, Clap, FromArgMatches, IntoApp};
+use colored::{ColoredString, Colorize};
+use futures::{stream::BoxStream, StreamExt, TryStreamExt};
 use itertools::Itertools;
+use matcher::Matcher;
 use onep_backend_api as api;
-use onep_backend_op as backend;
-use std::{collections::BTreeMap, convert::TryFrom};
+use output::Output;
+use std::{cmp::Ordering, convert::TryFrom, str::FromStr, time::SystemTime};
 use term_table::{
     row::Row,
     table_cell::{Alignment, TableCell},
     Table, TableStyle,
 };
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+
+/// What 1p exits with when it fails, so scripts can tell failures apart
+/// without matching on messages. Anything not covered by the others exits
+/// with `EXIT_FAILURE`, and a command stopped by Ctrl-C with 130.
+const EXIT_FAILURE: i32 = 1;
+/// The arguments couldn't be parsed, or didn't make sense together.
+const EXIT_USAGE: i32 = 2;
+/// The item, or whatever else was asked for, doesn't exist.
+const EXIT_NOT_FOUND: i32 = 3;
+/// Signing in is needed first, or the session has expired.
+const EXIT_AUTH_REQUIRED: i32 = 4;
+/// The backend, or the server behind it, couldn't be reached.
+const EXIT_UNREACHABLE: i32 = 5;
 
 #[derive(thiserror::Error, Debug)]
 enum Error {
     #[error("Couldn't find the requested item.")]
     NotFound,
+    #[error("Several items match {0:?}, pick one by its uuid:\n{1}")]
+    Ambiguous(String, String),
+}
+
+/// Options controlling how `list` and `search` display items.
+#[derive(Clap, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+struct ListOpt {
+    #[clap(long, short = 'u')]
+    show_uuids: bool,
+    #[clap(long, short = 'n')]
+    show_account_names: bool,
+    #[clap(long, short = 't')]
+    show_tags: bool,
+    /// Show when each item was created and last changed
+    #[clap(long, short = 'd')]
+    show_dates: bool,
+    /// Sort items by `title`, `vault`, or when they were `created` or
+    /// `updated`, oldest first. Vaults are ordered by their first item
+    #[clap(long)]
+    sort: Option<Sort>,
+    /// Reverse the order given by --sort
+    #[clap(long, requires = "sort")]
+    reverse: bool,
+    /// Only show favorite items
+    #[clap(long, short = 'f')]
+    favorites: bool,
+    /// Only show items in this vault, by name or uuid
+    #[clap(long)]
+    vault: Option<String>,
+    /// Only show items with this tag, or a tag nested beneath it. Can be
+    /// given more than once to only show items with every tag
+    #[clap(long, number_of_values = 1)]
+    tag: Vec<String>,
+    /// Only show items of this category, such as `login` or `secure-note`
+    #[clap(long)]
+    category: Option<api::ItemCategory>,
+    /// Fetch items from the backend even if the cached list hasn't expired
+    #[clap(long)]
+    refresh: bool,
+    /// Print one item per line as soon as it's found, rather than a tree
+    /// grouped by vault
+    #[clap(long)]
+    flat: bool,
+    /// Show at most this many items
+    #[clap(long)]
+    limit: Option<usize>,
+    /// Page of items to show when using --limit, counting from 1
+    #[clap(long, requires = "limit")]
+    page: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Title,
+    Created,
+    Updated,
+    Vault,
+}
+
+impl FromStr for Sort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "title" => Ok(Self::Title),
+            "created" => Ok(Self::Created),
+            "updated" => Ok(Self::Updated),
+            "vault" => Ok(Self::Vault),
+            _ => Err(format!("unknown sort order {}", s)),
+        }
+    }
+}
+
+impl Sort {
+    /// Sorts `results`, putting items the backend has no dates for last even
+    /// when `reverse` is set. Vaults are sorted by their names in `vaults`,
+    /// and items within them by title.
+    fn apply(
+        self,
+        results: &mut [api::ItemMetadata],
+        vaults: &[api::VaultMetadata],
+        reverse: bool,
+    ) {
+        let title = |v: &api::ItemMetadata| v.title.to_lowercase();
+        let vault = |v: &api::ItemMetadata| {
+            vaults
+                .iter()
+                .find(|vault| vault.uuid == v.vault_uuid)
+                .map(|v| v.name.to_lowercase())
+        };
+        let dates = |a: Option<SystemTime>, b: Option<SystemTime>| {
+            (a.is_none().cmp(&b.is_none()), a.cmp(&b))
+        };
+
+        results.sort_by(|a, b| {
+            let (undated, ordering) = match self {
+                Self::Title => (Ordering::Equal, title(a).cmp(&title(b))),
+                Self::Created => dates(a.created, b.created),
+                Self::Updated => dates(a.updated, b.updated),
+                Self::Vault => (
+                    Ordering::Equal,
+                    vault(a)
+                        .cmp(&vault(b))
+                        .then_with(|| title(a).cmp(&title(b))),
+                ),
+            };
+
+            undated.then(if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            })
+        });
+    }
+}
+
+impl ListOpt {
+    /// Swaps a vault given by name for its uuid, which is what backends
+    /// filter on.
+    async fn resolve_vault(&mut self, backend: &api::DynBackend) -> anyhow::Result<()> {
+        if let Some(name) = &self.vault {
+            self.vault = Some(vault::find(backend, name).await?.uuid);
+        }
+
+        Ok(())
+    }
+
+    /// Filters to have the backend apply where it can.
+    fn filter(&self) -> api::Filter<'_> {
+        let mut filter = api::Filter::default();
+        filter.vault = self.vault.as_deref();
+        filter.tags = &self.tag;
+        filter.category = self.category;
+        filter
+    }
+
+    /// The page of results asked for with `--limit` and `--page`.
+    fn page(&self) -> Option<api::Page> {
+        self.limit.map(|limit| {
+            api::Page::new(
+                self.page
+                    .unwrap_or(1)
+                    .saturating_sub(1)
+                    .saturating_mul(limit),
+                limit,
+            )
+        })
+    }
 }
 
 #[derive(Clap, Debug)]
 #[clap(author, version)]
+#[allow(clippy::struct_excessive_bools)]
 /// 1password cli for humans
+struct Args {
+    /// Fail when the backend's session has expired, rather than signing in
+    /// again
+    #[clap(long, global = true)]
+    no_reauth: bool,
+    /// Session token for op to use, such as one from `op signin --raw`, in
+    /// place of `OP_SESSION_*` or the session cached by `1p signin`
+    #[clap(long, global = true)]
+    session: Option<String>,
+    /// Fail when op returns a field 1p doesn't know about, such as after
+    /// upgrading op, rather than ignoring it
+    #[clap(long, global = true)]
+    strict_parse: bool,
+    /// Don't color output or draw trees with box-drawing characters, the
+    /// same as setting `NO_COLOR`
+    #[clap(long, global = true)]
+    no_color: bool,
+    /// Draw trees with plain ASCII rather than box-drawing characters, which
+    /// happens anyway when the locale isn't UTF-8
+    #[clap(long, global = true)]
+    ascii: bool,
+    /// Print long output straight to the terminal rather than through
+    /// `PAGER`
+    #[clap(long, global = true)]
+    no_pager: bool,
+    /// Show long values in tables in full, rather than cutting them short to
+    /// fit the terminal
+    #[clap(long, global = true)]
+    full: bool,
+    /// Print only the values asked for, without the account header, tree
+    /// lines, table borders or progress messages
+    #[clap(long, short = 'q', global = true)]
+    quiet: bool,
+    /// Log each call to the backend and each command it runs to stderr, along
+    /// with how long they took. `RUST_LOG` gives finer control
+    #[clap(long, short = 'v', global = true)]
+    verbose: bool,
+    #[clap(subcommand)]
+    opt: Opt,
+}
+
+#[derive(Clap, Debug)]
 enum Opt {
+    /// Sign in to your account and cache the session for future commands
+    Signin {
+        /// Shorthand of the account to sign in to, defaults to the last used
+        account: Option<String>,
+    },
+    /// Sign out of your account and forget the cached session
+    Signout,
+    /// Show the account you're signed in to, and whether it's through a
+    /// service account
+    Whoami,
     /// List all items
     #[clap(alias = "ls")]
     List {
-        #[clap(long, short = 'u')]
-        show_uuids: bool,
-        #[clap(long, short = 'n')]
-        show_account_names: bool,
+        #[clap(flatten)]
+        list: ListOpt,
+        /// List archived items instead
+        #[clap(long)]
+        archived: bool,
     },
     /// Search for an item
     Search {
-        #[clap(long, short = 'u')]
-        show_uuids: bool,
-        #[clap(long, short = 'n')]
-        show_account_names: bool,
+        #[clap(flatten)]
+        list: ListOpt,
         terms: String,
+        #[clap(flatten)]
+        matching: matcher::MatchOpt,
     },
     /// Show existing password and optionally put it on the clipboard
     #[clap(alias = "get")]
-    Show { uuid: String },
+    Show {
+        /// Items to show, by uuid or title, which are fetched in parallel
+        /// when there's more than one
+        #[clap(required = true)]
+        items: Vec<String>,
+        /// List the files attached to each item
+        #[clap(long, short = 'a')]
+        attachments: bool,
+        /// Show passwords and other concealed fields rather than masking them
+        #[clap(long, short = 'r')]
+        reveal: bool,
+        /// Copy the field with this number to the clipboard, or pick one by
+        /// pressing its number when given on its own
+        #[clap(long, short = 'c', value_name = "number")]
+        #[allow(clippy::option_option)]
+        copy: Option<Option<usize>>,
+    },
+    /// Show previous passwords of an item
+    History { uuid: String },
     /// Generates a new password and stores it in your password store
     #[clap(alias = "gen")]
     Generate {
@@ -58,147 +340,821 @@ enum Opt {
         /// Comma-separated list of tags to associate with the login
         #[clap(long, short = 't')]
         tags: Option<String>,
+        /// Vault to create the login in, by name or uuid, defaulting to the
+        /// one in your config
+        #[clap(long)]
+        vault: Option<String>,
+        /// Length of the generated password
+        #[clap(long, short = 'l')]
+        length: Option<usize>,
+        /// Don't include symbols in the generated password
+        #[clap(long)]
+        no_symbols: bool,
+        /// Only include digits in the generated password, useful for PINs
+        #[clap(long)]
+        digits_only: bool,
+        /// Avoid characters that are easily confused, such as l, 1 and I
+        #[clap(long)]
+        exclude_ambiguous: bool,
+    },
+    /// Create a copy of an item with the same fields and sections
+    Duplicate(duplicate::DuplicateOpt),
+    /// Print the current one-time password of an item, or add one to it
+    #[clap(alias = "otp")]
+    Totp(totp::TotpOpt),
+    /// Copy the password, or another field, of an item to the clipboard
+    Copy(copy::CopyOpt),
+    /// Create, delete and rename vaults
+    Vault {
+        #[clap(subcommand)]
+        cmd: vault::VaultOpt,
+    },
+    /// Add or remove tags on an item
+    Tag {
+        #[clap(subcommand)]
+        cmd: tag::TagOpt,
+    },
+    /// Change the title of an item
+    Rename { uuid: String, title: String },
+    /// Mark an item as a favorite
+    Fav { uuid: String },
+    /// Unmark an item as a favorite
+    Unfav { uuid: String },
+    /// Move an item to the archive
+    Archive { uuid: String },
+    /// Restore an item from the archive
+    Restore { uuid: String },
+    /// Generates a diceware passphrase locally, optionally saving it as a new
+    /// login
+    Passphrase(passphrase::PassphraseOpt),
+    /// List, download and upload documents
+    #[clap(alias = "document")]
+    Doc {
+        #[clap(subcommand)]
+        cmd: doc::DocOpt,
     },
+    /// Download files attached to items
+    Attachment {
+        #[clap(subcommand)]
+        cmd: attachment::AttachmentOpt,
+    },
+    /// Report weak, reused and old passwords
+    Audit(audit::AuditOpt),
+    /// Export every item, including passwords, to a file
+    Export(export::ExportOpt),
+    /// Import logins exported from another password manager
+    Import(import::ImportOpt),
+    /// Run a command with secrets set in its environment
+    Run(run::RunOpt),
+    /// Fill in secret references in a template
+    Inject(inject::InjectOpt),
+    /// Print the fields of tagged items as KEY=value lines for a .env file
+    Env(env::EnvOpt),
+    /// Git credential helper, used with credential.helper = !1p git-credential
+    GitCredential {
+        /// One of get, store or erase
+        operation: git_credential::Operation,
+    },
+    /// Run an ssh-agent serving the keys stored in SSH key items
+    #[cfg(unix)]
+    SshAgent(ssh_agent::SshAgentOpt),
+    /// Print a single password for use with `SSH_ASKPASS` or `SUDO_ASKPASS`
+    Askpass(askpass::AskpassOpt),
+    /// Serve passwords to libsecret clients as a Secret Service provider
+    #[cfg(target_os = "linux")]
+    SecretService,
+    /// Keep the backend signed in and items in memory, making other commands
+    /// faster
+    #[cfg(unix)]
+    Daemon(daemon::DaemonOpt),
+    /// Serve items read-only over HTTP to other tools on this machine,
+    /// authenticated with a token generated at startup
+    Serve(serve::ServeOpt),
+    /// Answer AI assistants over the Model Context Protocol on stdio, letting
+    /// them search item metadata and ask for fields one at a time
+    Mcp,
+    /// Report items that are added, changed or removed while it runs
+    Watch(watch::WatchOpt),
+    /// Browse items in a full-screen interface
+    Tui,
+    /// Print a completion script for your shell, which completes item titles
+    /// and vault names as well as commands
+    Completions(completions::CompletionsOpt),
+    /// Used by completion scripts to list item titles and vault names
+    #[clap(name = "__complete-items", setting = AppSettings::Hidden)]
+    CompleteItems(completions::CompleteItemsOpt),
 }
 
 #[tokio::main(core_threads = 1)]
 async fn main() {
-    if let Err(e) = run(&backend::OpBackend {}).await {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    // the config is needed before parsing to expand aliases and add any
+    // default flags
+    let res = config::load()
+        .map_err(anyhow::Error::from)
+        .and_then(|config| {
+            let app = Args::into_app();
+            let args = expand::aliases(&config, &app, std::env::args_os().collect())?;
+            let args = expand::defaults(&config, &app, args);
+
+            Ok((args, config))
+        });
+
+    let (args, config) = match res {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let matches = match Args::into_app().try_get_matches_from(&args) {
+        Ok(matches) => matches,
+        Err(e) => help(&config, args, &e).await,
+    };
+    let subcommand = matches.subcommand_name().unwrap_or_default().to_string();
+    let command = start(Args::from_arg_matches(&matches), &subcommand, config);
+
+    // on Ctrl-C the command is dropped before exiting, which kills anything
+    // the backend is waiting on rather than leaving it running. `run` leaves
+    // it to the command it ran, which gets the Ctrl-C too, to decide whether
+    // to stop
+    let res = if subcommand == "run" {
+        Some(command.await)
+    } else {
+        tokio::select! {
+            res = command => Some(res),
+            _ = tokio::signal::ctrl_c() => None,
+        }
+    };
+
+    match res {
+        Some(Ok(())) => {}
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(exit_code(&e));
+        }
+        None => std::process::exit(130),
     }
 }
 
-async fn run<T: api::Backend>(backend: &T) -> anyhow::Result<()>
-where
-    T::Error: 'static + std::error::Error + Send + Sync,
-{
-    match Opt::parse() {
-        Opt::List {
-            show_uuids,
-            show_account_names,
-        } => search(backend, None, show_uuids, show_account_names).await?,
+/// The exit code for an error, going by the first cause in its chain that
+/// says what kind of failure it was.
+fn exit_code(e: &anyhow::Error) -> i32 {
+    for cause in e.chain() {
+        if let Some(e) = cause.downcast_ref::<api::BackendError>() {
+            return match e {
+                api::BackendError::NotFound(_) => EXIT_NOT_FOUND,
+                api::BackendError::AuthRequired(_) => EXIT_AUTH_REQUIRED,
+                api::BackendError::Network(_) => EXIT_UNREACHABLE,
+                _ => EXIT_FAILURE,
+            };
+        }
+
+        let not_found = matches!(cause.downcast_ref(), Some(Error::NotFound))
+            || matches!(cause.downcast_ref(), Some(copy::Error::NoField(..)))
+            || matches!(cause.downcast_ref(), Some(copy::Error::NoNumber(..)))
+            || matches!(cause.downcast_ref(), Some(totp::Error::NoTotp(_)));
+        let usage = matches!(cause.downcast_ref(), Some(copy::Error::SeveralItems))
+            || matches!(cause.downcast_ref(), Some(totp::Error::NoItem));
+
+        if not_found {
+            return EXIT_NOT_FOUND;
+        } else if usage {
+            return EXIT_USAGE;
+        }
+    }
+
+    EXIT_FAILURE
+}
+
+/// Exits with the error clap gave parsing the arguments, hiding the
+/// subcommands the backend can't run when it's showing the help.
+async fn help(config: &config::Config, args: Vec<std::ffi::OsString>, error: &clap::Error) -> ! {
+    let showing_help = matches!(
+        error.kind,
+        clap::ErrorKind::DisplayHelp | clap::ErrorKind::MissingArgumentOrSubcommand
+    );
+
+    if showing_help {
+        if let Ok(backend) = backends::open(config, false) {
+            if let Ok(supported) = backend.capabilities().await {
+                if let Err(e) =
+                    capabilities::hide(Args::into_app(), supported).try_get_matches_from(args)
+                {
+                    usage(&e);
+                }
+            }
+        }
+    }
+
+    usage(error)
+}
+
+/// Prints clap's message and exits, with `EXIT_USAGE` unless it's the help
+/// or version that were asked for.
+fn usage(error: &clap::Error) -> ! {
+    if !error.use_stderr() {
+        error.exit();
+    }
+
+    eprint!("{}", error);
+    std::process::exit(EXIT_USAGE)
+}
+
+/// Logs spans as they close, which is when they know how long they took,
+/// if `--verbose` or `RUST_LOG` asked for them.
+fn trace(verbose: bool) {
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(filter) => EnvFilter::new(filter),
+        Err(_) if verbose => EnvFilter::new("onep=debug"),
+        Err(_) => return,
+    };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr)
+        .with_ansi(atty::is(atty::Stream::Stderr))
+        .init();
+}
+
+async fn start(args: Args, subcommand: &str, mut config: config::Config) -> anyhow::Result<()> {
+    let opt = args.opt;
+
+    trace(args.verbose);
+
+    if args.no_reauth {
+        config.op.reauth = false;
+    }
+
+    if args.strict_parse {
+        config.op.strict_parse = true;
+    }
+
+    if let Some(session) = args.session {
+        config.op.session = Some(session);
+    }
+
+    if args.ascii {
+        config.ascii = true;
+    }
+
+    if args.quiet {
+        config.quiet = true;
+    }
+
+    if args.no_pager {
+        config.no_pager = true;
+    }
+
+    if args.full {
+        config.tables.truncate = false;
+    }
+
+    if args.no_color || std::env::var_os("NO_COLOR").is_some() {
+        config.color = config::ColorChoice::Never;
+    }
+
+    colored::control::set_override(config.color.enabled());
+
+    #[cfg(unix)]
+    {
+        if let Opt::Daemon(opt) = opt {
+            return daemon::run(&config, opt).await;
+        }
+    }
+
+    if let Opt::Serve(opt) = opt {
+        return serve::run(config, opt).await;
+    }
+
+    if let Opt::Completions(opt) = &opt {
+        completions::generate(&mut Args::into_app(), opt);
+        return Ok(());
+    }
+
+    let fresh = match &opt {
+        Opt::List { list, .. } | Opt::Search { list, .. } => list.refresh,
+        // signing in is interactive, so has to happen in this process, and
+        // watching has to reach the backend every time to see what's changed
+        Opt::Signin { .. } | Opt::Signout | Opt::Whoami | Opt::Watch(_) => true,
+        _ => false,
+    };
+    let backend = backends::open(&config, fresh)?;
+    capabilities::check(&*backend, &config, subcommand).await?;
+
+    run(opt, &config, &*backend).await
+}
+
+#[allow(clippy::too_many_lines)]
+async fn run(opt: Opt, config: &config::Config, backend: &api::DynBackend) -> anyhow::Result<()> {
+    match opt {
+        Opt::Signin { account } => {
+            if account.is_some() {
+                capabilities::require(
+                    backend,
+                    config,
+                    capabilities::Need::MultipleAccounts,
+                    "sign in to other accounts",
+                )
+                .await?;
+            }
+
+            let account = account.or_else(|| config.account.clone());
+            backend.signin(account.as_deref()).await?;
+        }
+        Opt::Signout => backend.signout().await?,
+        Opt::Whoami => {
+            let account = backend.account().await?;
+            print!("{} ({})", account.name, account.domain);
+
+            if account.service_account {
+                print!(", through a service account");
+            }
+
+            println!();
+        }
+        Opt::List { mut list, archived } => {
+            list.resolve_vault(backend).await?;
+            search(backend, config, None, archived, &list).await?;
+        }
         Opt::Search {
+            mut list,
             terms,
-            show_uuids,
-            show_account_names,
-        } => search(backend, Some(terms), show_uuids, show_account_names).await?,
-        Opt::Show { uuid } => {
+            matching,
+        } => {
+            let matcher = Matcher::new(terms, &matching)?;
+            list.resolve_vault(backend).await?;
+            search(backend, config, Some(&matcher), false, &list).await?;
+        }
+        Opt::Show {
+            items,
+            attachments,
+            reveal,
+            copy,
+        } => {
+            if copy.is_some() && items.len() > 1 {
+                return Err(copy::Error::SeveralItems.into());
+            }
+
+            let results = backend.get_many(&items).await?;
+
+            // the fields have to be on screen to pick one to copy
+            let mut out = if copy.is_some() {
+                Output::stdout()
+            } else {
+                Output::new(config)
+            };
+
+            for (item, result) in items.iter().zip(results) {
+                let mut result = match result {
+                    Some(result) => result,
+                    None => find(backend, item).await?,
+                };
+
+                if config.otp.is_steam(item, &result.title) {
+                    otp::mark_steam(&mut result);
+                }
+
+                show(&mut out, config, result.clone(), attachments, reveal);
+
+                let number = match copy {
+                    Some(Some(number)) => Some(number),
+                    Some(None) => copy::pick(&result)?,
+                    None => None,
+                };
+
+                if let Some(number) = number {
+                    copy::numbered(config, &result, number).await?;
+                }
+            }
+        }
+        Opt::History { uuid } => {
             let result = backend.get(&uuid).await?.ok_or(Error::NotFound)?;
-            show(result);
+            history(config, result);
         }
         Opt::Generate {
             name,
             username,
             url,
             tags,
+            vault,
+            length,
+            no_symbols,
+            digits_only,
+            exclude_ambiguous,
         } => {
+            let vault = match vault.as_ref().or(config.vault.as_ref()) {
+                Some(vault) => Some(vault::find(backend, vault).await?.uuid),
+                None => None,
+            };
+            let mut recipe = api::PasswordRecipe::default();
+            recipe.length = length.unwrap_or(recipe.length);
+            recipe.letters = !digits_only;
+            recipe.digits = true;
+            recipe.symbols = !digits_only && !no_symbols;
+            recipe.exclude_ambiguous = exclude_ambiguous;
+
             let result = backend
-                .generate(&name, username.as_deref(), url.as_deref(), tags.as_deref())
+                .generate(
+                    &name,
+                    username.as_deref(),
+                    url.as_deref(),
+                    tags.as_deref(),
+                    vault.as_deref(),
+                    &recipe,
+                )
                 .await?;
-            show(result);
+            // the password's only just been generated, so it's shown in full
+            show(&mut Output::stdout(), config, result, false, true);
         }
+        Opt::Duplicate(opt) => duplicate::run(backend, config, opt).await?,
+        Opt::Totp(opt) => totp::run(backend, config, opt).await?,
+        Opt::Copy(opt) => copy::run(backend, config, opt).await?,
+        Opt::Vault { cmd } => vault::run(backend, cmd).await?,
+        Opt::Tag { cmd } => tag::run(backend, cmd).await?,
+        Opt::Rename { uuid, title } => {
+            let mut edit = api::ItemEdit::default();
+            edit.title = Some(title);
+
+            backend.edit(&uuid, &edit).await?;
+        }
+        Opt::Fav { uuid } => backend.set_favorite(&uuid, true).await?,
+        Opt::Unfav { uuid } => backend.set_favorite(&uuid, false).await?,
+        Opt::Archive { uuid } => backend.archive(&uuid).await?,
+        Opt::Restore { uuid } => backend.restore(&uuid).await?,
+        Opt::Passphrase(opt) => passphrase::run(backend, config, opt).await?,
+        Opt::Doc { cmd } => doc::run(backend, config, cmd).await?,
+        Opt::Attachment { cmd } => attachment::run(backend, cmd).await?,
+        Opt::Audit(opt) => audit::run(backend, config, opt).await?,
+        Opt::Export(opt) => export::run(backend, opt).await?,
+        Opt::Import(opt) => import::run(backend, config, opt).await?,
+        Opt::Run(opt) => run::run(backend, opt).await?,
+        Opt::Inject(opt) => inject::run(backend, opt).await?,
+        Opt::Env(opt) => env::run(backend, config, opt).await?,
+        Opt::GitCredential { operation } => git_credential::run(backend, operation).await?,
+        #[cfg(unix)]
+        Opt::SshAgent(opt) => ssh_agent::run(backend, opt).await?,
+        Opt::Askpass(opt) => askpass::run(backend, config, opt).await?,
+        #[cfg(target_os = "linux")]
+        Opt::SecretService => secret_service::run(backend).await?,
+        #[cfg(unix)]
+        Opt::Daemon(_) => unreachable!("daemon is started before a backend is opened"),
+        Opt::Serve(_) => unreachable!("server is started before a backend is opened"),
+        Opt::Completions(_) => unreachable!("completions are printed before a backend is opened"),
+        Opt::CompleteItems(opt) => completions::items(backend, opt).await?,
+        Opt::Mcp => mcp::run(backend, config).await?,
+        Opt::Watch(opt) => watch::run(backend, config, opt).await?,
+        Opt::Tui => tui::run(backend, config).await?,
     }
 
     Ok(())
 }
 
-#[allow(clippy::non_ascii_literal)]
-async fn search<T: api::Backend>(
-    backend: &T,
-    terms: Option<String>,
-    show_uuids: bool,
-    show_account_names: bool,
-) -> anyhow::Result<()>
-where
-    T::Error: 'static + std::error::Error + Send + Sync,
-{
+async fn search(
+    backend: &api::DynBackend,
+    config: &config::Config,
+    matcher: Option<&Matcher>,
+    archived: bool,
+    opt: &ListOpt,
+) -> anyhow::Result<()> {
+    let mut out = Output::new(config);
+
+    if opt.flat {
+        return search_flat(&mut out, backend, config, matcher, archived, opt).await;
+    }
+
     let (account, vaults, results) = tokio::try_join!(
         backend.account(),
         backend.vaults(),
-        backend.search(terms.as_deref())
+        results(backend, matcher, archived, opt)
     )?;
 
-    let mut results_grouped: BTreeMap<_, Vec<_>> = BTreeMap::new();
-    for (key, group) in &results.into_iter().group_by(|v| v.vault_uuid.clone()) {
-        results_grouped.insert(key, group.collect());
+    // results aren't necessarily ordered by vault, especially once sorted, in
+    // which case vaults are ordered by whichever item in them comes first
+    let mut results_grouped: Vec<(String, Vec<_>)> = Vec::new();
+    for result in results {
+        match results_grouped
+            .iter_mut()
+            .find(|(vault, _)| *vault == result.vault_uuid)
+        {
+            Some((_, group)) => group.push(result),
+            None => results_grouped.push((result.vault_uuid.clone(), vec![result])),
+        }
     }
 
-    // slow path for when vault is an exact match
-    if let Some(terms) = terms {
+    if opt.sort.is_none() {
+        results_grouped.sort_by_cached_key(|(uuid, _)| {
+            vaults
+                .iter()
+                .find(|v| v.uuid == *uuid)
+                .map(|v| v.name.to_lowercase())
+        });
+    }
+
+    // slow path for when vault is an exact match, which would add items
+    // outside of the page being shown
+    if let (Some(terms), None) = (matcher.and_then(Matcher::backend_terms), opt.limit) {
         if let Some(vault) = vaults
             .iter()
             .find(|v| v.name.to_lowercase() == terms.to_lowercase())
         {
-            results_grouped.insert(vault.uuid.clone(), backend.search(Some(&vault.uuid)).await?);
+            let mut results = backend
+                .search_filtered(Some(&vault.uuid), opt.filter())
+                .await?;
+
+            if let Some(sort) = opt.sort {
+                sort.apply(&mut results, &vaults, opt.reverse);
+            }
+
+            match results_grouped.iter_mut().find(|(v, _)| *v == vault.uuid) {
+                Some((_, group)) => *group = results,
+                None => results_grouped.push((vault.uuid.clone(), results)),
+            }
         }
     }
 
-    println!("{} ({})", account.name, account.domain);
+    if opt.favorites {
+        results_grouped = results_grouped
+            .into_iter()
+            .map(|(vault, group)| (vault, group.into_iter().filter(|v| v.favorite).collect()))
+            .filter(|(_, group): &(_, Vec<_>)| !group.is_empty())
+            .collect();
+    }
+
+    let results_grouped = results_grouped
+        .into_iter()
+        .map(|(vault, group)| {
+            let leaves = group
+                .into_iter()
+                .map(|result| tree::Leaf {
+                    details: details(&result, opt, &config.colors),
+                    title: result.title,
+                })
+                .collect();
 
-    let vault_count = results_grouped.len() - 1;
+            (vault, leaves)
+        })
+        .collect();
 
-    for (current_vault_index, (vault, group)) in results_grouped.into_iter().enumerate() {
-        let vault = vaults
-            .iter()
-            .find(|v| v.uuid == vault)
-            .map_or_else(|| format!("Unknown Vault ({})", vault), |v| v.name.clone());
+    tree::print(&mut out, config, &account, &vaults, results_grouped);
 
-        println!(
-            "{} {}",
-            if current_vault_index < vault_count {
-                "├──"
-            } else {
-                "└──"
-            },
-            vault.blue()
-        );
+    Ok(())
+}
+
+/// Looks up an item by searching for its title, for when it wasn't given by
+/// uuid.
+async fn find(backend: &api::DynBackend, title: &str) -> anyhow::Result<api::Item> {
+    let uuid = find_uuid(backend, title).await?;
+
+    Ok(backend.get(&uuid).await?.ok_or(Error::NotFound)?)
+}
+
+/// Finds the uuid of the item with the given title. An item with exactly
+/// that title is picked over partial matches, but otherwise there has to be
+/// only one match.
+async fn find_uuid(backend: &api::DynBackend, title: &str) -> anyhow::Result<String> {
+    let results = backend.search(Some(title)).await?;
 
-        let line_start = if current_vault_index < vault_count {
-            "│"
+    let exact: Vec<_> = results
+        .iter()
+        .filter(|v| v.title.to_lowercase() == title.to_lowercase())
+        .collect();
+    let uuid = match (exact.as_slice(), results.as_slice()) {
+        ([exact], _) => exact.uuid.clone(),
+        (_, [result]) => result.uuid.clone(),
+        (_, []) => return Err(Error::NotFound.into()),
+        (_, results) => {
+            let results = results
+                .iter()
+                .map(|v| format!("  {}  {} ({})", v.uuid, v.title, v.account_info))
+                .join("\n");
+
+            return Err(Error::Ambiguous(title.to_string(), results).into());
+        }
+    };
+
+    Ok(uuid)
+}
+
+/// Fetches the results to list, leaving paging to the backend where it can.
+async fn results(
+    backend: &api::DynBackend,
+    matcher: Option<&Matcher>,
+    archived: bool,
+    opt: &ListOpt,
+) -> Result<Vec<api::ItemMetadata>, api::BackendError> {
+    let terms = matcher.and_then(Matcher::backend_terms);
+    // terms the backend can't match are checked here against everything
+    let unmatched = matcher.filter(|v| v.backend_terms().is_none());
+
+    let deep = unmatched.map_or(false, Matcher::is_deep);
+
+    let mut results = match (opt.page(), unmatched) {
+        (Some(page), None)
+            if !archived && !opt.favorites && opt.sort.is_none() && opt.filter().is_empty() =>
+        {
+            return backend.search_page(terms, page).await;
+        }
+        // sorted results need every item, but favorites and terms the
+        // backend can't match can be picked out as the results stream in
+        (Some(page), _) if !archived && !deep && opt.sort.is_none() && opt.filter().is_empty() => {
+            return stream(backend, matcher, opt.favorites, Some(page))
+                .try_collect()
+                .await;
+        }
+        _ if archived => {
+            let mut results = backend.archived().await?;
+            results.retain(|v| opt.filter().matches(v));
+            results
+        }
+        (_, Some(matcher)) if deep => backend
+            .get_all()
+            .await?
+            .into_iter()
+            .filter(|(metadata, item)| {
+                opt.filter().matches(metadata)
+                    && (matcher.matches(metadata) || matcher.matches_item(item))
+            })
+            .map(|(metadata, _)| metadata)
+            .collect(),
+        _ if opt.filter().is_empty() => backend.search(terms).await?,
+        _ => backend.search_filtered(terms, opt.filter()).await?,
+    };
+
+    if opt.favorites {
+        results.retain(|v| v.favorite);
+    }
+
+    if let Some(matcher) = unmatched.filter(|v| !v.is_deep()) {
+        results.retain(|v| matcher.matches(v));
+    }
+
+    if let Some(sort) = opt.sort {
+        // only sorting by vault needs their names
+        let vaults = if sort == Sort::Vault {
+            backend.vaults().await?
         } else {
-            " "
+            Vec::new()
         };
 
-        let item_count = group.len() - 1;
-
-        for (current_item_index, result) in group.into_iter().enumerate() {
-            println!(
-                "{}   {} {}",
-                line_start,
-                if current_item_index < item_count {
-                    "├──"
-                } else {
-                    "└──"
-                },
-                result.title.trim()
-            );
-
-            let prefix = if current_item_index < item_count {
-                "│  "
-            } else {
-                "   "
-            };
+        sort.apply(&mut results, &vaults, opt.reverse);
+    }
 
-            if show_account_names && !result.account_info.trim().is_empty() {
-                println!(
-                    "{}   {} {}",
-                    line_start,
-                    prefix,
-                    result.account_info.trim().green()
-                );
-            }
+    Ok(match opt.page() {
+        Some(page) => page.slice(results),
+        None => results,
+    })
+}
 
-            if show_uuids {
-                println!("{}   {} {}", line_start, prefix, result.uuid.yellow());
-            }
-        }
+/// Streams a page of results, picking out favorites and checking any terms the
+/// backend can't match as they arrive, so the backend can stop as soon as the
+/// page is full.
+fn stream<'a>(
+    backend: &'a api::DynBackend,
+    matcher: Option<&'a Matcher>,
+    favorites: bool,
+    page: Option<api::Page>,
+) -> BoxStream<'a, Result<api::ItemMetadata, api::BackendError>> {
+    let page = page.unwrap_or(api::Page::new(0, usize::MAX));
+
+    backend
+        .search_stream(matcher.and_then(Matcher::backend_terms))
+        .try_filter(move |v| {
+            futures::future::ready(
+                (!favorites || v.favorite)
+                    && matcher.map_or(true, |matcher| {
+                        matcher.backend_terms().is_some() || matcher.matches(v)
+                    }),
+            )
+        })
+        .skip(page.offset)
+        .take(page.limit)
+        .boxed()
+}
+
+/// Prints each result on its own line, tab separated from its details, as
+/// soon as the backend returns it, or once they've all been returned when
+/// they need sorting.
+async fn search_flat(
+    out: &mut Output,
+    backend: &api::DynBackend,
+    config: &config::Config,
+    matcher: Option<&Matcher>,
+    archived: bool,
+    opt: &ListOpt,
+) -> anyhow::Result<()> {
+    let mut results = if archived
+        || opt.sort.is_some()
+        || !opt.filter().is_empty()
+        || matcher.map_or(false, Matcher::is_deep)
+    {
+        futures::stream::iter(
+            results(backend, matcher, archived, opt)
+                .await?
+                .into_iter()
+                .map(Ok),
+        )
+        .boxed()
+    } else {
+        stream(backend, matcher, opt.favorites, opt.page())
+    };
+
+    while let Some(result) = results.try_next().await? {
+        let mut line = vec![result.title.normal()];
+        line.extend(details(&result, opt, &config.colors));
+
+        out.println(line.iter().join("\t"));
     }
 
     Ok(())
 }
 
-fn show(item: api::Item) {
+fn details(
+    result: &api::ItemMetadata,
+    opt: &ListOpt,
+    colors: &config::ColorsConfig,
+) -> Vec<ColoredString> {
+    let mut details = Vec::new();
+
+    if opt.show_account_names && !result.account_info.trim().is_empty() {
+        details.push(result.account_info.trim().color(colors.account.0));
+    }
+
+    if opt.show_tags && !result.tags.is_empty() {
+        details.push(result.tags.join(", ").dimmed());
+    }
+
+    if opt.show_dates {
+        if let Some(created) = result.created {
+            details.push(format!("created {}", date(created)).color(colors.dates.0));
+        }
+
+        if let Some(updated) = result.updated {
+            details.push(format!("updated {}", date(updated)).color(colors.dates.0));
+        }
+    }
+
+    if opt.show_uuids {
+        details.push(result.uuid.color(colors.uuid.0));
+    }
+
+    details
+}
+
+fn date(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Local>::from(time)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// SSH private keys are shown as their public key, which is what's needed
+/// to grant the key access to a server and safe to have on screen.
+fn public_key(private_key: &str) -> (String, String) {
+    let value = match ssh::Key::parse(private_key) {
+        Ok(key) => key.public_key(),
+        Err(e) => format!("<{}>", e),
+    };
+
+    ("public key".to_string(), value)
+}
+
+/// Shown in place of the value of secret fields.
+#[allow(clippy::non_ascii_literal)]
+const MASK: &str = "••••••••";
+
+/// The name and value a field's shown with: one-time passwords as their
+/// current code, SSH keys as their public key and other secrets masked unless
+/// they're revealed.
+fn displayed(field: api::ItemField, reveal: bool) -> (String, String) {
+    match field.field_type {
+        api::ItemFieldType::Totp => {
+            let value = match otp::TwoFactorAuth::try_from(field.value.as_str()) {
+                // generating a code uses it up, so that's left to the totp
+                // command which saves the counter afterwards
+                Ok(tfa) if tfa.is_counter_based() => "run 1p totp for a code".to_string(),
+                Ok(tfa) => tfa.generate().value,
+                Err(e) => e.to_string(),
+            };
+
+            (field.name, value)
+        }
+        api::ItemFieldType::SshKey => public_key(&field.value),
+        _ if !reveal && field.is_secret() => (field.name, MASK.to_string()),
+        _ => (field.name, field.value.as_str().to_string()),
+    }
+}
+
+/// Shows an item's fields, each numbered in the order `--copy` takes them.
+fn show(
+    out: &mut Output,
+    config: &config::Config,
+    item: api::Item,
+    show_attachments: bool,
+    reveal: bool,
+) {
+    let mut number = 0;
+
     let mut table = Table::new();
     table.style = TableStyle::extended();
 
@@ -209,13 +1165,29 @@ fn show(item: api::Item) {
     )]));
 
     for field in item.fields {
+        let (name, value) = displayed(field, reveal);
+
+        number += 1;
+        table.add_row(Row::new(vec![
+            TableCell::new(numbered(config, number, name)),
+            TableCell::new_with_alignment(value, 1, Alignment::Right),
+        ]));
+    }
+
+    for url in item.urls {
+        let label = if url.label.is_empty() {
+            "website".to_string()
+        } else {
+            url.label
+        };
+
         table.add_row(Row::new(vec![
-            TableCell::new(field.name),
-            TableCell::new_with_alignment(field.value, 1, Alignment::Right),
+            TableCell::new(label),
+            TableCell::new_with_alignment(url.url, 1, Alignment::Right),
         ]));
     }
 
-    println!("{}", table.render());
+    table::print(out, config, table);
 
     for section in item.sections {
         if section.fields.is_empty() {
@@ -234,20 +1206,108 @@ fn show(item: api::Item) {
         }
 
         for field in section.fields {
-            let mut value = field.value;
-
-            if field.field_type == api::ItemFieldType::Totp {
-                if let Ok(tfa) = otp::TwoFactorAuth::try_from(value.as_ref()) {
-                    value = tfa.generate().value;
-                }
-            }
+            let (name, value) = displayed(field, reveal);
 
+            number += 1;
             table.add_row(Row::new(vec![
-                TableCell::new(field.name),
+                TableCell::new(numbered(config, number, name)),
                 TableCell::new_with_alignment(value, 1, Alignment::Right),
             ]));
         }
 
-        println!("{}", table.render());
+        table::print(out, config, table);
+    }
+
+    if show_attachments && !item.attachments.is_empty() {
+        let mut table = Table::new();
+        table.style = TableStyle::extended();
+
+        table.add_row(Row::new(vec![TableCell::new_with_alignment(
+            "Attachments",
+            2,
+            Alignment::Center,
+        )]));
+
+        for attachment in item.attachments {
+            table.add_row(Row::new(vec![
+                TableCell::new(attachment.name),
+                TableCell::new_with_alignment(
+                    format!("{} bytes", attachment.size),
+                    1,
+                    Alignment::Right,
+                ),
+            ]));
+        }
+
+        table::print(out, config, table);
+    }
+}
+
+/// A field's name, prefixed with the number `--copy` takes unless only
+/// values were asked for.
+fn numbered(config: &config::Config, number: usize, name: String) -> String {
+    if config.quiet {
+        name
+    } else {
+        format!("{}. {}", number, name)
+    }
+}
+
+fn history(config: &config::Config, item: api::Item) {
+    let mut table = Table::new();
+    table.style = TableStyle::extended();
+
+    table.add_row(Row::new(vec![TableCell::new_with_alignment(
+        item.title,
+        2,
+        Alignment::Center,
+    )]));
+
+    if item.history.is_empty() {
+        table.add_row(Row::new(vec![TableCell::new_with_alignment(
+            "No previous passwords",
+            2,
+            Alignment::Center,
+        )]));
+    }
+
+    for entry in item.history {
+        let time = entry.time.map_or_else(|| "unknown".to_string(), date);
+
+        table.add_row(Row::new(vec![
+            TableCell::new(time),
+            TableCell::new_with_alignment(entry.value.as_str(), 1, Alignment::Right),
+        ]));
+    }
+
+    table::print(&mut Output::stdout(), config, table);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{displayed, MASK};
+    use onep_backend_api as api;
+
+    const URI: &str = "otpauth://totp/Example:jordan?secret=JBSWY3DPEHPK3PXP&issuer=Example";
+
+    #[test]
+    fn totp_fields_are_never_shown_raw() {
+        for &reveal in &[false, true] {
+            let field =
+                api::ItemField::new("one-time password", URI).with_type(api::ItemFieldType::Totp);
+            let (name, value) = displayed(field, reveal);
+
+            assert_eq!(name, "one-time password");
+            assert!(!value.contains("otpauth"), "{}", value);
+            assert!(!value.contains("JBSWY3DPEHPK3PXP"), "{}", value);
+        }
+    }
+
+    #[test]
+    fn passwords_are_masked_unless_revealed() {
+        let field = || api::ItemField::new("password", "hunter2");
+
+        assert_eq!(displayed(field(), false).1, MASK);
+        assert_eq!(displayed(field(), true).1, "hunter2");
     }
 }
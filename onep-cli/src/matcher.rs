@@ -0,0 +1,109 @@
+//! Decides which items match the terms given to `search`.
+
+use clap::Clap;
+use onep_backend_api as api;
+use regex::{Regex, RegexBuilder};
+
+/// Options controlling how search terms are matched.
+#[derive(Clap, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct MatchOpt {
+    /// Treat the terms as a regular expression, matched against titles,
+    /// account names, URLs and tags
+    #[clap(long, short = 'e')]
+    regex: bool,
+    /// Only match text with the same case as the terms
+    #[clap(long, short = 'c')]
+    case_sensitive: bool,
+    /// Only match the terms as whole words, rather than anywhere in a word
+    #[clap(long, short = 'w')]
+    word: bool,
+    /// Also search the names and values of every field, fetching each item in
+    /// full to do so
+    #[clap(long)]
+    deep: bool,
+}
+
+enum Pattern {
+    /// Plain terms, matched by the backend in whatever way it normally
+    /// searches.
+    Terms(String),
+    Regex(Regex),
+}
+
+pub struct Matcher {
+    pattern: Pattern,
+    /// Whether fields are searched as well as the item's metadata.
+    deep: bool,
+}
+
+impl Matcher {
+    /// Backends only know how to match terms case insensitively anywhere in
+    /// a word, anything else is turned into a regex and matched here.
+    pub fn new(terms: String, opt: &MatchOpt) -> Result<Self, regex::Error> {
+        let pattern = if !opt.regex && !opt.case_sensitive && !opt.word {
+            Pattern::Terms(terms)
+        } else {
+            let mut pattern = if opt.regex {
+                terms
+            } else {
+                regex::escape(&terms)
+            };
+
+            if opt.word {
+                pattern = format!(r"\b(?:{})\b", pattern);
+            }
+
+            Pattern::Regex(
+                RegexBuilder::new(&pattern)
+                    .case_insensitive(!opt.case_sensitive)
+                    .build()?,
+            )
+        };
+
+        Ok(Self {
+            pattern,
+            deep: opt.deep,
+        })
+    }
+
+    /// Terms to hand to the backend's own search, if it can do the matching.
+    /// Otherwise every item is fetched and checked with [`Matcher::matches`].
+    pub fn backend_terms(&self) -> Option<&str> {
+        match &self.pattern {
+            Pattern::Terms(terms) if !self.deep => Some(terms),
+            _ => None,
+        }
+    }
+
+    /// Whether full items need to be fetched and checked with
+    /// [`Matcher::matches_item`].
+    pub fn is_deep(&self) -> bool {
+        self.deep
+    }
+
+    pub fn matches(&self, item: &api::ItemMetadata) -> bool {
+        std::iter::once(&item.title)
+            .chain(std::iter::once(&item.account_info))
+            .chain(&item.urls)
+            .chain(&item.tags)
+            .any(|v| self.is_match(v))
+    }
+
+    /// Matches the names and values of an item's fields, including those in
+    /// sections and concealed ones.
+    pub fn matches_item(&self, item: &api::Item) -> bool {
+        item.fields
+            .iter()
+            .chain(item.sections.iter().flat_map(|v| &v.fields))
+            .any(|v| self.is_match(&v.name) || self.is_match(&v.value))
+            || item.urls.iter().any(|v| self.is_match(&v.url))
+    }
+
+    fn is_match(&self, haystack: &str) -> bool {
+        match &self.pattern {
+            Pattern::Terms(terms) => haystack.to_lowercase().contains(&terms.to_lowercase()),
+            Pattern::Regex(regex) => regex.is_match(haystack),
+        }
+    }
+}
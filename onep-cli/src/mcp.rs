@@ -0,0 +1,259 @@
+//! A [Model Context Protocol] server over stdio, so local AI assistants can
+//! look items up without being handed the vault. Only two tools are offered:
+//! searching item metadata, which never includes secrets, and fetching a
+//! single field, which asks the user on the terminal every time it's called.
+//!
+//! [Model Context Protocol]: https://modelcontextprotocol.io
+
+use crate::{config::Config, copy, totp, vault};
+use onep_backend_api as api;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("There's no terminal to ask the user on, so {0:?} wasn't shared. Run 1p mcp from a terminal to allow fields to be fetched.")]
+    NoTerminal(String),
+    #[error("The user declined to share {0:?}.")]
+    Declined(String),
+}
+
+/// A JSON-RPC request, or a notification when it has no id.
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct Call {
+    name: String,
+    #[serde(default)]
+    arguments: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct SearchArgs {
+    query: Option<String>,
+    vault: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GetFieldArgs {
+    item: String,
+    field: String,
+}
+
+pub async fn run(backend: &api::DynBackend, config: &Config) -> anyhow::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(req) => handle(backend, config, req).await,
+            Err(e) => Some(reply(&Value::Null, Err((PARSE_ERROR, e.to_string())))),
+        };
+
+        // stdout carries nothing but the protocol, everything meant for the
+        // user goes to stderr or the terminal
+        if let Some(response) = response {
+            println!("{}", response);
+        }
+    }
+
+    Ok(())
+}
+
+fn reply(id: &Value, res: Result<Value, (i64, String)>) -> Value {
+    match res {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    }
+}
+
+async fn handle(backend: &api::DynBackend, config: &Config, req: Request) -> Option<Value> {
+    // notifications, such as `notifications/initialized`, aren't answered
+    let id = req.id?;
+
+    let res = match req.method.as_str() {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "1p", "version": env!("CARGO_PKG_VERSION") },
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools() })),
+        "tools/call" => call(backend, config, req.params).await,
+        method => Err((METHOD_NOT_FOUND, format!("unknown method {}", method))),
+    };
+
+    Some(reply(&id, res))
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "search_items",
+            "description": "Search the user's password manager for items whose title, username or urls match the query, returning their metadata but never their passwords or other secrets. Leave the query out to list every item.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Words to search for" },
+                    "vault": { "type": "string", "description": "Name or uuid of the only vault to search" },
+                },
+            },
+        },
+        {
+            "name": "get_field",
+            "description": "Get the value of a single field of an item, such as its password or username. The user is asked to allow every call and may decline, so only ask for fields that are needed.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "item": { "type": "string", "description": "Uuid or title of the item" },
+                    "field": { "type": "string", "description": "Name of the field, such as password or username" },
+                },
+                "required": ["item", "field"],
+            },
+        },
+    ])
+}
+
+async fn call(
+    backend: &api::DynBackend,
+    config: &Config,
+    params: Value,
+) -> Result<Value, (i64, String)> {
+    let invalid = |e: serde_json::Error| (INVALID_PARAMS, e.to_string());
+
+    let call: Call = serde_json::from_value(params).map_err(invalid)?;
+    let arguments = call.arguments.unwrap_or_else(|| json!({}));
+
+    let res = match call.name.as_str() {
+        "search_items" => {
+            search_items(backend, serde_json::from_value(arguments).map_err(invalid)?).await
+        }
+        "get_field" => {
+            get_field(
+                backend,
+                config,
+                serde_json::from_value(arguments).map_err(invalid)?,
+            )
+            .await
+        }
+        name => return Err((INVALID_PARAMS, format!("unknown tool {}", name))),
+    };
+
+    eprintln!(
+        "{} {}",
+        call.name,
+        if res.is_ok() { "answered" } else { "failed" }
+    );
+
+    // failures are reported to the assistant rather than as protocol errors,
+    // so it can tell the user why
+    let (text, is_error) = match res {
+        Ok(text) => (text, false),
+        Err(e) => (e.to_string(), true),
+    };
+
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    }))
+}
+
+async fn search_items(backend: &api::DynBackend, args: SearchArgs) -> anyhow::Result<String> {
+    let vault = match &args.vault {
+        Some(vault) => Some(vault::find(backend, vault).await?.uuid),
+        None => None,
+    };
+
+    let vaults: HashMap<_, _> = backend
+        .vaults()
+        .await?
+        .into_iter()
+        .map(|v| (v.uuid, v.name))
+        .collect();
+
+    let items: Vec<_> = backend
+        .search(args.query.as_deref())
+        .await?
+        .into_iter()
+        .filter(|v| vault.as_ref().map_or(true, |vault| &v.vault_uuid == vault))
+        .map(|v| {
+            json!({
+                "uuid": v.uuid,
+                "title": v.title,
+                "vault": vaults.get(&v.vault_uuid).unwrap_or(&v.vault_uuid),
+                "category": v.category.name(),
+                "username": v.account_info,
+                "urls": v.urls,
+                "tags": v.tags,
+                "favorite": v.favorite,
+            })
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&items)?)
+}
+
+async fn get_field(
+    backend: &api::DynBackend,
+    config: &Config,
+    args: GetFieldArgs,
+) -> anyhow::Result<String> {
+    let (uuid, item) = totp::get(backend, config, &args.item).await?;
+    let field = copy::find_field(&item, &args.field)
+        .ok_or_else(|| copy::Error::NoField(item.title.clone(), args.field.clone()))?;
+
+    let prompt = format!(
+        "An assistant connected to 1p mcp wants the {} field of {} ({}). Share it?",
+        field.name, item.title, uuid
+    );
+
+    let allowed = confirm(&prompt).map_err(|_| Error::NoTerminal(field.name.clone()))?;
+
+    if !allowed {
+        return Err(Error::Declined(field.name.clone()).into());
+    }
+
+    Ok(field.value.as_str().to_string())
+}
+
+/// Asks the user a yes/no question on the terminal 1p was started from, as
+/// stdin and stdout belong to the assistant.
+fn confirm(prompt: &str) -> std::io::Result<bool> {
+    let mut tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")?;
+
+    write!(tty, "{} [y/N] ", prompt)?;
+    tty.flush()?;
+
+    let mut answer = String::new();
+    std::io::BufReader::new(tty).read_line(&mut answer)?;
+
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
@@ -1,81 +1,446 @@
 //! Handles OTP code generation
-use std::convert::TryFrom;
+use onep_backend_api as api;
+use std::{
+    convert::TryFrom,
+    time::{SystemTime, UNIX_EPOCH},
+};
 use url::Url;
 
+/// How often time based codes change, in seconds, unless the URI says
+/// otherwise.
+const DEFAULT_PERIOD: u32 = 30;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("The one-time password's secret isn't valid base32.")]
+    Base32,
+    #[error("{0}:// URIs aren't one-time passwords, expected otpauth://.")]
+    NotOtpauth(String),
+    #[error("The otpauth:// URI doesn't have a secret.")]
+    NoSecret,
+    #[error("{0:?} isn't a kind of one-time password, expected totp or hotp.")]
+    UnknownKind(String),
+    #[error("The {0} algorithm isn't supported, expected SHA1, SHA256 or SHA512.")]
+    Algorithm(String),
+    #[error("One-time passwords can't be {0:?} digits long, expected 6 to 8.")]
+    Digits(String),
+    #[error("One-time passwords can't change every {0:?} seconds.")]
+    Period(String),
+    #[error("The otpauth://hotp URI doesn't have a counter.")]
+    NoCounter,
+    #[error("{0:?} isn't a valid counter.")]
+    Counter(String),
+    #[error("That isn't an otpauth-migration:// URI exported from Google Authenticator.")]
+    Migration,
+}
+
+/// The item's one-time password field, wherever it is in the item.
+pub fn field(item: &api::Item) -> Option<&api::ItemField> {
+    item.fields
+        .iter()
+        .chain(item.sections.iter().flat_map(|v| &v.fields))
+        .find(|v| v.field_type == api::ItemFieldType::Totp)
+}
+
+/// Builds the `otpauth://` URI authenticator apps enroll from, for secrets
+/// that are stored on their own rather than as a URI already.
+pub fn uri(secret: &str, issuer: &str, account: &str) -> String {
+    if let Ok(url) = Url::parse(secret) {
+        if url.scheme() == "otpauth" {
+            return secret.to_string();
+        }
+    }
+
+    let label = if account.is_empty() {
+        issuer.to_string()
+    } else {
+        format!("{}:{}", issuer, account)
+    };
+
+    let mut url = Url::parse("otpauth://totp/").unwrap();
+    url.set_path(&label);
+    url.query_pairs_mut()
+        .append_pair("secret", &secret.replace(" ", "").to_uppercase())
+        .append_pair("issuer", issuer);
+
+    url.as_str().to_string()
+}
+
+/// The characters Steam Guard codes are written with, in place of digits.
+const STEAM_ALPHABET: &str = "23456789BCDFGHJKMNPQRTVWXY";
+
+/// Marks the item's one-time password as a Steam Guard one, for items the
+/// config says are even though their URI doesn't.
+pub fn mark_steam(item: &mut api::Item) {
+    let title = item.title.clone();
+    let field = item
+        .fields
+        .iter_mut()
+        .chain(item.sections.iter_mut().flat_map(|v| &mut v.fields))
+        .find(|v| v.field_type == api::ItemFieldType::Totp);
+
+    if let Some(field) = field {
+        if let Ok(mut url) = Url::parse(&uri(&field.value, &title, "")) {
+            if param(&url, "encoder").as_deref() != Some("steam") {
+                url.query_pairs_mut().append_pair("encoder", "steam");
+            }
+
+            field.value = url.as_str().into();
+        }
+    }
+}
+
 pub enum TwoFactorAuth {
-    Totp(libreauth::oath::TOTP),
+    /// Time based, changing every period, given in seconds.
+    Totp(libreauth::oath::TOTP, u32),
+    /// Counter based, where the code moves on each time one is generated
+    /// rather than over time. Keeps the URI it was parsed from to work out the
+    /// next one.
+    Hotp(libreauth::oath::HOTP, Url, u64),
 }
 
 pub struct TwoFactorAuthResponse {
     pub value: String,
+    /// For counter based passwords, the URI with the counter moved on, which
+    /// has to be saved to the item so the same code isn't given out again.
+    pub next: Option<String>,
+    /// For time based passwords, the seconds left until the code changes.
+    pub remaining: Option<u64>,
 }
 
 impl TwoFactorAuth {
     pub fn generate(&self) -> TwoFactorAuthResponse {
         match &self {
-            TwoFactorAuth::Totp(inner) => TwoFactorAuthResponse {
+            TwoFactorAuth::Totp(inner, period) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |v| v.as_secs());
+                let period = u64::from(*period);
+
+                TwoFactorAuthResponse {
+                    value: inner.generate(),
+                    next: None,
+                    remaining: Some(period - now % period),
+                }
+            }
+            TwoFactorAuth::Hotp(inner, url, counter) => TwoFactorAuthResponse {
                 value: inner.generate(),
+                next: Some(with_counter(url, counter + 1)),
+                remaining: None,
             },
         }
     }
+
+    pub fn is_counter_based(&self) -> bool {
+        matches!(self, TwoFactorAuth::Hotp(..))
+    }
 }
 
-impl TryFrom<&str> for TwoFactorAuth {
-    type Error = ();
+/// Replaces the `counter` parameter of an `otpauth://hotp` URI, keeping every
+/// other parameter as it was.
+fn with_counter(url: &Url, counter: u64) -> String {
+    let pairs: Vec<_> = url
+        .query_pairs()
+        .filter(|v| v.0 != "counter")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
 
-    fn try_from(key: &str) -> Result<TwoFactorAuth, ()> {
-        if let Ok(uri) = url::Url::parse(&key) {
-            if let Ok(tfa) = Self::try_from(uri) {
-                return Ok(tfa);
-            }
-        }
+    let mut url = url.clone();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair("counter", &counter.to_string());
 
-        Ok(TwoFactorAuth::Totp(
-            libreauth::oath::TOTPBuilder::new()
-                .base32_key(&key.replace(" ", ""))
-                .finalize()
-                .unwrap(),
-        ))
-    }
+    url.as_str().to_string()
 }
 
-impl TryFrom<Url> for TwoFactorAuth {
-    type Error = ();
+/// An account from a Google Authenticator export.
+pub struct MigratedAccount {
+    pub issuer: String,
+    /// Usually the account name, sometimes prefixed with the issuer.
+    pub name: String,
+    /// The equivalent `otpauth://` URI.
+    pub uri: String,
+}
 
-    fn try_from(url: Url) -> Result<TwoFactorAuth, ()> {
-        if url.scheme() != "otpauth" {
-            return Err(());
+impl MigratedAccount {
+    /// The title of the item the account's most likely for.
+    pub fn title(&self) -> &str {
+        if self.issuer.is_empty() {
+            self.name.split(':').next().unwrap_or(&self.name)
+        } else {
+            &self.issuer
         }
+    }
+}
+
+/// A field of a protobuf message, only as far as exports need.
+enum Protobuf<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+fn varint(data: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0;
+
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = data.split_first().ok_or(Error::Migration)?;
+        *data = rest;
+        value |= u64::from(byte & 0x7f) << shift;
 
-        if url.host_str() != Some("totp") {
-            return Err(());
+        if byte & 0x80 == 0 {
+            return Ok(value);
         }
+    }
+
+    Err(Error::Migration)
+}
+
+/// Reads a protobuf message into its field numbers and values, skipping
+/// fixed size values as exports don't use them.
+fn protobuf(mut data: &[u8]) -> Result<Vec<(u64, Protobuf<'_>)>, Error> {
+    let mut fields = Vec::new();
+
+    while !data.is_empty() {
+        let key = varint(&mut data)?;
+
+        let value = match key & 0x7 {
+            0 => Protobuf::Varint(varint(&mut data)?),
+            2 => {
+                let len = usize::try_from(varint(&mut data)?).map_err(|_| Error::Migration)?;
+                if len > data.len() {
+                    return Err(Error::Migration);
+                }
+
+                let (value, rest) = data.split_at(len);
+                data = rest;
+                Protobuf::Bytes(value)
+            }
+            1 => {
+                data = data.get(8..).ok_or(Error::Migration)?;
+                continue;
+            }
+            5 => {
+                data = data.get(4..).ok_or(Error::Migration)?;
+                continue;
+            }
+            _ => return Err(Error::Migration),
+        };
+
+        fields.push((key >> 3, value));
+    }
 
-        let mut query = url.query_pairs();
+    Ok(fields)
+}
+
+/// Reads the accounts in an `otpauth-migration://offline?data=...` URI, the
+/// protobuf encoded batches Google Authenticator exports accounts in.
+pub fn migration(uri: &str) -> Result<Vec<MigratedAccount>, Error> {
+    let url = Url::parse(uri).map_err(|_| Error::Migration)?;
+
+    if url.scheme() != "otpauth-migration" {
+        return Err(Error::Migration);
+    }
+
+    // the data's base64, whose pluses become spaces if they weren't escaped
+    let data = param(&url, "data")
+        .ok_or(Error::Migration)?
+        .replace(' ', "+");
+    let data = base64::decode(&data).map_err(|_| Error::Migration)?;
+
+    let mut accounts = Vec::new();
+
+    for (number, value) in protobuf(&data)? {
+        let parameters = match (number, value) {
+            (1, Protobuf::Bytes(parameters)) => parameters,
+            _ => continue,
+        };
 
-        let mut builder = &mut libreauth::oath::TOTPBuilder::new();
+        let mut secret = None;
+        let mut name = String::new();
+        let mut issuer = String::new();
+        let mut algorithm = None;
+        let mut digits = None;
+        let mut counter = None;
+        let mut kind = "totp";
 
-        if let Some(secret) = query.find(|v| v.0 == "secret") {
-            builder = builder.base32_key(&secret.1);
+        for (number, value) in protobuf(parameters)? {
+            match (number, value) {
+                (1, Protobuf::Bytes(v)) => {
+                    secret = Some(base32::encode(
+                        base32::Alphabet::RFC4648 { padding: false },
+                        v,
+                    ));
+                }
+                (2, Protobuf::Bytes(v)) => name = String::from_utf8_lossy(v).into_owned(),
+                (3, Protobuf::Bytes(v)) => issuer = String::from_utf8_lossy(v).into_owned(),
+                (4, Protobuf::Varint(2)) => algorithm = Some("SHA256"),
+                (4, Protobuf::Varint(3)) => algorithm = Some("SHA512"),
+                (5, Protobuf::Varint(2)) => digits = Some("8"),
+                (6, Protobuf::Varint(1)) => kind = "hotp",
+                (7, Protobuf::Varint(v)) => counter = Some(v),
+                _ => {}
+            }
         }
 
-        if let Some(digits) = query.find(|v| v.0 == "digits") {
-            builder = builder.output_len(digits.1.parse().map_err(|_| ())?);
+        let secret = secret.ok_or(Error::Migration)?;
+
+        let mut url = Url::parse(&format!("otpauth://{}/", kind)).map_err(|_| Error::Migration)?;
+        if issuer.is_empty() || name.contains(':') {
+            url.set_path(&name);
+        } else {
+            url.set_path(&format!("{}:{}", issuer, name));
         }
 
-        if let Some(algorithm) = query.find(|v| v.0 == "algorithm") {
-            builder = builder.hash_function(match algorithm.1.as_ref() {
-                "sha1" => libreauth::hash::HashFunction::Sha1,
-                "sha256" => libreauth::hash::HashFunction::Sha256,
-                "sha512" => libreauth::hash::HashFunction::Sha512,
-                _ => return Err(()),
-            });
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("secret", &secret);
+
+            if !issuer.is_empty() {
+                query.append_pair("issuer", &issuer);
+            }
+            if let Some(algorithm) = algorithm {
+                query.append_pair("algorithm", algorithm);
+            }
+            if let Some(digits) = digits {
+                query.append_pair("digits", digits);
+            }
+            if kind == "hotp" {
+                query.append_pair("counter", &counter.unwrap_or(0).to_string());
+            }
         }
 
-        if let Some(period) = query.find(|v| v.0 == "period") {
-            builder = builder.period(period.1.parse().map_err(|_| ())?);
+        accounts.push(MigratedAccount {
+            issuer,
+            name,
+            uri: url.as_str().to_string(),
+        });
+    }
+
+    Ok(accounts)
+}
+
+fn param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|v| v.0 == name)
+        .map(|v| v.1.into_owned())
+}
+
+fn hash_function(url: &Url) -> Result<libreauth::hash::HashFunction, Error> {
+    match param(url, "algorithm") {
+        None => Ok(libreauth::hash::HashFunction::Sha1),
+        Some(algorithm) => match algorithm.to_lowercase().as_str() {
+            "sha1" => Ok(libreauth::hash::HashFunction::Sha1),
+            "sha256" => Ok(libreauth::hash::HashFunction::Sha256),
+            "sha512" => Ok(libreauth::hash::HashFunction::Sha512),
+            _ => Err(Error::Algorithm(algorithm)),
+        },
+    }
+}
+
+/// Decodes a base32 secret, which is often shown in groups separated by
+/// spaces and sometimes in lowercase.
+fn key(secret: &str) -> Result<Vec<u8>, Error> {
+    let secret = secret.replace(" ", "");
+
+    base32::decode(base32::Alphabet::RFC4648 { padding: false }, &secret)
+        .filter(|v| !v.is_empty())
+        .ok_or(Error::Base32)
+}
+
+/// The number of digits in each code, which the spec allows to be 6 to 8.
+fn digits(url: &Url) -> Result<usize, Error> {
+    match param(url, "digits") {
+        None => Ok(6),
+        Some(digits) => match digits.parse() {
+            Ok(v) if (6..=8).contains(&v) => Ok(v),
+            _ => Err(Error::Digits(digits)),
+        },
+    }
+}
+
+fn period(url: &Url) -> Result<u32, Error> {
+    match param(url, "period") {
+        None => Ok(DEFAULT_PERIOD),
+        Some(period) => match period.parse() {
+            Ok(v) if v > 0 => Ok(v),
+            _ => Err(Error::Period(period)),
+        },
+    }
+}
+
+impl TryFrom<&str> for TwoFactorAuth {
+    type Error = Error;
+
+    /// Takes either an `otpauth://` URI or a bare base32 secret, as some
+    /// password managers only store the latter.
+    fn try_from(key: &str) -> Result<TwoFactorAuth, Error> {
+        match url::Url::parse(&key) {
+            Ok(uri) if uri.scheme() == "otpauth" => Self::try_from(uri),
+            _ => Ok(TwoFactorAuth::Totp(
+                libreauth::oath::TOTPBuilder::new()
+                    .key(&self::key(key)?)
+                    .finalize()
+                    .map_err(|_| Error::Base32)?,
+                DEFAULT_PERIOD,
+            )),
+        }
+    }
+}
+
+impl TryFrom<Url> for TwoFactorAuth {
+    type Error = Error;
+
+    fn try_from(url: Url) -> Result<TwoFactorAuth, Error> {
+        if url.scheme() != "otpauth" {
+            return Err(Error::NotOtpauth(url.scheme().to_string()));
         }
 
-        Ok(TwoFactorAuth::Totp(builder.finalize().unwrap()))
+        let secret = key(&param(&url, "secret").ok_or(Error::NoSecret)?)?;
+
+        match url.host_str() {
+            // Steam ignores the other parameters, its codes are always five
+            // characters and change every 30 seconds
+            Some("totp") if param(&url, "encoder").as_deref() == Some("steam") => {
+                Ok(TwoFactorAuth::Totp(
+                    libreauth::oath::TOTPBuilder::new()
+                        .key(&secret)
+                        .output_len(5)
+                        .output_base(STEAM_ALPHABET)
+                        .finalize()
+                        .map_err(|_| Error::Base32)?,
+                    DEFAULT_PERIOD,
+                ))
+            }
+            Some("totp") => {
+                let period = period(&url)?;
+
+                Ok(TwoFactorAuth::Totp(
+                    libreauth::oath::TOTPBuilder::new()
+                        .key(&secret)
+                        .output_len(digits(&url)?)
+                        .hash_function(hash_function(&url)?)
+                        .period(period)
+                        .finalize()
+                        .map_err(|_| Error::Base32)?,
+                    period,
+                ))
+            }
+            Some("hotp") => {
+                let counter = param(&url, "counter").ok_or(Error::NoCounter)?;
+                let counter = counter.parse().map_err(|_| Error::Counter(counter))?;
+
+                let inner = libreauth::oath::HOTPBuilder::new()
+                    .key(&secret)
+                    .output_len(digits(&url)?)
+                    .hash_function(hash_function(&url)?)
+                    .counter(counter)
+                    .finalize()
+                    .map_err(|_| Error::Base32)?;
+
+                Ok(TwoFactorAuth::Hotp(inner, url, counter))
+            }
+            kind => Err(Error::UnknownKind(kind.unwrap_or_default().to_string())),
+        }
     }
 }
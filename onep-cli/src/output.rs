@@ -0,0 +1,127 @@
+//! Prints the output of commands like `list` and `show`, piping it through a
+//! pager the way git does when it's too long to fit on the terminal.
+
+use crate::config::Config;
+use ratatui::crossterm::terminal;
+use std::{
+    fmt::Display,
+    io::Write,
+    process::{Child, Command, Stdio},
+};
+
+/// Used when `PAGER` isn't set.
+const DEFAULT_PAGER: &str = "less -R";
+
+enum Sink {
+    Stdout,
+    /// Output held back until it's known whether it fits on a terminal this
+    /// many rows tall.
+    Held(String, usize),
+    Pager(Child),
+    /// The pager was quit, or whatever stdout was piped to stopped reading,
+    /// before everything was written, so the rest is dropped.
+    Closed,
+}
+
+/// Where a command's output goes. Anything held back is printed, or the pager
+/// waited on, when it's dropped.
+pub struct Output(Sink);
+
+impl Output {
+    /// Output that's paged when it's going to a terminal it won't fit on,
+    /// unless `--no-pager` was given.
+    pub fn new(config: &Config) -> Self {
+        if config.no_pager || !atty::is(atty::Stream::Stdout) {
+            return Self::stdout();
+        }
+
+        match terminal::size() {
+            Ok((_, rows)) => Self(Sink::Held(String::new(), rows.into())),
+            Err(_) => Self::stdout(),
+        }
+    }
+
+    /// Output that's never paged.
+    pub fn stdout() -> Self {
+        Self(Sink::Stdout)
+    }
+
+    /// Writes `text` followed by a newline, like `println!`.
+    pub fn println(&mut self, text: impl Display) {
+        self.write(&format!("{}\n", text));
+    }
+
+    fn write(&mut self, text: &str) {
+        match &mut self.0 {
+            Sink::Stdout => {
+                if std::io::stdout().write_all(text.as_bytes()).is_err() {
+                    self.0 = Sink::Closed;
+                }
+            }
+            Sink::Held(held, rows) => {
+                held.push_str(text);
+
+                if held.matches('\n').count() > *rows {
+                    let held = std::mem::take(held);
+                    self.0 = pager();
+                    self.write(&held);
+                }
+            }
+            Sink::Pager(pager) => {
+                let written = pager
+                    .stdin
+                    .as_mut()
+                    .map_or(Ok(()), |v| v.write_all(text.as_bytes()));
+
+                if written.is_err() {
+                    self.0 = Sink::Closed;
+                }
+            }
+            Sink::Closed => {}
+        }
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        match std::mem::replace(&mut self.0, Sink::Closed) {
+            Sink::Held(held, _) => {
+                // nothing's left to do if stdout's been closed
+                std::io::stdout().write_all(held.as_bytes()).ok();
+            }
+            Sink::Pager(mut pager) => {
+                // closing its stdin lets the pager know there's no more to come
+                drop(pager.stdin.take());
+
+                if let Err(e) = pager.wait() {
+                    eprintln!("Failed to wait for the pager: {}", e);
+                }
+            }
+            Sink::Stdout | Sink::Closed => {}
+        }
+    }
+}
+
+/// Starts `PAGER`, falling back to printing straight to stdout when it's set
+/// to nothing or `cat`, or can't be started.
+fn pager() -> Sink {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+    let mut args = pager.split_whitespace();
+
+    let program = match args.next() {
+        Some(program) if program != "cat" => program,
+        _ => return Sink::Stdout,
+    };
+
+    match Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => Sink::Pager(child),
+        Err(e) => {
+            eprintln!("Couldn't start the pager {:?}: {}", pager, e);
+            Sink::Stdout
+        }
+    }
+}
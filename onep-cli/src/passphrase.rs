@@ -0,0 +1,117 @@
+//! Generates diceware passphrases locally, without involving the backend
+//! unless the result is being saved.
+
+use crate::{config::Config, output::Output};
+use clap::Clap;
+use onep_backend_api as api;
+use rand::{rngs::OsRng, seq::SliceRandom};
+use std::path::{Path, PathBuf};
+
+const WORDLIST_URL: &str = "https://www.eff.org/files/2016/07/18/eff_large_wordlist.txt";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Couldn't find a wordlist at {0}, download the EFF large wordlist from {} to there or pass one with --wordlist", WORDLIST_URL)]
+    Missing(PathBuf),
+    #[error("Failed to read wordlist {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("Wordlist {0} doesn't contain any words")]
+    Empty(PathBuf),
+}
+
+#[derive(Clap, Debug)]
+pub struct PassphraseOpt {
+    /// Number of words in the passphrase
+    #[clap(long, short = 'w', default_value = "6")]
+    words: usize,
+    /// String to join the words with
+    #[clap(long, short = 's', default_value = "-")]
+    separator: String,
+    /// Wordlist to pick words from, either one word per line or in the EFF
+    /// diceware format. Defaults to the EFF large wordlist in 1p's data
+    /// directory
+    #[clap(long, parse(from_os_str))]
+    wordlist: Option<PathBuf>,
+    /// Save the passphrase as a new login with this name
+    #[clap(long)]
+    save: Option<String>,
+    /// Username to associate with the saved login
+    #[clap(long, short = 'n', requires = "save")]
+    username: Option<String>,
+    /// URL to associate with the saved login
+    #[clap(long, short = 'u', requires = "save")]
+    url: Option<String>,
+    /// Comma-separated list of tags to associate with the saved login
+    #[clap(long, short = 't', requires = "save")]
+    tags: Option<String>,
+}
+
+fn default_wordlist() -> Option<PathBuf> {
+    dirs::data_dir().map(|v| v.join("1p").join("eff_large_wordlist.txt"))
+}
+
+/// Reads a wordlist, taking the last column of each line so the dice rolls in
+/// the EFF lists are skipped.
+fn read_wordlist(path: &Path) -> Result<Vec<String>, Error> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Error::Missing(path.to_path_buf()))
+        }
+        Err(e) => return Err(Error::Read(path.to_path_buf(), e)),
+    };
+
+    let words: Vec<_> = contents
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .map(ToString::to_string)
+        .collect();
+
+    if words.is_empty() {
+        return Err(Error::Empty(path.to_path_buf()));
+    }
+
+    Ok(words)
+}
+
+pub async fn run(
+    backend: &api::DynBackend,
+    config: &Config,
+    opt: PassphraseOpt,
+) -> anyhow::Result<()> {
+    let path = opt
+        .wordlist
+        .or_else(default_wordlist)
+        .unwrap_or_else(|| PathBuf::from("eff_large_wordlist.txt"));
+    let wordlist = read_wordlist(&path)?;
+
+    let passphrase = (0..opt.words)
+        .filter_map(|_| wordlist.choose(&mut OsRng))
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(&opt.separator);
+
+    match opt.save {
+        Some(name) => {
+            let vault = match &config.vault {
+                Some(vault) => Some(crate::vault::find(backend, vault).await?.uuid),
+                None => None,
+            };
+
+            let result = backend
+                .create(
+                    &name,
+                    opt.username.as_deref(),
+                    opt.url.as_deref(),
+                    opt.tags.as_deref(),
+                    vault.as_deref(),
+                    &passphrase,
+                )
+                .await?;
+            crate::show(&mut Output::stdout(), config, result, false, true);
+        }
+        None => println!("{}", passphrase),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+//! Writes files only the current user can read, for anything with secrets in
+//! it, such as exports, rendered templates and the item cache.
+
+use std::{fs::File, io::Write, path::Path};
+
+/// Opens `path` for writing, truncating it, with mode 0600 on unix. A file
+/// that's already there is made private too, before anything is written.
+pub fn create(path: impl AsRef<Path>) -> std::io::Result<File> {
+    let mut file = std::fs::OpenOptions::new();
+    file.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        file.mode(0o600);
+    }
+
+    let file = file.open(path)?;
+
+    // the mode above is only used when the file's created
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(file)
+}
+
+/// Writes `contents` to `path`, like [`std::fs::write`] but private.
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    create(path)?.write_all(contents.as_ref())
+}
@@ -0,0 +1,47 @@
+//! Renders QR codes as text, for scanning straight off the terminal, and
+//! reads them back out of images.
+
+use qrcode::{render::unicode::Dense1x2, types::QrError, QrCode};
+use std::path::Path;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to render a QR code: {0}")]
+    Render(#[from] QrError),
+    #[error("Failed to open the image: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Couldn't find a QR code in the image.")]
+    NotFound,
+    #[error("Failed to read the QR code: {0}")]
+    Decode(#[from] rqrr::DeQRError),
+}
+
+/// Renders two rows of modules to a line. Terminals usually draw light text
+/// on a dark background, so the colours are swapped to come out dark on
+/// light as scanners expect.
+pub fn render(data: &str) -> Result<String, Error> {
+    Ok(QrCode::new(data)?
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build())
+}
+
+/// Reads the first QR code found in an image, such as a screenshot of a
+/// site's two-factor setup page.
+#[allow(clippy::cast_possible_truncation)]
+pub fn decode(path: &Path) -> Result<String, Error> {
+    let image = image::open(path)?.to_luma8();
+
+    // the coordinates are always within the image, so fit in a u32
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| image.get_pixel(x as u32, y as u32).0[0],
+    );
+
+    let grids = prepared.detect_grids();
+    let (_, content) = grids.first().ok_or(Error::NotFound)?.decode()?;
+
+    Ok(content)
+}
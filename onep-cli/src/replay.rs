@@ -0,0 +1,473 @@
+//! Records what a backend returns to a fixture file and plays it back later
+//! without the backend, so the CLI's output can be checked against a
+//! snapshot without op, an account or a network connection.
+//!
+//! `ONEP_RECORD=<file>` wraps whichever backend is configured, keeping every
+//! result it returns, keyed by the call and its arguments. Secrets in items,
+//! along with the contents of documents and attachments, are replaced before
+//! anything's written, as are passwords given when creating items. Recording
+//! again to the same file adds to it. `ONEP_REPLAY=<file>` then answers
+//! every call from the file, failing those it has nothing recorded for.
+
+use futures::Future;
+use onep_backend_api as api;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// What secrets are replaced with.
+const SCRUBBED: &str = "scrubbed";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to read or write fixtures: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The fixtures are corrupt: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Nothing was recorded for {0}, record it with ONEP_RECORD first")]
+    Missing(String),
+}
+
+/// What went wrong with a call that failed, so the same kind of
+/// [`api::BackendError`] can be given back.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    NotFound,
+    AuthRequired,
+    PermissionDenied,
+    Network,
+    Parse,
+    Unsupported,
+    Other,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Recorded {
+    Ok(serde_json::Value),
+    Err { kind: Kind, message: String },
+}
+
+impl Recorded {
+    fn from_error(e: &api::BackendError) -> Self {
+        let kind = match e {
+            api::BackendError::NotFound(_) => Kind::NotFound,
+            api::BackendError::AuthRequired(_) => Kind::AuthRequired,
+            api::BackendError::PermissionDenied(_) => Kind::PermissionDenied,
+            api::BackendError::Network(_) => Kind::Network,
+            api::BackendError::Parse(_) => Kind::Parse,
+            api::BackendError::Unsupported(_) => Kind::Unsupported,
+            _ => Kind::Other,
+        };
+
+        Self::Err {
+            kind,
+            message: e.to_string(),
+        }
+    }
+
+    fn into_result<T: DeserializeOwned>(self) -> Result<T, api::BackendError> {
+        match self {
+            Self::Ok(value) => serde_json::from_value(value)
+                .map_err(|e| api::BackendError::Parse(Error::from(e).into())),
+            Self::Err { kind, message } => Err(match kind {
+                Kind::NotFound => api::BackendError::NotFound(message.into()),
+                Kind::AuthRequired => api::BackendError::AuthRequired(message.into()),
+                Kind::PermissionDenied => api::BackendError::PermissionDenied(message.into()),
+                Kind::Network => api::BackendError::Network(message.into()),
+                Kind::Parse => api::BackendError::Parse(message.into()),
+                // the reason's only known when it's written, so it comes
+                // back as an error of its own
+                Kind::Unsupported | Kind::Other => api::BackendError::Other(message.into()),
+            }),
+        }
+    }
+}
+
+/// Replaces anything secret in a result before it's recorded.
+trait Scrub {
+    fn scrub(&mut self) {}
+}
+
+impl Scrub for () {}
+impl Scrub for api::Capabilities {}
+impl Scrub for api::AccountMetadata {}
+impl Scrub for api::VaultMetadata {}
+impl Scrub for api::ItemMetadata {}
+impl Scrub for api::DocumentMetadata {}
+
+impl Scrub for api::Item {
+    fn scrub(&mut self) {
+        let fields = self
+            .fields
+            .iter_mut()
+            .chain(self.sections.iter_mut().flat_map(|v| &mut v.fields));
+
+        for field in fields {
+            if field.is_secret() {
+                field.value = SCRUBBED.into();
+            }
+        }
+
+        // history only holds old passwords
+        for value in &mut self.history {
+            value.value = SCRUBBED.into();
+        }
+    }
+}
+
+impl<T: Scrub> Scrub for Option<T> {
+    fn scrub(&mut self) {
+        if let Some(v) = self {
+            v.scrub();
+        }
+    }
+}
+
+impl<T: Scrub> Scrub for Vec<T> {
+    fn scrub(&mut self) {
+        for v in self {
+            v.scrub();
+        }
+    }
+}
+
+impl<A: Scrub, B: Scrub> Scrub for (A, B) {
+    fn scrub(&mut self) {
+        self.0.scrub();
+        self.1.scrub();
+    }
+}
+
+/// The contents of a document or attachment.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+struct Contents(Vec<u8>);
+
+impl Scrub for Contents {
+    fn scrub(&mut self) {
+        self.0 = SCRUBBED.as_bytes().to_vec();
+    }
+}
+
+/// Keys a call by the method's name and its arguments.
+fn key(method: &str, args: impl Debug) -> String {
+    format!("{} {:?}", method, args)
+}
+
+/// Wraps a backend, recording what it returns, or stands in for one by
+/// playing back what was recorded.
+pub struct Fixtures {
+    /// The backend being recorded, or `None` when replaying.
+    inner: Option<Box<api::DynBackend>>,
+    path: PathBuf,
+    recorded: Mutex<BTreeMap<String, Recorded>>,
+}
+
+impl Fixtures {
+    /// Records calls to `inner` to the file at `path`, adding to anything
+    /// already there.
+    pub fn record(inner: Box<api::DynBackend>, path: PathBuf) -> Result<Self, Error> {
+        let recorded = if path.exists() {
+            load(&path)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            inner: Some(inner),
+            path,
+            recorded: Mutex::new(recorded),
+        })
+    }
+
+    /// Plays back the calls recorded to the file at `path`.
+    pub fn replay(path: PathBuf) -> Result<Self, Error> {
+        Ok(Self {
+            recorded: Mutex::new(load(&path)?),
+            inner: None,
+            path,
+        })
+    }
+
+    /// Makes a call to the backend being recorded, or looks up what it
+    /// returned when replaying.
+    async fn call<'a, T, F, Fut>(&'a self, key: String, call: F) -> Result<T, api::BackendError>
+    where
+        T: Serialize + DeserializeOwned + Clone + Scrub,
+        F: FnOnce(&'a api::DynBackend) -> Fut,
+        Fut: Future<Output = Result<T, api::BackendError>>,
+    {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return self.replayed(key),
+        };
+
+        let res = call(inner.as_ref()).await;
+
+        let recorded = match &res {
+            Ok(v) => {
+                let mut v = v.clone();
+                v.scrub();
+                Recorded::Ok(serde_json::to_value(v).map_err(Error::from)?)
+            }
+            Err(e) => Recorded::from_error(e),
+        };
+
+        let mut recorded_calls = self.recorded.lock().unwrap();
+        recorded_calls.insert(key, recorded);
+        save(&self.path, &recorded_calls)?;
+
+        res
+    }
+
+    fn replayed<T: DeserializeOwned>(&self, key: String) -> Result<T, api::BackendError> {
+        let recorded = self.recorded.lock().unwrap().get(&key).cloned();
+
+        match recorded {
+            Some(recorded) => recorded.into_result(),
+            None => Err(Error::Missing(key).into()),
+        }
+    }
+}
+
+impl From<Error> for api::BackendError {
+    fn from(e: Error) -> Self {
+        Self::Other(e.into())
+    }
+}
+
+fn load(path: &Path) -> Result<BTreeMap<String, Recorded>, Error> {
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+/// Writes every call recorded so far, sorted so re-recording only changes
+/// what the backend answers differently.
+fn save(path: &Path, recorded: &BTreeMap<String, Recorded>) -> Result<(), Error> {
+    let mut contents = serde_json::to_vec_pretty(recorded)?;
+    contents.push(b'\n');
+    std::fs::write(path, contents)?;
+
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl api::Backend for Fixtures {
+    type Error = api::BackendError;
+
+    async fn capabilities(&self) -> Result<api::Capabilities, Self::Error> {
+        self.call(key("capabilities", ()), |b| b.capabilities())
+            .await
+    }
+
+    async fn signin(&self, account: Option<&str>) -> Result<(), Self::Error> {
+        self.call(key("signin", account), |b| b.signin(account))
+            .await
+    }
+
+    async fn signout(&self) -> Result<(), Self::Error> {
+        self.call(key("signout", ()), |b| b.signout()).await
+    }
+
+    async fn account(&self) -> Result<api::AccountMetadata, Self::Error> {
+        self.call(key("account", ()), |b| b.account()).await
+    }
+
+    async fn vaults(&self) -> Result<Vec<api::VaultMetadata>, Self::Error> {
+        self.call(key("vaults", ()), |b| b.vaults()).await
+    }
+
+    async fn search(&self, terms: Option<&str>) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        self.call(key("search", terms), |b| b.search(terms)).await
+    }
+
+    async fn search_page(
+        &self,
+        terms: Option<&str>,
+        page: api::Page,
+    ) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        self.call(key("search_page", (terms, page)), |b| {
+            b.search_page(terms, page)
+        })
+        .await
+    }
+
+    async fn search_filtered(
+        &self,
+        terms: Option<&str>,
+        filter: api::Filter<'_>,
+    ) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        self.call(key("search_filtered", (terms, filter)), |b| {
+            b.search_filtered(terms, filter)
+        })
+        .await
+    }
+
+    async fn get(&self, uuid: &str) -> Result<Option<api::Item>, Self::Error> {
+        self.call(key("get", uuid), |b| b.get(uuid)).await
+    }
+
+    async fn get_many(&self, uuids: &[String]) -> Result<Vec<Option<api::Item>>, Self::Error> {
+        self.call(key("get_many", uuids), |b| b.get_many(uuids))
+            .await
+    }
+
+    async fn generate(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        recipe: &api::PasswordRecipe,
+    ) -> Result<api::Item, Self::Error> {
+        let key = key("generate", (name, username, url, tags, vault, recipe));
+
+        self.call(key, |b| {
+            b.generate(name, username, url, tags, vault, recipe)
+        })
+        .await
+    }
+
+    /// The password's left out of the key, so it isn't written anywhere.
+    async fn create(
+        &self,
+        name: &str,
+        username: Option<&str>,
+        url: Option<&str>,
+        tags: Option<&str>,
+        vault: Option<&str>,
+        password: &str,
+    ) -> Result<api::Item, Self::Error> {
+        let key = key("create", (name, username, url, tags, vault));
+
+        self.call(key, |b| {
+            b.create(name, username, url, tags, vault, password)
+        })
+        .await
+    }
+
+    /// Only the item's title goes in the key, so none of its fields are
+    /// written anywhere.
+    async fn create_item(
+        &self,
+        item: &api::Item,
+        category: api::ItemCategory,
+        tags: &[String],
+        vault: Option<&str>,
+    ) -> Result<api::Item, Self::Error> {
+        let key = key("create_item", (&item.title, category, tags, vault));
+
+        self.call(key, |b| b.create_item(item, category, tags, vault))
+            .await
+    }
+
+    async fn create_vault(&self, name: &str) -> Result<api::VaultMetadata, Self::Error> {
+        self.call(key("create_vault", name), |b| b.create_vault(name))
+            .await
+    }
+
+    async fn delete_vault(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.call(key("delete_vault", uuid), |b| b.delete_vault(uuid))
+            .await
+    }
+
+    async fn rename_vault(&self, uuid: &str, name: &str) -> Result<(), Self::Error> {
+        self.call(key("rename_vault", (uuid, name)), |b| {
+            b.rename_vault(uuid, name)
+        })
+        .await
+    }
+
+    /// Field values are left out of the key, as they may be secrets.
+    async fn edit(&self, uuid: &str, edit: &api::ItemEdit) -> Result<(), Self::Error> {
+        let fields: Vec<_> = edit.fields.iter().map(|v| &v.name).collect();
+        let key = key("edit", (uuid, &edit.title, fields));
+
+        self.call(key, |b| b.edit(uuid, edit)).await
+    }
+
+    async fn set_tags(&self, uuid: &str, tags: &[String]) -> Result<(), Self::Error> {
+        self.call(key("set_tags", (uuid, tags)), |b| b.set_tags(uuid, tags))
+            .await
+    }
+
+    async fn set_favorite(&self, uuid: &str, favorite: bool) -> Result<(), Self::Error> {
+        self.call(key("set_favorite", (uuid, favorite)), |b| {
+            b.set_favorite(uuid, favorite)
+        })
+        .await
+    }
+
+    /// The uri's left out of the key, as it holds the secret.
+    async fn set_otp(&self, uuid: &str, uri: &str) -> Result<(), Self::Error> {
+        self.call(key("set_otp", uuid), |b| b.set_otp(uuid, uri))
+            .await
+    }
+
+    async fn archived(&self) -> Result<Vec<api::ItemMetadata>, Self::Error> {
+        self.call(key("archived", ()), |b| b.archived()).await
+    }
+
+    async fn archive(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.call(key("archive", uuid), |b| b.archive(uuid)).await
+    }
+
+    async fn restore(&self, uuid: &str) -> Result<(), Self::Error> {
+        self.call(key("restore", uuid), |b| b.restore(uuid)).await
+    }
+
+    async fn documents(&self) -> Result<Vec<api::DocumentMetadata>, Self::Error> {
+        self.call(key("documents", ()), |b| b.documents()).await
+    }
+
+    async fn get_document(&self, uuid: &str) -> Result<Option<Vec<u8>>, Self::Error> {
+        let contents = self
+            .call(key("get_document", uuid), |b| async move {
+                Ok(b.get_document(uuid).await?.map(Contents))
+            })
+            .await?;
+
+        Ok(contents.map(|v| v.0))
+    }
+
+    async fn create_document(
+        &self,
+        path: &Path,
+        title: Option<&str>,
+        vault: Option<&str>,
+    ) -> Result<api::DocumentMetadata, Self::Error> {
+        self.call(key("create_document", (path, title, vault)), |b| {
+            b.create_document(path, title, vault)
+        })
+        .await
+    }
+
+    async fn get_attachment(
+        &self,
+        item_uuid: &str,
+        attachment_uuid: &str,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        let contents = self
+            .call(
+                key("get_attachment", (item_uuid, attachment_uuid)),
+                |b| async move {
+                    Ok(b.get_attachment(item_uuid, attachment_uuid)
+                        .await?
+                        .map(Contents))
+                },
+            )
+            .await?;
+
+        Ok(contents.map(|v| v.0))
+    }
+
+    async fn get_all(&self) -> Result<Vec<(api::ItemMetadata, api::Item)>, Self::Error> {
+        self.call(key("get_all", ()), |b| b.get_all()).await
+    }
+}
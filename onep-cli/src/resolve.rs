@@ -0,0 +1,131 @@
+//! Resolves secret references of the form `op://vault/item/[section/]field`
+//! to their values, for commands that hand secrets to other programs.
+
+use crate::vault;
+use onep_backend_api as api;
+use std::collections::HashMap;
+
+pub const PREFIX: &str = "op://";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid secret reference {0}, expected op://vault/item/[section/]field")]
+    InvalidReference(String),
+    #[error("Couldn't find an item named {1} in vault {0}")]
+    ItemNotFound(String, String),
+    #[error("Couldn't find field {0} referenced by {1}")]
+    FieldNotFound(String, String),
+}
+
+/// A parsed secret reference. Vaults and items can be referred to by either
+/// their name or uuid.
+#[derive(Debug)]
+struct Reference<'a> {
+    vault: &'a str,
+    item: &'a str,
+    section: Option<&'a str>,
+    field: &'a str,
+}
+
+impl<'a> Reference<'a> {
+    fn parse(reference: &'a str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidReference(reference.to_string());
+
+        if !reference.starts_with(PREFIX) {
+            return Err(invalid());
+        }
+
+        let parts: Vec<_> = reference[PREFIX.len()..].split('/').collect();
+
+        if parts.iter().any(|v| v.is_empty()) {
+            return Err(invalid());
+        }
+
+        match parts.as_slice() {
+            [vault, item, field] => Ok(Self {
+                vault,
+                item,
+                section: None,
+                field,
+            }),
+            [vault, item, section, field] => Ok(Self {
+                vault,
+                item,
+                section: Some(section),
+                field,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Looks up secret references against a backend, fetching each item at most
+/// once no matter how many of its fields are referenced.
+pub struct Resolver<'a> {
+    backend: &'a api::DynBackend,
+    items: HashMap<(String, String), api::Item>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(backend: &'a api::DynBackend) -> Self {
+        Self {
+            backend,
+            items: HashMap::new(),
+        }
+    }
+
+    async fn item(&mut self, vault: &str, item: &str) -> anyhow::Result<&api::Item> {
+        let key = (vault.to_string(), item.to_string());
+
+        if !self.items.contains_key(&key) {
+            let vault = vault::find(self.backend, vault).await?;
+
+            let metadata = self
+                .backend
+                .search(Some(&vault.uuid))
+                .await?
+                .into_iter()
+                .filter(|v| v.vault_uuid == vault.uuid)
+                .find(|v| v.uuid == item || v.title.eq_ignore_ascii_case(item))
+                .ok_or_else(|| Error::ItemNotFound(vault.name.clone(), item.to_string()))?;
+
+            let found = self
+                .backend
+                .get(&metadata.uuid)
+                .await?
+                .ok_or_else(|| Error::ItemNotFound(vault.name, item.to_string()))?;
+
+            self.items.insert(key.clone(), found);
+        }
+
+        Ok(&self.items[&key])
+    }
+
+    /// Returns the value of the field the reference points to.
+    pub async fn resolve(&mut self, reference: &str) -> anyhow::Result<String> {
+        let parsed = Reference::parse(reference)?;
+        let item = self.item(parsed.vault, parsed.item).await?;
+
+        let matches = |v: &&api::ItemField| v.name.eq_ignore_ascii_case(parsed.field);
+
+        let field = match parsed.section {
+            Some(section) => item
+                .sections
+                .iter()
+                .filter(|v| v.name.eq_ignore_ascii_case(section))
+                .flat_map(|v| &v.fields)
+                .find(matches),
+            None => item
+                .fields
+                .iter()
+                .chain(item.sections.iter().flat_map(|v| &v.fields))
+                .find(matches),
+        };
+
+        Ok(field
+            .ok_or_else(|| Error::FieldNotFound(parsed.field.to_string(), reference.to_string()))?
+            .value
+            .as_str()
+            .to_string())
+    }
+}
@@ -0,0 +1,69 @@
+//! Runs a command with secrets from the backend in its environment, so they
+//! never have to be written to disk.
+
+use crate::resolve::Resolver;
+use clap::Clap;
+use onep_backend_api as api;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid environment variable {0}, expected NAME=op://vault/item/field")]
+    InvalidEnv(String),
+    #[error("Failed to run {0}: {1}")]
+    Exec(String, std::io::Error),
+}
+
+#[derive(Clap, Debug)]
+pub struct RunOpt {
+    /// Environment variable to set from a secret reference, in the form
+    /// NAME=op://vault/item/[section/]field
+    #[clap(long, short = 'e', number_of_values = 1)]
+    env: Vec<String>,
+    /// Command to run, along with its arguments
+    #[clap(required = true, last = true)]
+    command: Vec<String>,
+}
+
+pub async fn run(backend: &api::DynBackend, opt: RunOpt) -> anyhow::Result<()> {
+    let mut resolver = Resolver::new(backend);
+    let mut env = Vec::new();
+
+    for var in opt.env {
+        let mut parts = var.splitn(2, '=');
+
+        let (name, reference) = match (parts.next(), parts.next()) {
+            (Some(name), Some(reference)) if !name.is_empty() => (name, reference),
+            _ => return Err(Error::InvalidEnv(var).into()),
+        };
+
+        env.push((name.to_string(), resolver.resolve(reference).await?));
+    }
+
+    let (program, args) = opt.command.split_at(1);
+
+    let status = tokio::process::Command::new(&program[0])
+        .args(args)
+        .envs(env)
+        .status()
+        .await
+        .map_err(|e| Error::Exec(program[0].clone(), e))?;
+
+    // the child's exit code is passed on so 1p can be dropped in front of
+    // any command in a script
+    std::process::exit(exit_code(status));
+}
+
+/// The code to exit with for a child that exited with `status`, which for a
+/// child killed by a signal is 128 plus the signal, as shells do.
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    status.code().unwrap_or(1)
+}
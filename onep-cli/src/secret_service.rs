@@ -0,0 +1,368 @@
+//! A read-only provider for the freedesktop [Secret Service API], letting
+//! libsecret clients such as GNOME apps and `NetworkManager` fetch passwords
+//! from the vault over D-Bus.
+//!
+//! Items are loaded when the service starts, so changes made afterwards are
+//! only picked up after a restart. Secrets are only handed out over plain
+//! sessions, which libsecret falls back to when encryption isn't offered.
+//!
+//! [Secret Service API]: https://specifications.freedesktop.org/secret-service/
+
+use dbus::{
+    arg::{PropMap, RefArg, Variant},
+    blocking::{stdintf::org_freedesktop_dbus::RequestNameReply, Connection},
+    MethodErr, Path,
+};
+use dbus_crossroads::Crossroads;
+use onep_backend_api as api;
+use std::{collections::HashMap, sync::Arc};
+
+const BUS_NAME: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const COLLECTION_PATH: &str = "/org/freedesktop/secrets/collection/onep";
+const DEFAULT_ALIAS_PATH: &str = "/org/freedesktop/secrets/aliases/default";
+const SESSION_PATH: &str = "/org/freedesktop/secrets/session";
+
+/// Returned in place of a prompt, as nothing ever needs unlocking.
+const NO_PROMPT: &str = "/";
+
+/// libsecret adds the name of the client's schema to its searches, which
+/// would stop any of our items from matching.
+const SCHEMA_ATTRIBUTE: &str = "xdg:schema";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Another Secret Service provider is already running")]
+    AlreadyRunning,
+}
+
+/// A secret as sent over the wire: the session it's for, encryption
+/// parameters, the secret itself and its content type.
+type Secret = (Path<'static>, Vec<u8>, Vec<u8>, String);
+
+fn read_only() -> MethodErr {
+    (
+        "org.freedesktop.DBus.Error.NotSupported",
+        "Items can't be changed through the Secret Service",
+    )
+        .into()
+}
+
+fn no_session() -> MethodErr {
+    (
+        "org.freedesktop.Secret.Error.NoSession",
+        "Session doesn't exist",
+    )
+        .into()
+}
+
+#[derive(Clone)]
+struct Item {
+    path: Path<'static>,
+    label: String,
+    attributes: HashMap<String, String>,
+    password: String,
+}
+
+impl Item {
+    fn matches(&self, attributes: &HashMap<String, String>) -> bool {
+        attributes
+            .iter()
+            .filter(|(k, _)| *k != SCHEMA_ATTRIBUTE)
+            .all(|(k, v)| self.attributes.get(k) == Some(v))
+    }
+
+    fn secret(&self, session: Path<'static>) -> Secret {
+        (
+            session,
+            Vec::new(),
+            self.password.as_bytes().to_vec(),
+            "text/plain".to_string(),
+        )
+    }
+}
+
+fn search(items: &[Item], attributes: &HashMap<String, String>) -> Vec<Path<'static>> {
+    items
+        .iter()
+        .filter(|v| v.matches(attributes))
+        .map(|v| v.path.clone())
+        .collect()
+}
+
+struct Service {
+    items: Arc<Vec<Item>>,
+    sessions: u64,
+}
+
+struct Collection {
+    items: Arc<Vec<Item>>,
+}
+
+struct Session;
+
+async fn load_items(backend: &api::DynBackend) -> anyhow::Result<Vec<Item>> {
+    let mut items = Vec::new();
+
+    for (metadata, item) in backend.get_all().await? {
+        let password = match item.password() {
+            Some(field) if !metadata.archived => field.value.as_str().to_string(),
+            _ => continue,
+        };
+
+        let mut attributes = HashMap::new();
+        attributes.insert("uuid".to_string(), metadata.uuid);
+        attributes.insert("title".to_string(), metadata.title.clone());
+
+        if !metadata.account_info.is_empty() {
+            attributes.insert("username".to_string(), metadata.account_info);
+        }
+
+        items.push(Item {
+            path: Path::from(format!("{}/i{}", COLLECTION_PATH, items.len())),
+            label: metadata.title,
+            attributes,
+            password,
+        });
+    }
+
+    Ok(items)
+}
+
+#[allow(clippy::too_many_lines)]
+fn serve(items: Vec<Item>) -> anyhow::Result<()> {
+    let items = Arc::new(items);
+    let mut cr = Crossroads::new();
+
+    let session = cr.register("org.freedesktop.Secret.Session", |b| {
+        b.method_with_cr("Close", (), (), |ctx, cr, ()| {
+            cr.remove::<Session>(ctx.path());
+            Ok(())
+        });
+    });
+
+    let service = cr.register("org.freedesktop.Secret.Service", |b| {
+        b.property("Collections")
+            .get(|_, _: &mut Service| Ok(vec![Path::from(COLLECTION_PATH)]));
+
+        b.method_with_cr(
+            "OpenSession",
+            ("algorithm", "input"),
+            ("output", "result"),
+            move |ctx, cr, (algorithm, _): (String, Variant<Box<dyn RefArg>>)| {
+                if algorithm != "plain" {
+                    return Err((
+                        "org.freedesktop.DBus.Error.NotSupported",
+                        "Only plain sessions are supported",
+                    )
+                        .into());
+                }
+
+                let data: &mut Service = cr
+                    .data_mut(ctx.path())
+                    .ok_or_else(|| MethodErr::no_path(ctx.path()))?;
+                data.sessions += 1;
+
+                let path = Path::from(format!("{}/s{}", SESSION_PATH, data.sessions));
+                cr.insert(path.clone(), &[session], Session);
+
+                Ok((Variant(String::new()), path))
+            },
+        );
+        b.method(
+            "CreateCollection",
+            ("properties", "alias"),
+            ("collection", "prompt"),
+            |_, _: &mut Service, _: (PropMap, String)| -> Result<(Path, Path), _> {
+                Err(read_only())
+            },
+        );
+        b.method(
+            "SearchItems",
+            ("attributes",),
+            ("unlocked", "locked"),
+            |_, data: &mut Service, (attributes,): (HashMap<String, String>,)| {
+                Ok((search(&data.items, &attributes), Vec::<Path>::new()))
+            },
+        );
+        // nothing is ever locked, so there's nothing to do when asked to
+        // unlock, and locking is refused by returning nothing as locked
+        b.method(
+            "Unlock",
+            ("objects",),
+            ("unlocked", "prompt"),
+            |_, _: &mut Service, (objects,): (Vec<Path<'static>>,)| {
+                Ok((objects, Path::from(NO_PROMPT)))
+            },
+        );
+        b.method(
+            "Lock",
+            ("objects",),
+            ("locked", "prompt"),
+            |_, _: &mut Service, _: (Vec<Path<'static>>,)| {
+                Ok((Vec::<Path>::new(), Path::from(NO_PROMPT)))
+            },
+        );
+        b.method_with_cr(
+            "GetSecrets",
+            ("items", "session"),
+            ("secrets",),
+            |ctx, cr, (paths, session): (Vec<Path<'static>>, Path<'static>)| {
+                if cr.data_mut::<Session>(&session).is_none() {
+                    return Err(no_session());
+                }
+
+                let data: &mut Service = cr
+                    .data_mut(ctx.path())
+                    .ok_or_else(|| MethodErr::no_path(ctx.path()))?;
+
+                let secrets: HashMap<Path<'static>, Secret> = data
+                    .items
+                    .iter()
+                    .filter(|v| paths.contains(&v.path))
+                    .map(|v| (v.path.clone(), v.secret(session.clone())))
+                    .collect();
+
+                Ok((secrets,))
+            },
+        );
+        b.method(
+            "ReadAlias",
+            ("name",),
+            ("collection",),
+            |_, _: &mut Service, (name,): (String,)| {
+                Ok((Path::from(if name == "default" {
+                    COLLECTION_PATH
+                } else {
+                    NO_PROMPT
+                }),))
+            },
+        );
+        b.method(
+            "SetAlias",
+            ("name", "collection"),
+            (),
+            |_, _: &mut Service, _: (String, Path)| -> Result<(), _> { Err(read_only()) },
+        );
+    });
+
+    let collection = cr.register("org.freedesktop.Secret.Collection", |b| {
+        b.property("Items").get(|_, data: &mut Collection| {
+            Ok(data
+                .items
+                .iter()
+                .map(|v| v.path.clone())
+                .collect::<Vec<_>>())
+        });
+        b.property("Label")
+            .get(|_, _: &mut Collection| Ok("1p".to_string()));
+        b.property("Locked").get(|_, _: &mut Collection| Ok(false));
+        b.property("Created").get(|_, _: &mut Collection| Ok(0_u64));
+        b.property("Modified")
+            .get(|_, _: &mut Collection| Ok(0_u64));
+
+        b.method(
+            "SearchItems",
+            ("attributes",),
+            ("results",),
+            |_, data: &mut Collection, (attributes,): (HashMap<String, String>,)| {
+                Ok((search(&data.items, &attributes),))
+            },
+        );
+        b.method(
+            "CreateItem",
+            ("properties", "secret", "replace"),
+            ("item", "prompt"),
+            |_, _: &mut Collection, _: (PropMap, Secret, bool)| -> Result<(Path, Path), _> {
+                Err(read_only())
+            },
+        );
+        b.method(
+            "Delete",
+            (),
+            ("prompt",),
+            |_, _: &mut Collection, ()| -> Result<(Path,), _> { Err(read_only()) },
+        );
+    });
+
+    let item = cr.register("org.freedesktop.Secret.Item", |b| {
+        b.property("Locked").get(|_, _: &mut Item| Ok(false));
+        b.property("Attributes")
+            .get(|_, data: &mut Item| Ok(data.attributes.clone()));
+        b.property("Label")
+            .get(|_, data: &mut Item| Ok(data.label.clone()));
+        b.property("Created").get(|_, _: &mut Item| Ok(0_u64));
+        b.property("Modified").get(|_, _: &mut Item| Ok(0_u64));
+
+        b.method_with_cr(
+            "GetSecret",
+            ("session",),
+            ("secret",),
+            |ctx, cr, (session,): (Path<'static>,)| {
+                if cr.data_mut::<Session>(&session).is_none() {
+                    return Err(no_session());
+                }
+
+                let data: &mut Item = cr
+                    .data_mut(ctx.path())
+                    .ok_or_else(|| MethodErr::no_path(ctx.path()))?;
+
+                Ok((data.secret(session),))
+            },
+        );
+        b.method(
+            "SetSecret",
+            ("secret",),
+            (),
+            |_, _: &mut Item, _: (Secret,)| -> Result<(), _> { Err(read_only()) },
+        );
+        b.method(
+            "Delete",
+            (),
+            ("prompt",),
+            |_, _: &mut Item, ()| -> Result<(Path,), _> { Err(read_only()) },
+        );
+    });
+
+    cr.insert(
+        SERVICE_PATH,
+        &[service],
+        Service {
+            items: items.clone(),
+            sessions: 0,
+        },
+    );
+
+    for path in &[COLLECTION_PATH, DEFAULT_ALIAS_PATH] {
+        cr.insert(
+            *path,
+            &[collection],
+            Collection {
+                items: items.clone(),
+            },
+        );
+    }
+
+    for v in items.iter() {
+        cr.insert(v.path.clone(), &[item], v.clone());
+    }
+
+    let conn = Connection::new_session()?;
+
+    if conn.request_name(BUS_NAME, false, false, true)? != RequestNameReply::PrimaryOwner {
+        return Err(Error::AlreadyRunning.into());
+    }
+
+    cr.serve(&conn)?;
+
+    Ok(())
+}
+
+pub async fn run(backend: &api::DynBackend) -> anyhow::Result<()> {
+    let items = load_items(backend).await?;
+    eprintln!("Serving {} items", items.len());
+
+    // the D-Bus connection blocks while serving, so it gets a thread to
+    // itself rather than holding up the runtime
+    tokio::task::spawn_blocking(move || serve(items)).await?
+}
@@ -0,0 +1,231 @@
+//! A read-only HTTP server on the local machine, so editors and scripts can
+//! fetch items through a single long running 1p rather than each driving the
+//! backend themselves. Every request needs the bearer token generated when
+//! the server starts, and is logged to stderr. With `--grpc` the same items
+//! are served over gRPC instead, see [`crate::grpc`].
+
+use crate::{backends, config::Config, private, totp};
+use clap::Clap;
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use onep_backend_api as api;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Serialize;
+use std::{convert::Infallible, net::SocketAddr, path::PathBuf, sync::Arc};
+
+#[derive(Clap, Debug)]
+pub struct ServeOpt {
+    /// Address to listen on. Anything other than a loopback address makes
+    /// items reachable from other machines to whoever has the token
+    #[clap(long, default_value = "127.0.0.1:8989")]
+    listen: SocketAddr,
+    /// Serve the gRPC interface in `proto/onep.proto` rather than JSON over
+    /// HTTP
+    #[clap(long)]
+    grpc: bool,
+}
+
+/// Everything requests are answered from.
+pub struct State {
+    pub backend: Box<api::DynBackend>,
+    pub config: Config,
+    pub token: String,
+}
+
+/// A failed request, sent back as `{"error": message}`.
+struct Failure(StatusCode, String);
+
+impl From<anyhow::Error> for Failure {
+    fn from(e: anyhow::Error) -> Self {
+        let status = match e.downcast_ref::<api::BackendError>() {
+            Some(api::BackendError::NotFound(_)) => StatusCode::NOT_FOUND,
+            _ if e.downcast_ref::<crate::Error>().is_some() => StatusCode::NOT_FOUND,
+            _ if matches!(e.downcast_ref(), Some(totp::Error::NoTotp(_))) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        Self(status, e.to_string())
+    }
+}
+
+impl From<api::BackendError> for Failure {
+    fn from(e: api::BackendError) -> Self {
+        anyhow::Error::from(e).into()
+    }
+}
+
+#[derive(Serialize)]
+struct Code {
+    code: String,
+    /// Seconds until the code changes, for time based one-time passwords.
+    remaining: Option<u64>,
+}
+
+/// Path the token is written to, so tools run by the same user can find it.
+fn token_path() -> Option<PathBuf> {
+    dirs::runtime_dir().map(|v| v.join("1p").join("serve-token"))
+}
+
+/// Writes the token where only the current user can read it.
+fn write_token(path: &std::path::Path, token: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    private::write(path, token)
+}
+
+pub async fn run(config: Config, opt: ServeOpt) -> anyhow::Result<()> {
+    let backend = backends::open(&config, false)?;
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .collect();
+
+    match token_path() {
+        Some(path) => {
+            write_token(&path, &token)?;
+            eprintln!("Token written to {}", path.display());
+        }
+        None => eprintln!("Token: {}", token),
+    }
+
+    if !opt.listen.ip().is_loopback() {
+        eprintln!(
+            "{} isn't a loopback address, so anyone with the token can fetch items from other machines",
+            opt.listen.ip()
+        );
+    }
+
+    let state = Arc::new(State {
+        backend,
+        config,
+        token,
+    });
+
+    if opt.grpc {
+        return crate::grpc::run(state, opt.listen).await;
+    }
+
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(handle(&state, req).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&opt.listen)?.serve(make_service);
+    eprintln!("Listening on http://{}", opt.listen);
+
+    server.await?;
+
+    Ok(())
+}
+
+async fn handle(state: &State, req: Request<Body>) -> Response<Body> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+
+    let res = if !authorised(state, &req) {
+        Err(Failure(
+            StatusCode::UNAUTHORIZED,
+            "missing or incorrect bearer token".to_string(),
+        ))
+    } else if method == Method::GET {
+        route(state, &req).await
+    } else {
+        Err(Failure(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "only GET is supported".to_string(),
+        ))
+    };
+
+    let (status, body) = match res {
+        Ok(body) => (StatusCode::OK, body),
+        Err(Failure(status, message)) => {
+            (status, serde_json::json!({ "error": message }).to_string())
+        }
+    };
+
+    eprintln!("{} {} {}", method, path, status.as_u16());
+
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .expect("response is valid")
+}
+
+fn authorised(state: &State, req: &Request<Body>) -> bool {
+    let given = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+
+    state.authorised(given)
+}
+
+impl State {
+    /// Whether an `Authorization` header carries the token, compared without
+    /// returning early so how long it takes doesn't give away how much of it
+    /// matched.
+    pub fn authorised(&self, header: Option<&str>) -> bool {
+        let given = header
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        given.len() == self.token.len()
+            && given
+                .bytes()
+                .zip(self.token.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+async fn route(state: &State, req: &Request<Body>) -> Result<String, Failure> {
+    let segments: Vec<_> = req.uri().path().trim_matches('/').split('/').collect();
+
+    let body = match segments.as_slice() {
+        ["items"] => {
+            let terms = req.uri().query().and_then(|query| {
+                url::form_urlencoded::parse(query.as_bytes())
+                    .find(|(k, _)| k == "q")
+                    .map(|(_, v)| v.into_owned())
+            });
+
+            serde_json::to_string(&state.backend.search(terms.as_deref()).await?)
+        }
+        ["items", uuid] => {
+            let item = state.backend.get(uuid).await?.ok_or_else(|| {
+                Failure(StatusCode::NOT_FOUND, crate::Error::NotFound.to_string())
+            })?;
+
+            serde_json::to_string(&item)
+        }
+        ["totp", uuid] => {
+            let (uuid, item) = totp::get_otp(&*state.backend, &state.config, uuid).await?;
+            let code = totp::generate(&*state.backend, &uuid, &item).await?;
+
+            serde_json::to_string(&Code {
+                code: code.value,
+                remaining: code.remaining,
+            })
+        }
+        _ => {
+            return Err(Failure(
+                StatusCode::NOT_FOUND,
+                format!("no such endpoint {}", req.uri().path()),
+            ))
+        }
+    };
+
+    body.map_err(|e| Failure(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
@@ -0,0 +1,42 @@
+//! Formatting values so they can be safely read back by a POSIX shell.
+
+use std::borrow::Cow;
+
+fn is_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c)
+}
+
+/// Quotes a value for use as a single shell word. Values made up entirely of
+/// characters the shell doesn't treat specially are returned as is, anything
+/// else is wrapped in single quotes, the only quoting in which nothing is
+/// expanded.
+pub fn quote(value: &str) -> Cow<'_, str> {
+    if !value.is_empty() && value.chars().all(is_safe) {
+        return Cow::Borrowed(value);
+    }
+
+    // a single quote can't be escaped inside single quotes, so the quoted
+    // string is closed, an escaped quote added and a new one opened.
+    Cow::Owned(format!("'{}'", value.replace('\'', r"'\''")))
+}
+
+/// Turns an arbitrary name into a valid environment variable name, in upper
+/// case with anything other than letters and digits replaced by underscores.
+pub fn variable_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
@@ -0,0 +1,170 @@
+//! An ssh-agent serving the keys stored in SSH key items. Keys are loaded
+//! into memory when the agent starts and challenges are signed in-process, so
+//! private keys are never written to disk.
+
+use crate::ssh::{self, Key, Reader};
+use clap::Clap;
+use onep_backend_api as api;
+use std::{os::unix::fs::PermissionsExt, path::PathBuf, sync::Arc};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{UnixListener, UnixStream},
+};
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+/// Largest message the agent will read, matching `OpenSSH`'s own agent.
+const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("No runtime directory to create the agent socket in, pass one with --socket")]
+    NoSocketPath,
+}
+
+#[derive(Clap, Debug)]
+pub struct SshAgentOpt {
+    /// Path to listen on, defaults to 1p/ssh-agent.sock in your runtime
+    /// directory
+    #[clap(long, short = 's', parse(from_os_str))]
+    socket: Option<PathBuf>,
+}
+
+async fn load_keys(backend: &api::DynBackend) -> anyhow::Result<Vec<Key>> {
+    let mut keys = Vec::new();
+
+    for (metadata, item) in backend.get_all().await? {
+        let fields = item
+            .fields
+            .iter()
+            .chain(item.sections.iter().flat_map(|v| &v.fields))
+            .filter(|v| v.field_type == api::ItemFieldType::SshKey);
+
+        for field in fields {
+            match Key::parse(&field.value) {
+                // the same key stored in more than one item is only offered
+                // once
+                Ok(key) if keys.iter().any(|v: &Key| v.public == key.public) => {}
+                Ok(mut key) => {
+                    if key.comment.is_empty() {
+                        key.comment.clone_from(&metadata.title);
+                    }
+
+                    keys.push(key);
+                }
+                Err(e) => eprintln!("Skipping key in {}: {}", metadata.title, e),
+            }
+        }
+    }
+
+    Ok(keys)
+}
+
+fn respond(keys: &[Key], request: &[u8]) -> Result<Vec<u8>, ssh::Error> {
+    let (&message_type, payload) = request.split_first().ok_or(ssh::Error::Truncated)?;
+
+    match message_type {
+        SSH_AGENTC_REQUEST_IDENTITIES => {
+            let mut response = vec![SSH_AGENT_IDENTITIES_ANSWER];
+
+            #[allow(clippy::cast_possible_truncation)]
+            ssh::write_u32(&mut response, keys.len() as u32);
+
+            for key in keys {
+                ssh::write_string(&mut response, &key.public);
+                ssh::write_string(&mut response, key.comment.as_bytes());
+            }
+
+            Ok(response)
+        }
+        SSH_AGENTC_SIGN_REQUEST => {
+            let mut reader = Reader::new(payload);
+            let public = reader.string()?;
+            let data = reader.string()?;
+            let flags = reader.u32()?;
+
+            let key = match keys.iter().find(|v| v.public == public) {
+                Some(key) => key,
+                None => return Ok(vec![SSH_AGENT_FAILURE]),
+            };
+
+            let mut response = vec![SSH_AGENT_SIGN_RESPONSE];
+            ssh::write_string(&mut response, &key.sign(data, flags)?);
+
+            Ok(response)
+        }
+        // adding and removing keys isn't supported, they're managed through
+        // the password manager instead
+        _ => Ok(vec![SSH_AGENT_FAILURE]),
+    }
+}
+
+async fn serve(keys: Arc<Vec<Key>>, mut stream: UnixStream) -> std::io::Result<()> {
+    loop {
+        let mut len = [0; 4];
+
+        match stream.read_exact(&mut len).await {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            res => res?,
+        };
+
+        let len = u32::from_be_bytes(len) as usize;
+
+        if len > MAX_MESSAGE_LEN {
+            return Ok(());
+        }
+
+        let mut request = vec![0; len];
+        stream.read_exact(&mut request).await?;
+
+        let response = respond(&keys, &request).unwrap_or_else(|e| {
+            eprintln!("Failed to handle agent request: {}", e);
+            vec![SSH_AGENT_FAILURE]
+        });
+
+        let mut message = Vec::with_capacity(response.len() + 4);
+        ssh::write_string(&mut message, &response);
+        stream.write_all(&message).await?;
+    }
+}
+
+pub async fn run(backend: &api::DynBackend, opt: SshAgentOpt) -> anyhow::Result<()> {
+    let keys = Arc::new(load_keys(backend).await?);
+
+    let socket = opt
+        .socket
+        .or_else(|| dirs::runtime_dir().map(|v| v.join("1p").join("ssh-agent.sock")))
+        .ok_or(Error::NoSocketPath)?;
+
+    if let Some(parent) = socket.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // a socket left behind by an agent that didn't shut down cleanly would
+    // stop us from binding
+    match std::fs::remove_file(&socket) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    let mut listener = UnixListener::bind(&socket)?;
+    std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))?;
+
+    println!("SSH_AUTH_SOCK={}; export SSH_AUTH_SOCK;", socket.display());
+    eprintln!("Serving {} keys", keys.len());
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let keys = keys.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = serve(keys, stream).await {
+                eprintln!("Agent connection failed: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,140 @@
+//! Prints tables fit to the width of the terminal, cutting long values short
+//! rather than letting them wrap onto lines of their own.
+
+use crate::{config::Config, output::Output};
+use itertools::Itertools;
+use ratatui::crossterm::terminal;
+use term_table::Table;
+
+/// Space taken up by the borders and padding of a table with this many
+/// columns, in the extended style every table is drawn in.
+fn borders(columns: usize) -> usize {
+    3 * columns + 1
+}
+
+fn columns(table: &Table) -> usize {
+    table
+        .rows
+        .iter()
+        .map(|row| row.cells.iter().map(|v| v.col_span).sum())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Prints `table`, or with `--quiet` just the values in each row, tab
+/// separated, leaving out borders and the headings that span every column.
+pub fn print(out: &mut Output, config: &Config, mut table: Table) {
+    if config.quiet {
+        let columns = columns(&table);
+
+        for row in &table.rows {
+            if columns > 1 && row.cells.len() == 1 {
+                continue;
+            }
+
+            out.println(row.cells.iter().map(|v| &v.data).join("\t"));
+        }
+
+        return;
+    }
+
+    fit(config, &mut table);
+    out.println(table.render());
+}
+
+/// Cuts the values in `table` short so it fits on the terminal, unless output
+/// isn't going to one or `--full` was given.
+fn fit(config: &Config, table: &mut Table) {
+    if !config.tables.truncate || !atty::is(atty::Stream::Stdout) {
+        return;
+    }
+
+    let width = match terminal::size() {
+        Ok((columns, _)) => usize::from(columns),
+        Err(_) => return,
+    };
+
+    let columns = columns(table);
+
+    // how wide each column would be with nothing cut short, going by the
+    // cells that only span the one column
+    let mut widths = vec![0; columns];
+    for row in &table.rows {
+        let mut column = 0;
+
+        for cell in &row.cells {
+            if cell.col_span == 1 {
+                widths[column] = widths[column].max(width_of(&cell.data));
+            }
+
+            column += cell.col_span;
+        }
+    }
+
+    let limits = limits(&widths, width.saturating_sub(borders(columns)));
+
+    for row in &mut table.rows {
+        let mut column = 0;
+
+        for cell in &mut row.cells {
+            let spanned = &limits[column..(column + cell.col_span).min(columns)];
+            // cells spanning several columns also get the space between them
+            let limit = spanned.iter().sum::<usize>() + 3 * cell.col_span.saturating_sub(1);
+
+            if width_of(&cell.data) > limit {
+                cell.data = truncate(&cell.data, limit, &config.tables.ellipsis).into();
+            }
+
+            column += cell.col_span;
+        }
+    }
+}
+
+/// The widest each column can be for them all to fit in `available`, taking
+/// space from the widest first so that short columns, such as field names,
+/// are left whole.
+fn limits(widths: &[usize], available: usize) -> Vec<usize> {
+    let mut sorted = widths.to_vec();
+    sorted.sort_unstable();
+
+    let mut remaining = available;
+    let mut limit = usize::MAX;
+
+    for (i, width) in sorted.iter().enumerate() {
+        let left = sorted.len() - i;
+
+        if width * left > remaining {
+            limit = remaining / left;
+            break;
+        }
+
+        remaining -= width;
+    }
+
+    widths.iter().map(|&v| v.min(limit)).collect()
+}
+
+/// Width of the longest line in a cell.
+fn width_of(text: &str) -> usize {
+    text.lines().map(|v| v.chars().count()).max().unwrap_or(0)
+}
+
+/// Cuts each line of `text` down to `limit` characters, ending those that were
+/// cut short with `ellipsis`.
+fn truncate(text: &str, limit: usize, ellipsis: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.chars().count() <= limit {
+                return line.to_string();
+            }
+
+            let keep = limit.saturating_sub(ellipsis.chars().count());
+
+            line.chars()
+                .take(keep)
+                .chain(ellipsis.chars())
+                .take(limit)
+                .collect::<String>()
+        })
+        .join("\n")
+}
@@ -0,0 +1,59 @@
+//! Commands for adding and removing tags on items.
+
+use crate::Error;
+use clap::Clap;
+use onep_backend_api as api;
+
+#[derive(Clap, Debug)]
+pub enum TagOpt {
+    /// Add tags to an item
+    Add {
+        uuid: String,
+        #[clap(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from an item
+    #[clap(alias = "remove")]
+    Rm {
+        uuid: String,
+        #[clap(required = true)]
+        tags: Vec<String>,
+    },
+}
+
+/// Fetches the tags currently on an item, backends only return tags as part
+/// of an item's metadata so this searches for the item by its uuid.
+async fn current_tags(backend: &api::DynBackend, uuid: &str) -> anyhow::Result<Vec<String>> {
+    let item = backend
+        .search(Some(uuid))
+        .await?
+        .into_iter()
+        .find(|v| v.uuid == uuid)
+        .ok_or(Error::NotFound)?;
+
+    Ok(item.tags)
+}
+
+pub async fn run(backend: &api::DynBackend, opt: TagOpt) -> anyhow::Result<()> {
+    match opt {
+        TagOpt::Add { uuid, tags } => {
+            let mut current = current_tags(backend, &uuid).await?;
+
+            for tag in tags {
+                if !current.contains(&tag) {
+                    current.push(tag);
+                }
+            }
+
+            backend.set_tags(&uuid, &current).await?;
+        }
+        TagOpt::Rm { uuid, tags } => {
+            let mut current = current_tags(backend, &uuid).await?;
+            current.retain(|v| !tags.contains(v));
+
+            backend.set_tags(&uuid, &current).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,315 @@
+//! Prints an item's one-time password, shows its secret as a QR code to
+//! enroll the same secret into another authenticator, or adds one to it.
+
+use crate::{cache, clipboard, config::Config, otp, qr};
+use clap::{AppSettings, Clap};
+use onep_backend_api as api;
+use std::{convert::TryFrom, io::Write, path::PathBuf, time::Duration};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0:?} doesn't have a one-time password.")]
+    NoTotp(String),
+    #[error("The QR code is for {0:?} rather than a one-time password.")]
+    NotOtpauth(String),
+    #[error("Pass the item to use.")]
+    NoItem,
+    #[error("Only time based one-time passwords can be watched.")]
+    NotTimeBased,
+}
+
+#[derive(Clap, Debug)]
+#[clap(setting = AppSettings::ArgRequiredElseHelp)]
+pub struct TotpOpt {
+    /// Item to use, by uuid or title
+    item: Option<String>,
+    /// Show the secret as a QR code to scan with an authenticator app, rather
+    /// than printing the current code
+    #[clap(long)]
+    qr: bool,
+    /// Keep showing the code with a countdown to when it changes, printing
+    /// each new one as it comes up
+    #[clap(long, short = 'w', conflicts_with = "qr")]
+    watch: bool,
+    /// Put the code on the clipboard rather than printing it, clearing it
+    /// again once the code changes
+    #[clap(long, short = 'c', conflicts_with_all = &["qr", "watch"])]
+    clip: bool,
+    #[clap(subcommand)]
+    cmd: Option<TotpCmd>,
+}
+
+#[derive(Clap, Debug)]
+enum TotpCmd {
+    /// Add a one-time password to an item, replacing any it already has
+    Add {
+        /// Item to add it to, by uuid or title
+        item: String,
+        /// The secret, either on its own or as an otpauth:// URI
+        #[clap(required_unless_present = "from-image")]
+        secret: Option<String>,
+        /// Read the secret from a QR code in an image, such as a screenshot
+        /// of the site's two-factor setup page
+        #[clap(long, conflicts_with = "secret")]
+        from_image: Option<PathBuf>,
+    },
+    /// Add one-time passwords exported from Google Authenticator to the items
+    /// with the same titles
+    Import {
+        /// The otpauth-migration:// URI from the export's QR code
+        #[clap(required_unless_present = "from-image")]
+        uri: Option<String>,
+        /// Read the URI from the export's QR code in an image
+        #[clap(long, conflicts_with = "uri")]
+        from_image: Option<PathBuf>,
+    },
+}
+
+/// Fetches an item by uuid or title, along with its uuid, marking its
+/// one-time password as a Steam Guard one if the config says it is.
+pub async fn get(
+    backend: &api::DynBackend,
+    config: &Config,
+    item: &str,
+) -> anyhow::Result<(String, api::Item)> {
+    let (uuid, mut found) = if let Some(found) = backend.get(item).await? {
+        (item.to_string(), found)
+    } else {
+        let uuid = crate::find_uuid(backend, item).await?;
+        let found = backend.get(&uuid).await?.ok_or(crate::Error::NotFound)?;
+
+        (uuid, found)
+    };
+
+    if config.otp.is_steam(&uuid, &found.title) {
+        otp::mark_steam(&mut found);
+    }
+
+    Ok((uuid, found))
+}
+
+/// Same as [`get`], but for items the config lists as offline ones it keeps
+/// their one-time password secret in the cache, falling back to it when the
+/// backend can't be reached.
+pub async fn get_otp(
+    backend: &api::DynBackend,
+    config: &Config,
+    item: &str,
+) -> anyhow::Result<(String, api::Item)> {
+    if config.otp.offline.is_empty() {
+        return get(backend, config, item).await;
+    }
+
+    match get(backend, config, item).await {
+        Ok((uuid, found)) => {
+            if let Err(e) = remember(config, &uuid, &found) {
+                eprintln!("Failed to update cache: {}", e);
+            }
+
+            Ok((uuid, found))
+        }
+        // the backend answering that there's no such item isn't a reason to
+        // use an old secret
+        Err(e)
+            if e.downcast_ref::<crate::Error>().is_some()
+                || matches!(
+                    e.downcast_ref::<api::BackendError>(),
+                    Some(api::BackendError::NotFound(_))
+                ) =>
+        {
+            Err(e)
+        }
+        Err(e) => {
+            let secrets = cache::Secrets::open()?;
+            let cached = match secrets.as_ref().and_then(|v| v.get(item)) {
+                Some(cached) => cached,
+                None => return Err(e),
+            };
+
+            eprintln!("Falling back to the cached one-time password: {}", e);
+
+            let (uuid, title, secret) = cached;
+            let item = api::Item::builder()
+                .title(title)
+                .field(
+                    api::ItemField::new("one-time password", secret)
+                        .with_type(api::ItemFieldType::Totp),
+                )
+                .build()?;
+
+            Ok((uuid.to_string(), item))
+        }
+    }
+}
+
+/// Keeps the item's secret if it's listed as an offline one, or forgets it
+/// if it no longer is. Counter based secrets are never kept, as the counter
+/// couldn't be saved without the backend.
+fn remember(config: &Config, uuid: &str, item: &api::Item) -> Result<(), cache::Error> {
+    let secret = otp::field(item).map(|v| v.value.as_str()).filter(|v| {
+        config.otp.is_offline(uuid, &item.title)
+            && otp::TwoFactorAuth::try_from(*v).map_or(false, |v| !v.is_counter_based())
+    });
+
+    match cache::Secrets::open()? {
+        Some(mut secrets) => secrets.set(uuid, &item.title, secret),
+        None => Ok(()),
+    }
+}
+
+/// Generates a code for the item's one-time password. For counter based
+/// ones the counter's moved on before the code is returned, so it's never
+/// given out twice even if saving fails.
+pub async fn generate(
+    backend: &api::DynBackend,
+    uuid: &str,
+    item: &api::Item,
+) -> anyhow::Result<otp::TwoFactorAuthResponse> {
+    let secret = otp::field(item).ok_or_else(|| Error::NoTotp(item.title.clone()))?;
+    let code = otp::TwoFactorAuth::try_from(secret.value.as_str())?.generate();
+
+    if let Some(next) = &code.next {
+        backend.set_otp(uuid, next).await?;
+    }
+
+    Ok(code)
+}
+
+/// The text of the QR code in the image, if given, or otherwise the value
+/// given on the command line.
+fn read(value: Option<String>, from_image: Option<PathBuf>) -> Result<String, qr::Error> {
+    match (value, from_image) {
+        (_, Some(path)) => qr::decode(&path),
+        (Some(value), None) => Ok(value),
+        (None, None) => unreachable!("clap requires a value or --from-image"),
+    }
+}
+
+async fn add(
+    backend: &api::DynBackend,
+    config: &Config,
+    item: &str,
+    secret: Option<String>,
+    from_image: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let (uuid, item) = get(backend, config, item).await?;
+
+    let image = from_image.is_some();
+    let secret = read(secret, from_image)?;
+
+    if image && !secret.starts_with("otpauth://") {
+        return Err(Error::NotOtpauth(secret).into());
+    }
+
+    otp::TwoFactorAuth::try_from(secret.as_str())?;
+
+    let account = item.username().map_or("", |v| v.value.as_str());
+    backend
+        .set_otp(&uuid, &otp::uri(&secret, &item.title, account))
+        .await?;
+
+    Ok(())
+}
+
+/// Adds each account in a Google Authenticator export to the item with the
+/// same title. Accounts without exactly one such item are skipped rather than
+/// risk adding the secret to the wrong one.
+async fn import(
+    backend: &api::DynBackend,
+    uri: Option<String>,
+    from_image: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let uri = read(uri, from_image)?;
+    let accounts = otp::migration(&uri)?;
+    let items = backend.search(None).await?;
+
+    for account in accounts {
+        let title = account.title();
+        let matches: Vec<_> = items
+            .iter()
+            .filter(|v| v.title.to_lowercase() == title.to_lowercase())
+            .collect();
+
+        match matches.as_slice() {
+            [item] => {
+                backend.set_otp(&item.uuid, &account.uri).await?;
+                eprintln!("Added {} to {}", account.name, item.title);
+            }
+            [] => eprintln!(
+                "Skipped {}, there's no item called {:?}",
+                account.name, title
+            ),
+            _ => eprintln!(
+                "Skipped {}, there are several items called {:?}",
+                account.name, title
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run(backend: &api::DynBackend, config: &Config, opt: TotpOpt) -> anyhow::Result<()> {
+    match opt.cmd {
+        Some(TotpCmd::Add {
+            item,
+            secret,
+            from_image,
+        }) => return add(backend, config, &item, secret, from_image).await,
+        Some(TotpCmd::Import { uri, from_image }) => return import(backend, uri, from_image).await,
+        None => {}
+    }
+
+    let item = opt.item.ok_or(Error::NoItem)?;
+    let (uuid, item) = get_otp(backend, config, &item).await?;
+
+    if opt.watch || opt.qr {
+        let secret = otp::field(&item).ok_or_else(|| Error::NoTotp(item.title.clone()))?;
+
+        if opt.watch {
+            return watch(&secret.value).await;
+        }
+
+        let account = item.username().map_or("", |v| v.value.as_str());
+        let uri = otp::uri(&secret.value, &item.title, account);
+
+        print!("{}", qr::render(&uri)?);
+        println!();
+
+        return Ok(());
+    }
+
+    let code = generate(backend, &uuid, &item).await?;
+
+    if opt.clip {
+        let clear_after = config.clear_clipboard_after(code.remaining);
+        clipboard::copy(&code.value, clear_after).await?;
+    } else {
+        println!("{}", code.value);
+    }
+
+    Ok(())
+}
+
+/// Shows the current code and the seconds left until it changes, updating
+/// the countdown in place and moving to a new line for each new code.
+async fn watch(secret: &str) -> anyhow::Result<()> {
+    let mut last = None;
+
+    loop {
+        // codes are worked out from the time they're parsed at, so the
+        // secret's parsed again each time
+        let code = otp::TwoFactorAuth::try_from(secret)?.generate();
+        let remaining = code.remaining.ok_or(Error::NotTimeBased)?;
+
+        if last.is_some() && last.as_ref() != Some(&code.value) {
+            println!();
+        }
+
+        print!("\r{} {:>3}s", code.value, remaining);
+        std::io::stdout().flush()?;
+        last = Some(code.value);
+
+        tokio::time::delay_for(Duration::from_secs(1)).await;
+    }
+}
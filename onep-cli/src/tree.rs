@@ -0,0 +1,232 @@
+//! Renders the contents of an account as a tree, grouped by vault.
+
+use crate::{config::Config, output::Output};
+use colored::{Color, ColoredString, Colorize};
+use itertools::Itertools;
+use onep_backend_api as api;
+
+/// A single entry in a vault, along with any extra lines to print beneath
+/// it.
+pub struct Leaf {
+    pub title: String,
+    pub details: Vec<ColoredString>,
+}
+
+/// Characters the tree is drawn with.
+struct Lines {
+    branch: &'static str,
+    last: &'static str,
+    pipe: &'static str,
+}
+
+#[allow(clippy::non_ascii_literal)]
+const UNICODE: Lines = Lines {
+    branch: "├──",
+    last: "└──",
+    pipe: "│",
+};
+
+/// Used in place of box-drawing characters when colors are off, as the output
+/// is likely going somewhere other than a terminal, when asked for with
+/// `--ascii` and when the locale isn't UTF-8.
+const ASCII: Lines = Lines {
+    branch: "|--",
+    last: "`--",
+    pipe: "|",
+};
+
+/// Prints each group of leaves beneath the vault with its uuid, in the order
+/// they're given.
+pub fn print(
+    out: &mut Output,
+    config: &Config,
+    account: &api::AccountMetadata,
+    vaults: &[api::VaultMetadata],
+    groups: Vec<(String, Vec<Leaf>)>,
+) {
+    // without the tree there's nothing to say which vault each leaf is in,
+    // so their details go on the same line, as with `--flat`
+    if config.quiet {
+        for leaf in groups.into_iter().flat_map(|(_, group)| group) {
+            let mut line = vec![leaf.title.trim().normal()];
+            line.extend(leaf.details);
+
+            out.println(line.iter().join("\t"));
+        }
+
+        return;
+    }
+
+    let lines = if config.color.enabled() && !config.ascii && utf8_locale() {
+        &UNICODE
+    } else {
+        &ASCII
+    };
+
+    out.println(render(
+        lines,
+        config.colors.vault.0,
+        account,
+        vaults,
+        groups,
+    ));
+}
+
+/// Draws the tree with `lines`, without the trailing newline.
+fn render(
+    lines: &Lines,
+    vault_color: Color,
+    account: &api::AccountMetadata,
+    vaults: &[api::VaultMetadata],
+    groups: Vec<(String, Vec<Leaf>)>,
+) -> String {
+    let mut tree = vec![format!("{} ({})", account.name, account.domain)];
+
+    let vault_count = groups.len().saturating_sub(1);
+
+    for (current_vault_index, (vault, group)) in groups.into_iter().enumerate() {
+        let vault = vaults
+            .iter()
+            .find(|v| v.uuid == vault)
+            .map_or_else(|| format!("Unknown Vault ({})", vault), |v| v.name.clone());
+
+        tree.push(format!(
+            "{} {}",
+            if current_vault_index < vault_count {
+                lines.branch
+            } else {
+                lines.last
+            },
+            vault.color(vault_color)
+        ));
+
+        let line_start = if current_vault_index < vault_count {
+            lines.pipe
+        } else {
+            " "
+        };
+
+        let item_count = group.len().saturating_sub(1);
+
+        for (current_item_index, leaf) in group.into_iter().enumerate() {
+            tree.push(format!(
+                "{}   {} {}",
+                line_start,
+                if current_item_index < item_count {
+                    lines.branch
+                } else {
+                    lines.last
+                },
+                leaf.title.trim()
+            ));
+
+            let prefix = if current_item_index < item_count {
+                lines.pipe
+            } else {
+                " "
+            };
+
+            for detail in leaf.details {
+                tree.push(format!("{}   {}   {}", line_start, prefix, detail));
+            }
+        }
+    }
+
+    tree.join("\n")
+}
+
+/// Whether the locale says the terminal takes UTF-8, going by the first of
+/// `LC_ALL`, `LC_CTYPE` and `LANG` that's set, the same way the C library
+/// does. Other platforms don't have a locale to go by, so are assumed to.
+fn utf8_locale() -> bool {
+    if !cfg!(unix) {
+        return true;
+    }
+
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|v| std::env::var(v).ok())
+        .find(|v| !v.is_empty())
+        .map_or(false, |v| {
+            let v = v.to_lowercase();
+            v.contains("utf-8") || v.contains("utf8")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Leaf, Lines, ASCII, UNICODE};
+    use colored::{Color, Colorize};
+    use onep_backend_api as api;
+
+    fn leaf(title: &str, details: &[&str]) -> Leaf {
+        Leaf {
+            title: title.to_string(),
+            details: details.iter().map(|v| v.normal()).collect(),
+        }
+    }
+
+    /// Two vaults with a couple of items each and an empty one between them,
+    /// the last not being one the account has.
+    fn tree(lines: &Lines) -> String {
+        colored::control::set_override(false);
+
+        render(
+            lines,
+            Color::Blue,
+            &api::AccountMetadata::new("Jordan", "example.1password.com"),
+            &[
+                api::VaultMetadata::new("v1", "Personal"),
+                api::VaultMetadata::new("v2", "Work"),
+            ],
+            vec![
+                (
+                    "v1".to_string(),
+                    vec![leaf("GitHub", &["octocat"]), leaf(" Netflix ", &[])],
+                ),
+                ("v2".to_string(), Vec::new()),
+                (
+                    "v3".to_string(),
+                    vec![leaf("Bank", &[]), leaf("Email", &["jordan", "work"])],
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    #[allow(clippy::non_ascii_literal)]
+    fn unicode() {
+        let expected = "\
+Jordan (example.1password.com)
+├── Personal
+│   ├── GitHub
+│   │   octocat
+│   └── Netflix
+├── Work
+└── Unknown Vault (v3)
+    ├── Bank
+    └── Email
+        jordan
+        work";
+
+        assert_eq!(tree(&UNICODE), expected);
+    }
+
+    #[test]
+    fn ascii() {
+        let expected = "\
+Jordan (example.1password.com)
+|-- Personal
+|   |-- GitHub
+|   |   octocat
+|   `-- Netflix
+|-- Work
+`-- Unknown Vault (v3)
+    |-- Bank
+    `-- Email
+        jordan
+        work";
+
+        assert_eq!(tree(&ASCII), expected);
+    }
+}
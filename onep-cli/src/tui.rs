@@ -0,0 +1,476 @@
+//! A full-screen browser for items, with a sidebar of vaults, a list of items
+//! filtered as you type and the selected item's fields alongside it.
+
+use crate::{config::Config, otp, MASK};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use onep_backend_api as api;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Row, Table},
+    DefaultTerminal, Frame,
+};
+use std::{collections::HashMap, convert::TryFrom};
+
+const HELP: &str =
+    "q quit  / filter  tab switch pane  r reveal  u/p/o copy username/password/otp  n new login";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Vaults,
+    Items,
+}
+
+/// What typing does, besides moving around.
+enum Mode {
+    Browse,
+    Filter,
+    /// Typing the name of a login to generate.
+    NewLogin(String),
+}
+
+struct App<'a> {
+    backend: &'a api::DynBackend,
+    config: &'a Config,
+    vaults: Vec<api::VaultMetadata>,
+    items: Vec<api::ItemMetadata>,
+    /// The selected vault, where the first entry is every vault and the rest
+    /// are offset by one from `vaults`.
+    vault: ListState,
+    /// Indices into `items` of those in the selected vault matching the
+    /// filter, best match first.
+    visible: Vec<usize>,
+    item: ListState,
+    filter: String,
+    focus: Focus,
+    mode: Mode,
+    /// Items fetched in full so far, keyed by uuid.
+    fetched: HashMap<String, api::Item>,
+    reveal: bool,
+    status: String,
+    /// Kept for as long as the browser is open, as on some platforms the
+    /// clipboard is emptied when it's dropped.
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl<'a> App<'a> {
+    fn new(
+        backend: &'a api::DynBackend,
+        config: &'a Config,
+        vaults: Vec<api::VaultMetadata>,
+        mut items: Vec<api::ItemMetadata>,
+    ) -> Self {
+        items.sort_by_key(|v| v.title.to_lowercase());
+
+        let mut app = Self {
+            backend,
+            config,
+            vaults,
+            items,
+            vault: ListState::default().with_selected(Some(0)),
+            visible: Vec::new(),
+            item: ListState::default(),
+            filter: String::new(),
+            focus: Focus::Items,
+            mode: Mode::Browse,
+            fetched: HashMap::new(),
+            reveal: false,
+            status: HELP.to_string(),
+            clipboard: None,
+        };
+        app.refilter();
+
+        app
+    }
+
+    fn selected_vault(&self) -> Option<&api::VaultMetadata> {
+        self.vault
+            .selected()
+            .and_then(|v| v.checked_sub(1))
+            .and_then(|v| self.vaults.get(v))
+    }
+
+    fn selected_item(&self) -> Option<&api::ItemMetadata> {
+        self.item
+            .selected()
+            .and_then(|v| self.visible.get(v))
+            .map(|v| &self.items[*v])
+    }
+
+    fn selected_detail(&self) -> Option<&api::Item> {
+        self.selected_item().and_then(|v| self.fetched.get(&v.uuid))
+    }
+
+    /// Works out which items to list after the vault or filter changes,
+    /// keeping items with the same score in title order.
+    fn refilter(&mut self) {
+        let matcher = SkimMatcherV2::default();
+        let vault = self.selected_vault().map(|v| v.uuid.clone());
+
+        let mut visible: Vec<_> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| vault.as_ref().map_or(true, |vault| v.vault_uuid == *vault))
+            .filter_map(|(i, v)| {
+                if self.filter.is_empty() {
+                    return Some((0, i));
+                }
+
+                matcher
+                    .fuzzy_match(&format!("{} {}", v.title, v.account_info), &self.filter)
+                    .map(|score| (score, i))
+            })
+            .collect();
+        visible.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        self.visible = visible.into_iter().map(|(_, i)| i).collect();
+        self.item.select(if self.visible.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+    }
+
+    /// Fetches the selected item in full, if it hasn't been already.
+    async fn fetch(&mut self) {
+        let uuid = match self.selected_item() {
+            Some(item) if !self.fetched.contains_key(&item.uuid) => item.uuid.clone(),
+            _ => return,
+        };
+
+        match self.backend.get(&uuid).await {
+            Ok(Some(mut item)) => {
+                if self.config.otp.is_steam(&uuid, &item.title) {
+                    otp::mark_steam(&mut item);
+                }
+
+                self.fetched.insert(uuid, item);
+            }
+            Ok(None) => self.status = "Couldn't find the selected item.".to_string(),
+            Err(e) => self.status = e.to_string(),
+        }
+    }
+
+    fn copy(&mut self, what: &str, value: Option<String>) {
+        let value = if let Some(value) = value {
+            value
+        } else {
+            self.status = format!("The selected item has no {}.", what);
+            return;
+        };
+
+        if self.clipboard.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(clipboard) => self.clipboard = Some(clipboard),
+                Err(e) => {
+                    self.status = format!("Failed to open the clipboard: {}", e);
+                    return;
+                }
+            }
+        }
+
+        self.status = match self.clipboard.as_mut().map(|v| v.set_text(value)) {
+            Some(Err(e)) => format!("Failed to copy the {}: {}", what, e),
+            _ => format!("Copied the {}.", what),
+        };
+    }
+
+    /// Generates a code for the selected item's one-time password, saving the
+    /// moved on counter first for counter based ones.
+    async fn otp(&mut self) -> anyhow::Result<Option<String>> {
+        let uuid = match self.selected_item() {
+            Some(item) => item.uuid.clone(),
+            None => return Ok(None),
+        };
+
+        let tfa = self
+            .fetched
+            .get(&uuid)
+            .and_then(otp::field)
+            .and_then(|v| otp::TwoFactorAuth::try_from(v.value.as_str()).ok());
+        let code = match tfa {
+            Some(tfa) => tfa.generate(),
+            None => return Ok(None),
+        };
+
+        if let Some(next) = code.next {
+            self.backend.set_otp(&uuid, &next).await?;
+            // fetched again with the new counter before it's next drawn
+            self.fetched.remove(&uuid);
+        }
+
+        Ok(Some(code.value))
+    }
+
+    /// Generates a login in the selected vault, or the one in the config
+    /// when every vault is shown, and selects it.
+    async fn create(&mut self, name: &str) -> anyhow::Result<()> {
+        let vault = match (self.selected_vault(), &self.config.vault) {
+            (Some(vault), _) => Some(vault.uuid.clone()),
+            (None, Some(vault)) => Some(crate::vault::find(self.backend, vault).await?.uuid),
+            (None, None) => None,
+        };
+
+        let item = self
+            .backend
+            .generate(
+                name,
+                None,
+                None,
+                None,
+                vault.as_deref(),
+                &api::PasswordRecipe::default(),
+            )
+            .await?;
+
+        self.items = self.backend.search(None).await?;
+        self.items.sort_by_key(|v| v.title.to_lowercase());
+        self.filter = name.to_string();
+        self.refilter();
+
+        if let Some(uuid) = self.selected_item().map(|v| v.uuid.clone()) {
+            self.fetched.insert(uuid, item);
+        }
+
+        Ok(())
+    }
+
+    /// Handles a key press, returning whether to keep running.
+    async fn key(&mut self, key: KeyEvent) -> bool {
+        match &mut self.mode {
+            Mode::Filter => match key.code {
+                KeyCode::Char(c) => {
+                    self.filter.push(c);
+                    self.refilter();
+                }
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.refilter();
+                }
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.refilter();
+                    self.mode = Mode::Browse;
+                }
+                KeyCode::Enter | KeyCode::Down | KeyCode::Up => {
+                    self.mode = Mode::Browse;
+                    self.focus = Focus::Items;
+                }
+                _ => {}
+            },
+            Mode::NewLogin(name) => match key.code {
+                KeyCode::Char(c) => name.push(c),
+                KeyCode::Backspace => {
+                    name.pop();
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Enter => {
+                    let name = std::mem::take(name);
+                    self.mode = Mode::Browse;
+                    self.status = match self.create(&name).await {
+                        Ok(()) => format!("Created {}.", name),
+                        Err(e) => e.to_string(),
+                    };
+                }
+                _ => {}
+            },
+            Mode::Browse => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return false,
+                KeyCode::Char('/') => {
+                    self.mode = Mode::Filter;
+                    self.focus = Focus::Items;
+                }
+                KeyCode::Tab | KeyCode::BackTab => {
+                    self.focus = match self.focus {
+                        Focus::Vaults => Focus::Items,
+                        Focus::Items => Focus::Vaults,
+                    };
+                }
+                KeyCode::Down | KeyCode::Char('j') => self.step(1),
+                KeyCode::Up | KeyCode::Char('k') => self.step(-1),
+                KeyCode::Char('r') => self.reveal = !self.reveal,
+                KeyCode::Char('u') => {
+                    let username = self
+                        .selected_detail()
+                        .and_then(api::Item::username)
+                        .map(|v| v.value.as_str().to_string());
+                    self.copy("username", username);
+                }
+                KeyCode::Char('p') => {
+                    let password = self
+                        .selected_detail()
+                        .and_then(api::Item::password)
+                        .map(|v| v.value.as_str().to_string());
+                    self.copy("password", password);
+                }
+                KeyCode::Char('o') => match self.otp().await {
+                    Ok(code) => self.copy("one-time password", code),
+                    Err(e) => self.status = e.to_string(),
+                },
+                KeyCode::Char('n') => self.mode = Mode::NewLogin(String::new()),
+                _ => {}
+            },
+        }
+
+        true
+    }
+
+    /// Moves the selection in the focused pane up or down.
+    fn step(&mut self, by: isize) {
+        let (state, len) = match self.focus {
+            Focus::Vaults => (&mut self.vault, self.vaults.len() + 1),
+            Focus::Items => (&mut self.item, self.visible.len()),
+        };
+
+        if len == 0 {
+            return;
+        }
+
+        let current = state.selected().unwrap_or(0);
+        let next = if by < 0 {
+            current.saturating_sub(by.unsigned_abs())
+        } else {
+            (current + by.unsigned_abs()).min(len - 1)
+        };
+        state.select(Some(next));
+
+        if self.focus == Focus::Vaults {
+            self.refilter();
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame<'_>) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [vaults, items, detail] = Layout::horizontal([
+            Constraint::Length(24),
+            Constraint::Percentage(35),
+            Constraint::Min(0),
+        ])
+        .areas(main);
+
+        let highlight = |focus| {
+            if self.focus == focus {
+                Style::new().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::new().add_modifier(Modifier::BOLD)
+            }
+        };
+
+        let vault_list = List::new(
+            std::iter::once("All vaults".to_string())
+                .chain(self.vaults.iter().map(|v| v.name.clone())),
+        )
+        .block(Block::bordered().title("Vaults"))
+        .highlight_style(highlight(Focus::Vaults));
+
+        let item_list = List::new(self.visible.iter().map(|i| {
+            let item = &self.items[*i];
+
+            ListItem::new(Line::from(vec![
+                Span::raw(item.title.trim().to_string()),
+                Span::raw(" "),
+                Span::raw(item.account_info.trim().to_string()).dim(),
+            ]))
+        }))
+        .block(Block::bordered().title(if self.filter.is_empty() {
+            "Items".to_string()
+        } else {
+            format!("Items matching {}", self.filter)
+        }))
+        .highlight_style(highlight(Focus::Items));
+
+        frame.render_stateful_widget(vault_list, vaults, &mut self.vault);
+        frame.render_stateful_widget(item_list, items, &mut self.item);
+
+        match self.selected_detail() {
+            Some(item) => frame.render_widget(
+                Table::new(
+                    fields(item, self.reveal),
+                    [Constraint::Percentage(35), Constraint::Fill(1)],
+                )
+                .block(Block::bordered().title(item.title.clone())),
+                detail,
+            ),
+            None => frame.render_widget(Block::bordered(), detail),
+        }
+
+        let status_line = match &self.mode {
+            Mode::Browse => self.status.clone(),
+            Mode::Filter => format!("/{}", self.filter),
+            Mode::NewLogin(name) => format!("Name of the new login: {}", name),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+}
+
+/// Rows of the detail pane, with secrets masked unless they're revealed and
+/// one-time passwords replaced by their current code.
+fn fields(item: &api::Item, reveal: bool) -> Vec<Row<'static>> {
+    let row = |field: &api::ItemField| {
+        let value = if field.field_type == api::ItemFieldType::Totp {
+            match otp::TwoFactorAuth::try_from(field.value.as_str()) {
+                // generating a code uses it up, so that's left for copying
+                Ok(tfa) if tfa.is_counter_based() => "press o for a code".to_string(),
+                Ok(tfa) => tfa.generate().value,
+                Err(e) => e.to_string(),
+            }
+        } else if !reveal && field.is_secret() {
+            MASK.to_string()
+        } else {
+            field.value.as_str().to_string()
+        };
+
+        Row::new(vec![field.name.clone(), value])
+    };
+
+    let mut rows: Vec<_> = item.fields.iter().map(row).collect();
+
+    rows.extend(item.urls.iter().map(|v| {
+        let label = if v.label.is_empty() {
+            "website".to_string()
+        } else {
+            v.label.clone()
+        };
+
+        Row::new(vec![label, v.url.clone()])
+    }));
+
+    for section in &item.sections {
+        if section.fields.is_empty() {
+            continue;
+        }
+
+        rows.push(Row::new(vec![section.name.clone()]).bold());
+        rows.extend(section.fields.iter().map(row));
+    }
+
+    rows
+}
+
+pub async fn run(backend: &api::DynBackend, config: &Config) -> anyhow::Result<()> {
+    let (vaults, items) = tokio::try_join!(backend.vaults(), backend.search(None))?;
+    let mut app = App::new(backend, config, vaults, items);
+
+    let mut terminal = ratatui::try_init()?;
+    let res = browse(&mut app, &mut terminal).await;
+    ratatui::restore();
+
+    res
+}
+
+async fn browse(app: &mut App<'_>, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+    loop {
+        app.fetch().await;
+        terminal.draw(|frame| app.draw(frame))?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !app.key(key).await {
+                return Ok(());
+            }
+        }
+    }
+}
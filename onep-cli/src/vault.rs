@@ -0,0 +1,79 @@
+//! Commands for creating, deleting and renaming vaults.
+
+use crate::Error;
+use clap::Clap;
+use onep_backend_api as api;
+use std::io::{BufRead, Write};
+
+#[derive(Clap, Debug)]
+pub enum VaultOpt {
+    /// Create a new vault
+    #[clap(alias = "create")]
+    New { name: String },
+    /// Delete a vault and every item in it
+    #[clap(alias = "delete")]
+    Rm {
+        /// Name or uuid of the vault
+        vault: String,
+        /// Don't ask for confirmation
+        #[clap(long, short = 'y')]
+        yes: bool,
+    },
+    /// Rename a vault
+    Rename {
+        /// Name or uuid of the vault
+        vault: String,
+        name: String,
+    },
+}
+
+/// Looks up a vault by its uuid or, failing that, its name.
+pub async fn find(backend: &api::DynBackend, vault: &str) -> anyhow::Result<api::VaultMetadata> {
+    let mut vaults = backend.vaults().await?;
+
+    let idx = vaults
+        .iter()
+        .position(|v| v.uuid == vault)
+        .or_else(|| {
+            vaults
+                .iter()
+                .position(|v| v.name.to_lowercase() == vault.to_lowercase())
+        })
+        .ok_or(Error::NotFound)?;
+
+    Ok(vaults.swap_remove(idx))
+}
+
+/// Asks the user a yes/no question on stderr, so it doesn't end up mixed in
+/// with any output going to stdout.
+pub fn confirm(prompt: &str) -> std::io::Result<bool> {
+    eprint!("{} [y/N] ", prompt);
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+pub async fn run(backend: &api::DynBackend, opt: VaultOpt) -> anyhow::Result<()> {
+    match opt {
+        VaultOpt::New { name } => {
+            let vault = backend.create_vault(&name).await?;
+            println!("{}", vault.uuid);
+        }
+        VaultOpt::Rm { vault, yes } => {
+            let vault = find(backend, &vault).await?;
+
+            if yes || confirm(&format!("Delete {} and everything in it?", vault.name))? {
+                backend.delete_vault(&vault.uuid).await?;
+            }
+        }
+        VaultOpt::Rename { vault, name } => {
+            let vault = find(backend, &vault).await?;
+            backend.rename_vault(&vault.uuid, &name).await?;
+        }
+    }
+
+    Ok(())
+}
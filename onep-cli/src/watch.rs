@@ -0,0 +1,154 @@
+//! Polls the backend's item list and reports items that were added, changed
+//! or removed since the last look, to spot unexpected changes to shared
+//! vaults. Changes are found by when each item was last updated, so only
+//! metadata is ever fetched.
+
+use crate::{config::Config, vault};
+use clap::Clap;
+use colored::Colorize;
+use onep_backend_api as api;
+use std::{collections::HashMap, time::Duration};
+
+#[derive(Clap, Debug)]
+pub struct WatchOpt {
+    /// Only watch items in this vault, by name or uuid
+    #[clap(long)]
+    vault: Option<String>,
+    /// Seconds between fetches of the item list from the backend
+    #[clap(long, default_value = "60")]
+    interval: u64,
+    /// Also show a desktop notification for each change
+    #[clap(long)]
+    notify: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Change {
+    Added,
+    Changed,
+    Removed,
+}
+
+impl Change {
+    fn name(self) -> colored::ColoredString {
+        match self {
+            Self::Added => "added".green(),
+            Self::Changed => "changed".yellow(),
+            Self::Removed => "removed".red(),
+        }
+    }
+}
+
+/// Items by uuid, as of one fetch.
+type Snapshot = HashMap<String, api::ItemMetadata>;
+
+async fn snapshot(backend: &api::DynBackend, vault: Option<&str>) -> anyhow::Result<Snapshot> {
+    let mut filter = api::Filter::default();
+    filter.vault = vault;
+
+    Ok(backend
+        .search_filtered(None, filter)
+        .await?
+        .into_iter()
+        .map(|v| (v.uuid.clone(), v))
+        .collect())
+}
+
+/// What's different in `new`, in the order items were last updated.
+fn diff<'a>(old: &'a Snapshot, new: &'a Snapshot) -> Vec<(Change, &'a api::ItemMetadata)> {
+    let mut changes: Vec<_> = new
+        .values()
+        .filter_map(|item| match old.get(&item.uuid) {
+            None => Some((Change::Added, item)),
+            Some(old) if old.updated != item.updated => Some((Change::Changed, item)),
+            Some(_) => None,
+        })
+        .chain(
+            old.values()
+                .filter(|v| !new.contains_key(&v.uuid))
+                .map(|v| (Change::Removed, v)),
+        )
+        .collect();
+
+    changes.sort_by_key(|(_, v)| v.updated);
+    changes
+}
+
+pub async fn run(backend: &api::DynBackend, config: &Config, opt: WatchOpt) -> anyhow::Result<()> {
+    let vault = match &opt.vault {
+        Some(vault) => Some(vault::find(backend, vault).await?.uuid),
+        None => None,
+    };
+
+    let vaults: HashMap<_, _> = backend
+        .vaults()
+        .await?
+        .into_iter()
+        .map(|v| (v.uuid, v.name))
+        .collect();
+
+    let mut last = snapshot(backend, vault.as_deref()).await?;
+    if !config.quiet {
+        eprintln!("Watching {} items for changes", last.len());
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(opt.interval));
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        // a failed fetch is reported and tried again next time, rather than
+        // reporting every item as removed
+        let current = match snapshot(backend, vault.as_deref()).await {
+            Ok(current) => current,
+            Err(e) => {
+                eprintln!("Failed to fetch items: {}", e);
+                continue;
+            }
+        };
+
+        for (change, item) in diff(&last, &current) {
+            let vault = vaults.get(&item.vault_uuid).unwrap_or(&item.vault_uuid);
+
+            println!(
+                "{} {} {} in {} {}",
+                chrono::Local::now().format("%H:%M:%S"),
+                change.name(),
+                item.title,
+                vault.color(config.colors.vault.0),
+                item.uuid.color(config.colors.uuid.0),
+            );
+
+            if opt.notify {
+                notify(
+                    &format!("{} {}", item.title, change.name().clear()),
+                    &format!("in {}", vault),
+                );
+            }
+        }
+
+        last = current;
+    }
+}
+
+/// Shows a desktop notification, warning rather than failing when there's no
+/// way to.
+fn notify(summary: &str, body: &str) {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let mut cmd = std::process::Command::new("osascript");
+        cmd.arg("-e").arg(format!(
+            "display notification {:?} with title \"1p\" subtitle {:?}",
+            body, summary
+        ));
+        cmd
+    } else {
+        let mut cmd = std::process::Command::new("notify-send");
+        cmd.args(&["--app-name", "1p", summary, body]);
+        cmd
+    };
+
+    if let Err(e) = cmd.status() {
+        eprintln!("Couldn't show a notification: {}", e);
+    }
+}